@@ -0,0 +1,193 @@
+use crate::config::{constant_time_eq, AclUser, Config};
+use crate::glob::glob_match;
+use crate::protocol::{is_write_command, Command, COMMAND_COUNT, COMMAND_NAMES};
+use sha1::{Digest, Sha1};
+use std::sync::Arc;
+
+/// User connections start as, and that a one-argument AUTH logs in as
+pub const DEFAULT_USER: &str = "default";
+
+/// Categories a user's command patterns can name as `@category`
+pub const CATEGORIES: [&str; 3] = ["all", "read", "write"];
+
+/// Check whether the command at `index` in `COMMAND_NAMES` is in
+/// `category`, or None if there is no such category
+fn in_category(category: &str, index: usize) -> Option<bool> {
+    match category.to_ascii_lowercase().as_str() {
+        "all" => Some(true),
+        "read" => Some(!is_write_command(index)),
+        "write" => Some(is_write_command(index)),
+        _ => None,
+    }
+}
+
+/// Commands in `category`, or None if there is no such category
+pub fn category_commands(category: &str) -> Option<Vec<&'static str>> {
+    (0..COMMAND_COUNT)
+        .filter_map(|index| match in_category(category, index) {
+            Some(true) => Some(Some(COMMAND_NAMES[index])),
+            Some(false) => None,
+            None => Some(None),
+        })
+        .collect()
+}
+
+/// Check if a command pattern names a known category, or is a glob
+pub fn valid_pattern(pattern: &str) -> bool {
+    pattern
+        .strip_prefix('@')
+        .is_none_or(|category| in_category(category, 0).is_some())
+}
+
+/// A user connections can authenticate as, and the commands it may run
+#[derive(Debug)]
+pub struct User {
+    name: String,
+    password: Option<String>,
+    // Any password is accepted
+    nopass: bool,
+    patterns: Vec<String>,
+    // Whether each command in `COMMAND_NAMES` matches a pattern
+    allowed: [bool; COMMAND_COUNT],
+}
+
+impl User {
+    fn new(name: &str, password: Option<String>, nopass: bool, patterns: Vec<String>) -> Self {
+        let mut allowed = [false; COMMAND_COUNT];
+        for pattern in &patterns {
+            let pattern = pattern.to_ascii_lowercase();
+            for (index, name) in COMMAND_NAMES.iter().enumerate() {
+                allowed[index] |= match pattern.strip_prefix('@') {
+                    Some(category) => in_category(category, index).unwrap_or(false),
+                    None => glob_match(pattern.as_bytes(), name.as_bytes()),
+                };
+            }
+        }
+        Self {
+            name: name.to_string(),
+            password,
+            nopass,
+            patterns,
+            allowed,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn requires_password(&self) -> bool {
+        !self.nopass
+    }
+
+    /// Check a password in constant time; a user with neither a password nor
+    /// `nopass` cannot log in
+    pub fn check_password(&self, password: &str) -> bool {
+        match &self.password {
+            _ if self.nopass => true,
+            Some(correct) => constant_time_eq(password.as_bytes(), correct.as_bytes()),
+            None => false,
+        }
+    }
+
+    pub fn allows(&self, command: &Command) -> bool {
+        self.allowed[command.index()]
+    }
+
+    /// Check if every command is allowed, as for the default user
+    pub fn allows_all(&self) -> bool {
+        self.allowed.iter().all(|&allowed| allowed)
+    }
+
+    /// The user as ACL LIST shows it, with the password hashed
+    pub fn describe(&self) -> String {
+        let mut rules = vec![format!("user {} on", self.name)];
+        if self.nopass {
+            rules.push("nopass".to_string());
+        } else if let Some(password) = &self.password {
+            rules.push(format!("#{:x}", Sha1::digest(password.as_bytes())));
+        }
+        rules.push("~* &*".to_string());
+        if self.patterns.is_empty() {
+            rules.push("-@all".to_string());
+        }
+        rules.extend(self.patterns.iter().map(|pattern| format!("+{}", pattern)));
+        rules.join(" ")
+    }
+}
+
+/// The users defined by `requirepass` and the `acl` config section
+///
+/// The default user takes `requirepass` as its password, or `nopass` without
+/// one, and may run every command, unless the `acl` section has an entry
+/// named `default`.
+#[derive(Debug)]
+pub struct Acl {
+    // The default user comes first
+    users: Vec<Arc<User>>,
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        Self {
+            users: vec![Arc::new(User::new(
+                DEFAULT_USER,
+                None,
+                true,
+                vec!["@all".to_string()],
+            ))],
+        }
+    }
+}
+
+impl Acl {
+    pub fn from_config(config: &Config) -> Self {
+        let user = |entry: &AclUser| {
+            Arc::new(User::new(
+                &entry.name,
+                entry.password.clone(),
+                entry.nopass,
+                entry.commands.clone(),
+            ))
+        };
+        let default = config
+            .acl
+            .iter()
+            .find(|entry| entry.name == DEFAULT_USER)
+            .map(user)
+            .unwrap_or_else(|| {
+                Arc::new(User::new(
+                    DEFAULT_USER,
+                    config.requirepass.clone(),
+                    config.requirepass.is_none(),
+                    vec!["@all".to_string()],
+                ))
+            });
+
+        let mut users = vec![default];
+        users.extend(
+            config
+                .acl
+                .iter()
+                .filter(|entry| entry.name != DEFAULT_USER)
+                .map(user),
+        );
+        Self { users }
+    }
+
+    pub fn default_user(&self) -> &Arc<User> {
+        &self.users[0]
+    }
+
+    pub fn users(&self) -> &[Arc<User>] {
+        &self.users
+    }
+
+    /// The user `name`, if `password` is theirs
+    pub fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        self.users
+            .iter()
+            .find(|user| user.name == name && user.check_password(password))
+            .cloned()
+    }
+}
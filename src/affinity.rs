@@ -0,0 +1,105 @@
+use tracing::{info, warn};
+
+/// Cores the worker threads are pinned to, in the order they take them
+///
+/// Only cores this process may run on are used, so a `taskset` or cgroup
+/// limit is respected. With `numa_aware` the cores are grouped by NUMA node,
+/// so the first workers share a node before any spill onto the next one.
+/// Returns None where thread affinity is not supported.
+pub fn worker_cores(numa_aware: bool) -> Option<Vec<usize>> {
+    #[cfg(target_os = "linux")]
+    {
+        use nix::sched::{sched_getaffinity, CpuSet};
+        use nix::unistd::Pid;
+
+        let allowed = match sched_getaffinity(Pid::from_raw(0)) {
+            Ok(set) => set,
+            Err(e) => {
+                warn!(
+                    "Could not read CPU affinity, workers will not be pinned: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        let mut cores: Vec<usize> = (0..CpuSet::count())
+            .filter(|&core| allowed.is_set(core).unwrap_or(false))
+            .collect();
+
+        if numa_aware {
+            let nodes = numa_nodes();
+            cores.sort_by_key(|core| {
+                nodes
+                    .iter()
+                    .position(|node| node.contains(core))
+                    .unwrap_or(usize::MAX)
+            });
+        }
+
+        (!cores.is_empty()).then_some(cores)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = numa_aware;
+        warn!("CPU affinity is not supported on this platform, workers will not be pinned");
+        None
+    }
+}
+
+/// Pin the calling worker thread to `core`
+pub fn pin_worker(thread_id: usize, core: usize) {
+    #[cfg(target_os = "linux")]
+    {
+        use nix::sched::{sched_setaffinity, CpuSet};
+        use nix::unistd::Pid;
+
+        let mut set = CpuSet::new();
+        let result = set
+            .set(core)
+            .and_then(|()| sched_setaffinity(Pid::from_raw(0), &set));
+        match result {
+            Ok(()) => info!("Worker {} pinned to core {}", thread_id, core),
+            Err(e) => warn!("Could not pin worker {} to core {}: {}", thread_id, core, e),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = (thread_id, core);
+}
+
+/// The cores of each NUMA node, in node order; empty if the machine does not
+/// report its nodes
+#[cfg(target_os = "linux")]
+fn numa_nodes() -> Vec<Vec<usize>> {
+    let mut nodes: Vec<(usize, Vec<usize>)> = std::fs::read_dir("/sys/devices/system/node")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let node = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()?;
+            let cpulist = std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            Some((node, parse_cpulist(&cpulist)))
+        })
+        .collect();
+    nodes.sort_by_key(|(node, _)| *node);
+    nodes.into_iter().map(|(_, cores)| cores).collect()
+}
+
+/// Parse a kernel CPU list such as `0-3,8-11`
+#[cfg(target_os = "linux")]
+fn parse_cpulist(list: &str) -> Vec<usize> {
+    let mut cores = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
+            cores.extend(start..=end);
+        }
+    }
+    cores
+}
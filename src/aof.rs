@@ -0,0 +1,482 @@
+use crate::config::Config;
+use crate::protocol::resp::write_resp_value;
+use crate::protocol::{Command, CommandExecutor, RespParser, RespValue};
+use crate::snapshot;
+use bytes::Bytes;
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use feoxdb::FeoxStore;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+/// Writes queued for the log thread before writers wait on the disk
+const QUEUE_CAPACITY: usize = 65536;
+
+/// Bytes read per step while replaying a log
+const REPLAY_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FsyncPolicy {
+    Always,
+    EverySec,
+    No,
+}
+
+enum Message {
+    /// A RESP-encoded write command, run with database `db` selected
+    Append { db: usize, command: Vec<u8> },
+    /// Replace the log with the rewritten one at `path`
+    Rewrite {
+        path: PathBuf,
+        done: Sender<io::Result<()>>,
+    },
+}
+
+/// Append-only log of write commands, replayed at startup
+///
+/// Workers queue every write that succeeded on a bounded channel, and a
+/// dedicated thread appends them to `appendfilename` and fsyncs it as
+/// `appendfsync` asks; once the queue is full, writers wait on the disk.
+/// While the log is on, write commands run one at a time, holding the
+/// ordering lock from execution until they are queued, so the log replays
+/// them in the order they took effect. Reads are not affected.
+///
+/// Commands whose outcome depends on when they ran are logged as that
+/// outcome: SPOP as an SREM of the members it took, BLPOP / BRPOP as the
+/// LPOP / RPOP that served them, and relative expiries followed by a
+/// PEXPIREAT of the time they set.
+///
+/// BGREWRITEAOF compacts the log into a snapshot of the keyspace, in the
+/// format SAVE writes, that later writes are appended after.
+pub struct AppendLog {
+    store: Arc<FeoxStore>,
+    path: PathBuf,
+    sender: Sender<Message>,
+    order: Mutex<()>,
+    rewriting: AtomicBool,
+    last_write_ok: Arc<AtomicBool>,
+}
+
+impl AppendLog {
+    /// Open the log for appending and start its writer thread
+    pub fn open(store: Arc<FeoxStore>, config: &Config) -> io::Result<Arc<Self>> {
+        let path = PathBuf::from(&config.appendfilename);
+        let policy = match config.appendfsync.as_str() {
+            "always" => FsyncPolicy::Always,
+            "no" => FsyncPolicy::No,
+            _ => FsyncPolicy::EverySec,
+        };
+        let last_write_ok = Arc::new(AtomicBool::new(true));
+        let writer = Writer {
+            file: BufWriter::new(open_append(&path)?),
+            path: path.clone(),
+            policy,
+            // Unknown until the first SELECT, as the old log may end anywhere
+            db: None,
+            last_sync: Instant::now(),
+            dirty: false,
+            ok: Arc::clone(&last_write_ok),
+        };
+
+        let (sender, receiver) = bounded(QUEUE_CAPACITY);
+        std::thread::Builder::new()
+            .name("aof-writer".to_string())
+            .spawn(move || writer.run(receiver))?;
+
+        Ok(Arc::new(Self {
+            store,
+            path,
+            sender,
+            order: Mutex::new(()),
+            rewriting: AtomicBool::new(false),
+            last_write_ok,
+        }))
+    }
+
+    /// Replay the log at `appendfilename` into the store, returning how many
+    /// keys its snapshot restored and how many commands were run after it,
+    /// or None if there is no log yet
+    pub fn replay(store: &Arc<FeoxStore>, config: &Config) -> io::Result<Option<(usize, usize)>> {
+        let file = match File::open(&config.appendfilename) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+
+        // A rewritten log opens with a snapshot
+        let keys = if reader.fill_buf()?.starts_with(snapshot::MAGIC) {
+            snapshot::read_records(store, &mut reader)?
+        } else {
+            0
+        };
+
+        let commands_start = reader.stream_position()?;
+        let mut fed = 0;
+
+        let mut executor = CommandExecutor::new(Arc::clone(store), config);
+        let mut parser = RespParser::new();
+        let mut chunk = vec![0u8; REPLAY_CHUNK];
+        let mut commands = 0;
+        let mut failed = 0;
+        loop {
+            let read = reader.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            parser.feed(&chunk[..read]);
+            fed += read as u64;
+            while let Some(value) = parser
+                .parse_next()
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?
+            {
+                let replayed = match Command::from_resp(value) {
+                    Ok(Command::Select(db)) => executor.select_db(db),
+                    Ok(command) => !matches!(executor.execute(command), RespValue::Error(_)),
+                    Err(_) => false,
+                };
+                if !replayed {
+                    failed += 1;
+                }
+                commands += 1;
+            }
+        }
+
+        if parser.buffered() > 0 {
+            // A crash can cut the last write short; drop it so new writes
+            // are not appended to a broken command
+            let valid = commands_start + fed - parser.buffered() as u64;
+            OpenOptions::new()
+                .write(true)
+                .open(&config.appendfilename)?
+                .set_len(valid)?;
+            warn!(
+                "{} ended in a truncated command, which was removed",
+                config.appendfilename
+            );
+        }
+        if failed > 0 {
+            warn!(
+                "{} of {} commands in {} failed to replay",
+                failed, commands, config.appendfilename
+            );
+        }
+        Ok(Some((keys, commands)))
+    }
+
+    /// Take the ordering lock a write command runs under
+    pub fn lock_order(&self) -> MutexGuard<'_, ()> {
+        self.order.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Log a write command that succeeded with `reply`, run with database
+    /// `db` selected
+    pub fn log(&self, db: usize, args: &[Bytes], reply: &RespValue) {
+        let Some(name) = args.first() else {
+            return;
+        };
+
+        if name.eq_ignore_ascii_case(b"SPOP") {
+            let members = match reply {
+                RespValue::BulkString(Some(member)) => vec![member.clone()],
+                RespValue::Array(Some(members)) => members
+                    .iter()
+                    .filter_map(|member| match member {
+                        RespValue::BulkString(Some(member)) => Some(member.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            if !members.is_empty() && args.len() > 1 {
+                let mut srem = vec![Bytes::from_static(b"SREM"), args[1].clone()];
+                srem.extend(members);
+                self.append(db, &srem);
+            }
+            return;
+        }
+
+        if name.eq_ignore_ascii_case(b"BLPOP") || name.eq_ignore_ascii_case(b"BRPOP") {
+            if let RespValue::Array(Some(served)) = reply {
+                if let Some(RespValue::BulkString(Some(key))) = served.first() {
+                    let pop = if name.eq_ignore_ascii_case(b"BLPOP") {
+                        Bytes::from_static(b"LPOP")
+                    } else {
+                        Bytes::from_static(b"RPOP")
+                    };
+                    self.append(db, &[pop, key.clone()]);
+                }
+            }
+            return;
+        }
+
+        self.append(db, args);
+
+        // A relative expiry is logged with the deadline it set, so replay
+        // does not restart the clock
+        if let Some(ttl_ms) = relative_expiry(args, reply) {
+            let at = unix_time_millis().saturating_add(ttl_ms);
+            self.append(
+                db,
+                &[
+                    Bytes::from_static(b"PEXPIREAT"),
+                    args[1].clone(),
+                    Bytes::from(at.to_string()),
+                ],
+            );
+        }
+    }
+
+    /// Compact the log on a background thread, returning false if a
+    /// rewrite is already running
+    pub fn bgrewrite(self: &Arc<Self>) -> bool {
+        if self
+            .rewriting
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        let log = Arc::clone(self);
+        std::thread::spawn(move || {
+            match log.rewrite() {
+                Ok(keys) => info!("Append-only file rewritten with {} keys", keys),
+                Err(e) => error!(
+                    "Rewriting append-only file {} failed: {}",
+                    log.path.display(),
+                    e
+                ),
+            }
+            log.rewriting.store(false, Ordering::Release);
+        });
+        true
+    }
+
+    pub fn rewrite_in_progress(&self) -> bool {
+        self.rewriting.load(Ordering::Relaxed)
+    }
+
+    /// Whether the last write to the log succeeded, as INFO reports it
+    pub fn last_write_ok(&self) -> bool {
+        self.last_write_ok.load(Ordering::Relaxed)
+    }
+
+    fn append(&self, db: usize, args: &[Bytes]) {
+        let command = encode(args);
+        // Only fails once the writer thread is gone, at shutdown
+        let _ = self.sender.send(Message::Append { db, command });
+    }
+
+    /// Write a snapshot under a temporary name and have the writer swap it
+    /// in, holding off writes so none fall between the two
+    fn rewrite(&self) -> io::Result<usize> {
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let _order = self.lock_order();
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        let keys = snapshot::write_records(&self.store, &mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+
+        let stopped = || io::Error::other("append-only log writer has stopped");
+        let (done, result) = bounded(1);
+        self.sender
+            .send(Message::Rewrite { path: temp, done })
+            .map_err(|_| stopped())?;
+        result.recv().map_err(|_| stopped())??;
+        Ok(keys)
+    }
+}
+
+/// The log file, owned by the writer thread
+struct Writer {
+    file: BufWriter<File>,
+    path: PathBuf,
+    policy: FsyncPolicy,
+    // Database the log has selected
+    db: Option<usize>,
+    last_sync: Instant,
+    // Written since the last fsync
+    dirty: bool,
+    ok: Arc<AtomicBool>,
+}
+
+impl Writer {
+    fn run(mut self, receiver: Receiver<Message>) {
+        loop {
+            let message = match receiver.recv_timeout(Duration::from_secs(1)) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => {
+                    // An idle log still gets its last writes synced
+                    let result = self.sync(false);
+                    self.record(result);
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // Write whatever else is queued before flushing once
+            self.handle(message);
+            for message in receiver.try_iter().take(QUEUE_CAPACITY) {
+                self.handle(message);
+            }
+            let result = self
+                .file
+                .flush()
+                .and_then(|()| self.sync(self.policy == FsyncPolicy::Always));
+            self.record(result);
+        }
+        let result = self.file.flush().and_then(|()| self.sync(true));
+        self.record(result);
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Append { db, command } => {
+                let result = self.write(db, &command);
+                self.record(result);
+            }
+            Message::Rewrite { path, done } => {
+                let _ = done.send(self.swap(&path));
+            }
+        }
+    }
+
+    fn write(&mut self, db: usize, command: &[u8]) -> io::Result<()> {
+        if self.db != Some(db) {
+            self.file.write_all(&encode(&[
+                Bytes::from_static(b"SELECT"),
+                Bytes::from(db.to_string()),
+            ]))?;
+            self.db = Some(db);
+        }
+        self.dirty = true;
+        self.file.write_all(command)
+    }
+
+    /// Fsync if the policy calls for it, or unconditionally with `force`
+    fn sync(&mut self, force: bool) -> io::Result<()> {
+        let due = match self.policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::EverySec => self.last_sync.elapsed() >= Duration::from_secs(1),
+            FsyncPolicy::No => false,
+        };
+        if self.dirty && (force || due) {
+            self.file.get_ref().sync_data()?;
+            self.dirty = false;
+            self.last_sync = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Replace the log with the rewritten one at `path`
+    fn swap(&mut self, path: &Path) -> io::Result<()> {
+        self.file.flush()?;
+        fs::rename(path, &self.path)?;
+        self.file = BufWriter::new(open_append(&self.path)?);
+        // Commands after a snapshot replay from database 0
+        self.db = Some(0);
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Record the outcome of a write, logging only when it starts failing
+    fn record(&self, result: io::Result<()>) {
+        let ok = result.is_ok();
+        if let Err(e) = result {
+            if self.ok.load(Ordering::Relaxed) {
+                error!(
+                    "Writing append-only file {} failed: {}",
+                    self.path.display(),
+                    e
+                );
+            }
+        }
+        self.ok.store(ok, Ordering::Relaxed);
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Encode a command as a RESP array of bulk strings
+fn encode(args: &[Bytes]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_resp_value(
+        &mut buf,
+        &RespValue::Array(Some(
+            args.iter()
+                .map(|arg| RespValue::BulkString(Some(arg.clone())))
+                .collect(),
+        )),
+    );
+    buf
+}
+
+/// The relative expiry in milliseconds a write just set from its own
+/// arguments, or None if it set none
+fn relative_expiry(args: &[Bytes], reply: &RespValue) -> Option<u64> {
+    let name = args.first()?;
+    let number = |arg: Option<&Bytes>| std::str::from_utf8(arg?).ok()?.parse::<u64>().ok();
+    // The EX or PX among a command's options
+    let option = |options: &[Bytes]| {
+        options.windows(2).find_map(|pair| {
+            if pair[0].eq_ignore_ascii_case(b"EX") {
+                number(Some(&pair[1]))?.checked_mul(1000)
+            } else if pair[0].eq_ignore_ascii_case(b"PX") {
+                number(Some(&pair[1]))
+            } else {
+                None
+            }
+        })
+    };
+    let ok = matches!(reply, RespValue::SimpleString(_));
+
+    if name.eq_ignore_ascii_case(b"SET") {
+        let options = args.get(3..)?;
+        let has = |flag: &[u8]| options.iter().any(|option| option.eq_ignore_ascii_case(flag));
+        // A SET that NX or XX held back carries no expiry either; with GET
+        // the reply is the old value, present exactly when XX lets it through
+        let applied = match (has(b"NX"), has(b"XX"), has(b"GET")) {
+            (false, false, _) => true,
+            (_, _, false) => ok,
+            (nx, _, true) => matches!(reply, RespValue::BulkString(None)) == nx,
+        };
+        return option(options).filter(|_| applied);
+    }
+    if name.eq_ignore_ascii_case(b"GETEX") {
+        return option(args.get(2..)?)
+            .filter(|_| matches!(reply, RespValue::BulkString(Some(_))));
+    }
+    if name.eq_ignore_ascii_case(b"SETEX") {
+        return number(args.get(2))?.checked_mul(1000).filter(|_| ok);
+    }
+    if name.eq_ignore_ascii_case(b"PSETEX") {
+        return number(args.get(2)).filter(|_| ok);
+    }
+
+    let set = matches!(reply, RespValue::Integer(1));
+    if name.eq_ignore_ascii_case(b"EXPIRE") {
+        return number(args.get(2))?.checked_mul(1000).filter(|_| set);
+    }
+    if name.eq_ignore_ascii_case(b"PEXPIRE") {
+        return number(args.get(2)).filter(|_| set);
+    }
+    None
+}
+
+fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
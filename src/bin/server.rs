@@ -22,6 +22,22 @@ struct Args {
     #[arg(short = 'd', long)]
     data_path: Option<String>,
 
+    /// Snapshot file for SAVE / BGSAVE, loaded at startup without a data path
+    #[arg(long, default_value = "dump.feox")]
+    dbfilename: String,
+
+    /// Log every write to an append-only file and replay it at startup
+    #[arg(long)]
+    appendonly: bool,
+
+    /// Append-only file used with --appendonly
+    #[arg(long, default_value = "appendonly.aof")]
+    appendfilename: String,
+
+    /// When to fsync the append-only file: always, everysec or no
+    #[arg(long, default_value = "everysec")]
+    appendfsync: String,
+
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
@@ -37,6 +53,34 @@ struct Args {
     /// Password for AUTH command
     #[arg(long)]
     requirepass: Option<String>,
+
+    /// Maximum number of connected clients
+    #[arg(long, default_value_t = 10000)]
+    maxclients: u64,
+
+    /// Pin each worker thread to its own core (Linux)
+    #[arg(long)]
+    cpu_affinity: bool,
+
+    /// Give each worker its own listening socket with SO_REUSEPORT (Linux)
+    #[arg(long)]
+    reuse_port: bool,
+
+    /// Bytes read from a client socket per read call
+    #[arg(long, default_value_t = 16 * 1024)]
+    read_buffer_size: usize,
+
+    /// Port to accept TLS connections on
+    #[arg(long)]
+    tls_port: Option<u16>,
+
+    /// PEM certificate chain for TLS
+    #[arg(long)]
+    tls_cert_file: Option<String>,
+
+    /// PEM private key for TLS
+    #[arg(long)]
+    tls_key_file: Option<String>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -81,6 +125,17 @@ fn main() -> anyhow::Result<()> {
             port: args.port,
             threads,
             data_path: args.data_path,
+            dbfilename: args.dbfilename,
+            appendonly: args.appendonly,
+            appendfilename: args.appendfilename,
+            appendfsync: args.appendfsync,
+            maxclients: args.maxclients,
+            reuse_port: args.reuse_port,
+            read_buffer_size: args.read_buffer_size,
+            cpu_affinity: args.cpu_affinity,
+            tls_port: args.tls_port,
+            tls_cert_file: args.tls_cert_file,
+            tls_key_file: args.tls_key_file,
             ..Default::default()
         };
 
@@ -105,7 +160,8 @@ fn main() -> anyhow::Result<()> {
         warn!(
             "WARNING: Authentication is enabled but server is bound to {}. \
             AUTH credentials will be sent in PLAINTEXT over the network. \
-            Consider binding to localhost only or using SSH tunnels for remote access.",
+            Consider binding to localhost only, using SSH tunnels for remote access, \
+            or having remote clients connect to the TLS port (tls_port).",
             config.bind_addr
         );
     }
@@ -0,0 +1,85 @@
+use crossbeam_channel::{bounded, Receiver, Sender};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Wakes clients blocked in BLPOP / BRPOP when their lists are pushed to
+///
+/// A blocked connection stays parked on its own worker. The hub remembers
+/// which workers have clients waiting on each key, and a push sends the key
+/// to those workers' inboxes; each drains its inbox in the event loop and
+/// retries the pops. Pushes cost one atomic load while nothing is blocked.
+pub struct BlockingHub {
+    blocked: AtomicUsize,
+    // Clients waiting on each key, counted per worker
+    waiting: DashMap<Vec<u8>, HashMap<usize, usize>>,
+    senders: Vec<Sender<Vec<u8>>>,
+    receivers: Vec<Receiver<Vec<u8>>>,
+}
+
+impl BlockingHub {
+    pub fn new(num_threads: usize) -> Arc<Self> {
+        let (senders, receivers) = (0..num_threads).map(|_| bounded(1024)).unzip();
+        Arc::new(Self {
+            blocked: AtomicUsize::new(0),
+            waiting: DashMap::new(),
+            senders,
+            receivers,
+        })
+    }
+
+    /// The inbox of ready keys a worker thread drains
+    pub fn inbox(&self, thread_id: usize) -> Receiver<Vec<u8>> {
+        self.receivers[thread_id].clone()
+    }
+
+    /// Check if any client is blocked
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.blocked.load(Ordering::Relaxed) > 0
+    }
+
+    /// Clients blocked right now, across every worker
+    pub fn blocked_count(&self) -> usize {
+        self.blocked.load(Ordering::Relaxed)
+    }
+
+    pub fn block(&self, thread_id: usize, keys: &[Vec<u8>]) {
+        self.blocked.fetch_add(1, Ordering::Relaxed);
+        for key in keys {
+            *self
+                .waiting
+                .entry(key.clone())
+                .or_default()
+                .entry(thread_id)
+                .or_insert(0) += 1;
+        }
+    }
+
+    pub fn unblock(&self, thread_id: usize, keys: &[Vec<u8>]) {
+        for key in keys {
+            if let Some(mut threads) = self.waiting.get_mut(key) {
+                if let Some(count) = threads.get_mut(&thread_id) {
+                    *count -= 1;
+                    if *count == 0 {
+                        threads.remove(&thread_id);
+                    }
+                }
+            }
+            self.waiting.remove_if(key, |_, threads| threads.is_empty());
+        }
+        self.blocked.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Tell every worker with a client waiting on `key` that it has elements
+    pub fn signal_ready(&self, key: &[u8]) {
+        let Some(threads) = self.waiting.get(key) else {
+            return;
+        };
+        for &thread_id in threads.keys() {
+            // A full inbox already holds wake-ups the worker will act on
+            let _ = self.senders[thread_id].try_send(key.to_vec());
+        }
+    }
+}
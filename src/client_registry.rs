@@ -1,7 +1,9 @@
 use crate::network::Connection;
 use dashmap::DashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 #[derive(Debug, Clone)]
 pub struct ClientInfo {
@@ -14,17 +16,36 @@ pub struct ClientInfo {
     pub flags: Vec<String>,
     pub thread_id: usize,
     pub db: usize,
+    // Channel, pattern and sharded channel subscriptions
+    pub sub: usize,
+    pub psub: usize,
+    pub ssub: usize,
+    pub last_activity: Instant,
+    // Name of the last command run, as CLIENT LIST shows it
+    pub cmd: &'static str,
+    // Client library details sent with CLIENT SETINFO
+    pub lib_name: Option<String>,
+    pub lib_ver: Option<String>,
 }
 
 /// Global registry for all client connections
+///
+/// CLIENT KILL marks the matched connections here; the worker owning each
+/// one picks it up on its next event loop tick and closes it. Checking for
+/// marks costs one atomic load while none are pending.
 pub struct ClientRegistry {
     clients: Arc<DashMap<usize, ClientInfo>>,
+    // Connections to close, with the worker thread that owns each
+    killed: DashMap<usize, usize>,
+    pending_kills: AtomicUsize,
 }
 
 impl ClientRegistry {
     pub fn new() -> Self {
         Self {
             clients: Arc::new(DashMap::new()),
+            killed: DashMap::new(),
+            pending_kills: AtomicUsize::new(0),
         }
     }
 
@@ -39,7 +60,14 @@ impl ClientRegistry {
             commands_processed: conn.commands_processed,
             flags: conn.flags.clone(),
             thread_id,
-            db: 0,
+            db: conn.db,
+            sub: conn.channel_subscriptions,
+            psub: conn.pattern_subscriptions,
+            ssub: conn.shard_subscriptions,
+            last_activity: conn.last_activity(),
+            cmd: conn.last_command(),
+            lib_name: None,
+            lib_ver: None,
         };
         self.clients.insert(conn.connection_id, info);
     }
@@ -50,12 +78,46 @@ impl ClientRegistry {
             entry.name = conn.client_name.clone();
             entry.commands_processed = conn.commands_processed;
             entry.flags = conn.flags.clone();
+            entry.db = conn.db;
+            entry.sub = conn.channel_subscriptions;
+            entry.psub = conn.pattern_subscriptions;
+            entry.ssub = conn.shard_subscriptions;
+            entry.last_activity = conn.last_activity();
+            entry.cmd = conn.last_command();
+        }
+    }
+
+    /// Set a client's name right away, ahead of the next `update`
+    pub fn set_name(&self, connection_id: usize, name: Option<String>) {
+        if let Some(mut entry) = self.clients.get_mut(&connection_id) {
+            entry.name = name;
+        }
+    }
+
+    /// Set a client's library name and version, as CLIENT SETINFO sends
+    /// them; None leaves the other unchanged
+    pub fn set_lib_info(
+        &self,
+        connection_id: usize,
+        lib_name: Option<String>,
+        lib_ver: Option<String>,
+    ) {
+        if let Some(mut entry) = self.clients.get_mut(&connection_id) {
+            if let Some(lib_name) = lib_name {
+                entry.lib_name = Some(lib_name).filter(|name| !name.is_empty());
+            }
+            if let Some(lib_ver) = lib_ver {
+                entry.lib_ver = Some(lib_ver).filter(|ver| !ver.is_empty());
+            }
         }
     }
 
     /// Unregister a client connection
     pub fn unregister(&self, connection_id: usize) {
         self.clients.remove(&connection_id);
+        if self.killed.remove(&connection_id).is_some() {
+            self.pending_kills.fetch_sub(1, Ordering::Relaxed);
+        }
     }
 
     /// Get all client information
@@ -71,7 +133,8 @@ impl ClientRegistry {
         self.clients.get(&connection_id).map(|e| e.clone())
     }
 
-    /// Kill clients matching criteria
+    /// Mark clients matching criteria to be closed by their workers,
+    /// returning their ids
     pub fn kill_clients(
         &self,
         filter_id: Option<usize>,
@@ -99,29 +162,41 @@ impl ClientRegistry {
             }
 
             if let Some(client_type) = filter_type {
+                let is_pubsub = client.flags.contains(&"pubsub".to_string());
                 match client_type {
-                    "normal" => {
-                        if !client.flags.contains(&"pubsub".to_string()) {
-                            should_kill = true;
-                        }
-                    }
-                    "pubsub" => {
-                        if client.flags.contains(&"pubsub".to_string()) {
-                            should_kill = true;
-                        }
-                    }
+                    "normal" if !is_pubsub => should_kill = true,
+                    "pubsub" if is_pubsub => should_kill = true,
                     _ => {}
                 }
             }
 
             if should_kill {
                 to_kill.push(client.id);
+                if self.killed.insert(client.id, client.thread_id).is_none() {
+                    self.pending_kills.fetch_add(1, Ordering::Relaxed);
+                }
             }
         }
 
         to_kill
     }
 
+    /// Take the killed connections owned by worker `thread_id`
+    pub fn take_kills(&self, thread_id: usize) -> Vec<usize> {
+        if self.pending_kills.load(Ordering::Relaxed) == 0 {
+            return Vec::new();
+        }
+        let mut taken = Vec::new();
+        self.killed.retain(|&id, &mut owner| {
+            if owner == thread_id {
+                taken.push(id);
+            }
+            owner != thread_id
+        });
+        self.pending_kills.fetch_sub(taken.len(), Ordering::Relaxed);
+        taken
+    }
+
     /// Count total clients
     pub fn client_count(&self) -> usize {
         self.clients.len()
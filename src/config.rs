@@ -1,6 +1,11 @@
+use crate::acl::{valid_pattern, Acl};
+use crate::pubsub::parse_notify_flags;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,21 +22,78 @@ pub struct Config {
     /// Path to FeOx data file (None for memory-only)
     pub data_path: Option<String>,
 
+    /// Snapshot file written by SAVE / BGSAVE, and loaded at startup in
+    /// memory-only mode
+    #[serde(default = "default_dbfilename")]
+    pub dbfilename: String,
+
+    /// Log every write to an append-only file, replayed at startup
+    #[serde(default)]
+    pub appendonly: bool,
+
+    /// Append-only file, used when `appendonly` is set
+    #[serde(default = "default_appendfilename")]
+    pub appendfilename: String,
+
+    /// When the append-only file is fsynced: `always` after every batch of
+    /// writes, `everysec` at most once a second, or `no` to leave it to the OS
+    #[serde(default = "default_appendfsync")]
+    pub appendfsync: String,
+
     /// Maximum connections per thread
     pub max_connections_per_thread: usize,
 
+    /// Maximum number of connected clients across all threads
+    #[serde(default = "default_maxclients")]
+    pub maxclients: u64,
+
     /// Connection buffer size (per connection)
     pub connection_buffer_size: usize,
 
+    /// Bytes read from a client socket per `read` call. The buffer is shared
+    /// by all of a worker's connections, so a bigger one costs memory once
+    /// per worker, not per connection, and saves syscalls on large pipelines.
+    #[serde(default = "default_read_buffer_size")]
+    pub read_buffer_size: usize,
+
     /// TCP nodelay
     pub tcp_nodelay: bool,
 
-    /// Pipeline queue depth
+    /// Give each worker its own listening socket with SO_REUSEPORT, so the
+    /// kernel balances connections across workers (Linux only)
+    #[serde(default)]
+    pub reuse_port: bool,
+
+    /// Replies a client may leave unread before its connection stops
+    /// running further pipelined commands
     pub max_pipeline_depth: usize,
 
-    /// Enable NUMA awareness
+    /// Bytes of unread replies past which a connection stops running
+    /// further pipelined commands
+    #[serde(default = "default_max_reply_buffer_size")]
+    pub max_reply_buffer_size: usize,
+
+    /// Longest bulk string a client may send, as Redis `proto-max-bulk-len`
+    #[serde(default = "default_proto_max_bulk_len")]
+    pub proto_max_bulk_len: usize,
+
+    /// Most arguments a client may send in one command
+    #[serde(default = "default_proto_max_multibulk_len")]
+    pub proto_max_multibulk_len: usize,
+
+    /// Bytes of a partly received command a connection may buffer before it
+    /// is closed, as Redis `client-query-buffer-limit`
+    #[serde(default = "default_client_query_buffer_limit")]
+    pub client_query_buffer_limit: usize,
+
+    /// Enable NUMA awareness; pinned workers fill one node's cores before
+    /// moving to the next
     pub numa_aware: bool,
 
+    /// Pin each worker thread to its own core (Linux only)
+    #[serde(default)]
+    pub cpu_affinity: bool,
+
     /// Maximum memory for FeOx store (per shard)
     pub max_memory_per_shard: Option<usize>,
 
@@ -45,30 +107,274 @@ pub struct Config {
     /// Log level
     pub log_level: String,
 
+    /// Close client connections idle for this many seconds (0 disables)
+    #[serde(default)]
+    pub timeout: u64,
+
+    /// Keyspace notification classes to publish, as in Redis
+    /// `notify-keyspace-events` (empty disables them)
+    #[serde(default)]
+    pub notify_keyspace_events: String,
+
+    /// Log commands taking at least this many microseconds (negative disables)
+    #[serde(default = "default_slowlog_log_slower_than")]
+    pub slowlog_log_slower_than: i64,
+
+    /// Number of entries kept in the slow log
+    #[serde(default = "default_slowlog_max_len")]
+    pub slowlog_max_len: u64,
+
+    /// Number of logical databases selectable with SELECT
+    #[serde(default = "default_databases")]
+    pub databases: usize,
+
+    /// Port for TLS connections (None disables TLS)
+    #[serde(default)]
+    pub tls_port: Option<u16>,
+
+    /// PEM certificate chain presented to TLS clients
+    #[serde(default)]
+    pub tls_cert_file: Option<String>,
+
+    /// PEM private key for the TLS certificate
+    #[serde(default)]
+    pub tls_key_file: Option<String>,
+
     /// Password for AUTH command (Redis-compatible)
     /// None means no authentication required
     #[serde(skip_serializing_if = "Option::is_none")]
     pub requirepass: Option<String>,
+
+    /// Users that can log in with `AUTH username password`, each limited to
+    /// the commands matching its patterns; an entry named `default` replaces
+    /// the default user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acl: Vec<AclUser>,
+
+    /// Settings changed at runtime via CONFIG SET, shared by every clone
+    #[serde(skip)]
+    pub runtime: Arc<RuntimeConfig>,
+
+    /// Users resolved from `requirepass` and `acl`, shared by every clone
+    #[serde(skip)]
+    pub acl_users: Arc<Acl>,
+}
+
+/// A user in the `acl` config section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AclUser {
+    pub name: String,
+
+    /// Password to AUTH with; a user without one or `nopass` cannot log in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+
+    /// Accept any password
+    #[serde(default)]
+    pub nopass: bool,
+
+    /// Commands the user may run, as lowercase globs such as `h*` or the
+    /// categories `@all`, `@read` and `@write`
+    #[serde(default = "default_acl_commands")]
+    pub commands: Vec<String>,
+}
+
+fn default_acl_commands() -> Vec<String> {
+    vec!["@all".to_string()]
+}
+
+fn default_dbfilename() -> String {
+    "dump.feox".to_string()
+}
+
+fn default_appendfilename() -> String {
+    "appendonly.aof".to_string()
+}
+
+fn default_appendfsync() -> String {
+    "everysec".to_string()
+}
+
+fn default_databases() -> usize {
+    16
+}
+
+fn default_read_buffer_size() -> usize {
+    16 * 1024
+}
+
+fn default_max_reply_buffer_size() -> usize {
+    4 * 1024 * 1024
+}
+
+fn default_proto_max_bulk_len() -> usize {
+    512 * 1024 * 1024
+}
+
+fn default_proto_max_multibulk_len() -> usize {
+    1024 * 1024
+}
+
+fn default_client_query_buffer_limit() -> usize {
+    1024 * 1024 * 1024
+}
+
+fn default_maxclients() -> u64 {
+    10000
+}
+
+fn default_slowlog_log_slower_than() -> i64 {
+    10000
+}
+
+fn default_slowlog_max_len() -> u64 {
+    128
+}
+
+/// Config values that can be changed while the server is running
+#[derive(Debug, Default)]
+pub struct RuntimeConfig {
+    maxmemory: AtomicU64,
+    timeout: AtomicU64,
+    maxclients: AtomicU64,
+    slowlog_log_slower_than: AtomicI64,
+    slowlog_max_len: AtomicU64,
+    notify_flags: AtomicU32,
+    // `maxmemory-policy`, `appendonly` and `save`, which clients such as job
+    // queues set and read back but the server does not act on
+    inert: RwLock<HashMap<&'static str, String>>,
+}
+
+impl RuntimeConfig {
+    /// Seed the runtime values from a static config
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            maxmemory: AtomicU64::new(config.max_memory_per_shard.unwrap_or(0) as u64),
+            timeout: AtomicU64::new(config.timeout),
+            maxclients: AtomicU64::new(config.maxclients),
+            slowlog_log_slower_than: AtomicI64::new(config.slowlog_log_slower_than),
+            slowlog_max_len: AtomicU64::new(config.slowlog_max_len),
+            notify_flags: AtomicU32::new(
+                parse_notify_flags(&config.notify_keyspace_events).unwrap_or(0),
+            ),
+            inert: RwLock::new(HashMap::from([
+                ("maxmemory-policy", "noeviction".to_string()),
+                (
+                    "appendonly",
+                    if config.appendonly { "yes" } else { "no" }.to_string(),
+                ),
+                ("save", String::new()),
+            ])),
+        }
+    }
+
+    pub fn maxmemory(&self) -> u64 {
+        self.maxmemory.load(Ordering::Relaxed)
+    }
+
+    pub fn set_maxmemory(&self, bytes: u64) {
+        self.maxmemory.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Idle timeout in seconds (0 disables)
+    pub fn timeout(&self) -> u64 {
+        self.timeout.load(Ordering::Relaxed)
+    }
+
+    pub fn set_timeout(&self, secs: u64) {
+        self.timeout.store(secs, Ordering::Relaxed);
+    }
+
+    pub fn maxclients(&self) -> u64 {
+        self.maxclients.load(Ordering::Relaxed)
+    }
+
+    pub fn set_maxclients(&self, maxclients: u64) {
+        self.maxclients.store(maxclients, Ordering::Relaxed);
+    }
+
+    /// Slow log threshold in microseconds (negative disables)
+    pub fn slowlog_log_slower_than(&self) -> i64 {
+        self.slowlog_log_slower_than.load(Ordering::Relaxed)
+    }
+
+    pub fn set_slowlog_log_slower_than(&self, micros: i64) {
+        self.slowlog_log_slower_than
+            .store(micros, Ordering::Relaxed);
+    }
+
+    pub fn slowlog_max_len(&self) -> u64 {
+        self.slowlog_max_len.load(Ordering::Relaxed)
+    }
+
+    pub fn set_slowlog_max_len(&self, len: u64) {
+        self.slowlog_max_len.store(len, Ordering::Relaxed);
+    }
+
+    /// Keyspace notification flags, see `crate::pubsub::parse_notify_flags`
+    pub fn notify_flags(&self) -> u32 {
+        self.notify_flags.load(Ordering::Relaxed)
+    }
+
+    pub fn set_notify_flags(&self, flags: u32) {
+        self.notify_flags.store(flags, Ordering::Relaxed);
+    }
+
+    /// Value last set for a parameter the server does not act on
+    pub fn inert(&self, name: &str) -> String {
+        let inert = self.inert.read().unwrap_or_else(|e| e.into_inner());
+        inert.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn set_inert(&self, name: &'static str, value: String) {
+        let mut inert = self.inert.write().unwrap_or_else(|e| e.into_inner());
+        inert.insert(name, value);
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self {
+        let mut config = Self {
             bind_addr: "127.0.0.1".to_string(),
             port: 6379,
             threads: num_cpus::get(),
             data_path: None,
+            dbfilename: default_dbfilename(),
+            appendonly: false,
+            appendfilename: default_appendfilename(),
+            appendfsync: default_appendfsync(),
             max_connections_per_thread: 10000,
+            maxclients: default_maxclients(),
             connection_buffer_size: 16 * 1024, // 16KB
+            read_buffer_size: default_read_buffer_size(),
             tcp_nodelay: true,
+            reuse_port: false,
             max_pipeline_depth: 1000,
+            max_reply_buffer_size: default_max_reply_buffer_size(),
+            proto_max_bulk_len: default_proto_max_bulk_len(),
+            proto_max_multibulk_len: default_proto_max_multibulk_len(),
+            client_query_buffer_limit: default_client_query_buffer_limit(),
             numa_aware: false,
+            cpu_affinity: false,
             max_memory_per_shard: Some(1024 * 1024 * 1024), // 1GB per shard
             enable_ttl: true,
             file_size: Some(10 * 1024 * 1024 * 1024), // 10GB default for persistent storage
             log_level: "info".to_string(),
+            timeout: 0,
+            slowlog_log_slower_than: default_slowlog_log_slower_than(),
+            slowlog_max_len: default_slowlog_max_len(),
+            databases: default_databases(),
+            notify_keyspace_events: String::new(),
+            tls_port: None,
+            tls_cert_file: None,
+            tls_key_file: None,
             requirepass: None,
-        }
+            acl: Vec::new(),
+            runtime: Arc::default(),
+            acl_users: Arc::default(),
+        };
+        config.runtime = Arc::new(RuntimeConfig::from_config(&config));
+        config
     }
 }
 
@@ -98,6 +404,8 @@ impl Config {
             }
         }
 
+        config.runtime = Arc::new(RuntimeConfig::from_config(&config));
+        config.acl_users = Arc::new(Acl::from_config(&config));
         Ok(config)
     }
 
@@ -118,29 +426,95 @@ impl Config {
             anyhow::bail!("port must be > 0");
         }
 
+        if self.maxclients == 0 {
+            anyhow::bail!("maxclients must be > 0");
+        }
+
+        if self.databases == 0 {
+            anyhow::bail!("databases must be > 0");
+        }
+
+        if parse_notify_flags(&self.notify_keyspace_events).is_none() {
+            anyhow::bail!("notify_keyspace_events contains an unknown flag");
+        }
+
+        if let Some(tls_port) = self.tls_port {
+            if tls_port == 0 || tls_port == self.port {
+                anyhow::bail!("tls_port must be > 0 and differ from port");
+            }
+            if self.tls_cert_file.is_none() || self.tls_key_file.is_none() {
+                anyhow::bail!("tls_port requires tls_cert_file and tls_key_file");
+            }
+        }
+
+        if self.appendonly && self.data_path.is_some() {
+            anyhow::bail!(
+                "appendonly needs memory-only mode; the data file already persists writes"
+            );
+        }
+
+        if !matches!(self.appendfsync.as_str(), "always" | "everysec" | "no") {
+            anyhow::bail!("appendfsync must be always, everysec or no");
+        }
+
+        if self.max_pipeline_depth == 0 || self.max_reply_buffer_size == 0 {
+            anyhow::bail!("max_pipeline_depth and max_reply_buffer_size must be > 0");
+        }
+
+        let mut names = std::collections::HashSet::new();
+        for user in &self.acl {
+            if user.name.is_empty() || user.name.contains(char::is_whitespace) {
+                anyhow::bail!("acl user names must be non-empty and contain no spaces");
+            }
+            if !names.insert(user.name.as_str()) {
+                anyhow::bail!("acl user '{}' is defined twice", user.name);
+            }
+            if let Some(pattern) = user.commands.iter().find(|p| !valid_pattern(p)) {
+                anyhow::bail!(
+                    "acl user '{}' has unknown category '{}'",
+                    user.name,
+                    pattern
+                );
+            }
+        }
+
         if self.connection_buffer_size < 1024 {
             anyhow::bail!("connection_buffer_size must be >= 1024");
         }
 
+        if self.read_buffer_size < 1024 {
+            anyhow::bail!("read_buffer_size must be >= 1024");
+        }
+
+        if self.proto_max_bulk_len < 1024 * 1024 {
+            anyhow::bail!("proto_max_bulk_len must be >= 1048576");
+        }
+
+        if self.proto_max_multibulk_len < 1024 {
+            anyhow::bail!("proto_max_multibulk_len must be >= 1024");
+        }
+
+        if self.client_query_buffer_limit < 1024 * 1024 {
+            anyhow::bail!("client_query_buffer_limit must be >= 1048576");
+        }
+
         Ok(())
     }
 
     /// Check if authentication is required
     pub fn auth_required(&self) -> bool {
-        self.requirepass.is_some()
+        self.acl_users.default_user().requires_password()
     }
 
-    /// Validate password (constant-time comparison)
+    /// Validate the default user's password (constant-time comparison)
     pub fn check_password(&self, password: &str) -> bool {
-        match &self.requirepass {
-            Some(correct) => constant_time_eq(password.as_bytes(), correct.as_bytes()),
-            None => false,
-        }
+        let user = self.acl_users.default_user();
+        user.requires_password() && user.check_password(password)
     }
 }
 
 /// Constant-time string comparison to prevent timing attacks
-fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
         return false;
     }
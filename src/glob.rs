@@ -0,0 +1,139 @@
+/// Check whether `text` matches the glob `pattern`, as Redis does
+///
+/// Supports `*`, `?`, character classes such as `[abc]`, `[a-z]` and `[^x]`,
+/// and `\` to match the next character literally, inside or outside a class.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let mut p = 0;
+    let mut t = 0;
+    // Pattern and text positions to retry from if matching after the last
+    // `*` fails; the star then swallows one more character
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() {
+            if pattern[p] == b'*' {
+                p += 1;
+                backtrack = Some((p, t));
+                continue;
+            }
+            if let Some(next) = match_token(pattern, p, text[t]) {
+                p = next;
+                t += 1;
+                continue;
+            }
+        }
+
+        match backtrack {
+            Some((star_p, star_t)) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, t));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// Position just past the character class that opens with `[` at `start`
+pub fn class_end(pattern: &[u8], start: usize) -> usize {
+    match_class(pattern, start + 1, 0).1
+}
+
+/// Match one character against the token at `p`, returning the position of
+/// the next token if it matches
+fn match_token(pattern: &[u8], p: usize, c: u8) -> Option<usize> {
+    match pattern[p] {
+        b'?' => Some(p + 1),
+        b'[' => {
+            let (matched, next) = match_class(pattern, p + 1, c);
+            matched.then_some(next)
+        }
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        literal => (literal == c).then_some(p + 1),
+    }
+}
+
+/// Match a character against the class body starting at `p`, just past `[`
+///
+/// An unterminated class runs to the end of the pattern, as in Redis.
+fn match_class(pattern: &[u8], mut p: usize, c: u8) -> (bool, usize) {
+    let negated = pattern.get(p) == Some(&b'^');
+    if negated {
+        p += 1;
+    }
+
+    let mut matched = false;
+    while p < pattern.len() {
+        match pattern[p] {
+            b']' => {
+                p += 1;
+                break;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == c;
+                p += 2;
+            }
+            start if p + 2 < pattern.len() && pattern[p + 1] == b'-' => {
+                let end = pattern[p + 2];
+                let (low, high) = if start <= end {
+                    (start, end)
+                } else {
+                    (end, start)
+                };
+                matched |= (low..=high).contains(&c);
+                p += 3;
+            }
+            literal => {
+                matched |= literal == c;
+                p += 1;
+            }
+        }
+    }
+
+    (matched != negated, p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(pattern.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn ranges() {
+        assert!(matches("[a-c]", "a"));
+        assert!(matches("[a-c]", "b"));
+        assert!(matches("[a-c]", "c"));
+        assert!(!matches("[a-c]", "d"));
+        assert!(!matches("[a-c]", "-"));
+        // A reversed range matches the same characters
+        assert!(matches("[c-a]", "b"));
+        assert!(matches("user:[0-9]*", "user:42"));
+        assert!(!matches("user:[0-9]*", "user:x1"));
+    }
+
+    #[test]
+    fn negated_classes() {
+        assert!(matches("[^x]", "y"));
+        assert!(!matches("[^x]", "x"));
+        assert!(!matches("[^x]", ""));
+        assert!(!matches("[^x]", "yy"));
+        assert!(matches("[^a-c]", "d"));
+        assert!(!matches("[^a-c]", "b"));
+    }
+
+    #[test]
+    fn escaped_brackets() {
+        assert!(matches(r"\[a\]", "[a]"));
+        assert!(!matches(r"\[a\]", "a"));
+        assert!(matches(r"[\]]", "]"));
+        assert!(matches(r"[\[]", "["));
+        assert!(!matches(r"[\]]", "a"));
+        assert!(matches(r"key\*", "key*"));
+        assert!(!matches(r"key\*", "keys"));
+    }
+}
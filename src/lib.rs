@@ -5,11 +5,23 @@
 //!
 //! # Architecture
 //!
-//! - Thread-per-core model with CPU affinity
+//! - Thread-per-core model with optional CPU affinity
 //! - mio-based event loop for cross-platform async I/O
 //! - Zero-copy RESP protocol implementation
 //! - Lock-free data structures where possible
 
+/// Users, passwords and command permissions
+pub mod acl;
+
+/// CPU pinning for worker threads
+pub mod affinity;
+
+/// Append-only log of write commands
+pub mod aof;
+
+/// Parking for clients blocked in BLPOP / BRPOP
+pub mod blocking;
+
 /// Client registry for connection management
 pub mod client_registry;
 
@@ -19,6 +31,12 @@ pub mod config;
 /// Error types and result aliases
 pub mod error;
 
+/// Glob pattern matching for keys and channels
+pub mod glob;
+
+/// MONITOR command streaming
+pub mod monitor;
+
 /// Network layer for connection management
 pub mod network;
 
@@ -31,6 +49,21 @@ pub mod pubsub;
 /// Core server implementation
 pub mod server;
 
+/// Lua scripting for EVAL / EVALSHA
+pub mod scripting;
+
+/// Keyspace snapshots for SAVE / BGSAVE
+pub mod snapshot;
+
+/// Log of slow commands for SLOWLOG
+pub mod slowlog;
+
+/// Per-command statistics for INFO
+pub mod stats;
+
+/// Version counters for WATCH
+pub mod watch;
+
 pub use client_registry::ClientRegistry;
 pub use config::Config;
 pub use error::{Error, Result};
@@ -0,0 +1,101 @@
+use bytes::Bytes;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Fans the commands every worker processes out to MONITOR connections
+///
+/// Each worker owns an inbox it drains in its event loop and writes to its
+/// own monitors. Nothing is formatted or sent unless a monitor is connected.
+pub struct MonitorHub {
+    monitors: AtomicUsize,
+    thread_monitors: Vec<AtomicUsize>,
+    senders: Vec<Sender<Bytes>>,
+    receivers: Vec<Receiver<Bytes>>,
+}
+
+impl MonitorHub {
+    pub fn new(num_threads: usize) -> Arc<Self> {
+        let (senders, receivers) = (0..num_threads).map(|_| bounded(1024)).unzip();
+        Arc::new(Self {
+            monitors: AtomicUsize::new(0),
+            thread_monitors: (0..num_threads).map(|_| AtomicUsize::new(0)).collect(),
+            senders,
+            receivers,
+        })
+    }
+
+    /// The inbox a worker thread drains for its monitors
+    pub fn inbox(&self, thread_id: usize) -> Receiver<Bytes> {
+        self.receivers[thread_id].clone()
+    }
+
+    /// Check if any monitor is connected
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.monitors.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn add_monitor(&self, thread_id: usize) {
+        self.thread_monitors[thread_id].fetch_add(1, Ordering::Relaxed);
+        self.monitors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn remove_monitor(&self, thread_id: usize) {
+        self.thread_monitors[thread_id].fetch_sub(1, Ordering::Relaxed);
+        self.monitors.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Send a processed command to every thread with a monitor
+    pub fn feed(&self, db: usize, addr: Option<SocketAddr>, args: &[Bytes]) {
+        let line = Bytes::from(format_monitor_line(db, addr, args));
+        for (thread_id, sender) in self.senders.iter().enumerate() {
+            if self.thread_monitors[thread_id].load(Ordering::Relaxed) > 0 {
+                // A monitor that falls this far behind just misses lines
+                let _ = sender.try_send(line.clone());
+            }
+        }
+    }
+}
+
+/// Format a command as `<ts> [db addr] "CMD" "arg"...`, as Redis does
+fn format_monitor_line(db: usize, addr: Option<SocketAddr>, args: &[Bytes]) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let addr = addr.map(|a| a.to_string()).unwrap_or_default();
+
+    let mut line = format!(
+        "{}.{:06} [{} {}]",
+        now.as_secs(),
+        now.subsec_micros(),
+        db,
+        addr
+    );
+    for arg in args {
+        line.push(' ');
+        quote_arg(&mut line, arg);
+    }
+    line
+}
+
+/// Quote an argument with C-style escapes for unprintable bytes
+fn quote_arg(out: &mut String, arg: &[u8]) {
+    out.push('"');
+    for &b in arg {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x07 => out.push_str("\\a"),
+            0x08 => out.push_str("\\b"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    out.push('"');
+}
@@ -1,14 +1,20 @@
+use crate::acl::{User, DEFAULT_USER};
+use crate::aof::AppendLog;
+use crate::blocking::BlockingHub;
 use crate::config::Config;
-use crate::protocol::resp::{write_resp_value, RespValue};
-use crate::protocol::{Command, CommandExecutor, RespParser};
+use crate::monitor::MonitorHub;
+use crate::protocol::resp::{write_resp_value_for, RespValue};
+use crate::protocol::{Command, CommandExecutor, RespParser, WRONGTYPE_ERROR};
 use crate::pubsub::PubSubMessage;
+use crate::stats::ServerStats;
+use crate::watch::{WatchTable, WatchVersion};
 use bytes::Bytes;
 use feoxdb::FeoxStore;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::os::fd::RawFd;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, PartialEq)]
 enum TransactionState {
@@ -16,6 +22,38 @@ enum TransactionState {
     Queuing,
 }
 
+/// A BLPOP / BRPOP waiting for one of its lists to get an element
+#[derive(Debug)]
+struct BlockedPop {
+    // Scoped to the database the command ran in
+    keys: Vec<Vec<u8>>,
+    left: bool,
+    since: Instant,
+    deadline: Option<Instant>,
+    // CLIENT REPLY silenced the command, so its eventual reply is dropped
+    silent: bool,
+}
+
+/// Reply mode set with CLIENT REPLY
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReplyMode {
+    On,
+    Off,
+    // Drop the reply to the next command only
+    Skip,
+}
+
+impl ReplyMode {
+    fn parse(arg: &[u8]) -> Option<Self> {
+        match arg.to_ascii_uppercase().as_slice() {
+            b"ON" => Some(ReplyMode::On),
+            b"OFF" => Some(ReplyMode::Off),
+            b"SKIP" => Some(ReplyMode::Skip),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum PubSubOp {
     Subscribe(Vec<Vec<u8>>),
@@ -26,6 +64,13 @@ pub enum PubSubOp {
     PubSubChannels { pattern: Option<Vec<u8>> },
     PubSubNumSub { channels: Vec<Vec<u8>> },
     PubSubNumPat,
+    SSubscribe(Vec<Vec<u8>>),
+    SUnsubscribe(Option<Vec<Vec<u8>>>),
+    // Drop every subscription without sending unsubscribe messages
+    UnsubscribeAll,
+    SPublish { channel: Vec<u8>, message: Vec<u8> },
+    PubSubShardChannels { pattern: Option<Vec<u8>> },
+    PubSubShardNumSub { channels: Vec<Vec<u8>> },
 }
 
 /// Manages a client connection with RESP protocol handling
@@ -41,13 +86,31 @@ pub struct Connection {
     // Authentication state
     authenticated: bool,
     auth_required: bool,
+    // The user commands run as, the default one until AUTH names another
+    user: Arc<User>,
+
+    // Protocol version negotiated via HELLO (2 or 3)
+    resp_version: u8,
+
+    // CLIENT REPLY state; replies written from `suppressed_reply` on are
+    // dropped once the current command finishes
+    reply_mode: ReplyMode,
+    suppressed_reply: Option<usize>,
 
     // Single consolidated write buffer for better performance
     pub write_buffer: Vec<u8>,
     write_position: usize,
+    // Whether the socket is registered for writable events
+    write_pending: bool,
 
-    // Pipeline tracking
+    // Pipeline tracking: replies buffered since the write buffer last
+    // drained, and the limits past which no more commands run
     pipeline_depth: usize,
+    max_pipeline_depth: usize,
+    max_reply_buffer_size: usize,
+    // Commands or unread input may be waiting, held back by full replies
+    // or a blocking pop that has just finished
+    held_back: bool,
 
     // Connection state
     closed: bool,
@@ -55,19 +118,48 @@ pub struct Connection {
     // Pub/Sub state
     pub connection_id: usize,
     pub subscription_count: usize,
+    pub channel_subscriptions: usize,
+    pub pattern_subscriptions: usize,
+    pub shard_subscriptions: usize,
     pending_pubsub_messages: VecDeque<PubSubMessage>,
 
     // Client metadata
     pub client_name: Option<String>,
+    pub db: usize,
     pub client_addr: Option<SocketAddr>,
     pub connected_at: u64, // Unix timestamp in seconds
     pub commands_processed: u64,
+    last_activity: Instant,
+    // Index in `COMMAND_NAMES` of the last command run
+    last_command: Option<usize>,
     pub flags: Vec<String>, // Client flags (e.g., "pubsub", "master", "replica")
+    // Registry CLIENT LIST and INFO read this connection's details from
+    client_registry: Option<Arc<crate::client_registry::ClientRegistry>>,
+
+    // MONITOR state; the hub is shared with the worker thread's id
+    monitor_hub: Option<(Arc<MonitorHub>, usize)>,
+    is_monitor: bool,
+
+    // Server stats and the worker thread's shard, for socket reads/writes
+    stats: Option<(Arc<ServerStats>, usize)>,
+
+    // Blocking list pops; commands after a blocked one stay unparsed until
+    // it is served or times out
+    blocking_hub: Option<(Arc<BlockingHub>, usize)>,
+    blocked: Option<BlockedPop>,
+
+    // Append-only log that successful writes are recorded in
+    append_log: Option<Arc<AppendLog>>,
 
     // Transaction state
     transaction_state: TransactionState,
-    queued_commands: Vec<Command>,
-    watched_keys: HashSet<Vec<u8>>,
+    // A command was refused while queuing, so EXEC must abort
+    transaction_dirty: bool,
+    // Queued with their raw arguments, for the append-only log
+    queued_commands: Vec<(Command, Vec<Bytes>)>,
+    // Each watched key as stored, with its version and whether it existed
+    // at WATCH time
+    watched_keys: HashMap<Vec<u8>, (WatchVersion, bool)>,
 }
 
 impl Connection {
@@ -81,7 +173,79 @@ impl Connection {
         self.executor = self
             .executor
             .clone()
-            .with_client_info(registry, self.connection_id);
+            .with_client_info(Arc::clone(&registry), self.connection_id);
+        self.client_registry = Some(registry);
+    }
+
+    /// Set the slow log that slow commands are recorded in
+    pub fn set_slowlog(&mut self, slowlog: Arc<crate::slowlog::SlowLog>) {
+        self.executor = self.executor.clone().with_slowlog(slowlog);
+    }
+
+    /// Set the server stats this connection records into
+    pub fn set_stats(&mut self, stats: Arc<ServerStats>, thread_id: usize) {
+        self.executor = self
+            .executor
+            .clone()
+            .with_stats(Arc::clone(&stats), thread_id);
+        self.stats = Some((stats, thread_id));
+    }
+
+    /// Set the server's run id reported by INFO
+    pub fn set_run_id(&mut self, run_id: Arc<str>) {
+        self.executor = self.executor.clone().with_run_id(run_id);
+    }
+
+    /// Set where SAVE and BGSAVE write snapshots
+    pub fn set_snapshots(&mut self, snapshots: Arc<crate::snapshot::Snapshots>) {
+        self.executor = self.executor.clone().with_snapshots(snapshots);
+    }
+
+    /// Set the append-only log writes are recorded in
+    pub fn set_append_log(&mut self, append_log: Arc<AppendLog>) {
+        self.executor = self
+            .executor
+            .clone()
+            .with_append_log(Arc::clone(&append_log));
+        self.append_log = Some(append_log);
+    }
+
+    /// Set the hub that commands are streamed to MONITOR clients through
+    pub fn set_monitor_hub(&mut self, hub: Arc<MonitorHub>, thread_id: usize) {
+        self.monitor_hub = Some((hub, thread_id));
+    }
+
+    /// Set the table of watched keys shared by every connection
+    pub fn set_watch_table(&mut self, watches: Arc<WatchTable>) {
+        self.executor = self.executor.clone().with_watch_table(watches);
+    }
+
+    /// Set the hub that parks this connection in BLPOP / BRPOP
+    pub fn set_blocking_hub(&mut self, hub: Arc<BlockingHub>, thread_id: usize) {
+        self.executor = self.executor.clone().with_blocking_hub(Arc::clone(&hub));
+        self.blocking_hub = Some((hub, thread_id));
+    }
+
+    /// Check if this connection is streaming commands via MONITOR
+    pub fn is_monitor(&self) -> bool {
+        self.is_monitor
+    }
+
+    #[inline]
+    fn monitors_active(&self) -> bool {
+        self.monitor_hub
+            .as_ref()
+            .is_some_and(|(hub, _)| hub.is_active())
+    }
+
+    /// Set the pub/sub registry INFO reports channel and pattern counts from
+    pub fn set_pubsub_registry(&mut self, registry: Arc<crate::pubsub::GlobalRegistry>) {
+        self.executor = self.executor.clone().with_pubsub_registry(registry);
+    }
+
+    /// Set the keyspace notifier used to publish key events
+    pub fn set_notifier(&mut self, notifier: Arc<crate::pubsub::KeyspaceNotifier>) {
+        self.executor = self.executor.clone().with_notifier(notifier);
     }
 
     /// Create a new connection handler with address
@@ -106,25 +270,51 @@ impl Connection {
 
         Self {
             fd,
-            parser: RespParser::new(),
+            parser: RespParser::with_limits(
+                config.proto_max_bulk_len,
+                config.proto_max_multibulk_len,
+                config.client_query_buffer_limit,
+            ),
             executor,
             authenticated: !auth_required, // If no auth required, consider authenticated
             auth_required,
+            user: Arc::clone(config.acl_users.default_user()),
+            resp_version: 2,
+            reply_mode: ReplyMode::On,
+            suppressed_reply: None,
             write_buffer: Vec::with_capacity(buffer_size),
             write_position: 0,
+            write_pending: false,
             pipeline_depth: 0,
+            max_pipeline_depth: config.max_pipeline_depth,
+            max_reply_buffer_size: config.max_reply_buffer_size,
+            held_back: false,
             closed: false,
             connection_id,
             subscription_count: 0,
+            channel_subscriptions: 0,
+            pattern_subscriptions: 0,
+            shard_subscriptions: 0,
             pending_pubsub_messages: VecDeque::new(),
             client_name: None,
+            db: 0,
             client_addr: addr,
             connected_at: now,
             commands_processed: 0,
+            last_activity: Instant::now(),
+            last_command: None,
             flags: Vec::new(),
+            monitor_hub: None,
+            is_monitor: false,
+            client_registry: None,
+            stats: None,
+            blocking_hub: None,
+            blocked: None,
+            append_log: None,
             transaction_state: TransactionState::None,
+            transaction_dirty: false,
             queued_commands: Vec::new(),
-            watched_keys: HashSet::new(),
+            watched_keys: HashMap::new(),
         }
     }
 
@@ -140,6 +330,8 @@ impl Connection {
         if !self.closed {
             self.closed = true;
         }
+        self.unblock();
+        self.stop_monitor();
     }
 
     /// Set authentication status
@@ -152,147 +344,401 @@ impl Connection {
         !self.auth_required || self.authenticated
     }
 
+    /// Log in as `user`, whose permissions apply from the next command
+    pub fn set_user(&mut self, user: Arc<User>) {
+        self.executor.set_user(Arc::clone(&user));
+        self.user = user;
+        self.set_authenticated(true);
+    }
+
+    /// The user this connection is logged in as
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
     /// Process incoming data with inline execution
     /// Returns pub/sub operations that need to be executed
     pub fn process_read(&mut self, data: &[u8]) -> crate::error::Result<Vec<PubSubOp>> {
-        let mut pubsub_ops = Vec::new();
+        self.last_activity = Instant::now();
+        if let Some((stats, shard)) = &self.stats {
+            stats.record_read(*shard);
+        }
 
         // Feed data to parser
         self.parser.feed(data);
 
+        self.process_pending()
+    }
+
+    /// Run the commands already buffered in the parser, e.g. those pipelined
+    /// behind a blocking pop that has just finished
+    pub fn process_pending(&mut self) -> crate::error::Result<Vec<PubSubOp>> {
+        let mut pubsub_ops = Vec::new();
+
         // Only clear buffer if all previous writes have been consumed
         if self.write_position >= self.write_buffer.len() {
             self.write_buffer.clear();
             self.write_position = 0;
+            self.pipeline_depth = 0;
         }
 
-        // Parse and execute commands inline
-        while let Some(resp_value) = self
-            .parser
-            .parse_next()
-            .map_err(crate::error::Error::Protocol)?
-        {
+        // Parse and execute commands inline, stopping at a blocked pop or
+        // once the client has too many replies left to read
+        self.held_back = false;
+        while self.blocked.is_none() && !self.replies_full() {
+            let resp_value = match self.parser.parse_next() {
+                Ok(Some(resp_value)) => resp_value,
+                Ok(None) => break,
+                Err(e) => {
+                    // Like Redis, report the error and close once it is sent
+                    let reason = e.strip_prefix("Protocol error: ").unwrap_or(&e);
+                    write_resp_value_for(
+                        &mut self.write_buffer,
+                        &RespValue::Error(format!("ERR Protocol error: {}", reason)),
+                        self.resp_version,
+                    );
+                    self.closed = true;
+                    break;
+                }
+            };
+
             // Update command counter
             self.commands_processed += 1;
 
-            // Fast-path for common commands (SET/GET) if not in transaction
-            if self.transaction_state == TransactionState::None && self.try_fast_path(&resp_value) {
+            self.discard_suppressed_reply();
+            self.suppressed_reply = match self.reply_mode {
+                ReplyMode::On => None,
+                ReplyMode::Off => Some(self.write_buffer.len()),
+                ReplyMode::Skip => {
+                    self.reply_mode = ReplyMode::On;
+                    Some(self.write_buffer.len())
+                }
+            };
+
+            // Fast-path for common commands (SET/GET) if not in transaction;
+            // it reads unprefixed keys, so only database 0 can use it, and it
+            // skips keyspace notifications, the append-only log and the
+            // auth, permission and pub/sub mode checks
+            if self.transaction_state == TransactionState::None
+                && self.authenticated
+                && !self.is_in_pubsub_mode()
+                && self.executor.unrestricted()
+                && self.db == 0
+                && !self.executor.notifications_active()
+                && !self.monitors_active()
+                && self.append_log.is_none()
+                && self.try_fast_path(&resp_value)
+            {
                 self.pipeline_depth += 1;
                 continue;
             }
 
-            // Parse command (slow path)
-            let command = Command::from_resp(resp_value).map_err(crate::error::Error::Protocol)?;
+            // Keep the raw arguments for the slow log, monitors and the
+            // append-only log; cloning only bumps their reference counts
+            let slowlog_enabled = self.executor.slowlog_enabled();
+            let command_line =
+                if slowlog_enabled || self.monitors_active() || self.append_log.is_some() {
+                    command_args(&resp_value)
+                } else {
+                    Vec::new()
+                };
+
+            // Parse command (slow path); a bad command gets an error reply
+            // and the connection carries on, but inside MULTI it also fails
+            // the transaction
+            let command = match Command::from_resp(resp_value) {
+                Ok(command) => {
+                    self.last_command = Some(command.index());
+                    command
+                }
+                Err(e) => {
+                    if self.transaction_state == TransactionState::Queuing {
+                        self.transaction_dirty = true;
+                    }
+                    write_resp_value_for(
+                        &mut self.write_buffer,
+                        &RespValue::Error(format!("ERR {}", e)),
+                        self.resp_version,
+                    );
+                    self.pipeline_depth += 1;
+                    continue;
+                }
+            };
 
             // Check for quit
             if matches!(command, Command::Quit) {
                 self.closed = true;
                 self.write_buffer.extend_from_slice(b"+OK\r\n");
+                self.discard_suppressed_reply();
                 return Ok(pubsub_ops);
             }
 
-            // Check if in pub/sub mode and restrict commands
-            if self.is_in_pubsub_mode() && !command.is_allowed_in_pubsub_mode() {
-                write_resp_value(
+            // RESET is accepted in any mode and before authentication
+            if matches!(command, Command::Reset) {
+                if self.is_in_pubsub_mode() {
+                    pubsub_ops.push(PubSubOp::UnsubscribeAll);
+                }
+                self.reset();
+                write_resp_value_for(
+                    &mut self.write_buffer,
+                    &RespValue::SimpleString(Bytes::from_static(b"RESET")),
+                    self.resp_version,
+                );
+                self.pipeline_depth += 1;
+                continue;
+            }
+
+            // Subscribed RESP2 clients are limited to pub/sub commands, while
+            // RESP3 clients get messages as pushes and can run anything
+            if self.is_in_pubsub_mode()
+                && self.resp_version == 2
+                && !command.is_allowed_in_pubsub_mode()
+            {
+                write_resp_value_for(
                     &mut self.write_buffer,
                     &RespValue::Error(
-                        "-ERR only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT allowed in this context".to_string(),
+                        "ERR only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET allowed in this context".to_string(),
                     ),
+                    self.resp_version,
+                );
+                continue;
+            }
+
+            if self.is_monitor {
+                write_resp_value_for(
+                    &mut self.write_buffer,
+                    &RespValue::Error("ERR only QUIT allowed in MONITOR mode".to_string()),
+                    self.resp_version,
                 );
                 continue;
             }
 
+            if self.monitors_active() {
+                self.feed_monitors(&command, &command_line);
+            }
+
+            // Only AUTH, HELLO and PING (Redis-compatible) run before
+            // authenticating, so nothing can be queued or watched first
+            if !self.authenticated
+                && !matches!(
+                    command,
+                    Command::Auth { .. } | Command::Hello { .. } | Command::Ping(_)
+                )
+            {
+                write_resp_value_for(
+                    &mut self.write_buffer,
+                    &RespValue::Error("NOAUTH Authentication required.".to_string()),
+                    self.resp_version,
+                );
+                self.pipeline_depth += 1;
+                continue;
+            }
+
+            // Commands outside the user's allow-list are refused, except the
+            // ones that switch to another user
+            if self.authenticated
+                && !matches!(command, Command::Auth { .. } | Command::Hello { .. })
+            {
+                if let Some(response) = self.executor.check_permission(&command) {
+                    if self.transaction_state == TransactionState::Queuing {
+                        self.transaction_dirty = true;
+                    }
+                    write_resp_value_for(&mut self.write_buffer, &response, self.resp_version);
+                    self.pipeline_depth += 1;
+                    continue;
+                }
+            }
+
             // Special handling for CLIENT SETNAME - update connection metadata
             if let Command::Client {
                 ref subcommand,
                 ref args,
             } = command
             {
+                // The registry is refreshed after each batch, so bring this
+                // connection's entry up to date for commands earlier in it
+                if subcommand.eq_ignore_ascii_case("LIST")
+                    || subcommand.eq_ignore_ascii_case("INFO")
+                {
+                    if let Some(registry) = &self.client_registry {
+                        registry.update(self);
+                    }
+                }
+
                 if subcommand.to_uppercase() == "SETNAME" && !args.is_empty() {
-                    self.client_name = Some(String::from_utf8_lossy(&args[0]).to_string());
+                    // An empty name clears it, as in Redis
+                    self.client_name = (!args[0].is_empty())
+                        .then(|| String::from_utf8_lossy(&args[0]).to_string());
                 }
+
+                // CLIENT REPLY only acknowledges ON; OFF and SKIP go unanswered
+                if subcommand.eq_ignore_ascii_case("REPLY") && args.len() == 1 {
+                    match ReplyMode::parse(&args[0]) {
+                        Some(ReplyMode::On) => {
+                            self.reply_mode = ReplyMode::On;
+                            self.suppressed_reply = None;
+                        }
+                        Some(mode) => {
+                            if self.reply_mode == ReplyMode::On || mode == ReplyMode::Off {
+                                self.reply_mode = mode;
+                            }
+                            self.suppressed_reply.get_or_insert(self.write_buffer.len());
+                        }
+                        None => {}
+                    }
+                }
+            }
+
+            // HELLO negotiates the protocol and may authenticate, so it runs
+            // ahead of the auth check and is never queued
+            if let Command::Hello {
+                proto,
+                auth,
+                setname,
+            } = command
+            {
+                let response = self.hello(proto, auth, setname);
+                write_resp_value_for(&mut self.write_buffer, &response, self.resp_version);
+                self.pipeline_depth += 1;
+                continue;
             }
 
             // Handle transaction commands
             match command {
                 Command::Multi => {
                     if self.transaction_state == TransactionState::Queuing {
-                        write_resp_value(
+                        write_resp_value_for(
                             &mut self.write_buffer,
-                            &RespValue::Error("-ERR MULTI calls can not be nested".to_string()),
+                            &RespValue::Error("ERR MULTI calls can not be nested".to_string()),
+                            self.resp_version,
                         );
                         continue;
                     }
                     self.transaction_state = TransactionState::Queuing;
+                    self.transaction_dirty = false;
                     self.queued_commands.clear();
-                    write_resp_value(
+                    write_resp_value_for(
                         &mut self.write_buffer,
                         &RespValue::SimpleString(Bytes::from_static(b"OK")),
+                        self.resp_version,
                     );
                     continue;
                 }
                 Command::Exec => {
                     if self.transaction_state != TransactionState::Queuing {
-                        write_resp_value(
+                        write_resp_value_for(
                             &mut self.write_buffer,
-                            &RespValue::Error("-ERR EXEC without MULTI".to_string()),
+                            &RespValue::Error("ERR EXEC without MULTI".to_string()),
+                            self.resp_version,
+                        );
+                        continue;
+                    }
+
+                    if self.transaction_dirty {
+                        self.transaction_state = TransactionState::None;
+                        self.transaction_dirty = false;
+                        self.queued_commands.clear();
+                        self.clear_watches();
+                        write_resp_value_for(
+                            &mut self.write_buffer,
+                            &RespValue::Error(
+                                "EXECABORT Transaction discarded because of previous errors."
+                                    .to_string(),
+                            ),
+                            self.resp_version,
+                        );
+                        continue;
+                    }
+
+                    // Abort if any watched key changed since WATCH
+                    let watch_violated = self
+                        .watched_keys
+                        .iter()
+                        .any(|(key, state)| self.executor.watch_state(key) != *state);
+
+                    if watch_violated {
+                        self.transaction_state = TransactionState::None;
+                        self.queued_commands.clear();
+                        self.clear_watches();
+                        write_resp_value_for(
+                            &mut self.write_buffer,
+                            &RespValue::Array(None),
+                            self.resp_version,
                         );
                         continue;
                     }
 
                     // Execute all queued commands
                     let mut results = Vec::new();
-                    for queued_cmd in self.queued_commands.drain(..) {
-                        results.push(self.executor.execute(queued_cmd));
+                    for (queued_cmd, args) in std::mem::take(&mut self.queued_commands) {
+                        let result = match queued_cmd {
+                            Command::Select(db) => self.select(db),
+                            cmd => self.execute_logged(cmd, &args),
+                        };
+                        results.push(result);
                     }
 
                     self.transaction_state = TransactionState::None;
-                    self.watched_keys.clear();
+                    self.clear_watches();
 
-                    write_resp_value(&mut self.write_buffer, &RespValue::Array(Some(results)));
+                    write_resp_value_for(
+                        &mut self.write_buffer,
+                        &RespValue::Array(Some(results)),
+                        self.resp_version,
+                    );
                     continue;
                 }
                 Command::Discard => {
                     if self.transaction_state != TransactionState::Queuing {
-                        write_resp_value(
+                        write_resp_value_for(
                             &mut self.write_buffer,
-                            &RespValue::Error("-ERR DISCARD without MULTI".to_string()),
+                            &RespValue::Error("ERR DISCARD without MULTI".to_string()),
+                            self.resp_version,
                         );
                         continue;
                     }
 
                     self.transaction_state = TransactionState::None;
                     self.queued_commands.clear();
-                    self.watched_keys.clear();
+                    self.clear_watches();
 
-                    write_resp_value(
+                    write_resp_value_for(
                         &mut self.write_buffer,
                         &RespValue::SimpleString(Bytes::from_static(b"OK")),
+                        self.resp_version,
                     );
                     continue;
                 }
                 Command::Watch(ref keys) => {
                     if self.transaction_state == TransactionState::Queuing {
-                        write_resp_value(
+                        write_resp_value_for(
                             &mut self.write_buffer,
-                            &RespValue::Error("-ERR WATCH inside MULTI is not allowed".to_string()),
+                            &RespValue::Error("ERR WATCH inside MULTI is not allowed".to_string()),
+                            self.resp_version,
                         );
                         continue;
                     }
                     for key in keys {
-                        self.watched_keys.insert(key.clone());
+                        // Keep the first state if a key is watched twice
+                        let scoped = self.executor.scoped_key(key);
+                        if !self.watched_keys.contains_key(&scoped) {
+                            let (scoped, state) = self.executor.watch(key);
+                            self.watched_keys.insert(scoped, state);
+                        }
                     }
-                    write_resp_value(
+                    write_resp_value_for(
                         &mut self.write_buffer,
                         &RespValue::SimpleString(Bytes::from_static(b"OK")),
+                        self.resp_version,
                     );
                     continue;
                 }
                 Command::Unwatch => {
-                    self.watched_keys.clear();
-                    write_resp_value(
+                    self.clear_watches();
+                    write_resp_value_for(
                         &mut self.write_buffer,
                         &RespValue::SimpleString(Bytes::from_static(b"OK")),
+                        self.resp_version,
                     );
                     continue;
                 }
@@ -301,70 +747,427 @@ impl Connection {
 
             // If in transaction, queue the command
             if self.transaction_state == TransactionState::Queuing {
-                self.queued_commands.push(command);
-                write_resp_value(
+                if !command.is_allowed_in_transaction() {
+                    self.transaction_dirty = true;
+                    write_resp_value_for(
+                        &mut self.write_buffer,
+                        &RespValue::Error(
+                            "ERR Command not allowed inside a transaction".to_string(),
+                        ),
+                        self.resp_version,
+                    );
+                    continue;
+                }
+                self.queued_commands.push((command, command_line));
+                write_resp_value_for(
                     &mut self.write_buffer,
                     &RespValue::SimpleString(Bytes::from_static(b"QUEUED")),
+                    self.resp_version,
                 );
                 continue;
             }
 
-            // Check authentication for non-AUTH commands
-            let response = if !self.authenticated && !matches!(command, Command::Auth(_)) {
-                // Allow PING without auth (Redis-compatible)
-                if matches!(command, Command::Ping(_)) {
-                    self.executor.execute(command)
-                } else {
-                    RespValue::Error("-NOAUTH Authentication required.".to_string())
-                }
-            } else {
-                // Special handling for AUTH command
-                if let Command::Auth(password) = &command {
+            let started = slowlog_enabled.then(Instant::now);
+
+            // Special handling for AUTH command
+            let response = if let Command::Auth { username, password } = &command {
+                let password_str = String::from_utf8_lossy(password);
+                match username {
+                    Some(username) => {
+                        let username = String::from_utf8_lossy(username);
+                        match self.executor.authenticate(&username, &password_str) {
+                            Some(user) => {
+                                self.set_user(user);
+                                RespValue::SimpleString(Bytes::from_static(b"OK"))
+                            }
+                            None => RespValue::Error(
+                                "WRONGPASS invalid username-password pair or user is disabled."
+                                    .to_string(),
+                            ),
+                        }
+                    }
                     // Check if password is configured
-                    if !self.auth_required {
-                        RespValue::Error(
-                            "-ERR Client sent AUTH, but no password is set".to_string(),
-                        )
-                    } else {
-                        let password_str = String::from_utf8_lossy(password);
-                        if self.executor.check_auth(&password_str) {
-                            self.set_authenticated(true);
+                    None if !self.auth_required => {
+                        RespValue::Error("ERR Client sent AUTH, but no password is set".to_string())
+                    }
+                    None => match self.executor.authenticate(DEFAULT_USER, &password_str) {
+                        Some(user) => {
+                            self.set_user(user);
                             RespValue::SimpleString(Bytes::from_static(b"OK"))
-                        } else {
-                            RespValue::Error("-ERR invalid password".to_string())
                         }
-                    }
-                } else if command.is_pubsub_command() {
-                    // Capture subcommand for error message if needed
-                    let subcommand_str = if let Command::PubSub { ref subcommand, .. } = command {
-                        Some(subcommand.clone())
-                    } else {
-                        None
-                    };
+                        None => RespValue::Error("ERR invalid password".to_string()),
+                    },
+                }
+            } else if let Command::Select(db) = command {
+                self.select(db)
+            } else if let (Command::Ping(message), true) =
+                (&command, self.is_in_pubsub_mode() && self.resp_version == 2)
+            {
+                // Subscribed RESP2 clients get PING answered in the shape of
+                // a pub/sub message
+                RespValue::Array(Some(vec![
+                    RespValue::BulkString(Some(Bytes::from_static(b"pong"))),
+                    RespValue::BulkString(Some(message.clone().unwrap_or_default())),
+                ]))
+            } else if let Command::Monitor = command {
+                self.start_monitor()
+            } else if command.is_pubsub_command() {
+                // Capture subcommand for error message if needed
+                let subcommand_str = if let Command::PubSub { ref subcommand, .. } = command {
+                    Some(subcommand.clone())
+                } else {
+                    None
+                };
 
-                    if let Some(pubsub_op) = command.to_pubsub_op() {
-                        pubsub_ops.push(pubsub_op);
-                        // Response will be sent after processing by pub/sub manager
-                        continue;
-                    } else if let Some(subcommand) = subcommand_str {
-                        // Unknown PUBSUB subcommand
-                        RespValue::Error(format!("ERR Unknown PUBSUB subcommand '{}'", subcommand))
-                    } else {
-                        RespValue::Error("ERR Failed to process pub/sub command".to_string())
-                    }
+                if let Some(pubsub_op) = command.to_pubsub_op() {
+                    pubsub_ops.push(pubsub_op);
+                    // Response will be sent after processing by pub/sub manager
+                    continue;
+                } else if let Some(subcommand) = subcommand_str {
+                    // Unknown PUBSUB subcommand
+                    RespValue::Error(format!("ERR Unknown PUBSUB subcommand '{}'", subcommand))
                 } else {
-                    self.executor.execute(command)
+                    RespValue::Error("ERR Failed to process pub/sub command".to_string())
                 }
+            } else if let Command::BLPop { keys, timeout } | Command::BRPop { keys, timeout } =
+                &command
+            {
+                let left = matches!(command, Command::BLPop { .. });
+                let keys: Vec<_> = keys.iter().map(|k| self.executor.scoped_key(k)).collect();
+                let timeout = *timeout;
+                let response = self.execute_logged(command, &command_line);
+                if matches!(response, RespValue::Array(None)) && self.block(keys, left, timeout) {
+                    // Replied to once served or timed out
+                    continue;
+                }
+                response
+            } else {
+                self.execute_logged(command, &command_line)
             };
 
-            write_resp_value(&mut self.write_buffer, &response);
+            if let Some(started) = started {
+                self.executor.log_slow_command(
+                    started.elapsed(),
+                    command_line,
+                    self.client_addr,
+                    self.client_name.as_deref(),
+                );
+            }
+
+            write_resp_value_for(&mut self.write_buffer, &response, self.resp_version);
 
             self.pipeline_depth += 1;
         }
 
+        self.discard_suppressed_reply();
+        self.held_back = self.replies_full();
         Ok(pubsub_ops)
     }
 
+    /// Execute a command, recording it in the append-only log if it wrote
+    fn execute_logged(&self, command: Command, args: &[Bytes]) -> RespValue {
+        // Scripts log the writes they make themselves, under the same lock
+        let writes = command.is_write();
+        let script = matches!(command, Command::Eval { .. } | Command::EvalSha { .. });
+        let Some(append_log) = self.append_log.as_ref().filter(|_| writes || script) else {
+            return self.executor.execute(command);
+        };
+        let _order = append_log.lock_order();
+        let response = self.executor.execute(command);
+        if writes && !matches!(response, RespValue::Error(_)) {
+            append_log.log(self.db, args, &response);
+        }
+        response
+    }
+
+    /// Check if buffered replies are past the pipeline limits
+    fn replies_full(&self) -> bool {
+        let pending = self.write_buffer.len() - self.write_position;
+        pending > 0
+            && (pending >= self.max_reply_buffer_size
+                || self.pipeline_depth >= self.max_pipeline_depth)
+    }
+
+    /// Check if commands or unread input are waiting on this connection
+    pub fn is_held_back(&self) -> bool {
+        self.held_back
+    }
+
+    /// Check if held-back commands can run, now that replies have drained
+    /// below the pipeline limits
+    pub fn can_resume(&self) -> bool {
+        self.held_back && !self.replies_full()
+    }
+
+    /// Park this connection on `keys` until a push serves it or the timeout
+    /// passes, returning false if blocking is not available
+    fn block(&mut self, keys: Vec<Vec<u8>>, left: bool, timeout: std::time::Duration) -> bool {
+        let Some((hub, thread_id)) = &self.blocking_hub else {
+            return false;
+        };
+        hub.block(*thread_id, &keys);
+
+        let now = Instant::now();
+        self.blocked = Some(BlockedPop {
+            keys,
+            left,
+            since: now,
+            deadline: (!timeout.is_zero()).then(|| now + timeout),
+            silent: self.suppressed_reply.is_some(),
+        });
+
+        // A push that landed before the hub knew about us sent no wake-up
+        self.serve_blocked();
+        true
+    }
+
+    fn unblock(&mut self) {
+        if let Some(blocked) = self.blocked.take() {
+            if let Some((hub, thread_id)) = &self.blocking_hub {
+                hub.unblock(*thread_id, &blocked.keys);
+            }
+        }
+    }
+
+    /// Finish a blocked pop with its reply
+    fn finish_blocked(&mut self, response: RespValue) {
+        let silent = self.blocked.as_ref().is_some_and(|b| b.silent);
+        self.unblock();
+        if !silent {
+            write_resp_value_for(&mut self.write_buffer, &response, self.resp_version);
+        }
+        self.pipeline_depth += 1;
+        // Whatever was pipelined behind the pop can run now
+        self.held_back = true;
+    }
+
+    /// Check if this connection is parked in BLPOP / BRPOP
+    pub fn is_blocked(&self) -> bool {
+        self.blocked.is_some()
+    }
+
+    /// When this connection started waiting on `key`, if it is
+    pub fn blocked_on(&self, key: &[u8]) -> Option<Instant> {
+        self.blocked
+            .as_ref()
+            .filter(|b| b.keys.iter().any(|k| k == key))
+            .map(|b| b.since)
+    }
+
+    /// Retry a blocked pop, replying if one of its lists had an element
+    pub fn serve_blocked(&mut self) -> bool {
+        let Some(blocked) = &self.blocked else {
+            return false;
+        };
+        let order = self.append_log.as_ref().map(|log| log.lock_order());
+        let response = self.executor.pop_first(&blocked.keys, blocked.left);
+        if matches!(response, RespValue::Array(None)) {
+            return false;
+        }
+        if let Some(append_log) = &self.append_log {
+            // Logged as the pop that served the blocked command
+            let name: &'static [u8] = if blocked.left { b"BLPOP" } else { b"BRPOP" };
+            append_log.log(self.db, &[Bytes::from_static(name)], &response);
+        }
+        drop(order);
+        self.finish_blocked(response);
+        true
+    }
+
+    /// Reply with a null array if a blocked pop has waited out its timeout
+    pub fn expire_blocked(&mut self, now: Instant) -> bool {
+        let expired = self
+            .blocked
+            .as_ref()
+            .and_then(|b| b.deadline)
+            .is_some_and(|deadline| deadline <= now);
+        if expired {
+            self.finish_blocked(RespValue::Array(None));
+        }
+        expired
+    }
+
+    /// Drop whatever the last command wrote if CLIENT REPLY silenced it
+    fn discard_suppressed_reply(&mut self) {
+        if let Some(start) = self.suppressed_reply.take() {
+            self.write_buffer.truncate(start);
+        }
+    }
+
+    /// Switch this connection into MONITOR mode
+    fn start_monitor(&mut self) -> RespValue {
+        let Some((hub, thread_id)) = &self.monitor_hub else {
+            return RespValue::Error("ERR MONITOR not available".to_string());
+        };
+        if !self.is_monitor {
+            hub.add_monitor(*thread_id);
+            self.is_monitor = true;
+        }
+        RespValue::SimpleString(Bytes::from_static(b"OK"))
+    }
+
+    /// Leave MONITOR mode, if this connection is in it
+    fn stop_monitor(&mut self) {
+        if self.is_monitor {
+            self.is_monitor = false;
+            if let Some((hub, thread_id)) = &self.monitor_hub {
+                hub.remove_monitor(*thread_id);
+            }
+        }
+    }
+
+    /// Send a command line to every MONITOR client, hiding credentials
+    fn feed_monitors(&self, command: &Command, command_line: &[Bytes]) {
+        let Some((hub, _)) = &self.monitor_hub else {
+            return;
+        };
+        if matches!(command, Command::Auth { .. } | Command::Hello { .. }) {
+            let redacted: Vec<Bytes> = command_line
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| {
+                    if i == 0 {
+                        arg.clone()
+                    } else {
+                        Bytes::from_static(b"(redacted)")
+                    }
+                })
+                .collect();
+            hub.feed(self.db, self.client_addr, &redacted);
+        } else {
+            hub.feed(self.db, self.client_addr, command_line);
+        }
+    }
+
+    /// Write a line received from the monitor hub
+    pub fn queue_monitor_line(&mut self, line: Bytes) {
+        write_resp_value_for(
+            &mut self.write_buffer,
+            &RespValue::SimpleString(line),
+            self.resp_version,
+        );
+    }
+
+    /// Switch this connection to another logical database
+    fn select(&mut self, db: usize) -> RespValue {
+        if !self.executor.select_db(db) {
+            return RespValue::Error("ERR DB index is out of range".to_string());
+        }
+        self.db = db;
+        RespValue::SimpleString(Bytes::from_static(b"OK"))
+    }
+
+    /// Return the connection to the state it was opened in, as RESET does;
+    /// subscriptions are dropped separately through the pub/sub manager
+    /// Forget every watched key, as EXEC, DISCARD and UNWATCH do
+    fn clear_watches(&mut self) {
+        for key in self.watched_keys.keys() {
+            self.executor.unwatch(key);
+        }
+        self.watched_keys.clear();
+    }
+
+    fn reset(&mut self) {
+        self.transaction_state = TransactionState::None;
+        self.queued_commands.clear();
+        self.clear_watches();
+        self.set_subscription_counts(0, 0, 0);
+        self.select(0);
+        self.resp_version = 2;
+        self.reply_mode = ReplyMode::On;
+        self.suppressed_reply = None;
+        self.stop_monitor();
+        let user = Arc::clone(self.executor.default_user());
+        self.executor.set_user(Arc::clone(&user));
+        self.user = user;
+        self.authenticated = !self.auth_required;
+    }
+
+    /// Handle HELLO, switching protocol version and returning the server info map
+    fn hello(
+        &mut self,
+        proto: Option<u8>,
+        auth: Option<(Bytes, Bytes)>,
+        setname: Option<Bytes>,
+    ) -> RespValue {
+        if let Some(version) = proto {
+            if version != 2 && version != 3 {
+                return RespValue::Error("NOPROTO unsupported protocol version".to_string());
+            }
+        }
+
+        if let Some((username, password)) = auth {
+            let username = String::from_utf8_lossy(&username);
+            let password_str = String::from_utf8_lossy(&password);
+            let Some(user) = self.executor.authenticate(&username, &password_str) else {
+                return RespValue::Error(
+                    "WRONGPASS invalid username-password pair or user is disabled.".to_string(),
+                );
+            };
+            self.set_user(user);
+        }
+
+        if !self.authenticated {
+            return RespValue::Error(
+                "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time".to_string(),
+            );
+        }
+
+        if let Some(name) = setname {
+            self.client_name = Some(String::from_utf8_lossy(&name).to_string());
+            self.executor.execute(Command::Client {
+                subcommand: "SETNAME".to_string(),
+                args: vec![name.to_vec()],
+            });
+        }
+
+        if let Some(version) = proto {
+            self.resp_version = version;
+        }
+
+        let field =
+            |name: &'static str| RespValue::BulkString(Some(Bytes::from_static(name.as_bytes())));
+        RespValue::Map(vec![
+            (
+                field("server"),
+                RespValue::BulkString(Some(Bytes::from_static(b"feox"))),
+            ),
+            (
+                field("version"),
+                RespValue::BulkString(Some(Bytes::from_static(
+                    env!("CARGO_PKG_VERSION").as_bytes(),
+                ))),
+            ),
+            (field("proto"), RespValue::Integer(self.resp_version as i64)),
+            (field("id"), RespValue::Integer(self.connection_id as i64)),
+            (
+                field("mode"),
+                RespValue::BulkString(Some(Bytes::from_static(b"standalone"))),
+            ),
+            (
+                field("role"),
+                RespValue::BulkString(Some(Bytes::from_static(b"master"))),
+            ),
+            (field("modules"), RespValue::Array(Some(vec![]))),
+        ])
+    }
+
+    /// When the client last sent anything
+    pub fn last_activity(&self) -> Instant {
+        self.last_activity
+    }
+
+    /// Name of the last command run, or "NULL" before the first
+    pub fn last_command(&self) -> &'static str {
+        self.last_command
+            .map_or("NULL", |index| crate::protocol::COMMAND_NAMES[index])
+    }
+
+    /// Seconds since the client last sent anything
+    pub fn idle_secs(&self) -> u64 {
+        self.last_activity.elapsed().as_secs()
+    }
+
     /// Get pending write data as a single buffer slice
     pub fn pending_writes(&mut self) -> Option<&[u8]> {
         if self.write_position < self.write_buffer.len() {
@@ -377,6 +1180,26 @@ impl Connection {
     /// Mark bytes as written
     pub fn consume_writes(&mut self, n: usize) {
         self.write_position += n;
+        if let Some((stats, shard)) = &self.stats {
+            stats.record_write(*shard);
+        }
+
+        // Reset the buffer once fully flushed so it doesn't grow unbounded
+        if self.write_position >= self.write_buffer.len() {
+            self.write_buffer.clear();
+            self.write_position = 0;
+            self.pipeline_depth = 0;
+        }
+    }
+
+    /// Check if the connection is waiting for the socket to become writable
+    pub fn is_write_pending(&self) -> bool {
+        self.write_pending
+    }
+
+    /// Record whether the connection is registered for writable events
+    pub fn set_write_pending(&mut self, pending: bool) {
+        self.write_pending = pending;
     }
 
     /// Add a pub/sub message to the pending queue
@@ -389,9 +1212,13 @@ impl Connection {
         self.subscription_count > 0
     }
 
-    /// Update subscription count
-    pub fn set_subscription_count(&mut self, count: usize) {
+    /// Update the channel, pattern and sharded channel subscription counts
+    pub fn set_subscription_counts(&mut self, channels: usize, patterns: usize, shards: usize) {
+        let count = channels + patterns + shards;
         self.subscription_count = count;
+        self.channel_subscriptions = channels;
+        self.pattern_subscriptions = patterns;
+        self.shard_subscriptions = shards;
         // Update flags based on subscription status
         if count > 0 && !self.flags.contains(&"pubsub".to_string()) {
             self.flags.push("pubsub".to_string());
@@ -404,7 +1231,7 @@ impl Connection {
     pub fn process_pubsub_messages(&mut self) {
         while let Some(message) = self.pending_pubsub_messages.pop_front() {
             let resp = message.to_resp();
-            write_resp_value(&mut self.write_buffer, &resp);
+            write_resp_value_for(&mut self.write_buffer, &resp, self.resp_version);
         }
     }
 
@@ -415,6 +1242,7 @@ impl Connection {
         // Static responses
         const OK_RESPONSE: &[u8] = b"+OK\r\n";
         const NIL_RESPONSE: &[u8] = b"$-1\r\n";
+        const RESP3_NULL_RESPONSE: &[u8] = b"_\r\n";
 
         // Must be an array with at least 2 elements
         let args = match resp_value {
@@ -440,7 +1268,15 @@ impl Connection {
 
             // Simple SET without options
             if args.len() == 3 {
-                match self.executor.fast_set_bytes(key, value_bytes) {
+                self.last_command = Some(Command::SET_INDEX);
+                let started = Instant::now();
+                let result = self.executor.fast_set_bytes(key, value_bytes);
+                self.executor.record_command(
+                    Command::SET_INDEX,
+                    started.elapsed(),
+                    result.is_err(),
+                );
+                match result {
                     Ok(_) => {
                         self.write_buffer.extend_from_slice(OK_RESPONSE);
                         return true;
@@ -463,7 +1299,14 @@ impl Connection {
                 _ => return false,
             };
 
-            match self.executor.fast_get(key) {
+            self.last_command = Some(Command::GET_INDEX);
+            let started = Instant::now();
+            let result = self.executor.fast_get(key);
+            let failed = !matches!(result, Ok(_) | Err(feoxdb::FeoxError::KeyNotFound));
+            self.executor
+                .record_command(Command::GET_INDEX, started.elapsed(), failed);
+
+            match result {
                 Ok(value) => {
                     let mut num_buf = itoa::Buffer::new();
                     let len_str = num_buf.format(value.len());
@@ -476,7 +1319,19 @@ impl Connection {
                     return true;
                 }
                 Err(feoxdb::FeoxError::KeyNotFound) => {
-                    self.write_buffer.extend_from_slice(NIL_RESPONSE);
+                    // A miss may be a list or hash living under prefixed keys
+                    if self.executor.holds_other_type(key, "string") {
+                        self.write_buffer.push(b'-');
+                        self.write_buffer
+                            .extend_from_slice(WRONGTYPE_ERROR.as_bytes());
+                        self.write_buffer.extend_from_slice(b"\r\n");
+                    } else {
+                        if self.resp_version >= 3 {
+                            self.write_buffer.extend_from_slice(RESP3_NULL_RESPONSE);
+                        } else {
+                            self.write_buffer.extend_from_slice(NIL_RESPONSE);
+                        }
+                    }
                     return true;
                 }
                 Err(e) => {
@@ -492,3 +1347,24 @@ impl Connection {
         false // Not a fast-path command
     }
 }
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Watched keys are shared with other connections
+        self.clear_watches();
+    }
+}
+
+/// The command name and arguments of a request, as sent
+fn command_args(value: &RespValue) -> Vec<Bytes> {
+    match value {
+        RespValue::Array(Some(items)) => items
+            .iter()
+            .filter_map(|item| match item {
+                RespValue::BulkString(Some(arg)) => Some(arg.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -1,3 +1,7 @@
 mod connection;
+mod stream;
+mod tls;
 
 pub use connection::{Connection, PubSubOp};
+pub use stream::ClientStream;
+pub use tls::{load_server_config, TlsStream};
@@ -0,0 +1,163 @@
+use crate::error::{Error, Result};
+use mio::net::TcpStream;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection};
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::Arc;
+
+/// Build the rustls server config from PEM certificate chain and key files
+pub fn load_server_config(cert_file: &str, key_file: &str) -> Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_file_iter(cert_file)
+        .and_then(|certs| certs.collect::<std::result::Result<Vec<_>, _>>())
+        .map_err(|e| Error::Config(format!("failed to read {}: {}", cert_file, e)))?;
+    if certs.is_empty() {
+        return Err(Error::Config(format!("no certificates in {}", cert_file)));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_file)
+        .map_err(|e| Error::Config(format!("failed to read {}: {}", key_file, e)))?;
+
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|e| Error::Config(format!("invalid TLS certificate or key: {}", e)))?;
+
+    Ok(Arc::new(config))
+}
+
+/// Non-blocking TLS session over an accepted socket
+///
+/// Reads pull every available record off the socket and hand back decrypted
+/// bytes; writes encrypt into rustls and send as much as the socket takes.
+/// `WouldBlock` is returned whenever no plaintext is available yet (for
+/// example mid-handshake) or the socket cannot accept more output, so the
+/// caller can treat it exactly like a plain TCP stream.
+pub struct TlsStream {
+    tcp: TcpStream,
+    tls: ServerConnection,
+    // Decrypted bytes rustls holds that have not been read yet
+    plaintext_pending: usize,
+    // False when reading stopped before the socket reported WouldBlock
+    socket_drained: bool,
+    eof: bool,
+}
+
+impl TlsStream {
+    pub fn new(tcp: TcpStream, config: Arc<ServerConfig>) -> Result<Self> {
+        let tls = ServerConnection::new(config)
+            .map_err(|e| Error::System(format!("failed to start TLS session: {}", e)))?;
+        Ok(Self {
+            tcp,
+            tls,
+            plaintext_pending: 0,
+            socket_drained: true,
+            eof: false,
+        })
+    }
+
+    pub fn tcp(&self) -> &TcpStream {
+        &self.tcp
+    }
+
+    pub fn tcp_mut(&mut self) -> &mut TcpStream {
+        &mut self.tcp
+    }
+
+    /// Whether input is buffered that no further readiness event would report
+    pub fn has_buffered_input(&self) -> bool {
+        self.plaintext_pending > 0 || !self.socket_drained
+    }
+
+    /// Whether encrypted output is still waiting for the socket
+    pub fn has_buffered_output(&self) -> bool {
+        self.tls.wants_write()
+    }
+
+    /// Read and decrypt records until the socket is drained or rustls is full
+    fn read_records(&mut self) -> io::Result<()> {
+        self.socket_drained = false;
+        while self.tls.wants_read() {
+            match self.tls.read_tls(&mut self.tcp) {
+                Ok(0) => {
+                    self.eof = true;
+                    self.socket_drained = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    self.socket_drained = true;
+                    break;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+
+            match self.tls.process_new_packets() {
+                Ok(state) => self.plaintext_pending = state.plaintext_bytes_to_read(),
+                Err(e) => {
+                    // Best effort to tell the peer why the session failed
+                    let _ = self.write_records();
+                    return Err(io::Error::new(ErrorKind::InvalidData, e));
+                }
+            }
+        }
+
+        // Send handshake messages produced while reading straight away
+        self.write_records()
+    }
+
+    /// Send buffered records until done or the socket would block
+    fn write_records(&mut self) -> io::Result<()> {
+        while self.tls.wants_write() {
+            match self.tls.write_tls(&mut self.tcp) {
+                Ok(0) => return Err(io::Error::from(ErrorKind::WriteZero)),
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_records()?;
+
+        match self.tls.reader().read(buf) {
+            Ok(n) => {
+                self.plaintext_pending = self.plaintext_pending.saturating_sub(n);
+                Ok(n)
+            }
+            // A peer closing the socket without close_notify is still a close
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(0),
+            Err(e) if e.kind() == ErrorKind::WouldBlock && self.eof => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // Make room in rustls for new plaintext first
+        self.write_records()?;
+        let n = self.tls.writer().write(data)?;
+        self.write_records()?;
+
+        if n == 0 && !data.is_empty() {
+            return Err(io::Error::from(ErrorKind::WouldBlock));
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_records()?;
+        if self.tls.wants_write() {
+            return Err(io::Error::from(ErrorKind::WouldBlock));
+        }
+        Ok(())
+    }
+}
@@ -19,6 +19,13 @@ impl ClientOperations {
         }
     }
 
+    /// Connected clients, if the registry is set
+    pub fn client_count(&self) -> Option<usize> {
+        self.registry
+            .as_ref()
+            .map(|registry| registry.client_count())
+    }
+
     pub fn execute(
         &self,
         subcommand: &str,
@@ -27,13 +34,25 @@ impl ClientOperations {
     ) -> RespValue {
         match subcommand.to_uppercase().as_str() {
             "ID" => self.client_id(connection_id),
-            "LIST" => self.client_list(),
-            "SETNAME" => self.client_setname(args),
+            "LIST" => self.client_list(connection_id),
+            "SETNAME" => self.client_setname(args, connection_id),
             "GETNAME" => self.client_getname(connection_id),
+            "SETINFO" => self.client_setinfo(args, connection_id),
             "KILL" => self.client_kill(args),
             "INFO" => self.client_info(connection_id),
             "PAUSE" => self.client_pause(args),
             "UNPAUSE" => self.client_unpause(),
+            "NO-EVICT" | "NO-TOUCH" => self.client_toggle(subcommand, args),
+            // The connection applies the reply mode itself
+            "REPLY" => match args {
+                [mode] => match String::from_utf8_lossy(mode).to_uppercase().as_str() {
+                    "ON" | "OFF" | "SKIP" => RespValue::SimpleString(Bytes::from_static(b"OK")),
+                    _ => RespValue::Error("ERR syntax error".to_string()),
+                },
+                _ => RespValue::Error(
+                    "ERR wrong number of arguments for 'client|reply' command".to_string(),
+                ),
+            },
             _ => RespValue::Error(format!("-ERR Unknown CLIENT subcommand '{}'", subcommand)),
         }
     }
@@ -46,14 +65,16 @@ impl ClientOperations {
         }
     }
 
-    fn client_list(&self) -> RespValue {
+    fn client_list(&self, connection_id: Option<usize>) -> RespValue {
         if let Some(ref registry) = self.registry {
             let clients = registry.get_all_clients();
             let mut output = String::new();
 
             for client in clients {
+                // The caller's own entry is refreshed after this command
+                let current = Some(client.id) == connection_id;
                 output.push_str(&format!(
-                    "id={} addr={} fd={} name={} age={} idle={} flags={} db={} sub={} psub={} ssub={} multi=-1 qbuf=0 qbuf-free=0 argv-mem=0 multi-mem=0 rbs=0 rbp=0 obl=0 oll=0 omem=0 tot-mem=0 events=r cmd=client user=default redir=-1 resp=2\n",
+                    "id={} addr={} fd={} name={} age={} idle={} flags={} db={} sub={} psub={} ssub={} multi=-1 qbuf=0 qbuf-free=0 argv-mem=0 multi-mem=0 rbs=0 rbp=0 obl=0 oll=0 omem=0 tot-mem=0 events=r cmd={} user=default redir=-1 resp=2 lib-name={} lib-ver={} tot-cmds={}\n",
                     client.id,
                     client.addr.map(|a| a.to_string()).unwrap_or_else(|| "N/A".to_string()),
                     client.fd,
@@ -63,12 +84,16 @@ impl ClientOperations {
                         .unwrap_or_default()
                         .as_secs()
                         .saturating_sub(client.connected_at),
-                    0,
+                    if current { 0 } else { client.last_activity.elapsed().as_secs() },
                     if client.flags.is_empty() { "N".to_string() } else { client.flags.join("") },
                     client.db,
-                    0,  // subscriptions
-                    0,  // pattern subscriptions
-                    0,  // shard subscriptions
+                    client.sub,
+                    client.psub,
+                    client.ssub,
+                    if current { "client|list" } else { client.cmd },
+                    client.lib_name.as_deref().unwrap_or(""),
+                    client.lib_ver.as_deref().unwrap_or(""),
+                    client.commands_processed,
                 ));
             }
 
@@ -78,13 +103,19 @@ impl ClientOperations {
         }
     }
 
-    fn client_setname(&self, args: &[Vec<u8>]) -> RespValue {
+    fn client_setname(&self, args: &[Vec<u8>], connection_id: Option<usize>) -> RespValue {
         if args.is_empty() {
             RespValue::Error(
                 "-ERR wrong number of arguments for 'CLIENT SETNAME' command".to_string(),
             )
         } else {
-            // Name is stored in connection and updated in registry by caller
+            // The connection keeps its own copy; updating the registry here
+            // lets a pipelined GETNAME see the name before the batch finishes
+            if let (Some(ref registry), Some(conn_id)) = (&self.registry, connection_id) {
+                let name =
+                    (!args[0].is_empty()).then(|| String::from_utf8_lossy(&args[0]).to_string());
+                registry.set_name(conn_id, name);
+            }
             RespValue::SimpleString(Bytes::from_static(b"OK"))
         }
     }
@@ -105,6 +136,36 @@ impl ClientOperations {
         }
     }
 
+    fn client_setinfo(&self, args: &[Vec<u8>], connection_id: Option<usize>) -> RespValue {
+        let [attr, value] = args else {
+            return RespValue::Error(
+                "ERR wrong number of arguments for 'client|setinfo' command".to_string(),
+            );
+        };
+        let attr = String::from_utf8_lossy(attr).to_lowercase();
+        if attr != "lib-name" && attr != "lib-ver" {
+            return RespValue::Error(format!("ERR Unrecognized option '{}'", attr));
+        }
+        // CLIENT LIST separates fields with spaces, so only printable
+        // characters other than space are accepted
+        if value.iter().any(|&b| !(b'!'..=b'~').contains(&b)) {
+            return RespValue::Error(format!(
+                "ERR {} cannot contain spaces, newlines or special characters.",
+                attr
+            ));
+        }
+
+        if let (Some(ref registry), Some(conn_id)) = (&self.registry, connection_id) {
+            let value = String::from_utf8_lossy(value).to_string();
+            if attr == "lib-name" {
+                registry.set_lib_info(conn_id, Some(value), None);
+            } else {
+                registry.set_lib_info(conn_id, None, Some(value));
+            }
+        }
+        RespValue::SimpleString(Bytes::from_static(b"OK"))
+    }
+
     fn client_kill(&self, args: &[Vec<u8>]) -> RespValue {
         if args.is_empty() {
             return RespValue::Error(
@@ -157,7 +218,7 @@ impl ClientOperations {
         if let (Some(ref registry), Some(conn_id)) = (&self.registry, connection_id) {
             if let Some(client) = registry.get_client(conn_id) {
                 let info = format!(
-                    "id={}\naddr={}\nfd={}\nname={}\nage={}\nidle={}\nflags={}\ndb={}\nsub={}\npsub={}\nssub={}\nmulti=-1\nqbuf=0\nqbuf-free=0\nargv-mem=0\nmulti-mem=0\nrbs=0\nrbp=0\nobl=0\noll=0\nomem=0\ntot-mem=0\nevents=r\ncmd=client\nuser=default\nredir=-1\nresp=2",
+                    "id={}\naddr={}\nfd={}\nname={}\nage={}\nidle={}\nflags={}\ndb={}\nsub={}\npsub={}\nssub={}\nmulti=-1\nqbuf=0\nqbuf-free=0\nargv-mem=0\nmulti-mem=0\nrbs=0\nrbp=0\nobl=0\noll=0\nomem=0\ntot-mem=0\nevents=r\ncmd={}\nuser=default\nredir=-1\nresp=2\nlib-name={}\nlib-ver={}\ntot-cmds={}",
                     client.id,
                     client.addr.map(|a| a.to_string()).unwrap_or_else(|| "N/A".to_string()),
                     client.fd,
@@ -170,9 +231,13 @@ impl ClientOperations {
                     0,
                     if client.flags.is_empty() { "N".to_string() } else { client.flags.join("") },
                     client.db,
-                    0,  // subscriptions
-                    0,  // pattern subscriptions
-                    0,  // shard subscriptions
+                    client.sub,
+                    client.psub,
+                    client.ssub,
+                    "client|info",
+                    client.lib_name.as_deref().unwrap_or(""),
+                    client.lib_ver.as_deref().unwrap_or(""),
+                    client.commands_processed,
                 );
                 RespValue::BulkString(Some(Bytes::from(info)))
             } else {
@@ -197,6 +262,21 @@ impl ClientOperations {
     fn client_unpause(&self) -> RespValue {
         RespValue::SimpleString(Bytes::from_static(b"OK"))
     }
+
+    /// NO-EVICT and NO-TOUCH: nothing is evicted or LRU-tracked, so both
+    /// settings are accepted without effect
+    fn client_toggle(&self, subcommand: &str, args: &[Vec<u8>]) -> RespValue {
+        match args {
+            [flag] if flag.eq_ignore_ascii_case(b"ON") || flag.eq_ignore_ascii_case(b"OFF") => {
+                RespValue::SimpleString(Bytes::from_static(b"OK"))
+            }
+            [_] => RespValue::Error("ERR syntax error".to_string()),
+            _ => RespValue::Error(format!(
+                "ERR wrong number of arguments for 'client|{}' command",
+                subcommand.to_lowercase()
+            )),
+        }
+    }
 }
 
 impl Clone for ClientOperations {
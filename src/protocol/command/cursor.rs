@@ -0,0 +1,161 @@
+use once_cell::sync::Lazy;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+/// The byte ahead of a key encoded inline, so leading zero bytes survive and
+/// no cursor is ever 0
+const MARKER: u8 = 1;
+
+/// Longest key that fits inline, behind the marker, in a u64
+const MAX_INLINE: usize = 7;
+
+/// Set on cursors that are tokens into `RESUME_KEYS`; inline cursors stay
+/// below 2^57, so the two never meet
+const TOKEN_BIT: u64 = 1 << 63;
+
+/// Tokens handed out before a generation is retired
+const GENERATION_SIZE: usize = 1 << 16;
+
+/// Resume keys too long to go inline, by token
+///
+/// A token is derived from its key, so a page repeated or a scan restarted
+/// gets the same cursor rather than a new entry. Entries live for two
+/// generations, and a cursor used in the older one moves to the newer, so a
+/// scan in progress keeps its cursor however many others come and go.
+struct ResumeKeys {
+    current: HashMap<u64, Vec<u8>>,
+    previous: HashMap<u64, Vec<u8>>,
+}
+
+static RESUME_KEYS: Lazy<Mutex<ResumeKeys>> = Lazy::new(|| {
+    Mutex::new(ResumeKeys {
+        current: HashMap::new(),
+        previous: HashMap::new(),
+    })
+});
+
+impl ResumeKeys {
+    fn token(&mut self, key: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let mut token = hasher.finish() | TOKEN_BIT;
+        // Step past a different key that hashed alike
+        while let Some(found) = self.get(token) {
+            if found == key {
+                break;
+            }
+            token = token.wrapping_add(1) | TOKEN_BIT;
+        }
+
+        if !self.current.contains_key(&token) {
+            if self.current.len() >= GENERATION_SIZE {
+                self.previous = std::mem::take(&mut self.current);
+            }
+            self.previous.remove(&token);
+            self.current.insert(token, key.to_vec());
+        }
+        token
+    }
+
+    fn get(&self, token: u64) -> Option<&Vec<u8>> {
+        self.current
+            .get(&token)
+            .or_else(|| self.previous.get(&token))
+    }
+
+    fn resume(&mut self, token: u64) -> Option<Vec<u8>> {
+        let key = self.get(token)?.clone();
+        // Keep the key for as long as the scan goes on
+        self.token(&key);
+        Some(key)
+    }
+}
+
+/// The cursor a scan resuming from `key` hands back to the client
+///
+/// Clients expect an opaque unsigned 64-bit number, with 0 meaning done.
+/// Keys of up to seven bytes are carried in the cursor itself; longer ones
+/// are remembered on the server under a token.
+pub fn encode(key: &[u8]) -> String {
+    if key.len() <= MAX_INLINE {
+        let cursor = std::iter::once(&MARKER)
+            .chain(key)
+            .fold(0u64, |cursor, &byte| cursor << 8 | byte as u64);
+        return cursor.to_string();
+    }
+    RESUME_KEYS.lock().unwrap().token(key).to_string()
+}
+
+/// The key a cursor from `encode` resumes from, or None if it is not one
+pub fn decode(cursor: &[u8]) -> Option<Vec<u8>> {
+    if !cursor.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let cursor: u64 = std::str::from_utf8(cursor).ok()?.parse().ok()?;
+    if cursor & TOKEN_BIT != 0 {
+        return RESUME_KEYS.lock().unwrap().resume(cursor);
+    }
+
+    let bytes = cursor.to_be_bytes();
+    let start = bytes.iter().position(|&byte| byte != 0)?;
+    match bytes[start..].split_first() {
+        Some((&MARKER, key)) => Some(key.to_vec()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_keys() {
+        let keys: [&[u8]; 7] = [
+            b"",
+            b"a",
+            b"user:10",
+            b"user:1000",
+            b"\x00\x00leading zeros",
+            b"\xff\xff\xff\xff",
+            &[0x7f; 300],
+        ];
+        for key in keys {
+            let cursor = encode(key);
+            assert!(cursor.parse::<u64>().is_ok(), "{} is not a u64", cursor);
+            assert_ne!(cursor, "0");
+            assert_eq!(decode(cursor.as_bytes()).as_deref(), Some(key));
+        }
+    }
+
+    #[test]
+    fn long_keys_keep_their_cursor() {
+        let key = b"a resume key too long to go inline";
+        let cursor = encode(key);
+        assert_eq!(encode(key), cursor);
+
+        // A cursor still in use survives generations of others
+        for i in 0..3 * GENERATION_SIZE {
+            encode(format!("another long resume key {}", i).as_bytes());
+            if i % (GENERATION_SIZE / 2) == 0 {
+                assert_eq!(decode(cursor.as_bytes()).as_deref(), Some(&key[..]));
+            }
+        }
+        assert_eq!(decode(cursor.as_bytes()).as_deref(), Some(&key[..]));
+    }
+
+    #[test]
+    fn rejects_foreign_cursors() {
+        assert_eq!(decode(b"0"), None);
+        assert_eq!(decode(b""), None);
+        assert_eq!(decode(b"12a"), None);
+        assert_eq!(decode(b"-1"), None);
+        // 2 is not behind the marker byte
+        assert_eq!(decode(b"2"), None);
+        // Past u64
+        assert_eq!(decode(b"18446744073709551616"), None);
+        // A token nothing was stored under
+        assert_eq!(decode(TOKEN_BIT.to_string().as_bytes()), None);
+    }
+}
@@ -1,82 +1,325 @@
 use super::client::ClientOperations;
+use super::cursor;
 use super::hash::HashOperations;
 use super::list::ListOperations;
-use super::Command;
+use super::set::SetOperations;
+use super::zset::{format_score, ZSetOperations};
+use super::{
+    collection_deadline, db_key_prefix, db_of_key, deadline_key, is_write_command, BitOperation,
+    Command, ExpireCondition, COMMAND_NAMES,
+};
+use crate::acl::{self, User};
+use crate::aof::AppendLog;
+use crate::blocking::BlockingHub;
 use crate::client_registry::ClientRegistry;
 use crate::config::Config;
+use crate::glob::glob_match;
 use crate::protocol::resp::RespValue;
+use crate::pubsub::{
+    notify_flags_to_string, parse_notify_flags, GlobalRegistry, KeyspaceNotifier, NOTIFY_GENERIC,
+    NOTIFY_STRING,
+};
+use crate::scripting;
+use crate::slowlog::SlowLog;
+use crate::snapshot::Snapshots;
+use crate::stats::{ServerStats, StoreCounters};
+use crate::watch::{WatchTable, WatchVersion};
 use bytes::Bytes;
 use feoxdb::FeoxStore;
 use std::sync::Arc;
 
 /// Match a key against a glob pattern
 fn match_pattern(key: &[u8], pattern: &str) -> bool {
-    let key_str = String::from_utf8_lossy(key);
-    glob_match(pattern, &key_str)
+    glob_match(pattern.as_bytes(), key)
 }
 
-/// Simple glob pattern matching (* and ? support)
-fn glob_match(pattern: &str, text: &str) -> bool {
-    let mut p_idx = 0;
-    let mut t_idx = 0;
-    let mut star_idx = None;
-    let mut star_match = None;
-
-    let pattern_bytes = pattern.as_bytes();
-    let text_bytes = text.as_bytes();
-
-    while t_idx < text_bytes.len() {
-        if p_idx < pattern_bytes.len() {
-            match pattern_bytes[p_idx] {
-                b'*' => {
-                    star_idx = Some(p_idx);
-                    star_match = Some(t_idx);
-                    p_idx += 1;
-                }
-                b'?' => {
-                    p_idx += 1;
-                    t_idx += 1;
-                }
-                _ => {
-                    if pattern_bytes[p_idx] == text_bytes[t_idx] {
-                        p_idx += 1;
-                        t_idx += 1;
-                    } else if let Some(star) = star_idx {
-                        p_idx = star + 1;
-                        star_match = Some(star_match.unwrap() + 1);
-                        t_idx = star_match.unwrap();
-                    } else {
-                        return false;
-                    }
+/// Extract prefix from a pattern (everything before the first wildcard or escape)
+fn extract_prefix(pattern: &str) -> &str {
+    for (i, ch) in pattern.char_indices() {
+        if matches!(ch, '*' | '?' | '[' | '\\') {
+            return &pattern[..i];
+        }
+    }
+    pattern
+}
+
+/// Current wall-clock time in milliseconds since the Unix epoch
+fn unix_time_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Convert a millisecond TTL to the whole seconds FeoxDB stores
+///
+/// Rounds up, so a sub-second TTL lives for one second instead of none.
+fn millis_to_ttl_secs(millis: u64) -> u64 {
+    millis.div_ceil(1000)
+}
+
+/// Parse a string value as INCR reads it: a plain decimal i64, with no sign
+/// on positive numbers, no leading zeros and no whitespace
+fn parse_integer(value: &[u8]) -> Option<i64> {
+    let text = std::str::from_utf8(value).ok()?;
+    let n = text.parse::<i64>().ok()?;
+    (n.to_string() == text).then_some(n)
+}
+
+/// Format set members as a RESP array
+fn set_reply(members: crate::error::Result<Vec<Vec<u8>>>) -> RespValue {
+    match members {
+        Ok(members) => RespValue::Array(Some(
+            members
+                .into_iter()
+                .map(|m| RespValue::BulkString(Some(Bytes::from(m))))
+                .collect(),
+        )),
+        Err(e) => RespValue::Error(format!("ERR {}", e)),
+    }
+}
+
+/// Format the per-field integer replies of the hash field TTL commands
+fn field_codes_reply(codes: crate::error::Result<Vec<i64>>) -> RespValue {
+    match codes {
+        Ok(codes) => RespValue::Array(Some(codes.into_iter().map(RespValue::Integer).collect())),
+        Err(e) => RespValue::Error(format!("ERR {}", e)),
+    }
+}
+
+/// Format sorted set members as a RESP array, optionally interleaving scores
+fn zset_reply(entries: crate::error::Result<Vec<(Vec<u8>, f64)>>, with_scores: bool) -> RespValue {
+    match entries {
+        Ok(entries) => {
+            let mut items = Vec::with_capacity(entries.len() * if with_scores { 2 } else { 1 });
+            for (member, score) in entries {
+                items.push(RespValue::BulkString(Some(Bytes::from(member))));
+                if with_scores {
+                    items.push(RespValue::BulkString(Some(Bytes::from(format_score(
+                        score,
+                    )))));
                 }
             }
-        } else if let Some(star) = star_idx {
-            p_idx = star + 1;
-            star_match = Some(star_match.unwrap() + 1);
-            t_idx = star_match.unwrap();
-        } else {
-            return false;
+            RespValue::Array(Some(items))
         }
+        Err(e) => RespValue::Error(format!("ERR {}", e)),
     }
+}
 
-    // Check remaining pattern characters (should only be *)
-    while p_idx < pattern_bytes.len() && pattern_bytes[p_idx] == b'*' {
-        p_idx += 1;
-    }
+/// Database a physical store key belongs to
+///
+/// Collection records carry a one-letter type prefix (`L:`, `H:`, `S:`, `Z:`,
+/// and `X:` for deadlines) in front of the logical key, which is where a
+/// database prefix sits.
+fn physical_db(key: &[u8], databases: usize) -> usize {
+    let logical = match key {
+        [b'L' | b'H' | b'S' | b'Z' | b'X', b':', rest @ ..] => rest,
+        _ => key,
+    };
+    db_of_key(logical, databases)
+}
 
-    p_idx == pattern_bytes.len()
+/// Effect of a command that is reported through keyspace notifications
+///
+/// Captured before the command runs so the outcome can be compared against
+/// it afterwards.
+enum KeyEvent {
+    /// Keys written with these values; `with_ttl` if the write set an expiry
+    Set {
+        entries: Vec<(Vec<u8>, Bytes)>,
+        keep_ttl: bool,
+        with_ttl: bool,
+    },
+    /// Keys that existed and are being deleted
+    Del(Vec<Vec<u8>>),
+    /// Key whose TTL is being set; may delete it if the time has passed
+    Expire(Vec<u8>),
+    /// Key whose TTL is being removed
+    Persist(Vec<u8>),
+    /// Destination of a COPY, stored in database `db`
+    CopyTo { key: Vec<u8>, db: usize },
 }
 
-/// Extract prefix from a pattern (everything before the first wildcard)
-fn extract_prefix(pattern: &str) -> &str {
-    for (i, ch) in pattern.char_indices() {
-        if ch == '*' || ch == '?' || ch == '[' {
-            return &pattern[..i];
+/// Number of records fetched per range query when walking a key prefix
+const SCAN_BATCH: usize = 10000;
+
+/// Policies CONFIG SET accepts for `maxmemory-policy`, which is only recorded
+const MAXMEMORY_POLICIES: [&str; 8] = [
+    "noeviction",
+    "allkeys-lru",
+    "allkeys-lfu",
+    "allkeys-random",
+    "volatile-lru",
+    "volatile-lfu",
+    "volatile-random",
+    "volatile-ttl",
+];
+
+/// Visit every key/value pair stored under a prefix, in batches
+fn scan_prefix(store: &FeoxStore, prefix: &[u8], mut f: impl FnMut(&[u8], &[u8])) {
+    let mut start = prefix.to_vec();
+    let mut end = prefix.to_vec();
+    end.push(255);
+
+    loop {
+        let batch = match store.range_query(&start, &end, SCAN_BATCH) {
+            Ok(batch) => batch,
+            Err(_) => return,
+        };
+
+        for (key, value) in &batch {
+            if key.starts_with(prefix) {
+                f(key, value);
+            }
+        }
+
+        match batch.last() {
+            Some((last, _)) if batch.len() == SCAN_BATCH => {
+                // Resume just past the last key seen
+                start = last.clone();
+                start.push(0);
+            }
+            _ => return,
         }
     }
-    pattern
 }
 
+/// Parse a memory size such as `1048576`, `100mb` or `1gb` into bytes
+fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.to_lowercase();
+    let (digits, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => value.split_at(pos),
+        None => (value.as_str(), ""),
+    };
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Arity and key positions of a command in `COMMAND_NAMES`, as Redis
+/// reports them
+///
+/// Each entry is `(arity, first_key, last_key, step)`. A negative arity is
+/// a minimum, a negative last key counts from the end, and commands without
+/// keys, or that say where their keys are with an argument, have 0 for all
+/// three positions.
+fn command_spec(name: &str) -> Option<(i64, i64, i64, i64)> {
+    Some(match name {
+        "get" | "strlen" | "type" | "incr" | "decr" | "getdel" | "expiretime" | "pexpiretime"
+        | "ttl" | "pttl" | "persist" | "llen" | "hgetall" | "hlen" | "hkeys" | "hvals"
+        | "smembers" | "scard" | "zcard" => (2, 1, 1, 1),
+        "set" | "lpush" | "rpush" | "sadd" | "srem" | "hdel" | "hmget" | "zrem" | "zrank"
+        | "smismember" | "bitpos" | "hscan" | "expire" | "pexpire" | "expireat" | "pexpireat" => {
+            (-3, 1, 1, 1)
+        }
+        "getset" | "append" | "incrby" | "decrby" | "setnx" | "getbit" | "lindex" | "hget"
+        | "hexists" | "hstrlen" | "sismember" | "zscore" | "jsonpatch" | "spublish" => (3, 1, 1, 1),
+        "setrange" | "getrange" | "lrange" | "lset" | "lrem" | "hsetnx" | "hincrby" | "zincrby"
+        | "setex" | "psetex" | "setbit" | "cas" => (4, 1, 1, 1),
+        "getex" | "lpop" | "rpop" | "spop" | "bitcount" => (-2, 1, 1, 1),
+        "hset" | "zadd" | "zrange" | "zrangebyscore" => (-4, 1, 1, 1),
+        "linsert" => (5, 1, 1, 1),
+        "hexpire" | "hpexpire" => (-6, 1, 1, 1),
+        "httl" | "hpersist" => (-5, 1, 1, 1),
+        "del" | "exists" | "unlink" | "touch" | "mget" | "watch" | "sunion" | "sinter"
+        | "sdiff" | "ssubscribe" => (-2, 1, -1, 1),
+        "sunsubscribe" => (-1, 1, -1, 1),
+        "sunionstore" | "sinterstore" | "sdiffstore" => (-3, 1, -1, 1),
+        "mset" | "msetnx" => (-3, 1, -1, 2),
+        "blpop" | "brpop" => (-3, 1, -2, 1),
+        "copy" => (-3, 1, 2, 1),
+        "bitop" => (-4, 2, -1, 1),
+        "object" => (-2, 2, 2, 1),
+        "echo" | "select" | "keys" => (2, 0, 0, 0),
+        "publish" | "wait" => (3, 0, 0, 0),
+        "monitor" | "dbsize" | "multi" | "exec" | "discard" | "unwatch" | "save" | "lastsave"
+        | "bgrewriteaof" | "reset" | "asking" | "readonly" | "readwrite" => (1, 0, 0, 0),
+        "ping" | "info" | "command" | "quit" | "flushdb" | "flushall" | "hello" | "unsubscribe"
+        | "punsubscribe" | "bgsave" | "lolwut" => (-1, 0, 0, 0),
+        "config" | "debug" | "slowlog" | "scan" | "auth" | "subscribe" | "psubscribe"
+        | "pubsub" | "client" | "script" | "acl" | "cluster" => (-2, 0, 0, 0),
+        "eval" | "evalsha" | "sintercard" => (-3, 0, 0, 0),
+        _ => return None,
+    })
+}
+
+/// Commands flagged `pubsub` in COMMAND replies
+const PUBSUB_COMMANDS: [&str; 10] = [
+    "subscribe",
+    "unsubscribe",
+    "psubscribe",
+    "punsubscribe",
+    "publish",
+    "pubsub",
+    "ssubscribe",
+    "sunsubscribe",
+    "spublish",
+    "monitor",
+];
+
+/// Commands flagged `admin` in COMMAND replies
+const ADMIN_COMMANDS: [&str; 9] = [
+    "config",
+    "debug",
+    "slowlog",
+    "save",
+    "bgsave",
+    "lastsave",
+    "bgrewriteaof",
+    "acl",
+    "cluster",
+];
+
+/// Every command in Redis COMMAND format, in `COMMAND_NAMES` order
+///
+/// Each entry is `[name, arity, flags, first_key, last_key, step]`.
+fn command_table() -> Vec<Vec<RespValue>> {
+    COMMAND_NAMES
+        .iter()
+        .enumerate()
+        .map(|(index, &name)| {
+            let (arity, first_key, last_key, step) = command_spec(name).unwrap_or((-1, 0, 0, 0));
+            let mut flags = Vec::new();
+            if is_write_command(index) {
+                flags.push("write");
+            } else if first_key > 0 {
+                flags.push("readonly");
+            }
+            if PUBSUB_COMMANDS.contains(&name) {
+                flags.push("pubsub");
+            }
+            if ADMIN_COMMANDS.contains(&name) {
+                flags.push("admin");
+            }
+            vec![
+                RespValue::BulkString(Some(Bytes::from_static(name.as_bytes()))),
+                RespValue::Integer(arity),
+                RespValue::Array(Some(
+                    flags
+                        .into_iter()
+                        .map(|flag| RespValue::SimpleString(Bytes::from_static(flag.as_bytes())))
+                        .collect(),
+                )),
+                RespValue::Integer(first_key),
+                RespValue::Integer(last_key),
+                RespValue::Integer(step),
+            ]
+        })
+        .collect()
+}
+
+/// Error returned when a command targets a key holding another type
+pub const WRONGTYPE_ERROR: &str =
+    "WRONGTYPE Operation against a key holding the wrong kind of value";
+
 /// Executes parsed Redis commands against a FeoxStore
 ///
 /// Translates between Redis protocol semantics and FeOx operations.
@@ -85,11 +328,27 @@ pub struct CommandExecutor {
     store: Arc<FeoxStore>,
     list_ops: ListOperations,
     hash_ops: HashOperations,
+    set_ops: SetOperations,
+    zset_ops: ZSetOperations,
     client_ops: ClientOperations,
     config: Config, // Store config for auth checking
     start_time: std::time::Instant,
-    commands_processed: Arc<std::sync::atomic::AtomicU64>,
     connection_id: Option<usize>,
+    // Selected logical database and the prefix its keys are stored under
+    db: usize,
+    key_prefix: Vec<u8>,
+    notifier: Option<Arc<KeyspaceNotifier>>,
+    slowlog: Option<Arc<SlowLog>>,
+    // Shared stats and the worker shard this executor records into
+    stats: Option<(Arc<ServerStats>, usize)>,
+    run_id: Option<Arc<str>>,
+    blocking: Option<Arc<BlockingHub>>,
+    watches: Arc<WatchTable>,
+    snapshots: Option<Arc<Snapshots>>,
+    append_log: Option<Arc<AppendLog>>,
+    pubsub: Option<Arc<GlobalRegistry>>,
+    // The user the connection is logged in as, whose permissions apply
+    user: Arc<User>,
 }
 
 impl CommandExecutor {
@@ -97,16 +356,129 @@ impl CommandExecutor {
     pub fn new(store: Arc<FeoxStore>, config: &Config) -> Self {
         let list_ops = ListOperations::new(Arc::clone(&store));
         let hash_ops = HashOperations::new(Arc::clone(&store));
+        let set_ops = SetOperations::new(Arc::clone(&store));
+        let zset_ops = ZSetOperations::new(Arc::clone(&store));
         Self {
             store,
             list_ops,
             hash_ops,
+            set_ops,
+            zset_ops,
             client_ops: ClientOperations::new(),
             config: config.clone(),
             start_time: std::time::Instant::now(),
-            commands_processed: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             connection_id: None,
+            db: 0,
+            key_prefix: Vec::new(),
+            notifier: None,
+            slowlog: None,
+            stats: None,
+            run_id: None,
+            blocking: None,
+            watches: WatchTable::new(),
+            snapshots: None,
+            append_log: None,
+            pubsub: None,
+            user: Arc::clone(config.acl_users.default_user()),
+        }
+    }
+
+    /// Set the notifier used to publish keyspace events
+    pub fn with_notifier(mut self, notifier: Arc<KeyspaceNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Set the slow log shared by every connection
+    pub fn with_slowlog(mut self, slowlog: Arc<SlowLog>) -> Self {
+        self.slowlog = Some(slowlog);
+        self
+    }
+
+    /// Set the server stats and the worker shard to record commands in
+    pub fn with_stats(mut self, stats: Arc<ServerStats>, shard: usize) -> Self {
+        self.stats = Some((stats, shard));
+        self
+    }
+
+    /// Set the id of the server instance this executor runs in
+    pub fn with_run_id(mut self, run_id: Arc<str>) -> Self {
+        self.run_id = Some(run_id);
+        self
+    }
+
+    /// Set where SAVE and BGSAVE write snapshots
+    pub fn with_snapshots(mut self, snapshots: Arc<Snapshots>) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    /// Set the append-only log BGREWRITEAOF compacts
+    pub fn with_append_log(mut self, append_log: Arc<AppendLog>) -> Self {
+        self.append_log = Some(append_log);
+        self
+    }
+
+    /// Set the pub/sub registry INFO counts channels and patterns in
+    pub fn with_pubsub_registry(mut self, registry: Arc<GlobalRegistry>) -> Self {
+        self.pubsub = Some(registry);
+        self
+    }
+
+    /// Set the hub that wakes clients blocked on lists this executor pushes to
+    pub fn with_blocking_hub(mut self, hub: Arc<BlockingHub>) -> Self {
+        self.blocking = Some(hub);
+        self
+    }
+
+    /// Share the table of watched keys with every other connection, so
+    /// their writes abort this one's transactions
+    pub fn with_watch_table(mut self, watches: Arc<WatchTable>) -> Self {
+        self.watches = watches;
+        self
+    }
+
+    /// Record a command the connection answered without `execute`
+    #[inline]
+    pub fn record_command(&self, index: usize, elapsed: std::time::Duration, failed: bool) {
+        if let Some((stats, shard)) = &self.stats {
+            stats.record_command(*shard, index, elapsed, failed);
+        }
+    }
+
+    /// Check if commands should be timed for the slow log
+    #[inline]
+    pub fn slowlog_enabled(&self) -> bool {
+        self.slowlog.as_ref().is_some_and(|s| s.is_enabled())
+    }
+
+    /// Add a command to the slow log if it took long enough
+    pub fn log_slow_command(
+        &self,
+        elapsed: std::time::Duration,
+        args: Vec<Bytes>,
+        client_addr: Option<std::net::SocketAddr>,
+        client_name: Option<&str>,
+    ) {
+        if let Some(slowlog) = &self.slowlog {
+            slowlog.record(elapsed, args, client_addr, client_name);
+        }
+    }
+
+    /// Check if commands currently need to publish keyspace events
+    #[inline]
+    pub fn notifications_active(&self) -> bool {
+        self.notifier.as_ref().is_some_and(|n| n.is_active())
+    }
+
+    /// Switch to another logical database, returning false if out of range
+    pub fn select_db(&mut self, db: usize) -> bool {
+        if db >= self.config.databases {
+            return false;
         }
+        self.db = db;
+        self.key_prefix = db_key_prefix(db);
+        true
     }
 
     /// Set the client registry and connection ID for CLIENT command support
@@ -116,11 +488,105 @@ impl CommandExecutor {
         self
     }
 
+    /// Where to save a snapshot, with collection sizes first written to
+    /// their `:meta` records so the snapshot has them
+    fn prepare_save(&self) -> Option<&Arc<Snapshots>> {
+        let snapshots = self.snapshots.as_ref()?;
+        self.flush_metadata();
+        Some(snapshots)
+    }
+
+    /// Write the batched collection sizes through to the store
+    fn flush_metadata(&self) {
+        self.hash_ops.flush_metadata();
+        self.set_ops.flush_metadata();
+        self.zset_ops.flush_metadata();
+    }
+
+    /// Parameters visible to CONFIG GET, derived from the running config
+    fn config_params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("maxmemory", self.config.runtime.maxmemory().to_string()),
+            (
+                "maxmemory-policy",
+                self.config.runtime.inert("maxmemory-policy"),
+            ),
+            ("save", self.config.runtime.inert("save")),
+            ("dbfilename", self.config.dbfilename.clone()),
+            ("appendonly", self.config.runtime.inert("appendonly")),
+            ("appendfsync", self.config.appendfsync.clone()),
+            ("timeout", self.config.runtime.timeout().to_string()),
+            ("maxclients", self.config.runtime.maxclients().to_string()),
+            (
+                "slowlog-log-slower-than",
+                self.config.runtime.slowlog_log_slower_than().to_string(),
+            ),
+            (
+                "slowlog-max-len",
+                self.config.runtime.slowlog_max_len().to_string(),
+            ),
+            ("bind", self.config.bind_addr.clone()),
+            ("port", self.config.port.to_string()),
+            ("databases", self.config.databases.to_string()),
+            (
+                "proto-max-bulk-len",
+                self.config.proto_max_bulk_len.to_string(),
+            ),
+            (
+                "proto-max-multibulk-len",
+                self.config.proto_max_multibulk_len.to_string(),
+            ),
+            (
+                "client-query-buffer-limit",
+                self.config.client_query_buffer_limit.to_string(),
+            ),
+            (
+                "notify-keyspace-events",
+                notify_flags_to_string(self.config.runtime.notify_flags()),
+            ),
+        ]
+    }
+
     /// Check if password is correct
     pub fn check_auth(&self, password: &str) -> bool {
         self.config.check_password(password)
     }
 
+    /// Look up the user `name`, if `password` is theirs
+    pub fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        self.config.acl_users.authenticate(name, password)
+    }
+
+    /// The user connections start as
+    pub fn default_user(&self) -> &Arc<User> {
+        self.config.acl_users.default_user()
+    }
+
+    /// Switch the user whose permissions commands run under
+    pub fn set_user(&mut self, user: Arc<User>) {
+        self.user = user;
+    }
+
+    /// Check if the current user may run every command, so the fast path
+    /// can skip the permission check
+    #[inline]
+    pub fn unrestricted(&self) -> bool {
+        self.user.allows_all()
+    }
+
+    /// The NOPERM error for a command the current user may not run, or
+    /// None if it is allowed
+    pub fn check_permission(&self, command: &Command) -> Option<RespValue> {
+        if self.user.allows(command) {
+            return None;
+        }
+        Some(RespValue::Error(format!(
+            "NOPERM User {} has no permissions to run the '{}' command",
+            self.user.name(),
+            command.name()
+        )))
+    }
+
     // Fast-path SET operation
     #[inline(always)]
     pub fn fast_set(&self, key: &[u8], value: &[u8]) -> Result<(), feoxdb::FeoxError> {
@@ -130,8 +596,10 @@ impl CommandExecutor {
 
     // Fast-path SET operation with Bytes
     #[inline(always)]
-    pub fn fast_set_bytes(&self, key: &[u8], value: bytes::Bytes) -> Result<(), feoxdb::FeoxError> {
+    pub fn fast_set_bytes(&self, key: &[u8], value: bytes::Bytes) -> crate::error::Result<()> {
+        self.clear_collection(key)?;
         self.store.insert_bytes_with_timestamp(key, value, None)?;
+        self.watches.touch(key);
         Ok(())
     }
 
@@ -141,127 +609,1494 @@ impl CommandExecutor {
         self.store.get_bytes(key)
     }
 
-    /// Execute a command and return RESP response
-    #[inline]
-    pub fn execute(&self, cmd: Command) -> RespValue {
-        use std::sync::atomic::Ordering;
+    /// Overwrite a string value while keeping the key's remaining TTL
+    ///
+    /// Plain inserts clear the TTL, but in-place edits like APPEND must not.
+    fn insert_keep_ttl(&self, key: &[u8], value: Bytes) -> Result<(), feoxdb::FeoxError> {
+        match self.store.get_ttl(key) {
+            Ok(Some(ttl)) => {
+                self.store
+                    .insert_bytes_with_ttl_and_timestamp(key, value, ttl.max(1), None)
+            }
+            _ => self.store.insert_bytes_with_timestamp(key, value, None),
+        }
+        .map(|_| ())
+    }
 
-        // Increment command counter
-        self.commands_processed.fetch_add(1, Ordering::Relaxed);
+    /// Add `delta` to the integer stored as text at `key`, keeping its TTL; a
+    /// missing key counts as 0 and a None delta as an overflow
+    fn increment(&self, key: &[u8], delta: Option<i64>) -> RespValue {
+        loop {
+            let current = match self.store.get_bytes(key) {
+                Ok(current) => Some(current),
+                Err(feoxdb::FeoxError::KeyNotFound) => None,
+                Err(e) => return RespValue::Error(format!("ERR {}", e)),
+            };
+            let value = match &current {
+                Some(current) => match parse_integer(current) {
+                    Some(value) => value,
+                    None => {
+                        return RespValue::Error(
+                            "ERR value is not an integer or out of range".to_string(),
+                        )
+                    }
+                },
+                None => 0,
+            };
+            let Some(new_value) = delta.and_then(|delta| value.checked_add(delta)) else {
+                return RespValue::Error("ERR increment or decrement would overflow".to_string());
+            };
+            let new_bytes = new_value.to_string();
+
+            // Swap against what was read, so concurrent increments on
+            // other threads are not lost
+            let result = match &current {
+                Some(current) => {
+                    let ttl = match self.store.get_ttl(key) {
+                        Ok(Some(ttl)) => ttl.max(1),
+                        _ => 0,
+                    };
+                    self.store
+                        .compare_and_swap_with_ttl(key, current, new_bytes.as_bytes(), ttl)
+                }
+                // Another client may create the key first; then retry
+                // through the swap
+                None => self.insert_if_absent(key, new_bytes.as_bytes()),
+            };
+            match result {
+                Ok(true) => return RespValue::Integer(new_value),
+                Ok(false) => continue,
+                Err(e) => return RespValue::Error(format!("ERR {}", e)),
+            }
+        }
+    }
 
-        match cmd {
-            Command::Get(key) => match self.store.get_bytes(&key) {
-                Ok(value) => RespValue::BulkString(Some(value)),
-                Err(feoxdb::FeoxError::KeyNotFound) => RespValue::BulkString(None),
-                Err(e) => RespValue::Error(format!("ERR {}", e)),
-            },
+    /// Physical COPY destination, moved into database `db` if one was given
+    fn copy_destination(&self, dst: &[u8], db: Option<usize>) -> Vec<u8> {
+        match db {
+            Some(db) if db != self.db => {
+                let mut key = db_key_prefix(db);
+                key.extend_from_slice(&dst[self.key_prefix.len()..]);
+                key
+            }
+            _ => dst.to_vec(),
+        }
+    }
 
-            Command::Set { key, value, ex, px } => {
-                let result = if let Some(seconds) = ex {
-                    self.store
-                        .insert_bytes_with_ttl_and_timestamp(&key, value, seconds, None)
-                } else if let Some(millis) = px {
-                    self.store
-                        .insert_bytes_with_ttl_and_timestamp(&key, value, millis / 1000, None)
-                } else {
-                    self.store.insert_bytes_with_timestamp(&key, value, None)
-                };
+    /// Map a stored record to the user-visible key it represents, with its type
+    ///
+    /// Plain records are string keys. Each non-empty collection is reported
+    /// once, through its `T:{key}:meta` record; its other records are hidden.
+    fn logical_key<'a>(&self, key: &'a [u8], value: &[u8]) -> Option<(&'a [u8], &'static str)> {
+        let key_type = match key {
+            [b'L', b':', ..] => "list",
+            [b'H', b':', ..] => "hash",
+            [b'S', b':', ..] => "set",
+            [b'Z', b':', ..] => "zset",
+            [b'X', b':', ..] => return None,
+            _ => return Some((key, "string")),
+        };
+        if key.len() < 7 || !key.ends_with(b":meta") {
+            return None;
+        }
 
-                match result {
-                    Ok(_) => RespValue::SimpleString(Bytes::from_static(b"OK")),
-                    Err(e) => RespValue::Error(format!("ERR {}", e)),
-                }
+        // A field or member literally named `meta` also ends this way, so
+        // confirm the collection really exists
+        let logical = &key[2..key.len() - 5];
+        let exists = match key_type {
+            // List metadata is `head | tail | count`, 8 bytes each
+            "list" => {
+                value.len() >= 24 && u64::from_le_bytes(value[16..24].try_into().unwrap()) > 0
             }
+            "hash" => self.hash_ops.exists(logical),
+            "set" => self.set_ops.exists(logical),
+            _ => self.zset_ops.exists(logical),
+        };
+        exists.then_some((logical, key_type))
+    }
 
-            Command::Del(keys) => {
-                let mut count = 0i64;
-                for key in keys {
-                    if self.store.delete(&key).is_ok() {
-                        count += 1;
-                    }
+    /// Determine the Redis type of a logical key
+    ///
+    /// Collections live under `L:`/`H:`/`S:`/`Z:` prefixed physical keys, so
+    /// the plain key is checked first and then each collection namespace.
+    fn key_type(&self, key: &[u8]) -> &'static str {
+        if self.store.get_bytes(key).is_ok() {
+            "string"
+        } else if self.list_ops.exists(key) {
+            "list"
+        } else if self.hash_ops.exists(key) {
+            "hash"
+        } else if self.set_ops.exists(key) {
+            "set"
+        } else if self.zset_ops.exists(key) {
+            "zset"
+        } else {
+            "none"
+        }
+    }
+
+    /// Delete a logical key of any type, with all of a collection's physical
+    /// records, returning whether it existed
+    fn delete_key(&self, key: &[u8]) -> crate::error::Result<bool> {
+        let mut deleted = self.store.delete(key).is_ok();
+        deleted |= self.list_ops.delete(key)?;
+        deleted |= self.hash_ops.delete(key)?;
+        deleted |= self.set_ops.delete(key)?;
+        deleted |= self.zset_ops.delete(key)?;
+        self.store.delete(&deadline_key(key)).ok();
+        Ok(deleted)
+    }
+
+    /// Insert a string unless the key already holds a live one, returning
+    /// whether it was inserted
+    ///
+    /// The store counts an expired record as present until it is swept, so
+    /// one found here is deleted and the insert retried.
+    fn insert_if_absent(&self, key: &[u8], value: &[u8]) -> Result<bool, feoxdb::FeoxError> {
+        loop {
+            if self.store.insert_if_absent(key, value)? {
+                return Ok(true);
+            }
+            match self.store.get_bytes(key) {
+                Ok(_) => return Ok(false),
+                Err(feoxdb::FeoxError::KeyNotFound) => {
+                    self.store.delete(key).ok();
                 }
-                RespValue::Integer(count)
+                Err(e) => return Err(e),
             }
+        }
+    }
 
-            Command::Exists(keys) => {
-                let count = keys
-                    .iter()
-                    .filter(|key| self.store.contains_key(key))
-                    .count() as i64;
-                RespValue::Integer(count)
-            }
+    /// Delete any collection stored under `key`, ahead of a string write
+    /// that replaces it
+    fn clear_collection(&self, key: &[u8]) -> crate::error::Result<()> {
+        // A key holding a string holds nothing else
+        if self.store.contains_key(key) {
+            return Ok(());
+        }
+        self.list_ops.delete(key)?;
+        self.hash_ops.delete(key)?;
+        self.set_ops.delete(key)?;
+        self.zset_ops.delete(key)?;
+        self.store.delete(&deadline_key(key)).ok();
+        Ok(())
+    }
 
-            Command::Incr(key) => match self.store.atomic_increment(&key, 1) {
-                Ok(val) => RespValue::Integer(val),
-                Err(e) => RespValue::Error(format!("ERR {}", e)),
-            },
+    /// Delete a collection whose deadline has passed, or drop the deadline
+    /// of one that no longer exists so a new collection does not inherit it
+    fn expire_collection(&self, key: &[u8]) {
+        let Some(deadline) = collection_deadline(&self.store, key) else {
+            return;
+        };
+        if deadline <= unix_time_millis() as u64 {
+            self.delete_key(key).ok();
+        } else if self.key_type(key) == "none" {
+            self.store.delete(&deadline_key(key)).ok();
+        }
+    }
 
-            Command::IncrBy { key, delta } => match self.store.atomic_increment(&key, delta) {
-                Ok(val) => RespValue::Integer(val),
-                Err(e) => RespValue::Error(format!("ERR {}", e)),
-            },
+    /// Delete every collection whose deadline has passed, in any database
+    ///
+    /// Commands expire the collections they name; this catches the ones
+    /// nothing touches.
+    pub fn expire_collections(&self) {
+        let now = unix_time_millis() as u64;
+        let mut due = Vec::new();
+        scan_prefix(&self.store, b"X:", |key, value| {
+            if <[u8; 8]>::try_from(value).is_ok_and(|deadline| u64::from_le_bytes(deadline) <= now)
+            {
+                due.push(key[2..].to_vec());
+            }
+        });
+        for key in due {
+            self.delete_key(&key).ok();
+        }
+    }
 
-            Command::Decr(key) => match self.store.atomic_increment(&key, -1) {
-                Ok(val) => RespValue::Integer(val),
+    /// Give a collection a deadline in Unix milliseconds, deleting it if
+    /// the time has already passed
+    fn set_collection_deadline(&self, key: &[u8], deadline: u64) -> RespValue {
+        if deadline <= unix_time_millis() as u64 {
+            return match self.delete_key(key) {
+                Ok(deleted) => RespValue::Integer(deleted as i64),
                 Err(e) => RespValue::Error(format!("ERR {}", e)),
-            },
+            };
+        }
+        match self
+            .store
+            .insert(&deadline_key(key), &deadline.to_le_bytes())
+        {
+            Ok(_) => RespValue::Integer(1),
+            Err(e) => RespValue::Error(format!("ERR {}", e)),
+        }
+    }
 
-            Command::DecrBy { key, delta } => match self.store.atomic_increment(&key, -delta) {
-                Ok(val) => RespValue::Integer(val),
-                Err(e) => RespValue::Error(format!("ERR {}", e)),
-            },
+    /// Milliseconds a collection has left, or None inside if it has no TTL;
+    /// None if there is no collection under the key
+    fn collection_ttl_millis(&self, key: &[u8]) -> Option<Option<u64>> {
+        if self.key_type(key) == "none" {
+            return None;
+        }
+        let now = unix_time_millis() as u64;
+        Some(collection_deadline(&self.store, key).map(|deadline| deadline.saturating_sub(now)))
+    }
 
-            Command::Expire { key, seconds } => match self.store.update_ttl(&key, seconds) {
-                Ok(_) => RespValue::Integer(1),
-                Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(0),
-                Err(e) => RespValue::Error(format!("ERR {}", e)),
+    /// Milliseconds a key of any type has left, or None if it has no TTL
+    ///
+    /// FeoxDB keeps string TTLs in whole seconds and `get_ttl` rounds the
+    /// time left down, so the deadline falls within the second after the one
+    /// it reports. Counting to the end of that second keeps TTL and PTTL from
+    /// reading 0 straight after a sub-second PEXPIRE, whose TTL was rounded up.
+    fn ttl_millis(&self, key: &[u8]) -> std::result::Result<Option<u64>, feoxdb::FeoxError> {
+        match self.store.get_ttl(key) {
+            // Expired records linger until swept
+            Ok(Some(0)) if self.store.get_bytes(key).is_err() => {
+                Err(feoxdb::FeoxError::KeyNotFound)
+            }
+            Ok(ttl) => Ok(ttl.map(|secs| (secs + 1) * 1000)),
+            Err(feoxdb::FeoxError::KeyNotFound) => match self.collection_ttl_millis(key) {
+                Some(ttl) => Ok(ttl),
+                None => Err(feoxdb::FeoxError::KeyNotFound),
             },
+            Err(e) => Err(e),
+        }
+    }
 
-            Command::PExpire { key, milliseconds } => {
-                match self.store.update_ttl(&key, milliseconds / 1000) {
-                    Ok(_) => RespValue::Integer(1),
-                    Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(0),
-                    Err(e) => RespValue::Error(format!("ERR {}", e)),
-                }
+    /// Seconds a key of any type has left, rounded up, or None if it has no TTL
+    fn ttl_secs(&self, key: &[u8]) -> std::result::Result<Option<u64>, feoxdb::FeoxError> {
+        self.ttl_millis(key)
+            .map(|ttl| ttl.map(|millis| millis.div_ceil(1000)))
+    }
+
+    /// The Redis encoding a physical key would have, and its serialized
+    /// length for strings, or None if the key does not exist
+    fn object_encoding(&self, key: &[u8]) -> Option<(&'static str, usize)> {
+        match self.key_type(key) {
+            "string" => {
+                let value = self.store.get_bytes(key).unwrap_or_default();
+                let encoding =
+                    if std::str::from_utf8(&value).is_ok_and(|s| s.parse::<i64>().is_ok()) {
+                        "int"
+                    } else if value.len() <= 44 {
+                        "embstr"
+                    } else {
+                        "raw"
+                    };
+                Some((encoding, value.len()))
             }
+            "list" => Some(("quicklist", 0)),
+            "hash" | "set" => Some(("hashtable", 0)),
+            "zset" => Some(("skiplist", 0)),
+            _ => None,
+        }
+    }
 
-            Command::Ttl(key) => {
-                match self.store.get_ttl(&key) {
-                    Ok(Some(ttl)) => RespValue::Integer(ttl as i64),
-                    Ok(None) => RespValue::Integer(-1), // No TTL
-                    Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(-2),
-                    Err(e) => RespValue::Error(format!("ERR {}", e)),
-                }
+    /// Check if the key holds a value of any type other than `expected`
+    pub fn holds_other_type(&self, key: &[u8], expected: &str) -> bool {
+        (expected != "string" && self.store.get_bytes(key).is_ok())
+            || (expected != "list" && self.list_ops.exists(key))
+            || (expected != "hash" && self.hash_ops.exists(key))
+            || (expected != "set" && self.set_ops.exists(key))
+            || (expected != "zset" && self.zset_ops.exists(key))
+    }
+
+    /// Return a WRONGTYPE error if the command's key holds another type
+    ///
+    /// Commands that replace a value outright (SET, MSET, DEL) are not
+    /// checked, matching Redis; they delete whatever the key held instead.
+    fn check_wrong_type(&self, cmd: &Command) -> Option<RespValue> {
+        // Set algebra reads every source key; the destination is overwritten
+        if let Command::SUnion(keys)
+        | Command::SInter(keys)
+        | Command::SDiff(keys)
+        | Command::SInterCard { keys, .. }
+        | Command::SUnionStore { keys, .. }
+        | Command::SInterStore { keys, .. }
+        | Command::SDiffStore { keys, .. } = cmd
+        {
+            return keys
+                .iter()
+                .any(|key| self.holds_other_type(key, "set"))
+                .then(|| RespValue::Error(WRONGTYPE_ERROR.to_string()));
+        }
+
+        if let Command::BitOp { keys, .. } = cmd {
+            return keys
+                .iter()
+                .any(|key| self.holds_other_type(key, "string"))
+                .then(|| RespValue::Error(WRONGTYPE_ERROR.to_string()));
+        }
+
+        if let Command::BLPop { keys, .. } | Command::BRPop { keys, .. } = cmd {
+            return keys
+                .iter()
+                .any(|key| self.holds_other_type(key, "list"))
+                .then(|| RespValue::Error(WRONGTYPE_ERROR.to_string()));
+        }
+
+        let (key, expected) = match cmd {
+            Command::Get(key)
+            | Command::GetSet { key, .. }
+            | Command::GetDel(key)
+            | Command::GetEx { key, .. }
+            | Command::Append { key, .. }
+            | Command::StrLen(key)
+            | Command::SetRange { key, .. }
+            | Command::GetRange { key, .. }
+            | Command::SetBit { key, .. }
+            | Command::GetBit { key, .. }
+            | Command::BitCount { key, .. }
+            | Command::BitPos { key, .. }
+            | Command::Incr(key)
+            | Command::IncrBy { key, .. }
+            | Command::Decr(key)
+            | Command::DecrBy { key, .. }
+            | Command::JsonPatch { key, .. }
+            | Command::Cas { key, .. }
+            | Command::Set { key, get: true, .. } => (key, "string"),
+
+            Command::LPush { key, .. }
+            | Command::RPush { key, .. }
+            | Command::LPop { key, .. }
+            | Command::RPop { key, .. }
+            | Command::LLen(key)
+            | Command::LRange { key, .. }
+            | Command::LIndex { key, .. }
+            | Command::LSet { key, .. }
+            | Command::LRem { key, .. }
+            | Command::LInsert { key, .. } => (key, "list"),
+
+            Command::HSet { key, .. }
+            | Command::HGet { key, .. }
+            | Command::HMGet { key, .. }
+            | Command::HDel { key, .. }
+            | Command::HExists { key, .. }
+            | Command::HSetNx { key, .. }
+            | Command::HStrLen { key, .. }
+            | Command::HGetAll(key)
+            | Command::HScan { key, .. }
+            | Command::HLen(key)
+            | Command::HKeys(key)
+            | Command::HVals(key)
+            | Command::HIncrBy { key, .. }
+            | Command::HExpire { key, .. }
+            | Command::HPExpire { key, .. }
+            | Command::HTtl { key, .. }
+            | Command::HPersist { key, .. } => (key, "hash"),
+
+            Command::SAdd { key, .. }
+            | Command::SRem { key, .. }
+            | Command::SMembers(key)
+            | Command::SIsMember { key, .. }
+            | Command::SMIsMember { key, .. }
+            | Command::SCard(key)
+            | Command::SPop { key, .. } => (key, "set"),
+
+            Command::ZAdd { key, .. }
+            | Command::ZScore { key, .. }
+            | Command::ZIncrBy { key, .. }
+            | Command::ZCard(key)
+            | Command::ZRem { key, .. }
+            | Command::ZRange { key, .. }
+            | Command::ZRangeByScore { key, .. }
+            | Command::ZRank { key, .. } => (key, "zset"),
+
+            _ => return None,
+        };
+
+        // A plain key can only ever be a string, so skip the probes
+        if expected == "string" && self.store.contains_key(key) {
+            return None;
+        }
+
+        if self.holds_other_type(key, expected) {
+            Some(RespValue::Error(WRONGTYPE_ERROR.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Set a relative TTL, honouring an optional NX/XX/GT/LT condition
+    ///
+    /// A key without an expiry counts as infinite TTL for GT and LT.
+    fn expire(&self, key: &[u8], seconds: u64, condition: Option<ExpireCondition>) -> RespValue {
+        if let Some(condition) = condition {
+            let current = match self.ttl_secs(key) {
+                Ok(ttl) => ttl,
+                Err(feoxdb::FeoxError::KeyNotFound) => return RespValue::Integer(0),
+                Err(e) => return RespValue::Error(format!("ERR {}", e)),
+            };
+
+            let allowed = match (condition, current) {
+                (ExpireCondition::Nx, current) => current.is_none(),
+                (ExpireCondition::Xx, current) => current.is_some(),
+                (ExpireCondition::Gt, Some(ttl)) => seconds > ttl,
+                (ExpireCondition::Gt, None) => false,
+                (ExpireCondition::Lt, Some(ttl)) => seconds < ttl,
+                (ExpireCondition::Lt, None) => true,
+            };
+            if !allowed {
+                return RespValue::Integer(0);
             }
+        }
 
-            Command::PTtl(key) => {
-                match self.store.get_ttl(&key) {
-                    Ok(Some(ttl)) => RespValue::Integer((ttl * 1000) as i64),
-                    Ok(None) => RespValue::Integer(-1), // No TTL
-                    Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(-2),
-                    Err(e) => RespValue::Error(format!("ERR {}", e)),
-                }
+        match self.store.update_ttl(key, seconds) {
+            Ok(_) => RespValue::Integer(1),
+            Err(feoxdb::FeoxError::KeyNotFound) if self.key_type(key) != "none" => {
+                let deadline = unix_time_millis() as u64 + seconds.saturating_mul(1000);
+                self.set_collection_deadline(key, deadline)
             }
+            Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(0),
+            Err(e) => RespValue::Error(format!("ERR {}", e)),
+        }
+    }
 
-            Command::Persist(key) => match self.store.persist(&key) {
-                Ok(_) => RespValue::Integer(1),
-                Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(0),
+    /// Expire a key at an absolute Unix time in milliseconds
+    ///
+    /// The remaining time is rounded up to whole seconds. A timestamp that has already passed deletes the key.
+    fn expire_at_millis(&self, key: &[u8], timestamp_ms: i64) -> RespValue {
+        let remaining_ms = timestamp_ms.saturating_sub(unix_time_millis());
+        if remaining_ms <= 0 {
+            return match self.delete_key(key) {
+                Ok(deleted) => RespValue::Integer(deleted as i64),
                 Err(e) => RespValue::Error(format!("ERR {}", e)),
-            },
+            };
+        }
 
-            Command::MGet(keys) => {
-                let values: Vec<RespValue> = keys
-                    .into_iter()
-                    .map(|key| match self.store.get_bytes(&key) {
-                        Ok(value) => RespValue::BulkString(Some(value)),
-                        Err(_) => RespValue::BulkString(None),
-                    })
-                    .collect();
-                RespValue::Array(Some(values))
+        match self
+            .store
+            .update_ttl(key, millis_to_ttl_secs(remaining_ms as u64))
+        {
+            Ok(_) => RespValue::Integer(1),
+            Err(feoxdb::FeoxError::KeyNotFound) if self.key_type(key) != "none" => {
+                self.set_collection_deadline(key, timestamp_ms as u64)
             }
+            Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(0),
+            Err(e) => RespValue::Error(format!("ERR {}", e)),
+        }
+    }
 
-            Command::MSet(pairs) => {
-                for (key, value) in pairs {
+    /// Absolute expiry of a key in milliseconds, or -1 / -2 like TTL
+    fn expire_time_millis(&self, key: &[u8]) -> Result<i64, RespValue> {
+        match self.ttl_millis(key) {
+            Ok(Some(ttl)) => Ok(unix_time_millis() + ttl as i64),
+            Ok(None) => Ok(-1),
+            Err(feoxdb::FeoxError::KeyNotFound) => Ok(-2),
+            Err(e) => Err(RespValue::Error(format!("ERR {}", e))),
+        }
+    }
+
+    /// Write the result of a set operation to `destination`
+    fn store_set(
+        &self,
+        destination: &[u8],
+        members: crate::error::Result<Vec<Vec<u8>>>,
+    ) -> RespValue {
+        match members.and_then(|members| self.set_ops.store(destination, &members)) {
+            Ok(count) => RespValue::Integer(count),
+            Err(e) => RespValue::Error(format!("ERR {}", e)),
+        }
+    }
+
+    /// Count user-visible keys in database `db`
+    ///
+    /// Each collection occupies several physical `L:`/`H:`/`S:`/`Z:`
+    /// records, so those are subtracted from the raw record count and replaced by one
+    /// entry per non-empty collection.
+    fn logical_key_count(&self, db: usize) -> usize {
+        let databases = self.config.databases;
+        let key_prefix = db_key_prefix(db);
+        let mut internal = 0usize;
+        let mut collections = 0usize;
+        // Meta records look like `T:{key}:meta`
+        let in_db = |meta_key: &[u8]| db_of_key(&meta_key[2..meta_key.len() - 5], databases) == db;
+        let scoped = |type_prefix: &[u8]| [type_prefix, &key_prefix].concat();
+
+        // List metadata is `head | tail | count`, 8 bytes each
+        scan_prefix(&self.store, &scoped(b"L:"), |key, value| {
+            internal += 1;
+            if key.ends_with(b":meta") && value.len() >= 24 && in_db(key) {
+                let count = u64::from_le_bytes(value[16..24].try_into().unwrap());
+                if count > 0 {
+                    collections += 1;
+                }
+            }
+        });
+
+        scan_prefix(&self.store, &scoped(b"X:"), |_, _| internal += 1);
+
+        // Hash, set and sorted set metadata is a single member counter
+        self.hash_ops.flush_metadata();
+        self.set_ops.flush_metadata();
+        self.zset_ops.flush_metadata();
+        for type_prefix in [&b"H:"[..], &b"S:"[..], &b"Z:"[..]] {
+            scan_prefix(&self.store, &scoped(type_prefix), |key, value| {
+                internal += 1;
+                if key.ends_with(b":meta") && value.len() == 8 && in_db(key) {
+                    let count = i64::from_le_bytes(value[0..8].try_into().unwrap());
+                    if count > 0 {
+                        collections += 1;
+                    }
+                }
+            });
+        }
+
+        let strings = if db == 0 {
+            // Everything unprefixed, minus the strings of other databases
+            let mut other = 0usize;
+            for other_db in 1..databases {
+                scan_prefix(&self.store, &db_key_prefix(other_db), |_, _| other += 1);
+            }
+            self.store.len().saturating_sub(internal + other)
+        } else {
+            let mut count = 0usize;
+            scan_prefix(&self.store, &key_prefix, |_, _| count += 1);
+            count
+        };
+
+        strings + collections
+    }
+
+    /// Delete every record of the selected database, or of all databases
+    ///
+    /// FeOx has no clear operation, so keys are removed in batches. Pending
+    /// collection counters are flushed first so a late delta cannot recreate
+    /// metadata for a collection that no longer exists. Keys written by other
+    /// clients while the flush runs may survive it.
+    fn flush_keys(&self, all_databases: bool) {
+        self.hash_ops.flush_metadata();
+        self.set_ops.flush_metadata();
+        self.zset_ops.flush_metadata();
+
+        let databases = self.config.databases;
+        if all_databases {
+            self.delete_prefix(&[], |_| true);
+        } else if self.db == 0 {
+            self.delete_prefix(&[], |key| physical_db(key, databases) == 0);
+        } else {
+            // Strings and each collection type keep this database contiguous
+            for type_prefix in [&b""[..], b"L:", b"H:", b"S:", b"Z:", b"X:"] {
+                let prefix = [type_prefix, &self.key_prefix].concat();
+                self.delete_prefix(&prefix, |_| true);
+            }
+        }
+    }
+
+    /// Delete the records under `prefix` accepted by `filter`, in batches
+    fn delete_prefix(&self, prefix: &[u8], filter: impl Fn(&[u8]) -> bool) {
+        let mut start_key = prefix.to_vec();
+        let mut end_key = prefix.to_vec();
+        end_key.extend_from_slice(&[0xFF; 255]);
+        loop {
+            let batch = match self.store.range_query(&start_key, &end_key, SCAN_BATCH) {
+                Ok(batch) => batch,
+                Err(_) => return,
+            };
+
+            for (key, _) in &batch {
+                // A concurrent DEL may have removed it already
+                if key.starts_with(prefix) && filter(key) {
+                    self.store.delete(key).ok();
+                }
+            }
+
+            match batch.last() {
+                Some((last, _)) if batch.len() == SCAN_BATCH => {
+                    start_key = last.clone();
+                    start_key.push(0);
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Start watching a key of the selected database, returning the key as
+    /// stored and its state for `watch_state`
+    pub fn watch(&self, key: &[u8]) -> (Vec<u8>, (WatchVersion, bool)) {
+        let key = self.scoped_key(key);
+        let version = self.watches.watch(&key);
+        let state = (version, self.watch_exists(&key));
+        (key, state)
+    }
+
+    /// Stop watching a key returned by `watch`
+    pub fn unwatch(&self, key: &[u8]) {
+        self.watches.unwatch(key);
+    }
+
+    /// The write version of a watched key, and whether it exists
+    ///
+    /// EXEC aborts when this differs from what WATCH saw. Expiry is not a
+    /// write, so a key that existed at WATCH time and has since expired
+    /// shows up as a change in existence instead.
+    pub fn watch_state(&self, key: &[u8]) -> (WatchVersion, bool) {
+        (self.watches.version(key), self.watch_exists(key))
+    }
+
+    fn watch_exists(&self, key: &[u8]) -> bool {
+        self.expire_collection(key);
+        self.key_type(key) != "none"
+    }
+
+    /// A key as it is stored for the selected database
+    pub fn scoped_key(&self, key: &[u8]) -> Vec<u8> {
+        [&self.key_prefix[..], key].concat()
+    }
+
+    /// Pop one element from the first non-empty list, for BLPOP / BRPOP
+    ///
+    /// Keys are already scoped to the selected database. Replies with the
+    /// key and element, or a null array if every list is empty.
+    pub fn pop_first(&self, keys: &[Vec<u8>], left: bool) -> RespValue {
+        for key in keys {
+            let popped = if left {
+                self.list_ops.lpop(key, None)
+            } else {
+                self.list_ops.rpop(key, None)
+            };
+            match popped {
+                Ok(values) => {
+                    if let Some(value) = values.into_iter().next() {
+                        self.watches.touch(key);
+                        return RespValue::Array(Some(vec![
+                            RespValue::BulkString(Some(Bytes::copy_from_slice(
+                                &key[self.key_prefix.len()..],
+                            ))),
+                            RespValue::BulkString(Some(value)),
+                        ]));
+                    }
+                }
+                Err(e) => return RespValue::Error(format!("ERR {}", e)),
+            }
+        }
+        RespValue::Array(None)
+    }
+
+    /// Execute a command and return RESP response
+    #[inline]
+    pub fn execute(&self, cmd: Command) -> RespValue {
+        // Scope keys to the selected database
+        let mut cmd = cmd;
+        if !self.key_prefix.is_empty() {
+            for key in cmd.keys_mut() {
+                key.splice(0..0, self.key_prefix.iter().copied());
+            }
+        }
+
+        // Collections past their deadline are gone before the command runs
+        for key in cmd.keys_mut() {
+            self.expire_collection(key);
+        }
+
+        let event = match &self.notifier {
+            Some(notifier) if notifier.is_active() => self.key_event(&cmd),
+            _ => None,
+        };
+
+        // Lists that gain elements may have clients blocked on them
+        let pushed = match (&self.blocking, &cmd) {
+            (
+                Some(hub),
+                Command::LPush { key, .. }
+                | Command::RPush { key, .. }
+                | Command::LInsert { key, .. },
+            ) if hub.is_active() => Some(key.clone()),
+            _ => None,
+        };
+
+        let index = cmd.index();
+
+        // Writes move the version of every key they name, for WATCH
+        let touched: Vec<Vec<u8>> = if self.watches.is_active() && is_write_command(index) {
+            if matches!(
+                cmd,
+                Command::FlushDb | Command::FlushAll | Command::Copy { db: Some(_), .. }
+            ) {
+                self.watches.touch_all();
+            }
+            cmd.keys_mut().into_iter().map(|key| key.clone()).collect()
+        } else {
+            Vec::new()
+        };
+
+        let started = self.stats.is_some().then(std::time::Instant::now);
+        let response = self.execute_command(cmd);
+        for key in &touched {
+            self.watches.touch(key);
+        }
+        if let Some(started) = started {
+            let failed = matches!(response, RespValue::Error(_));
+            self.record_command(index, started.elapsed(), failed);
+        }
+
+        if let (Some(event), Some(notifier)) = (event, &self.notifier) {
+            if !matches!(response, RespValue::Error(_)) {
+                self.publish_key_event(notifier, event, &response);
+            }
+        }
+
+        if let (Some(key), Some(hub)) = (pushed, &self.blocking) {
+            if matches!(response, RespValue::Integer(len) if len > 0) {
+                hub.signal_ready(&key);
+            }
+        }
+
+        response
+    }
+
+    /// Capture what a mutating command is about to do, for notifications
+    fn key_event(&self, cmd: &Command) -> Option<KeyEvent> {
+        match cmd {
+            Command::Set {
+                key,
+                value,
+                ex,
+                px,
+                pxat,
+                keepttl,
+                ..
+            } => Some(KeyEvent::Set {
+                entries: vec![(key.clone(), value.clone())],
+                keep_ttl: *keepttl,
+                with_ttl: ex.is_some() || px.is_some() || pxat.is_some(),
+            }),
+            Command::SetEx { key, value, .. } | Command::PSetEx { key, value, .. } => {
+                Some(KeyEvent::Set {
+                    entries: vec![(key.clone(), value.clone())],
+                    keep_ttl: false,
+                    with_ttl: true,
+                })
+            }
+            Command::GetSet { key, value } | Command::SetNx { key, value } => Some(KeyEvent::Set {
+                entries: vec![(key.clone(), value.clone())],
+                keep_ttl: false,
+                with_ttl: false,
+            }),
+            Command::MSet(pairs) | Command::MSetNx(pairs) => Some(KeyEvent::Set {
+                entries: pairs.clone(),
+                keep_ttl: false,
+                with_ttl: false,
+            }),
+            Command::Del(keys) | Command::Unlink(keys) => Some(KeyEvent::Del(
+                keys.iter()
+                    .filter(|key| self.key_type(key) != "none")
+                    .cloned()
+                    .collect(),
+            )),
+            Command::GetDel(key) => Some(KeyEvent::Del(vec![key.clone()])),
+            Command::Expire { key, .. }
+            | Command::PExpire { key, .. }
+            | Command::ExpireAt { key, .. }
+            | Command::PExpireAt { key, .. } => Some(KeyEvent::Expire(key.clone())),
+            Command::Persist(key) => Some(KeyEvent::Persist(key.clone())),
+            Command::Copy { dst, db, .. } => Some(KeyEvent::CopyTo {
+                key: self.copy_destination(dst, *db),
+                db: db.unwrap_or(self.db),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Publish the notifications for a command that ran without error
+    fn publish_key_event(
+        &self,
+        notifier: &KeyspaceNotifier,
+        event: KeyEvent,
+        response: &RespValue,
+    ) {
+        let logical = |key: &[u8]| key[self.key_prefix.len()..].to_vec();
+
+        match event {
+            KeyEvent::Set {
+                entries,
+                keep_ttl,
+                with_ttl,
+            } => {
+                for (key, value) in entries {
+                    // NX / XX may have skipped the write
+                    if self.store.get_bytes(&key).ok().as_ref() != Some(&value) {
+                        continue;
+                    }
+                    notifier.notify(NOTIFY_STRING, "set", &logical(&key), self.db);
+                    if !keep_ttl {
+                        notifier.forget_expiry(self.db, &key);
+                    }
+                    if with_ttl {
+                        if let Ok(Some(ttl)) = self.store.get_ttl(&key) {
+                            notifier.notify(NOTIFY_GENERIC, "expire", &logical(&key), self.db);
+                            notifier.track_expiry(self.db, &key, ttl);
+                        }
+                    }
+                }
+            }
+            KeyEvent::Del(keys) => {
+                for key in keys {
+                    if self.key_type(&key) == "none" {
+                        notifier.notify(NOTIFY_GENERIC, "del", &logical(&key), self.db);
+                        notifier.forget_expiry(self.db, &key);
+                    }
+                }
+            }
+            KeyEvent::Expire(key) => {
+                if !matches!(response, RespValue::Integer(1)) {
+                    return;
+                }
+                match self.ttl_secs(&key) {
+                    Ok(Some(ttl)) => {
+                        notifier.notify(NOTIFY_GENERIC, "expire", &logical(&key), self.db);
+                        notifier.track_expiry(self.db, &key, ttl);
+                    }
+                    // A TTL of 0 leaves the key persistent
+                    Ok(None) => notifier.forget_expiry(self.db, &key),
+                    // A time in the past deletes the key
+                    Err(_) => {
+                        notifier.notify(NOTIFY_GENERIC, "del", &logical(&key), self.db);
+                        notifier.forget_expiry(self.db, &key);
+                    }
+                }
+            }
+            KeyEvent::Persist(key) => {
+                if matches!(response, RespValue::Integer(1)) {
+                    notifier.forget_expiry(self.db, &key);
+                }
+            }
+            KeyEvent::CopyTo { key, db } => {
+                if !matches!(response, RespValue::Integer(1)) {
+                    return;
+                }
+                let name = &key[db_key_prefix(db).len()..];
+                notifier.notify(NOTIFY_GENERIC, "copy_to", name, db);
+                match self.store.get_ttl(&key) {
+                    Ok(Some(ttl)) => notifier.track_expiry(db, &key, ttl),
+                    _ => notifier.forget_expiry(db, &key),
+                }
+            }
+        }
+    }
+
+    /// Record a write a script made in the append-only log
+    pub fn log_write(&self, args: &[Bytes], reply: &RespValue) {
+        if let Some(append_log) = &self.append_log {
+            append_log.log(self.db, args, reply);
+        }
+    }
+
+    /// Run a command whose keys are already scoped to the selected database
+    fn execute_command(&self, cmd: Command) -> RespValue {
+        if let Some(err) = self.check_wrong_type(&cmd) {
+            return err;
+        }
+
+        match cmd {
+            Command::Get(key) => match self.store.get_bytes(&key) {
+                Ok(value) => RespValue::BulkString(Some(value)),
+                Err(feoxdb::FeoxError::KeyNotFound) => RespValue::BulkString(None),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::Set {
+                key,
+                value,
+                ex,
+                px,
+                pxat,
+                nx,
+                xx,
+                keepttl,
+                get,
+            } => {
+                // get_bytes honours expiry, so an expired key counts as absent;
+                // NX and XX also count a collection under the key
+                let old_value = self.store.get_bytes(&key).ok();
+                let exists = old_value.is_some() || ((nx || xx) && self.key_type(&key) != "none");
+
+                if (nx && exists) || (xx && !exists) {
+                    return if get {
+                        RespValue::BulkString(old_value)
+                    } else {
+                        RespValue::BulkString(None)
+                    };
+                }
+
+                // KEEPTTL carries the remaining TTL over to the new value
+                let kept_ttl = if keepttl && exists {
+                    match self.store.get_ttl(&key) {
+                        Ok(Some(ttl)) => Some(ttl.max(1)),
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
+
+                // EXAT / PXAT become a TTL from now; a time already passed
+                // leaves the key deleted, as Redis does
+                let until = match pxat {
+                    Some(at) => match (at as i64).saturating_sub(unix_time_millis()) {
+                        remaining if remaining > 0 => Some(remaining as u64),
+                        _ => {
+                            return match self.delete_key(&key) {
+                                Ok(_) if get => RespValue::BulkString(old_value),
+                                Ok(_) => RespValue::SimpleString(Bytes::from_static(b"OK")),
+                                Err(e) => RespValue::Error(format!("ERR {}", e)),
+                            };
+                        }
+                    },
+                    None => None,
+                };
+
+                if let Err(e) = self.clear_collection(&key) {
+                    return RespValue::Error(format!("ERR {}", e));
+                }
+
+                let ttl = ex
+                    .or(kept_ttl)
+                    .or_else(|| px.or(until).map(millis_to_ttl_secs));
+                let result = if nx {
+                    // Another client may have created the key since the check
+                    match self.insert_if_absent(&key, &value) {
+                        Ok(true) => match ttl {
+                            Some(seconds) => self.store.update_ttl(&key, seconds),
+                            None => Ok(()),
+                        },
+                        Ok(false) if get => {
+                            return RespValue::BulkString(self.store.get_bytes(&key).ok())
+                        }
+                        Ok(false) => return RespValue::BulkString(None),
+                        Err(e) => Err(e),
+                    }
+                } else if let Some(seconds) = ttl {
+                    self.store
+                        .insert_bytes_with_ttl_and_timestamp(&key, value, seconds, None)
+                        .map(|_| ())
+                } else {
+                    self.store
+                        .insert_bytes_with_timestamp(&key, value, None)
+                        .map(|_| ())
+                };
+
+                match result {
+                    Ok(_) if get => RespValue::BulkString(old_value),
+                    Ok(_) => RespValue::SimpleString(Bytes::from_static(b"OK")),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::SetEx {
+                key,
+                seconds,
+                value,
+            } => match u64::try_from(seconds) {
+                Ok(seconds) if seconds > 0 => self.execute_command(Command::Set {
+                    key,
+                    value,
+                    ex: Some(seconds),
+                    px: None,
+                    pxat: None,
+                    nx: false,
+                    xx: false,
+                    keepttl: false,
+                    get: false,
+                }),
+                _ => RespValue::Error("ERR invalid expire time in 'setex' command".to_string()),
+            },
+
+            Command::PSetEx {
+                key,
+                milliseconds,
+                value,
+            } => match u64::try_from(milliseconds) {
+                Ok(millis) if millis > 0 => self.execute_command(Command::Set {
+                    key,
+                    value,
+                    ex: None,
+                    px: Some(millis),
+                    pxat: None,
+                    nx: false,
+                    xx: false,
+                    keepttl: false,
+                    get: false,
+                }),
+                _ => RespValue::Error("ERR invalid expire time in 'psetex' command".to_string()),
+            },
+
+            Command::SetNx { key, value } => match self.execute_command(Command::Set {
+                key,
+                value,
+                ex: None,
+                px: None,
+                pxat: None,
+                nx: true,
+                xx: false,
+                keepttl: false,
+                get: false,
+            }) {
+                RespValue::SimpleString(_) => RespValue::Integer(1),
+                RespValue::BulkString(None) => RespValue::Integer(0),
+                reply => reply,
+            },
+
+            Command::GetSet { key, value } => {
+                let old_value = self.store.get_bytes(&key).ok();
+                match self.store.insert_bytes_with_timestamp(&key, value, None) {
+                    Ok(_) => RespValue::BulkString(old_value),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::GetDel(key) => match self.store.get_bytes(&key) {
+                Ok(value) => {
+                    let _ = self.store.delete(&key);
+                    RespValue::BulkString(Some(value))
+                }
+                Err(feoxdb::FeoxError::KeyNotFound) => RespValue::BulkString(None),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::GetEx {
+                key,
+                ex,
+                px,
+                persist,
+            } => match self.store.get_bytes(&key) {
+                Ok(value) => {
+                    let result = if let Some(seconds) = ex {
+                        self.store.update_ttl(&key, seconds)
+                    } else if let Some(millis) = px {
+                        self.store.update_ttl(&key, millis_to_ttl_secs(millis))
+                    } else if persist {
+                        self.store.persist(&key)
+                    } else {
+                        Ok(())
+                    };
+
+                    match result {
+                        Ok(_) => RespValue::BulkString(Some(value)),
+                        Err(e) => RespValue::Error(format!("ERR {}", e)),
+                    }
+                }
+                Err(feoxdb::FeoxError::KeyNotFound) => RespValue::BulkString(None),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::Append { key, value } => {
+                let new_value = match self.store.get_bytes(&key) {
+                    Ok(current) => {
+                        let mut combined = Vec::with_capacity(current.len() + value.len());
+                        combined.extend_from_slice(&current);
+                        combined.extend_from_slice(&value);
+                        Bytes::from(combined)
+                    }
+                    Err(_) => value,
+                };
+
+                // FeOx can't store empty values, so appending nothing to a
+                // missing key leaves it absent
+                if new_value.is_empty() {
+                    return RespValue::Integer(0);
+                }
+
+                let len = new_value.len() as i64;
+                match self.insert_keep_ttl(&key, new_value) {
+                    Ok(_) => RespValue::Integer(len),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::StrLen(key) => match self.store.get_bytes(&key) {
+                Ok(value) => RespValue::Integer(value.len() as i64),
+                Err(_) => RespValue::Integer(0),
+            },
+
+            Command::SetRange { key, offset, value } => {
+                let current = self.store.get_bytes(&key).ok();
+                let current_len = current.as_ref().map(|v| v.len()).unwrap_or(0);
+
+                // Nothing to write: report the current length untouched
+                if value.is_empty() {
+                    return RespValue::Integer(current_len as i64);
+                }
+
+                // Check the size before allocating it, as the offset is the
+                // client's to choose
+                let end = match offset.checked_add(value.len()) {
+                    Some(end) if end <= self.config.proto_max_bulk_len => end,
+                    _ => {
+                        return RespValue::Error(
+                            "ERR string exceeds maximum allowed size (proto-max-bulk-len)"
+                                .to_string(),
+                        )
+                    }
+                };
+
+                // Zero-pad up to the offset when writing past the end
+                let mut new_value = current.map(|v| v.to_vec()).unwrap_or_default();
+                if new_value.len() < end {
+                    new_value.resize(end, 0);
+                }
+                new_value[offset..end].copy_from_slice(&value);
+
+                let len = new_value.len() as i64;
+                match self.insert_keep_ttl(&key, Bytes::from(new_value)) {
+                    Ok(_) => RespValue::Integer(len),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::GetRange { key, start, end } => {
+                let value = match self.store.get_bytes(&key) {
+                    Ok(value) => value,
+                    Err(_) => return RespValue::BulkString(Some(Bytes::new())),
+                };
+
+                let len = value.len() as i64;
+
+                // Convert negative indices to positive
+                let start = if start < 0 {
+                    (len + start).max(0)
+                } else {
+                    start
+                };
+                let end = if end < 0 { len + end } else { end };
+
+                // Clamp to valid range
+                let end = end.min(len - 1);
+
+                if len == 0 || start > end || start >= len {
+                    return RespValue::BulkString(Some(Bytes::new()));
+                }
+
+                RespValue::BulkString(Some(value.slice(start as usize..=end as usize)))
+            }
+
+            // Bit 0 is the most significant bit of the first byte
+            Command::SetBit { key, offset, value } => {
+                let mut bytes = self
+                    .store
+                    .get_bytes(&key)
+                    .map(|v| v.to_vec())
+                    .unwrap_or_default();
+                let byte = (offset / 8) as usize;
+                let mask = 0x80u8 >> (offset % 8);
+                if bytes.len() <= byte {
+                    bytes.resize(byte + 1, 0);
+                }
+                let old = bytes[byte] & mask != 0;
+                if value {
+                    bytes[byte] |= mask;
+                } else {
+                    bytes[byte] &= !mask;
+                }
+
+                match self.insert_keep_ttl(&key, Bytes::from(bytes)) {
+                    Ok(_) => RespValue::Integer(old as i64),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::GetBit { key, offset } => {
+                let byte = self
+                    .store
+                    .get_bytes(&key)
+                    .ok()
+                    .and_then(|v| v.get((offset / 8) as usize).copied())
+                    .unwrap_or(0);
+                RespValue::Integer((byte & (0x80 >> (offset % 8)) != 0) as i64)
+            }
+
+            Command::BitCount {
+                key,
+                range,
+                in_bits,
+            } => {
+                let value = match self.store.get_bytes(&key) {
+                    Ok(value) => value,
+                    Err(_) => return RespValue::Integer(0),
+                };
+                let Some((start, end)) = range else {
+                    let count: u32 = value.iter().map(|b| b.count_ones()).sum();
+                    return RespValue::Integer(count as i64);
+                };
+
+                // Negative indices count from the end, as in GETRANGE
+                let len = if in_bits {
+                    value.len() as i64 * 8
+                } else {
+                    value.len() as i64
+                };
+                let start = if start < 0 {
+                    (len + start).max(0)
+                } else {
+                    start
+                };
+                let end = if end < 0 { len + end } else { end }.min(len - 1);
+                if start > end {
+                    return RespValue::Integer(0);
+                }
+
+                let count = if in_bits {
+                    (start..=end)
+                        .filter(|&i| value[(i / 8) as usize] & (0x80 >> (i % 8)) != 0)
+                        .count()
+                } else {
+                    value[start as usize..=end as usize]
+                        .iter()
+                        .map(|b| b.count_ones() as usize)
+                        .sum()
+                };
+                RespValue::Integer(count as i64)
+            }
+
+            Command::BitPos {
+                key,
+                bit,
+                start,
+                end,
+                in_bits,
+            } => {
+                let value = match self.store.get_bytes(&key) {
+                    Ok(value) => value,
+                    // A missing key is all zero bits
+                    Err(_) => return RespValue::Integer(if bit { -1 } else { 0 }),
+                };
+
+                let len = if in_bits {
+                    value.len() as i64 * 8
+                } else {
+                    value.len() as i64
+                };
+                let start = match start.unwrap_or(0) {
+                    start if start < 0 => (len + start).max(0),
+                    start => start,
+                };
+                let end_given = end.is_some();
+                let end = match end.unwrap_or(-1) {
+                    end if end < 0 => len + end,
+                    end => end,
+                }
+                .min(len - 1);
+                if start > end {
+                    return RespValue::Integer(-1);
+                }
+
+                // Search bit by bit over the range, in bits either way
+                let (first, last) = if in_bits {
+                    (start, end)
+                } else {
+                    (start * 8, end * 8 + 7)
+                };
+                let found = (first..=last)
+                    .find(|&i| (value[(i / 8) as usize] & (0x80 >> (i % 8)) != 0) == bit);
+                match found {
+                    Some(position) => RespValue::Integer(position),
+                    // Without an end, the string counts as padded with zeros
+                    None if !bit && !end_given => RespValue::Integer(last + 1),
+                    None => RespValue::Integer(-1),
+                }
+            }
+
+            Command::BitOp {
+                op,
+                destination,
+                keys,
+            } => {
+                let sources: Vec<Bytes> = keys
+                    .iter()
+                    .map(|key| self.store.get_bytes(key).unwrap_or_default())
+                    .collect();
+                let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+
+                // Shorter sources are zero-extended to the longest
+                let byte = |source: &Bytes, i: usize| source.get(i).copied().unwrap_or(0);
+                let result: Vec<u8> = (0..len)
+                    .map(|i| {
+                        let mut bytes = sources.iter().map(|source| byte(source, i));
+                        let first = bytes.next().unwrap_or(0);
+                        match op {
+                            BitOperation::And => bytes.fold(first, |acc, b| acc & b),
+                            BitOperation::Or => bytes.fold(first, |acc, b| acc | b),
+                            BitOperation::Xor => bytes.fold(first, |acc, b| acc ^ b),
+                            BitOperation::Not => !first,
+                        }
+                    })
+                    .collect();
+
+                // FeOx can't store empty values, so an empty result deletes
+                // the destination
+                let written = if result.is_empty() {
+                    match self.store.delete(&destination) {
+                        Ok(_) | Err(feoxdb::FeoxError::KeyNotFound) => Ok(()),
+                        Err(e) => Err(e),
+                    }
+                } else {
+                    self.store
+                        .insert_bytes_with_timestamp(&destination, Bytes::from(result), None)
+                        .map(|_| ())
+                };
+                match written {
+                    Ok(()) => RespValue::Integer(len as i64),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            // Deletion is already synchronous, so UNLINK is just DEL
+            Command::Del(keys) | Command::Unlink(keys) => {
+                let mut count = 0i64;
+                for key in keys {
+                    match self.delete_key(&key) {
+                        Ok(true) => count += 1,
+                        Ok(false) => {}
+                        Err(e) => return RespValue::Error(format!("ERR {}", e)),
+                    }
+                }
+                RespValue::Integer(count)
+            }
+
+            // No access-time tracking, so TOUCH only reports existing keys
+            Command::Exists(keys) | Command::Touch(keys) => {
+                let count = keys
+                    .iter()
+                    .filter(|key| self.key_type(key) != "none")
+                    .count() as i64;
+                RespValue::Integer(count)
+            }
+
+            Command::Type(key) => {
+                RespValue::SimpleString(Bytes::from_static(self.key_type(&key).as_bytes()))
+            }
+
+            Command::Incr(key) => self.increment(&key, Some(1)),
+
+            Command::IncrBy { key, delta } => self.increment(&key, Some(delta)),
+
+            Command::Decr(key) => self.increment(&key, Some(-1)),
+
+            Command::DecrBy { key, delta } => self.increment(&key, delta.checked_neg()),
+
+            Command::Expire {
+                key,
+                seconds,
+                condition,
+            } => self.expire(&key, seconds, condition),
+
+            Command::PExpire {
+                key,
+                milliseconds,
+                condition,
+            } => self.expire(&key, millis_to_ttl_secs(milliseconds), condition),
+
+            Command::ExpireAt { key, timestamp } => {
+                self.expire_at_millis(&key, timestamp.saturating_mul(1000))
+            }
+
+            Command::PExpireAt { key, timestamp_ms } => self.expire_at_millis(&key, timestamp_ms),
+
+            Command::ExpireTime(key) => match self.expire_time_millis(&key) {
+                Ok(ms) if ms >= 0 => RespValue::Integer((ms + 999) / 1000),
+                Ok(code) => RespValue::Integer(code),
+                Err(e) => e,
+            },
+
+            Command::PExpireTime(key) => match self.expire_time_millis(&key) {
+                Ok(ms) => RespValue::Integer(ms),
+                Err(e) => e,
+            },
+
+            Command::Ttl(key) => {
+                match self.ttl_secs(&key) {
+                    Ok(Some(ttl)) => RespValue::Integer(ttl as i64),
+                    Ok(None) => RespValue::Integer(-1), // No TTL
+                    Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(-2),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::PTtl(key) => {
+                match self.ttl_millis(&key) {
+                    Ok(Some(ttl)) => RespValue::Integer(ttl as i64),
+                    Ok(None) => RespValue::Integer(-1), // No TTL
+                    Err(feoxdb::FeoxError::KeyNotFound) => RespValue::Integer(-2),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::Persist(key) => match self.store.persist(&key) {
+                Ok(_) => RespValue::Integer(1),
+                Err(feoxdb::FeoxError::KeyNotFound) => {
+                    RespValue::Integer(self.store.delete(&deadline_key(&key)).is_ok() as i64)
+                }
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::Copy {
+                src,
+                dst,
+                db,
+                replace,
+            } => {
+                if db.is_some_and(|db| db >= self.config.databases) {
+                    return RespValue::Error("ERR DB index is out of range".to_string());
+                }
+                let dst = self.copy_destination(&dst, db);
+                if src == dst {
+                    return RespValue::Error(
+                        "ERR source and destination objects are the same".to_string(),
+                    );
+                }
+
+                // Collections are spread over many physical keys, so only
+                // plain string values can be copied
+                let value = match self.store.get_bytes(&src) {
+                    Ok(value) => value,
+                    Err(_) if self.key_type(&src) == "none" => return RespValue::Integer(0),
+                    Err(_) => {
+                        return RespValue::Error(
+                            "ERR COPY is only supported for string values".to_string(),
+                        )
+                    }
+                };
+                match self.key_type(&dst) {
+                    "none" => {}
+                    _ if !replace => return RespValue::Integer(0),
+                    "string" => {}
+                    _ => {
+                        return RespValue::Error(
+                            "ERR COPY is only supported for string values".to_string(),
+                        )
+                    }
+                }
+
+                let result = match self.store.get_ttl(&src) {
+                    Ok(Some(ttl)) => self.store.insert_bytes_with_ttl_and_timestamp(
+                        &dst,
+                        value,
+                        ttl.max(1),
+                        None,
+                    ),
+                    _ => self.store.insert_bytes_with_timestamp(&dst, value, None),
+                };
+                match result {
+                    Ok(_) => RespValue::Integer(1),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::MGet(keys) => {
+                // FeoxStore has no multi-get, so each key is its own lookup
+                let values: Vec<RespValue> = keys
+                    .into_iter()
+                    .map(|key| match self.store.get_bytes(&key) {
+                        Ok(value) => RespValue::BulkString(Some(value)),
+                        Err(_) => RespValue::BulkString(None),
+                    })
+                    .collect();
+                RespValue::Array(Some(values))
+            }
+
+            Command::MSet(pairs) => {
+                for (key, value) in pairs {
+                    if let Err(e) = self.clear_collection(&key) {
+                        return RespValue::Error(format!("ERR {}", e));
+                    }
                     // Pass None to let FeOx generate a new timestamp
                     if let Err(e) = self.store.insert_with_timestamp(&key, &value, None) {
                         return RespValue::Error(format!("ERR {}", e));
@@ -270,6 +2105,37 @@ impl CommandExecutor {
                 RespValue::SimpleString(Bytes::from_static(b"OK"))
             }
 
+            Command::MSetNx(pairs) => {
+                if pairs.iter().any(|(key, _)| self.key_type(key) != "none") {
+                    return RespValue::Integer(0);
+                }
+                // The store has no multi-key transaction, so each key is
+                // inserted only if still absent, and the ones already set
+                // are removed again if another client created one first
+                let mut inserted: Vec<&[u8]> = Vec::new();
+                for (key, value) in &pairs {
+                    // A key named twice takes the later value, as in Redis
+                    let result = if inserted.contains(&key.as_slice()) {
+                        self.store.insert(key, value).map(|_| true)
+                    } else {
+                        self.insert_if_absent(key, value)
+                    };
+                    let reply = match result {
+                        Ok(true) => {
+                            inserted.push(key);
+                            continue;
+                        }
+                        Ok(false) => RespValue::Integer(0),
+                        Err(e) => RespValue::Error(format!("ERR {}", e)),
+                    };
+                    for key in inserted {
+                        self.store.delete(key).ok();
+                    }
+                    return reply;
+                }
+                RespValue::Integer(1)
+            }
+
             Command::Ping(msg) => match msg {
                 Some(m) => RespValue::BulkString(Some(m)),
                 None => RespValue::SimpleString(Bytes::from_static(b"PONG")),
@@ -277,304 +2143,535 @@ impl CommandExecutor {
 
             Command::Echo(msg) => RespValue::BulkString(Some(msg)),
 
+            Command::Lolwut => RespValue::BulkString(Some(Bytes::from(format!(
+                "FeOx ver. {}\n",
+                env!("CARGO_PKG_VERSION")
+            )))),
+
             Command::Config { action, args } => {
                 match action.to_uppercase().as_str() {
                     "GET" => {
                         // Return empty config for compatibility with redis-benchmark
                         if args.is_empty() {
-                            RespValue::Array(Some(vec![]))
-                        } else {
-                            // Return nil for any specific config request
-                            let mut results = Vec::new();
-                            for arg in args {
-                                results.push(RespValue::BulkString(Some(arg)));
-                                results.push(RespValue::BulkString(None)); // nil value
+                            return RespValue::Array(Some(vec![]));
+                        }
+
+                        let params = self.config_params();
+                        let mut results = Vec::new();
+                        for (name, value) in &params {
+                            let matched = args.iter().any(|pattern| {
+                                glob_match(&pattern.to_ascii_lowercase(), name.as_bytes())
+                            });
+                            if matched {
+                                results.push((
+                                    RespValue::BulkString(Some(Bytes::from_static(
+                                        name.as_bytes(),
+                                    ))),
+                                    RespValue::BulkString(Some(Bytes::from(value.clone()))),
+                                ));
                             }
-                            RespValue::Array(Some(results))
                         }
+                        RespValue::Map(results)
+                    }
+                    "SET" => {
+                        if args.is_empty() || args.len() % 2 != 0 {
+                            return RespValue::Error(
+                                "ERR wrong number of arguments for 'config|set' command"
+                                    .to_string(),
+                            );
+                        }
+
+                        for pair in args.chunks(2) {
+                            let name = String::from_utf8_lossy(&pair[0]).to_lowercase();
+                            let value = String::from_utf8_lossy(&pair[1]);
+                            let applied = match name.as_str() {
+                                "maxmemory" => parse_memory(&value)
+                                    .map(|bytes| self.config.runtime.set_maxmemory(bytes))
+                                    .ok_or("argument couldn't be parsed into an integer"),
+                                "timeout" => value
+                                    .parse::<u64>()
+                                    .map(|secs| self.config.runtime.set_timeout(secs))
+                                    .map_err(|_| "argument couldn't be parsed into an integer"),
+                                "maxclients" => match value.parse::<u64>() {
+                                    Ok(maxclients) if maxclients > 0 => {
+                                        self.config.runtime.set_maxclients(maxclients);
+                                        Ok(())
+                                    }
+                                    _ => Err("argument must be between 1 and 18446744073709551615"),
+                                },
+                                "slowlog-log-slower-than" => value
+                                    .parse::<i64>()
+                                    .map(|micros| {
+                                        self.config.runtime.set_slowlog_log_slower_than(micros)
+                                    })
+                                    .map_err(|_| "argument couldn't be parsed into an integer"),
+                                "slowlog-max-len" => value
+                                    .parse::<u64>()
+                                    .map(|len| self.config.runtime.set_slowlog_max_len(len))
+                                    .map_err(|_| "argument couldn't be parsed into an integer"),
+                                "notify-keyspace-events" => parse_notify_flags(&value)
+                                    .map(|flags| self.config.runtime.set_notify_flags(flags))
+                                    .ok_or("Invalid event class character. Use 'Ag$lshzxeKE'."),
+                                "maxmemory-policy" => match value.to_ascii_lowercase().as_str() {
+                                    policy if MAXMEMORY_POLICIES.contains(&policy) => {
+                                        self.config
+                                            .runtime
+                                            .set_inert("maxmemory-policy", policy.to_string());
+                                        Ok(())
+                                    }
+                                    _ => Err("argument must be a valid maxmemory policy"),
+                                },
+                                "appendonly" => match value.to_ascii_lowercase().as_str() {
+                                    flag @ ("yes" | "no") => {
+                                        self.config
+                                            .runtime
+                                            .set_inert("appendonly", flag.to_string());
+                                        Ok(())
+                                    }
+                                    _ => Err("argument must be 'yes' or 'no'"),
+                                },
+                                "save" => {
+                                    let params: Vec<&str> = value.split_whitespace().collect();
+                                    let valid = params.len().is_multiple_of(2)
+                                        && params.iter().all(|n| n.parse::<u64>().is_ok());
+                                    if valid {
+                                        self.config.runtime.set_inert("save", params.join(" "));
+                                        Ok(())
+                                    } else {
+                                        Err("Invalid save parameters")
+                                    }
+                                }
+                                _ if self
+                                    .config_params()
+                                    .iter()
+                                    .any(|(known, _)| *known == name) =>
+                                {
+                                    Err("can't set immutable config")
+                                }
+                                _ => {
+                                    return RespValue::Error(format!(
+                                        "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                                        name
+                                    ));
+                                }
+                            };
+                            if let Err(reason) = applied {
+                                return RespValue::Error(format!(
+                                    "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                                    name, reason
+                                ));
+                            }
+                        }
+                        RespValue::SimpleString(Bytes::from_static(b"OK"))
+                    }
+                    "RESETSTAT" => {
+                        if !args.is_empty() {
+                            return RespValue::Error(
+                                "ERR wrong number of arguments for 'config|resetstat' command"
+                                    .to_string(),
+                            );
+                        }
+                        if let Some((stats, _)) = &self.stats {
+                            stats.reset(&self.store.stats());
+                        }
+                        if let Some(slowlog) = &self.slowlog {
+                            slowlog.reset();
+                        }
+                        RespValue::SimpleString(Bytes::from_static(b"OK"))
+                    }
+                    _ => RespValue::Error(format!("ERR Unknown CONFIG subcommand '{}'", action)),
+                }
+            }
+
+            Command::Command { subcommand, args } => {
+                let table = command_table();
+                let Some(subcommand) = subcommand else {
+                    return RespValue::Array(Some(
+                        table
+                            .into_iter()
+                            .map(|cmd| RespValue::Array(Some(cmd)))
+                            .collect(),
+                    ));
+                };
+
+                match subcommand.to_uppercase().as_str() {
+                    "COUNT" => RespValue::Integer(table.len() as i64),
+                    "DOCS" => {
+                        // No documentation is kept; an empty reply makes
+                        // clients fall back to their built-in help
+                        RespValue::Map(vec![])
+                    }
+                    "INFO" if args.is_empty() => RespValue::Array(Some(
+                        table
+                            .into_iter()
+                            .map(|cmd| RespValue::Array(Some(cmd)))
+                            .collect(),
+                    )),
+                    "INFO" => RespValue::Array(Some(
+                        args.iter()
+                            .map(|name| {
+                                table
+                                    .iter()
+                                    .find(|cmd| {
+                                        matches!(&cmd[0], RespValue::BulkString(Some(n)) if n.eq_ignore_ascii_case(name.as_ref()))
+                                    })
+                                    .map(|cmd| RespValue::Array(Some(cmd.clone())))
+                                    .unwrap_or(RespValue::Array(None))
+                            })
+                            .collect(),
+                    )),
+                    _ => RespValue::Error(format!(
+                        "ERR unknown subcommand '{}'. Try COMMAND HELP.",
+                        subcommand
+                    )),
+                }
+            }
+
+            Command::Debug { subcommand, args } => {
+                match (subcommand.to_uppercase().as_str(), args.as_slice()) {
+                    // Blocks the worker thread, which is fine for a test-only command
+                    ("SLEEP", [seconds]) => match String::from_utf8_lossy(seconds).parse::<f64>() {
+                        Ok(secs) if secs.is_finite() && secs >= 0.0 => {
+                            std::thread::sleep(std::time::Duration::from_secs_f64(secs));
+                            RespValue::SimpleString(Bytes::from_static(b"OK"))
+                        }
+                        _ => RespValue::Error("ERR value is not a valid float".to_string()),
+                    },
+                    ("JMAP", []) | ("SET-ACTIVE-EXPIRE", [_]) => {
+                        RespValue::SimpleString(Bytes::from_static(b"OK"))
+                    }
+                    ("OBJECT", [key]) => {
+                        let key = [&self.key_prefix[..], key].concat();
+                        let Some((encoding, length)) = self.object_encoding(&key) else {
+                            return RespValue::Error("ERR no such key".to_string());
+                        };
+                        RespValue::SimpleString(Bytes::from(format!(
+                            "Value at:0x0 refcount:1 encoding:{} serializedlength:{} lru:0 lru_seconds_idle:0",
+                            encoding, length
+                        )))
+                    }
+                    ("SLEEP" | "JMAP" | "SET-ACTIVE-EXPIRE" | "OBJECT", _) => {
+                        RespValue::Error(format!(
+                            "ERR wrong number of arguments for 'debug|{}' command",
+                            subcommand.to_lowercase()
+                        ))
+                    }
+                    _ => RespValue::Error(format!(
+                        "ERR unknown subcommand '{}'. Try DEBUG HELP.",
+                        subcommand
+                    )),
+                }
+            }
+
+            // Nothing is evicted, so access times and frequencies are not
+            // tracked; keys always report as just used
+            Command::Object { subcommand, args } => {
+                let subcommand = subcommand.to_uppercase();
+                match (subcommand.as_str(), args.as_slice()) {
+                    ("ENCODING" | "FREQ" | "IDLETIME" | "REFCOUNT", [key]) => {
+                        let key = [&self.key_prefix[..], key].concat();
+                        let Some((encoding, _)) = self.object_encoding(&key) else {
+                            return RespValue::Error("ERR no such key".to_string());
+                        };
+                        match subcommand.as_str() {
+                            "ENCODING" => RespValue::BulkString(Some(Bytes::from(encoding))),
+                            "REFCOUNT" => RespValue::Integer(1),
+                            _ => RespValue::Integer(0),
+                        }
+                    }
+                    ("ENCODING" | "FREQ" | "IDLETIME" | "REFCOUNT", _) => {
+                        RespValue::Error(format!(
+                            "ERR wrong number of arguments for 'object|{}' command",
+                            subcommand.to_lowercase()
+                        ))
+                    }
+                    _ => RespValue::Error(format!(
+                        "ERR unknown subcommand '{}'. Try OBJECT HELP.",
+                        subcommand
+                    )),
+                }
+            }
+
+            Command::SlowLog { subcommand, args } => {
+                let Some(slowlog) = &self.slowlog else {
+                    return RespValue::Error("ERR slow log not available".to_string());
+                };
+                match (subcommand.to_uppercase().as_str(), args.as_slice()) {
+                    ("GET", [] | [_]) => {
+                        let count = match args.first() {
+                            None => 10,
+                            Some(arg) => match String::from_utf8_lossy(arg).parse::<i64>() {
+                                Ok(-1) => usize::MAX,
+                                Ok(count) if count >= 0 => count as usize,
+                                _ => {
+                                    return RespValue::Error(
+                                        "ERR count should be greater than or equal to -1"
+                                            .to_string(),
+                                    )
+                                }
+                            },
+                        };
+                        let bulk = |b: Bytes| RespValue::BulkString(Some(b));
+                        let entries = slowlog
+                            .get(count)
+                            .into_iter()
+                            .map(|entry| {
+                                RespValue::Array(Some(vec![
+                                    RespValue::Integer(entry.id as i64),
+                                    RespValue::Integer(entry.timestamp as i64),
+                                    RespValue::Integer(entry.duration_micros as i64),
+                                    RespValue::Array(Some(
+                                        entry.args.into_iter().map(bulk).collect(),
+                                    )),
+                                    bulk(Bytes::from(
+                                        entry
+                                            .client_addr
+                                            .map(|addr| addr.to_string())
+                                            .unwrap_or_default(),
+                                    )),
+                                    bulk(Bytes::from(entry.client_name.unwrap_or_default())),
+                                ]))
+                            })
+                            .collect();
+                        RespValue::Array(Some(entries))
                     }
-                    "SET" => {
-                        // Pretend to set config successfully
+                    ("LEN", []) => RespValue::Integer(slowlog.len() as i64),
+                    ("RESET", []) => {
+                        slowlog.reset();
                         RespValue::SimpleString(Bytes::from_static(b"OK"))
                     }
-                    _ => RespValue::Error(format!("ERR Unknown CONFIG subcommand '{}'", action)),
+                    ("GET" | "LEN" | "RESET", _) => RespValue::Error(format!(
+                        "ERR wrong number of arguments for 'slowlog|{}' command",
+                        subcommand.to_lowercase()
+                    )),
+                    _ => RespValue::Error(format!(
+                        "ERR unknown subcommand '{}'. Try SLOWLOG HELP.",
+                        subcommand
+                    )),
                 }
             }
 
-            Command::Command => {
-                // Return supported commands in Redis COMMAND format
-                // Each command entry: [name, arity, flags, first_key, last_key, step]
-                let commands = vec![
-                    // Basic commands
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"GET"))),
-                        RespValue::Integer(2), // arity (command + 1 key)
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"readonly"),
-                        ))])),
-                        RespValue::Integer(1), // first key position
-                        RespValue::Integer(1), // last key position
-                        RespValue::Integer(1), // step
-                    ],
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"SET"))),
-                        RespValue::Integer(-3), // arity (variable, min 3)
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                    ],
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"DEL"))),
-                        RespValue::Integer(-2), // arity (variable, min 2)
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(-1), // all args are keys
-                        RespValue::Integer(1),
-                    ],
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"EXISTS"))),
-                        RespValue::Integer(-2),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"readonly"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(-1),
-                        RespValue::Integer(1),
-                    ],
-                    // Atomic operations
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"INCR"))),
-                        RespValue::Integer(2),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                    ],
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"DECR"))),
-                        RespValue::Integer(2),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                    ],
-                    // TTL commands
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"EXPIRE"))),
-                        RespValue::Integer(3),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                    ],
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"TTL"))),
-                        RespValue::Integer(2),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"readonly"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                    ],
-                    // Bulk operations
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"MGET"))),
-                        RespValue::Integer(-2),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"readonly"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(-1),
-                        RespValue::Integer(1),
-                    ],
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"MSET"))),
-                        RespValue::Integer(-3),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(-1),
-                        RespValue::Integer(2), // key-value pairs
-                    ],
-                    // Server commands
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"PING"))),
-                        RespValue::Integer(-1),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"fast"),
-                        ))])),
-                        RespValue::Integer(0),
-                        RespValue::Integer(0),
-                        RespValue::Integer(0),
-                    ],
-                    // FeOx-specific
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"JSONPATCH"))),
-                        RespValue::Integer(3),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                    ],
-                    vec![
-                        RespValue::BulkString(Some(Bytes::from_static(b"CAS"))),
-                        RespValue::Integer(4),
-                        RespValue::Array(Some(vec![RespValue::BulkString(Some(
-                            Bytes::from_static(b"write"),
-                        ))])),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                        RespValue::Integer(1),
-                    ],
-                ];
+            Command::Quit => RespValue::SimpleString(Bytes::from_static(b"OK")),
 
-                RespValue::Array(Some(
-                    commands
-                        .into_iter()
-                        .map(|cmd| RespValue::Array(Some(cmd)))
-                        .collect(),
-                ))
+            // Handled in connection.rs, which owns the state it clears
+            Command::Reset => RespValue::SimpleString(Bytes::from_static(b"RESET")),
+
+            Command::FlushDb => {
+                self.flush_keys(false);
+                RespValue::SimpleString(Bytes::from_static(b"OK"))
             }
 
-            Command::Quit => RespValue::SimpleString(Bytes::from_static(b"OK")),
+            Command::FlushAll => {
+                self.flush_keys(true);
+                RespValue::SimpleString(Bytes::from_static(b"OK"))
+            }
 
-            Command::FlushDb => {
-                // FeOx doesn't have a direct flush method
-                // For in-memory mode: would need to recreate the store
-                // For persistent mode: would need to delete files and recreate
-                // Since we can't recreate the store from here, return error
-                RespValue::Error("ERR FLUSHDB requires server restart. For persistent mode, also delete data files.".to_string())
+            // SELECT changes the connection's database in connection.rs
+            Command::Select(_) => {
+                RespValue::Error("ERR SELECT should be handled in connection layer".to_string())
             }
 
-            Command::Keys(pattern) => {
-                // Use range_query to get all keys, then filter by pattern
-                let prefix = extract_prefix(&pattern);
-
-                // Calculate end key for prefix scan
-                let (start_key, end_key) = if prefix.is_empty() {
-                    // Scan all keys
-                    (vec![], vec![0xFF; 255])
-                } else if pattern == prefix {
-                    // Exact match, no wildcards
-                    return match self.store.get_bytes(prefix.as_bytes()) {
-                        Ok(_) => {
-                            let keys =
-                                vec![RespValue::BulkString(Some(Bytes::from(prefix.to_string())))];
-                            RespValue::Array(Some(keys))
+            Command::DbSize => RespValue::Integer(self.logical_key_count(self.db) as i64),
+
+            Command::Save => match self.prepare_save() {
+                Some(snapshots) => match snapshots.save() {
+                    Ok(_) => RespValue::SimpleString(Bytes::from_static(b"OK")),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                },
+                None => RespValue::Error("ERR snapshots not available".to_string()),
+            },
+
+            Command::BgSave => match self.prepare_save() {
+                Some(snapshots) if snapshots.bgsave() => {
+                    RespValue::SimpleString(Bytes::from_static(b"Background saving started"))
+                }
+                Some(_) => RespValue::Error("ERR Background save already in progress".to_string()),
+                None => RespValue::Error("ERR snapshots not available".to_string()),
+            },
+
+            Command::LastSave => match &self.snapshots {
+                Some(snapshots) => RespValue::Integer(snapshots.last_save() as i64),
+                None => RespValue::Error("ERR snapshots not available".to_string()),
+            },
+
+            Command::Eval { script, keys, args } => scripting::eval(self, script, keys, args),
+            Command::EvalSha { sha1, keys, args } => scripting::evalsha(self, &sha1, keys, args),
+            Command::Script { subcommand, args } => scripting::script(&subcommand, args),
+
+            Command::Acl { subcommand, args } => {
+                let bulk = |s: String| RespValue::BulkString(Some(Bytes::from(s)));
+                let users = self.config.acl_users.users();
+                match (subcommand.to_uppercase().as_str(), args.as_slice()) {
+                    ("WHOAMI", []) => bulk(self.user.name().to_string()),
+                    ("LIST", []) => {
+                        RespValue::Array(Some(users.iter().map(|u| bulk(u.describe())).collect()))
+                    }
+                    ("USERS", []) => RespValue::Array(Some(
+                        users.iter().map(|u| bulk(u.name().to_string())).collect(),
+                    )),
+                    ("CAT", []) => RespValue::Array(Some(
+                        acl::CATEGORIES
+                            .iter()
+                            .map(|category| bulk(category.to_string()))
+                            .collect(),
+                    )),
+                    ("CAT", [category]) => {
+                        match acl::category_commands(&String::from_utf8_lossy(category)) {
+                            Some(names) => RespValue::Array(Some(
+                                names
+                                    .into_iter()
+                                    .map(|name| bulk(name.to_string()))
+                                    .collect(),
+                            )),
+                            None => RespValue::Error(format!(
+                                "ERR Unknown category '{}'",
+                                String::from_utf8_lossy(category)
+                            )),
                         }
-                        Err(_) => RespValue::Array(Some(vec![])),
+                    }
+                    ("WHOAMI" | "LIST" | "USERS" | "CAT", _) => RespValue::Error(format!(
+                        "ERR wrong number of arguments for 'acl|{}' command",
+                        subcommand.to_lowercase()
+                    )),
+                    _ => RespValue::Error(format!(
+                        "ERR unknown subcommand '{}'. Try ACL HELP.",
+                        subcommand
+                    )),
+                }
+            }
+
+            Command::BgRewriteAof => match &self.append_log {
+                Some(append_log) => {
+                    self.flush_metadata();
+                    if append_log.bgrewrite() {
+                        RespValue::SimpleString(Bytes::from_static(
+                            b"Background append only file rewriting started",
+                        ))
+                    } else {
+                        RespValue::Error(
+                            "ERR Background append only file rewriting already in progress"
+                                .to_string(),
+                        )
+                    }
+                }
+                None => RespValue::Error("ERR appendonly is not enabled".to_string()),
+            },
+
+            Command::Keys(pattern) => {
+                // Match within the selected database's key prefix
+                let pattern = format!("{}{}", String::from_utf8_lossy(&self.key_prefix), pattern);
+                let databases = self.config.databases;
+                let prefix = extract_prefix(&pattern).as_bytes();
+
+                // Exact match, no wildcards
+                if prefix.len() == pattern.len() {
+                    let keys = if db_of_key(prefix, databases) == self.db
+                        && self.key_type(prefix) != "none"
+                    {
+                        let key = &prefix[self.key_prefix.len()..];
+                        vec![RespValue::BulkString(Some(Bytes::copy_from_slice(key)))]
+                    } else {
+                        vec![]
                     };
+                    return RespValue::Array(Some(keys));
+                }
+
+                // Collections are found through their `:meta` records
+                self.hash_ops.flush_metadata();
+                self.set_ops.flush_metadata();
+                self.zset_ops.flush_metadata();
+
+                // A literal prefix narrows the walk to the string range and
+                // the matching range of each collection namespace
+                let ranges = if prefix.is_empty() {
+                    vec![Vec::new()]
                 } else {
-                    // Prefix scan with pattern matching
-                    let mut end = prefix.as_bytes().to_vec();
-                    end.push(b'~'); // Use tilde as upper bound
-                    (prefix.as_bytes().to_vec(), end)
+                    [&b""[..], b"L:", b"H:", b"S:", b"Z:"]
+                        .iter()
+                        .map(|type_prefix| [type_prefix, prefix].concat())
+                        .collect()
                 };
 
-                // Get keys using range_query
-                match self.store.range_query(&start_key, &end_key, 100000) {
-                    Ok(pairs) => {
-                        let keys: Vec<RespValue> = pairs
-                            .into_iter()
-                            .filter(|(key, _)| match_pattern(key, &pattern))
-                            .map(|(key, _)| RespValue::BulkString(Some(Bytes::from(key))))
-                            .collect();
-                        RespValue::Array(Some(keys))
-                    }
-                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                let mut keys = Vec::new();
+                for range in ranges {
+                    scan_prefix(&self.store, &range, |key, value| {
+                        if let Some((key, _)) = self.logical_key(key, value) {
+                            if match_pattern(key, &pattern) && db_of_key(key, databases) == self.db
+                            {
+                                keys.push(RespValue::BulkString(Some(Bytes::copy_from_slice(
+                                    &key[self.key_prefix.len()..],
+                                ))));
+                            }
+                        }
+                    });
                 }
+                RespValue::Array(Some(keys))
             }
 
             Command::Scan {
                 cursor,
                 count,
                 pattern,
+                key_type,
             } => {
-                // Parse cursor (empty or "0" means start from beginning)
-                let start_key = if cursor.is_empty() || cursor == b"0" {
-                    vec![]
+                // Other databases are reached through their key prefix
+                let databases = self.config.databases;
+                let pattern = if self.key_prefix.is_empty() {
+                    pattern
                 } else {
-                    cursor.clone()
+                    Some(format!(
+                        "{}{}",
+                        String::from_utf8_lossy(&self.key_prefix),
+                        pattern.as_deref().unwrap_or("*")
+                    ))
                 };
 
-                // For prefix patterns, optimize the scan range
-                let (scan_start, scan_end) = if let Some(ref pat) = pattern {
-                    let prefix = extract_prefix(pat);
-                    if !prefix.is_empty() && pat.starts_with(prefix) && pat.contains('*') {
-                        // Optimize for prefix patterns like "user:*"
-                        let mut end = prefix.as_bytes().to_vec();
-                        end.push(b'~');
-
-                        // Adjust start if cursor is past the prefix
-                        let actual_start = if start_key.len() > prefix.len()
-                            && start_key.starts_with(prefix.as_bytes())
-                        {
-                            start_key
-                        } else if start_key.is_empty() {
-                            prefix.as_bytes().to_vec()
-                        } else {
-                            start_key
-                        };
-
-                        (actual_start, end)
-                    } else {
-                        (start_key, vec![0xFF; 255])
-                    }
+                // Cursor 0 starts from the beginning; others encode the key
+                // to resume from
+                let start_key = if cursor == b"0" {
+                    vec![]
                 } else {
-                    (start_key, vec![0xFF; 255])
+                    match cursor::decode(&cursor) {
+                        Some(key) => key,
+                        None => return RespValue::Error("ERR invalid cursor".to_string()),
+                    }
                 };
 
-                // Get keys using range_query (get a bit more than requested to ensure we have enough after filtering)
-                let fetch_count = if pattern.is_some() { count * 2 } else { count };
-                match self
-                    .store
-                    .range_query(&scan_start, &scan_end, fetch_count + 1)
-                {
-                    Ok(pairs) => {
-                        let mut keys = Vec::new();
-                        let mut next_cursor = None;
-
-                        for (key, _) in pairs.into_iter() {
-                            // Skip if we've collected enough
-                            if keys.len() >= count {
-                                next_cursor = Some(key.clone());
-                                break;
-                            }
-
-                            // Apply pattern filter if specified
-                            if let Some(ref pat) = pattern {
-                                if !match_pattern(&key, pat) {
-                                    continue;
-                                }
-                            }
-
-                            keys.push(RespValue::BulkString(Some(Bytes::from(key.clone()))));
-                        }
-
-                        // Format response: [cursor, [keys...]]
-                        let cursor_str = if let Some(next) = next_cursor {
-                            Bytes::from(next)
+                // Collections are found through their `:meta` records, so
+                // pending counters have to be written out first
+                self.hash_ops.flush_metadata();
+                self.set_ops.flush_metadata();
+                self.zset_ops.flush_metadata();
+
+                // Collection records of a key sort far from its string form,
+                // so the whole keyspace is walked rather than a prefix range.
+                // COUNT is how many records to visit; the one after them is
+                // where the next call resumes
+                match self.store.range_query(&start_key, &[0xFF; 255], count + 1) {
+                    Ok(mut pairs) => {
+                        let next_cursor = if pairs.len() > count {
+                            cursor::encode(&pairs.swap_remove(count).0)
                         } else {
-                            Bytes::from_static(b"0") // End of iteration
+                            "0".to_string()
                         };
 
+                        let keys = pairs
+                            .iter()
+                            .filter_map(|(key, value)| self.logical_key(key, value))
+                            .filter(|(key, found_type)| {
+                                pattern.as_ref().is_none_or(|pat| match_pattern(key, pat))
+                                    && key_type.as_ref().is_none_or(|t| t == found_type)
+                                    && db_of_key(key, databases) == self.db
+                            })
+                            .map(|(key, _)| {
+                                RespValue::BulkString(Some(Bytes::copy_from_slice(
+                                    &key[self.key_prefix.len()..],
+                                )))
+                            })
+                            .collect();
+
                         RespValue::Array(Some(vec![
-                            RespValue::BulkString(Some(cursor_str)),
+                            RespValue::BulkString(Some(Bytes::from(next_cursor))),
                             RespValue::Array(Some(keys)),
                         ]))
                     }
@@ -583,12 +2680,28 @@ impl CommandExecutor {
             }
 
             Command::Info(section) => {
-                use std::sync::atomic::Ordering;
-
                 // Get actual stats
                 let uptime = self.start_time.elapsed().as_secs();
-                let commands = self.commands_processed.load(Ordering::Relaxed);
+                let (commands, reads, writes, connections) = match &self.stats {
+                    Some((stats, _)) => (
+                        stats.total_commands(),
+                        stats.total_reads(),
+                        stats.total_writes(),
+                        stats.total_connections(),
+                    ),
+                    None => (0, 0, 0, 0),
+                };
                 let stats = self.store.stats();
+                let counters = match &self.stats {
+                    Some((server_stats, _)) => server_stats.store_counters(&stats),
+                    None => StoreCounters {
+                        operations: stats.total_operations,
+                        gets: stats.total_gets,
+                        inserts: stats.total_inserts,
+                        hits: stats.cache_hits,
+                        misses: stats.cache_misses,
+                    },
+                };
 
                 // Format memory size
                 let format_bytes = |bytes: usize| -> String {
@@ -616,15 +2729,41 @@ impl CommandExecutor {
                         "# Server\r\n\
                         redis_version:feox-{}\r\n\
                         redis_mode:standalone\r\n\
+                        os:{} {}\r\n\
+                        arch_bits:{}\r\n\
                         process_id:{}\r\n\
-                        tcp_port:6379\r\n\
+                        run_id:{}\r\n\
+                        tcp_port:{}\r\n\
                         uptime_in_seconds:{}\r\n",
                         env!("CARGO_PKG_VERSION"),
+                        std::env::consts::OS,
+                        std::env::consts::ARCH,
+                        usize::BITS,
                         std::process::id(),
+                        self.run_id.as_deref().unwrap_or_default(),
+                        self.config.port,
                         uptime
                     ));
                 }
 
+                // Clients section
+                if section.is_none()
+                    || section
+                        .as_ref()
+                        .is_some_and(|s| s.eq_ignore_ascii_case("clients"))
+                {
+                    info.push_str(&format!(
+                        "# Clients\r\n\
+                        connected_clients:{}\r\n\
+                        cluster_connections:0\r\n\
+                        maxclients:{}\r\n\
+                        blocked_clients:{}\r\n",
+                        self.client_ops.client_count().unwrap_or(0),
+                        self.config.runtime.maxclients(),
+                        self.blocking.as_ref().map_or(0, |hub| hub.blocked_count())
+                    ));
+                }
+
                 // Memory section
                 if section.is_none()
                     || section
@@ -645,6 +2784,48 @@ impl CommandExecutor {
                     ));
                 }
 
+                // Persistence section
+                if let Some(snapshots) = self.snapshots.as_ref().filter(|_| {
+                    section.is_none()
+                        || section
+                            .as_ref()
+                            .is_some_and(|s| s.eq_ignore_ascii_case("persistence"))
+                }) {
+                    info.push_str(&format!(
+                        "# Persistence\r\n\
+                        loading:0\r\n\
+                        rdb_bgsave_in_progress:{}\r\n\
+                        rdb_last_save_time:{}\r\n\
+                        rdb_last_bgsave_status:{}\r\n\
+                        rdb_last_bgsave_time_sec:{}\r\n\
+                        aof_enabled:{}\r\n\
+                        aof_rewrite_in_progress:{}\r\n\
+                        aof_last_write_status:{}\r\n",
+                        snapshots.in_progress() as u8,
+                        snapshots.last_save(),
+                        if snapshots.last_save_ok() {
+                            "ok"
+                        } else {
+                            "err"
+                        },
+                        snapshots.last_save_secs(),
+                        self.append_log.is_some() as u8,
+                        self.append_log
+                            .as_ref()
+                            .is_some_and(|log| log.rewrite_in_progress())
+                            as u8,
+                        if self
+                            .append_log
+                            .as_ref()
+                            .is_none_or(|log| log.last_write_ok())
+                        {
+                            "ok"
+                        } else {
+                            "err"
+                        }
+                    ));
+                }
+
                 // Stats section
                 if section.is_none()
                     || section
@@ -654,7 +2835,7 @@ impl CommandExecutor {
                 {
                     info.push_str(&format!(
                         "# Stats\r\n\
-                        total_connections_received:0\r\n\
+                        total_connections_received:{}\r\n\
                         total_commands_processed:{}\r\n\
                         instantaneous_ops_per_sec:0\r\n\
                         total_net_input_bytes:0\r\n\
@@ -664,31 +2845,117 @@ impl CommandExecutor {
                         total_inserts:{}\r\n\
                         keyspace_hits:{}\r\n\
                         keyspace_misses:{}\r\n\
-                        cache_hit_rate:{:.2}\r\n",
+                        cache_hit_rate:{:.2}\r\n\
+                        total_reads_processed:{}\r\n\
+                        total_writes_processed:{}\r\n\
+                        pubsub_channels:{}\r\n\
+                        pubsub_patterns:{}\r\n",
+                        connections,
                         commands,
-                        stats.total_operations,
-                        stats.total_gets,
-                        stats.total_inserts,
-                        stats.cache_hits,
-                        stats.cache_misses,
-                        stats.cache_hit_rate * 100.0
+                        counters.operations,
+                        counters.gets,
+                        counters.inserts,
+                        counters.hits,
+                        counters.misses,
+                        counters.hit_rate(),
+                        reads,
+                        writes,
+                        self.pubsub
+                            .as_ref()
+                            .map_or(0, |registry| registry.get_channel_count()),
+                        self.pubsub
+                            .as_ref()
+                            .map_or(0, |registry| registry.get_pattern_count())
                     ));
                 }
 
-                // Keyspace section
+                // Replication section; this is always a standalone master,
+                // and the replication id is the server's run id
                 if section.is_none()
                     || section
                         .as_ref()
-                        .map(|s| s.eq_ignore_ascii_case("keyspace"))
+                        .map(|s| s.eq_ignore_ascii_case("replication"))
                         .unwrap_or(false)
                 {
                     info.push_str(&format!(
-                        "# Keyspace\r\n\
-                        db0:keys={},expires=0,avg_ttl=0\r\n",
-                        stats.record_count
+                        "# Replication\r\n\
+                        role:master\r\n\
+                        connected_slaves:0\r\n\
+                        master_failover_state:no-failover\r\n\
+                        master_replid:{}\r\n\
+                        master_replid2:0000000000000000000000000000000000000000\r\n\
+                        master_repl_offset:0\r\n\
+                        second_repl_offset:-1\r\n\
+                        repl_backlog_active:0\r\n",
+                        self.run_id.as_deref().unwrap_or_default()
                     ));
                 }
 
+                // Keyspace section
+                if section.is_none()
+                    || section
+                        .as_ref()
+                        .map(|s| s.eq_ignore_ascii_case("keyspace"))
+                        .unwrap_or(false)
+                {
+                    // Databases without keys are left out, as Redis does
+                    info.push_str("# Keyspace\r\n");
+                    for db in 0..self.config.databases {
+                        let keys = self.logical_key_count(db);
+                        if keys > 0 {
+                            info.push_str(&format!(
+                                "db{}:keys={},expires=0,avg_ttl=0\r\n",
+                                db, keys
+                            ));
+                        }
+                    }
+                }
+
+                // Per-command sections are only listed when asked for
+                if let Some((server_stats, _)) = &self.stats {
+                    let wants = |name: &str| {
+                        section
+                            .as_ref()
+                            .is_some_and(|s| s.eq_ignore_ascii_case(name))
+                    };
+
+                    if wants("commandstats") {
+                        info.push_str("# Commandstats\r\n");
+                        for (index, name) in COMMAND_NAMES.iter().enumerate() {
+                            let cmd = server_stats.command(index);
+                            if cmd.calls == 0 {
+                                continue;
+                            }
+                            info.push_str(&format!(
+                                "cmdstat_{}:calls={},usec={},usec_per_call={:.2},\
+                                rejected_calls=0,failed_calls={}\r\n",
+                                name,
+                                cmd.calls,
+                                cmd.usec,
+                                cmd.usec as f64 / cmd.calls as f64,
+                                cmd.failed_calls
+                            ));
+                        }
+                    }
+
+                    if wants("latencystats") {
+                        info.push_str("# Latencystats\r\n");
+                        for (index, name) in COMMAND_NAMES.iter().enumerate() {
+                            let cmd = server_stats.command(index);
+                            if cmd.calls == 0 {
+                                continue;
+                            }
+                            info.push_str(&format!(
+                                "latency_percentiles_usec_{}:p50={:.3},p99={:.3},p99.9={:.3}\r\n",
+                                name,
+                                cmd.percentile_usec(50.0),
+                                cmd.percentile_usec(99.0),
+                                cmd.percentile_usec(99.9)
+                            ));
+                        }
+                    }
+                }
+
                 RespValue::BulkString(Some(Bytes::from(info)))
             }
 
@@ -758,6 +3025,11 @@ impl CommandExecutor {
                 Err(e) => RespValue::Error(format!("ERR {}", e)),
             },
 
+            // Never blocks here: the connection parks the client when the
+            // reply is a null array, and inside MULTI it is returned as is
+            Command::BLPop { keys, .. } => self.pop_first(&keys, true),
+            Command::BRPop { keys, .. } => self.pop_first(&keys, false),
+
             Command::LLen(key) => match self.list_ops.llen(&key) {
                 Ok(count) => RespValue::Integer(count),
                 Err(e) => RespValue::Error(format!("ERR {}", e)),
@@ -779,6 +3051,32 @@ impl CommandExecutor {
                 Err(e) => RespValue::Error(format!("ERR {}", e)),
             },
 
+            Command::LSet { key, index, value } => {
+                if !self.list_ops.exists(&key) {
+                    return RespValue::Error("ERR no such key".to_string());
+                }
+                match self.list_ops.lset(&key, index, value) {
+                    Ok(true) => RespValue::SimpleString(Bytes::from_static(b"OK")),
+                    Ok(false) => RespValue::Error("ERR index out of range".to_string()),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::LRem { key, count, value } => match self.list_ops.lrem(&key, count, &value) {
+                Ok(removed) => RespValue::Integer(removed),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::LInsert {
+                key,
+                before,
+                pivot,
+                value,
+            } => match self.list_ops.linsert(&key, before, &pivot, value) {
+                Ok(len) => RespValue::Integer(len),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
             Command::HSet { key, fields } => {
                 let field_refs = fields.iter().map(|(f, v)| (f.as_slice(), v.clone()));
                 match self.hash_ops.hset(&key, field_refs) {
@@ -816,6 +3114,46 @@ impl CommandExecutor {
                 Err(e) => RespValue::Error(format!("ERR {}", e)),
             },
 
+            Command::HSetNx { key, field, value } => {
+                match self.hash_ops.hsetnx(&key, &field, value) {
+                    Ok(set) => RespValue::Integer(if set { 1 } else { 0 }),
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::HExpire {
+                key,
+                seconds,
+                condition,
+                fields,
+            } => field_codes_reply(self.hash_ops.hexpire(
+                &key,
+                seconds.saturating_mul(1000),
+                condition,
+                &fields,
+            )),
+
+            Command::HPExpire {
+                key,
+                milliseconds,
+                condition,
+                fields,
+            } => field_codes_reply(
+                self.hash_ops
+                    .hexpire(&key, milliseconds, condition, &fields),
+            ),
+
+            Command::HTtl { key, fields } => field_codes_reply(self.hash_ops.httl(&key, &fields)),
+
+            Command::HPersist { key, fields } => {
+                field_codes_reply(self.hash_ops.hpersist(&key, &fields))
+            }
+
+            Command::HStrLen { key, field } => match self.hash_ops.hstrlen(&key, &field) {
+                Ok(len) => RespValue::Integer(len),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
             Command::HGetAll(key) => match self.hash_ops.hgetall(&key) {
                 Ok(pairs) => {
                     let mut result = Vec::new();
@@ -851,6 +3189,51 @@ impl CommandExecutor {
                 Err(e) => RespValue::Error(format!("ERR {}", e)),
             },
 
+            Command::HScan {
+                key,
+                cursor,
+                count,
+                pattern,
+            } => {
+                // Cursor "0" starts a new iteration; others encode the field
+                // to resume from, as SCAN cursors do
+                let start = if cursor == b"0" {
+                    vec![]
+                } else {
+                    match cursor::decode(&cursor) {
+                        Some(field) => field,
+                        None => return RespValue::Error("ERR invalid cursor".to_string()),
+                    }
+                };
+
+                match self.hash_ops.hscan(&key, &start, count) {
+                    Ok((pairs, next_cursor)) => {
+                        let mut items = Vec::new();
+                        for (field, value) in pairs {
+                            if let Some(ref pat) = pattern {
+                                if !match_pattern(&field, pat) {
+                                    continue;
+                                }
+                            }
+                            items.push(RespValue::BulkString(Some(Bytes::from(field))));
+                            items.push(RespValue::BulkString(Some(value)));
+                        }
+
+                        // Format response: [cursor, [field, value, ...]]
+                        let cursor_str = match next_cursor {
+                            Some(next) => Bytes::from(cursor::encode(&next)),
+                            None => Bytes::from_static(b"0"), // End of iteration
+                        };
+
+                        RespValue::Array(Some(vec![
+                            RespValue::BulkString(Some(cursor_str)),
+                            RespValue::Array(Some(items)),
+                        ]))
+                    }
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
             Command::HIncrBy { key, field, delta } => {
                 match self.hash_ops.hincrby(&key, &field, delta) {
                     Ok(new_value) => RespValue::Integer(new_value),
@@ -858,7 +3241,136 @@ impl CommandExecutor {
                 }
             }
 
-            Command::Auth(_) => {
+            Command::SAdd { key, members } => match self.set_ops.sadd(&key, members) {
+                Ok(added) => RespValue::Integer(added),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::SRem { key, members } => match self.set_ops.srem(&key, members) {
+                Ok(removed) => RespValue::Integer(removed),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::SMembers(key) => set_reply(self.set_ops.smembers(&key)),
+
+            Command::SIsMember { key, member } => match self.set_ops.sismember(&key, &member) {
+                Ok(is_member) => RespValue::Integer(if is_member { 1 } else { 0 }),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::SMIsMember { key, members } => match self.set_ops.smismember(&key, &members) {
+                Ok(flags) => RespValue::Array(Some(
+                    flags
+                        .into_iter()
+                        .map(|is_member| RespValue::Integer(is_member as i64))
+                        .collect(),
+                )),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::SCard(key) => match self.set_ops.scard(&key) {
+                Ok(count) => RespValue::Integer(count),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::SPop { key, count } => match self.set_ops.spop(&key, count.unwrap_or(1)) {
+                // Without a count the reply is a single member or nil
+                Ok(members) if count.is_none() => RespValue::BulkString(members.into_iter().next()),
+                Ok(members) => RespValue::Array(Some(
+                    members
+                        .into_iter()
+                        .map(|m| RespValue::BulkString(Some(m)))
+                        .collect(),
+                )),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::SUnion(keys) => set_reply(self.set_ops.sunion(&keys)),
+            Command::SInter(keys) => set_reply(self.set_ops.sinter(&keys)),
+            Command::SDiff(keys) => set_reply(self.set_ops.sdiff(&keys)),
+            Command::SInterCard { keys, limit } => match self.set_ops.sintercard(&keys, limit) {
+                Ok(count) => RespValue::Integer(count as i64),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::SUnionStore { destination, keys } => {
+                self.store_set(&destination, self.set_ops.sunion(&keys))
+            }
+            Command::SInterStore { destination, keys } => {
+                self.store_set(&destination, self.set_ops.sinter(&keys))
+            }
+            Command::SDiffStore { destination, keys } => {
+                self.store_set(&destination, self.set_ops.sdiff(&keys))
+            }
+
+            Command::ZAdd {
+                key,
+                options,
+                members,
+            } => match self.zset_ops.zadd(&key, members, options) {
+                Ok(count) => RespValue::Integer(count),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::ZScore { key, member } => match self.zset_ops.zscore(&key, &member) {
+                Ok(Some(score)) => RespValue::BulkString(Some(Bytes::from(format_score(score)))),
+                Ok(None) => RespValue::BulkString(None),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::ZIncrBy { key, delta, member } => {
+                match self.zset_ops.zincrby(&key, delta, &member) {
+                    Ok(score) => RespValue::BulkString(Some(Bytes::from(format_score(score)))),
+                    Err(crate::error::Error::Protocol(msg)) => {
+                        RespValue::Error(format!("ERR {}", msg))
+                    }
+                    Err(e) => RespValue::Error(format!("ERR {}", e)),
+                }
+            }
+
+            Command::ZCard(key) => match self.zset_ops.zcard(&key) {
+                Ok(count) => RespValue::Integer(count),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::ZRem { key, members } => match self.zset_ops.zrem(&key, members) {
+                Ok(removed) => RespValue::Integer(removed),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::ZRange {
+                key,
+                start,
+                stop,
+                with_scores,
+            } => zset_reply(self.zset_ops.zrange(&key, start, stop), with_scores),
+
+            Command::ZRangeByScore {
+                key,
+                min,
+                max,
+                with_scores,
+                limit,
+            } => {
+                // A negative offset yields nothing; a negative count means no limit
+                let (offset, count) = limit.unwrap_or((0, -1));
+                if offset < 0 {
+                    return RespValue::Array(Some(vec![]));
+                }
+                zset_reply(
+                    self.zset_ops
+                        .zrangebyscore(&key, min, max, offset as usize, count),
+                    with_scores,
+                )
+            }
+
+            Command::ZRank { key, member } => match self.zset_ops.zrank(&key, &member) {
+                Ok(Some(rank)) => RespValue::Integer(rank),
+                Ok(None) => RespValue::BulkString(None),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+
+            Command::Auth { .. } => {
                 // This should be handled in connection.rs
                 // If we get here, it means auth is not configured
                 if self.config.requirepass.is_none() {
@@ -876,24 +3388,88 @@ impl CommandExecutor {
                 .client_ops
                 .execute(subcommand, args, self.connection_id),
 
-            // Pub/Sub commands are handled in connection.rs
+            // Connections publish through the pub/sub handler; scripts land
+            // here and publish through the registry
+            Command::Publish { channel, message } => match &self.pubsub {
+                Some(registry) => {
+                    RespValue::Integer(registry.publish(channel, Bytes::from(message)) as i64)
+                }
+                None => RespValue::Error("ERR Pub/Sub is not available".to_string()),
+            },
+            Command::SPublish { channel, message } => match &self.pubsub {
+                Some(registry) => {
+                    RespValue::Integer(registry.shard_publish(channel, Bytes::from(message)) as i64)
+                }
+                None => RespValue::Error("ERR Pub/Sub is not available".to_string()),
+            },
+
+            // Other Pub/Sub commands are handled in connection.rs
             Command::Subscribe(_)
             | Command::Unsubscribe(_)
             | Command::PSubscribe(_)
             | Command::PUnsubscribe(_)
-            | Command::Publish { .. }
-            | Command::PubSub { .. } => RespValue::Error(
-                "-ERR Pub/Sub commands should be handled in connection layer".to_string(),
+            | Command::PubSub { .. }
+            | Command::SSubscribe(_)
+            | Command::SUnsubscribe(_) => RespValue::Error(
+                "ERR Pub/Sub commands should be handled in connection layer".to_string(),
             ),
 
+            // HELLO changes per-connection protocol state in connection.rs
+            Command::Hello { .. } => {
+                RespValue::Error("ERR HELLO should be handled in connection layer".to_string())
+            }
+
+            // There are no replicas, so none acknowledged the writes
+            Command::Wait { .. } => RespValue::Integer(0),
+
+            // There is only ever this one node, so routing hints change nothing
+            Command::Asking | Command::ReadOnly | Command::ReadWrite => {
+                RespValue::SimpleString(Bytes::from_static(b"OK"))
+            }
+
+            Command::Cluster { subcommand, args } => {
+                match (subcommand.to_uppercase().as_str(), args.as_slice()) {
+                    ("INFO", []) => {
+                        RespValue::BulkString(Some(Bytes::from_static(b"cluster_enabled:0\r\n")))
+                    }
+                    ("MYID", []) => RespValue::BulkString(Some(Bytes::from(
+                        self.run_id.as_deref().unwrap_or_default().to_string(),
+                    ))),
+                    ("INFO" | "MYID", _) => RespValue::Error(format!(
+                        "ERR wrong number of arguments for 'cluster|{}' command",
+                        subcommand.to_lowercase()
+                    )),
+                    _ => RespValue::Error(
+                        "ERR This instance has cluster support disabled".to_string(),
+                    ),
+                }
+            }
+
+            // MONITOR needs the worker's hub, which only connection.rs has
+            Command::Monitor => {
+                RespValue::Error("ERR MONITOR should be handled in connection layer".to_string())
+            }
+
             // Transaction commands are handled in connection.rs
             Command::Multi
             | Command::Exec
             | Command::Discard
             | Command::Watch(_)
             | Command::Unwatch => RespValue::Error(
-                "-ERR Transaction commands should be handled in connection layer".to_string(),
+                "ERR Transaction commands should be handled in connection layer".to_string(),
             ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_command_has_a_spec() {
+        for name in COMMAND_NAMES {
+            assert!(command_spec(name).is_some(), "no COMMAND spec for {}", name);
+        }
+    }
+}
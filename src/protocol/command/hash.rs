@@ -1,12 +1,15 @@
+use super::ExpireCondition;
 use crate::error::{Error, Result};
 use bytes::Bytes;
 use feoxdb::FeoxStore;
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use once_cell::sync::Lazy;
 
-struct MetadataTracker {
+/// Batches collection size deltas before writing them to `:meta` keys
+pub(super) struct MetadataTracker {
     pending_updates: HashMap<Vec<u8>, i64>,
     last_flush: Instant,
     flush_interval: Duration,
@@ -14,7 +17,7 @@ struct MetadataTracker {
 }
 
 impl MetadataTracker {
-    fn new() -> Self {
+    pub(super) fn new() -> Self {
         Self {
             pending_updates: HashMap::new(),
             last_flush: Instant::now(),
@@ -23,25 +26,45 @@ impl MetadataTracker {
         }
     }
 
-    fn add_update(&mut self, key: Vec<u8>, delta: i64) {
+    pub(super) fn add_update(&mut self, key: Vec<u8>, delta: i64) {
         *self.pending_updates.entry(key).or_insert(0) += delta;
     }
 
-    fn should_flush(&self) -> bool {
+    pub(super) fn should_flush(&self) -> bool {
         self.pending_updates.len() >= self.max_batch_size
             || self.last_flush.elapsed() >= self.flush_interval
     }
 
-    fn take_updates(&mut self) -> HashMap<Vec<u8>, i64> {
+    pub(super) fn take_updates(&mut self) -> HashMap<Vec<u8>, i64> {
         self.last_flush = Instant::now();
         std::mem::take(&mut self.pending_updates)
     }
 }
 
-static GLOBAL_METADATA_TRACKER: Lazy<Arc<RwLock<MetadataTracker>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(MetadataTracker::new()))
+const TRACKER_SHARDS: usize = 32;
+
+/// Pending hash size deltas, sharded by meta key so writes to different
+/// hashes rarely contend on the same lock
+static GLOBAL_METADATA_TRACKER: Lazy<Vec<RwLock<MetadataTracker>>> = Lazy::new(|| {
+    (0..TRACKER_SHARDS)
+        .map(|_| RwLock::new(MetadataTracker::new()))
+        .collect()
 });
 
+fn tracker_shard(meta_key: &[u8]) -> &'static RwLock<MetadataTracker> {
+    let mut hasher = DefaultHasher::new();
+    meta_key.hash(&mut hasher);
+    &GLOBAL_METADATA_TRACKER[hasher.finish() as usize % TRACKER_SHARDS]
+}
+
+/// One HSCAN batch: field/value pairs and the cursor to resume from
+pub type HashScanPage = (Vec<(Vec<u8>, Bytes)>, Option<Vec<u8>>);
+
+/// Per-field reply of HEXPIRE / HTTL / HPERSIST for a missing field
+const NO_SUCH_FIELD: i64 = -2;
+/// Per-field reply of HTTL / HPERSIST for a field without a TTL
+const NO_FIELD_TTL: i64 = -1;
+
 #[derive(Clone)]
 pub struct HashOperations {
     store: Arc<FeoxStore>,
@@ -52,9 +75,15 @@ impl HashOperations {
         Self { store }
     }
 
-    fn flush_metadata(&self) {
-        let mut tracker = GLOBAL_METADATA_TRACKER.write().unwrap();
-        let updates = tracker.take_updates();
+    pub fn flush_metadata(&self) {
+        for shard in GLOBAL_METADATA_TRACKER.iter() {
+            self.flush_shard(shard);
+        }
+    }
+
+    fn flush_shard(&self, shard: &RwLock<MetadataTracker>) {
+        // Take the batch first so the increments run without the lock held
+        let updates = shard.write().unwrap().take_updates();
 
         for (meta_key, delta) in updates {
             if delta != 0 {
@@ -63,18 +92,39 @@ impl HashOperations {
         }
     }
 
-    fn maybe_flush_metadata(&self) {
-        let should_flush = GLOBAL_METADATA_TRACKER.read().unwrap().should_flush();
+    /// Queue a change in a hash's field count, flushing its shard when due
+    fn track_fields(&self, meta_key: Vec<u8>, delta: i64) {
+        let shard = tracker_shard(&meta_key);
+        let should_flush = {
+            let mut tracker = shard.write().unwrap();
+            tracker.add_update(meta_key, delta);
+            tracker.should_flush()
+        };
         if should_flush {
-            self.flush_metadata();
+            self.flush_shard(shard);
         }
     }
 
-    fn parse_metadata(data: &[u8]) -> i64 {
-        if data.len() < 8 {
-            return 0;
+    fn field_key(key: &[u8], field: &[u8]) -> Vec<u8> {
+        let mut field_key = Vec::with_capacity(key.len() + field.len() + 5);
+        field_key.extend_from_slice(b"H:");
+        field_key.extend_from_slice(key);
+        field_key.extend_from_slice(b":f:");
+        field_key.extend_from_slice(field);
+        field_key
+    }
+
+    /// Check if a field record is present and not past its TTL
+    ///
+    /// Range queries still return expired records until the store sweeps
+    /// them, so listings check each field they visit.
+    fn field_live(&self, field_key: &[u8]) -> bool {
+        match self.store.get_ttl(field_key) {
+            // get_ttl rounds down, so a field in its last second reads 0 too
+            Ok(Some(0)) => self.store.get_bytes(field_key).is_ok(),
+            Ok(_) | Err(feoxdb::FeoxError::TtlNotEnabled) => true,
+            Err(_) => false,
         }
-        i64::from_le_bytes(data[0..8].try_into().unwrap())
     }
 
     pub fn hset<'a>(&self, key: &[u8], fields: impl Iterator<Item = (&'a [u8], Bytes)>) -> Result<i64> {
@@ -101,8 +151,7 @@ impl HashOperations {
             meta_key.extend_from_slice(key);
             meta_key.extend_from_slice(b":meta");
 
-            GLOBAL_METADATA_TRACKER.write().unwrap().add_update(meta_key, new_fields_count);
-            self.maybe_flush_metadata();
+            self.track_fields(meta_key, new_fields_count);
         }
 
         Ok(new_fields_count)
@@ -151,14 +200,14 @@ impl HashOperations {
             field_key.extend_from_slice(b":f:");
             field_key.extend_from_slice(&field);
 
-            if self.store.delete(&field_key).is_ok() {
+            let live = self.field_live(&field_key);
+            if self.store.delete(&field_key).is_ok() && live {
                 deleted_count += 1;
             }
         }
 
         if deleted_count > 0 {
-            GLOBAL_METADATA_TRACKER.write().unwrap().add_update(meta_key, -deleted_count);
-            self.maybe_flush_metadata();
+            self.track_fields(meta_key, -deleted_count);
         }
 
         Ok(deleted_count)
@@ -170,100 +219,307 @@ impl HashOperations {
         field_key.extend_from_slice(key);
         field_key.extend_from_slice(b":f:");
         field_key.extend_from_slice(field);
-        Ok(self.store.contains_key(&field_key))
+        Ok(self.field_live(&field_key))
+    }
+
+    pub fn hsetnx(&self, key: &[u8], field: &[u8], value: Bytes) -> Result<bool> {
+        let mut field_key = Vec::with_capacity(key.len() + field.len() + 5);
+        field_key.extend_from_slice(b"H:");
+        field_key.extend_from_slice(key);
+        field_key.extend_from_slice(b":f:");
+        field_key.extend_from_slice(field);
+
+        // insert_if_absent settles a race with another HSETNX; a field past
+        // its TTL still blocks it until swept, so that one is cleared first
+        while !self.store.insert_if_absent(&field_key, &value)? {
+            if self.field_live(&field_key) {
+                return Ok(false);
+            }
+            self.store.delete(&field_key).ok();
+        }
+
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"H:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+
+        self.track_fields(meta_key, 1);
+
+        Ok(true)
+    }
+
+    pub fn hstrlen(&self, key: &[u8], field: &[u8]) -> Result<i64> {
+        Ok(self.hget(key, field)?.map_or(0, |value| value.len() as i64))
     }
 
     pub fn hgetall(&self, key: &[u8]) -> Result<Vec<(Vec<u8>, Bytes)>> {
+        let mut results = Vec::new();
+        self.for_each_field(key, |field, value| {
+            results.push((field.to_vec(), Bytes::from(value)));
+        })?;
+        Ok(results)
+    }
+
+    /// Return the live fields among the next `count`, plus the next cursor
+    ///
+    /// The cursor is the field to resume from, or empty to start from the
+    /// beginning. The next cursor is the first field not visited, so
+    /// filtering the page afterwards never loses the resume point. A `None`
+    /// cursor means the hash has been fully iterated.
+    pub fn hscan(&self, key: &[u8], cursor: &[u8], count: usize) -> Result<HashScanPage> {
         let mut prefix = Vec::with_capacity(key.len() + 5);
         prefix.extend_from_slice(b"H:");
         prefix.extend_from_slice(key);
         prefix.extend_from_slice(b":f:");
         let prefix_len = prefix.len();
 
-        let start_key = prefix.clone();
+        let mut start_key = prefix.clone();
+        start_key.extend_from_slice(cursor);
         let mut end_key = prefix.clone();
         end_key.push(255);
 
-        let mut results = Vec::new();
+        match self.store.range_query(&start_key, &end_key, count + 1) {
+            Ok(mut pairs) => {
+                let next_cursor = if pairs.len() > count {
+                    Some(pairs.swap_remove(count).0[prefix_len..].to_vec())
+                } else {
+                    None
+                };
+                let results: Vec<(Vec<u8>, Bytes)> = pairs
+                    .into_iter()
+                    .filter(|(field_key, _)| {
+                        field_key.starts_with(&prefix) && self.field_live(field_key)
+                    })
+                    .map(|(field_key, value)| {
+                        (field_key[prefix_len..].to_vec(), Bytes::from(value))
+                    })
+                    .collect();
+                Ok((results, next_cursor))
+            }
+            Err(e) => Err(Error::Database(e)),
+        }
+    }
 
-        match self.store.range_query(&start_key, &end_key, 10000) {
-            Ok(pairs) => {
-                for (field_key, value) in pairs {
-                    if field_key.starts_with(&prefix) {
-                        let field_name = field_key[prefix_len..].to_vec();
-                        results.push((field_name, Bytes::from(value)));
-                    }
+    /// Check if a hash with at least one field exists under this key
+    ///
+    /// Probes the field range directly because the `:meta` counter is
+    /// flushed lazily and may lag behind recent HSETs.
+    pub fn exists(&self, key: &[u8]) -> bool {
+        let mut prefix = Vec::with_capacity(key.len() + 5);
+        prefix.extend_from_slice(b"H:");
+        prefix.extend_from_slice(key);
+        prefix.extend_from_slice(b":f:");
+
+        let mut start_key = prefix.clone();
+        let mut end_key = prefix.clone();
+        end_key.push(255);
+
+        // Skip over fields that expired but have not been swept yet
+        loop {
+            let Ok(pairs) = self.store.range_query(&start_key, &end_key, 16) else {
+                return false;
+            };
+            if pairs
+                .iter()
+                .any(|(field_key, _)| self.field_live(field_key))
+            {
+                return true;
+            }
+            match pairs.last() {
+                Some((last, _)) if pairs.len() == 16 => {
+                    start_key = last.clone();
+                    start_key.push(0);
                 }
-                Ok(results)
+                _ => return false,
             }
-            Err(e) => Err(Error::Database(e)),
         }
     }
 
-    pub fn hlen(&self, key: &[u8]) -> Result<i64> {
-        self.flush_metadata();
+    /// Delete the hash's counter and every field, returning whether it had
+    /// any live fields
+    pub fn delete(&self, key: &[u8]) -> Result<bool> {
+        let existed = self.exists(key);
 
         let mut meta_key = Vec::with_capacity(key.len() + 7);
         meta_key.extend_from_slice(b"H:");
         meta_key.extend_from_slice(key);
         meta_key.extend_from_slice(b":meta");
 
-        match self.store.get_bytes(&meta_key) {
-            Ok(meta_bytes) => Ok(Self::parse_metadata(&meta_bytes)),
-            Err(_) => Ok(0),
-        }
-    }
+        // Settle pending deltas so they cannot recreate the counter
+        self.flush_shard(tracker_shard(&meta_key));
 
-    pub fn hkeys(&self, key: &[u8]) -> Result<Vec<Vec<u8>>> {
         let mut prefix = Vec::with_capacity(key.len() + 5);
         prefix.extend_from_slice(b"H:");
         prefix.extend_from_slice(key);
         prefix.extend_from_slice(b":f:");
-        let prefix_len = prefix.len();
+        super::delete_prefixed(&self.store, &prefix)?;
+        self.store.delete(&meta_key).ok();
 
-        let start_key = prefix.clone();
-        let mut end_key = prefix.clone();
-        end_key.push(255);
+        Ok(existed)
+    }
 
-        let mut results = Vec::new();
+    /// Count the hash's fields
+    ///
+    /// Walks the field records rather than reading the `:meta` counter,
+    /// which is flushed lazily and can drift when writes race, so HLEN
+    /// always agrees with HKEYS.
+    pub fn hlen(&self, key: &[u8]) -> Result<i64> {
+        let mut count = 0;
+        self.for_each_field(key, |_, _| count += 1)?;
+        Ok(count)
+    }
 
-        match self.store.range_query(&start_key, &end_key, 10000) {
-            Ok(pairs) => {
-                for (field_key, _) in pairs {
-                    if field_key.starts_with(&prefix) {
-                        let field_name = field_key[prefix_len..].to_vec();
-                        results.push(field_name);
-                    }
-                }
-                Ok(results)
-            }
-            Err(e) => Err(Error::Database(e)),
-        }
+    pub fn hkeys(&self, key: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let mut results = Vec::new();
+        self.for_each_field(key, |field, _| results.push(field.to_vec()))?;
+        Ok(results)
     }
 
     pub fn hvals(&self, key: &[u8]) -> Result<Vec<Bytes>> {
+        let mut results = Vec::new();
+        self.for_each_field(key, |_, value| results.push(Bytes::from(value)))?;
+        Ok(results)
+    }
+
+    /// Visit every field name and value of a hash, fetching them in batches
+    fn for_each_field(&self, key: &[u8], mut f: impl FnMut(&[u8], Vec<u8>)) -> Result<()> {
+        const BATCH: usize = 10000;
+
         let mut prefix = Vec::with_capacity(key.len() + 5);
         prefix.extend_from_slice(b"H:");
         prefix.extend_from_slice(key);
         prefix.extend_from_slice(b":f:");
+        let prefix_len = prefix.len();
 
-        let start_key = prefix.clone();
+        let mut start_key = prefix.clone();
         let mut end_key = prefix.clone();
         end_key.push(255);
 
-        let mut results = Vec::new();
+        loop {
+            let pairs = self
+                .store
+                .range_query(&start_key, &end_key, BATCH)
+                .map_err(Error::Database)?;
+            let more = pairs.len() == BATCH;
+
+            if let Some((last, _)) = pairs.last() {
+                // Resume just past the last field seen
+                start_key = last.clone();
+                start_key.push(0);
+            }
+            for (field_key, value) in pairs {
+                if field_key.starts_with(&prefix) && self.field_live(&field_key) {
+                    f(&field_key[prefix_len..], value);
+                }
+            }
 
-        match self.store.range_query(&start_key, &end_key, 10000) {
-            Ok(pairs) => {
-                for (field_key, value) in pairs {
-                    if field_key.starts_with(&prefix) {
-                        results.push(Bytes::from(value));
-                    }
+            if !more {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Give fields a TTL of `milliseconds`, rounded up to whole seconds,
+    /// returning per field 1 if it was set, 0 if `condition` did not hold,
+    /// 2 if a TTL of 0 deleted the field, or -2 if there is no such field
+    pub fn hexpire(
+        &self,
+        key: &[u8],
+        milliseconds: u64,
+        condition: Option<ExpireCondition>,
+        fields: &[Vec<u8>],
+    ) -> Result<Vec<i64>> {
+        let seconds = milliseconds.div_ceil(1000);
+        let mut deleted = 0;
+        let mut results = Vec::with_capacity(fields.len());
+
+        for field in fields {
+            let field_key = Self::field_key(key, field);
+            if !self.field_live(&field_key) {
+                results.push(NO_SUCH_FIELD);
+                continue;
+            }
+
+            let current = self.store.get_ttl(&field_key).map_err(Error::Database)?;
+            let allowed = match (condition, current) {
+                (None, _) => true,
+                (Some(ExpireCondition::Nx), current) => current.is_none(),
+                (Some(ExpireCondition::Xx), current) => current.is_some(),
+                (Some(ExpireCondition::Gt), Some(ttl)) => seconds > ttl,
+                (Some(ExpireCondition::Gt), None) => false,
+                (Some(ExpireCondition::Lt), Some(ttl)) => seconds < ttl,
+                (Some(ExpireCondition::Lt), None) => true,
+            };
+            if !allowed {
+                results.push(0);
+            } else if seconds == 0 {
+                if self.store.delete(&field_key).is_ok() {
+                    deleted += 1;
+                }
+                results.push(2);
+            } else {
+                match self.store.update_ttl(&field_key, seconds) {
+                    Ok(()) => results.push(1),
+                    Err(feoxdb::FeoxError::KeyNotFound) => results.push(NO_SUCH_FIELD),
+                    Err(e) => return Err(Error::Database(e)),
                 }
-                Ok(results)
             }
-            Err(e) => Err(Error::Database(e)),
         }
+
+        if deleted > 0 {
+            let mut meta_key = Vec::with_capacity(key.len() + 7);
+            meta_key.extend_from_slice(b"H:");
+            meta_key.extend_from_slice(key);
+            meta_key.extend_from_slice(b":meta");
+
+            self.track_fields(meta_key, -deleted);
+        }
+
+        Ok(results)
+    }
+
+    /// Seconds each field has left to live, -1 for fields without a TTL
+    /// and -2 for missing fields
+    pub fn httl(&self, key: &[u8], fields: &[Vec<u8>]) -> Result<Vec<i64>> {
+        fields
+            .iter()
+            .map(|field| {
+                let field_key = Self::field_key(key, field);
+                if !self.field_live(&field_key) {
+                    return Ok(NO_SUCH_FIELD);
+                }
+                match self.store.get_ttl(&field_key) {
+                    Ok(Some(ttl)) => Ok(ttl as i64),
+                    Ok(None) | Err(feoxdb::FeoxError::TtlNotEnabled) => Ok(NO_FIELD_TTL),
+                    Err(feoxdb::FeoxError::KeyNotFound) => Ok(NO_SUCH_FIELD),
+                    Err(e) => Err(Error::Database(e)),
+                }
+            })
+            .collect()
+    }
+
+    /// Remove the TTL of each field, returning per field 1 if it had one,
+    /// -1 if it did not, or -2 if there is no such field
+    pub fn hpersist(&self, key: &[u8], fields: &[Vec<u8>]) -> Result<Vec<i64>> {
+        fields
+            .iter()
+            .map(|field| {
+                let field_key = Self::field_key(key, field);
+                if !self.field_live(&field_key) {
+                    return Ok(NO_SUCH_FIELD);
+                }
+                match self.store.get_ttl(&field_key) {
+                    Ok(Some(_)) => match self.store.persist(&field_key) {
+                        Ok(()) => Ok(1),
+                        Err(feoxdb::FeoxError::KeyNotFound) => Ok(NO_SUCH_FIELD),
+                        Err(e) => Err(Error::Database(e)),
+                    },
+                    Ok(None) | Err(feoxdb::FeoxError::TtlNotEnabled) => Ok(NO_FIELD_TTL),
+                    Err(feoxdb::FeoxError::KeyNotFound) => Ok(NO_SUCH_FIELD),
+                    Err(e) => Err(Error::Database(e)),
+                }
+            })
+            .collect()
     }
 
     pub fn hincrby(&self, key: &[u8], field: &[u8], delta: i64) -> Result<i64> {
@@ -278,7 +534,7 @@ impl HashOperations {
         meta_key.extend_from_slice(key);
         meta_key.extend_from_slice(b":meta");
 
-        let field_exists = self.store.contains_key(&field_key);
+        let field_exists = self.field_live(&field_key);
 
         let new_value = if field_exists {
             match self.store.get_bytes(&field_key) {
@@ -309,8 +565,7 @@ impl HashOperations {
                 Err(_) => delta,
             }
         } else {
-            GLOBAL_METADATA_TRACKER.write().unwrap().add_update(meta_key, 1);
-            self.maybe_flush_metadata();
+            self.track_fields(meta_key, 1);
             delta
         };
 
@@ -1,11 +1,41 @@
 use crate::error::{Error, Result};
 use bytes::Bytes;
 use feoxdb::FeoxStore;
-use std::sync::Arc;
+use once_cell::sync::Lazy;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 const INITIAL_POSITION: i64 = 1_000_000_000;
 const MAX_RETRIES: usize = 10;
 
+/// Locks serialising changes to lists, striped by key
+///
+/// A list is its metadata plus one record per position, which can't be
+/// updated together. Without the lock a pop could advance past a slot
+/// whose push had updated the metadata but not yet written the element,
+/// losing it and leaving LRANGE short of LLEN. Every operation that reads
+/// or writes elements holds the lock for its key, so positions between
+/// head and tail always hold an element.
+static LIST_LOCKS: Lazy<Vec<Mutex<()>>> = Lazy::new(|| (0..64).map(|_| Mutex::new(())).collect());
+
+fn lock_list(key: &[u8]) -> MutexGuard<'static, ()> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let stripe = hasher.finish() as usize % LIST_LOCKS.len();
+    // A panic elsewhere leaves nothing half-done that the lock protects
+    LIST_LOCKS[stripe]
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Metadata and elements of a list, read together for rewriting
+struct ListSnapshot {
+    meta: Bytes,
+    head: i64,
+    tail: i64,
+    values: Vec<Bytes>,
+}
+
 #[derive(Clone)]
 pub struct ListOperations {
     store: Arc<FeoxStore>,
@@ -35,6 +65,7 @@ impl ListOperations {
     }
 
     pub fn lpush(&self, key: &[u8], values: Vec<Bytes>) -> Result<i64> {
+        let _guard = lock_list(key);
         let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
 
         // Process values in order: LPUSH key a b c pushes a, then b, then c
@@ -100,6 +131,7 @@ impl ListOperations {
     }
 
     pub fn rpush(&self, key: &[u8], values: Vec<Bytes>) -> Result<i64> {
+        let _guard = lock_list(key);
         let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
 
         for value in values {
@@ -162,6 +194,7 @@ impl ListOperations {
     }
 
     pub fn lpop(&self, key: &[u8], count: Option<usize>) -> Result<Vec<Bytes>> {
+        let _guard = lock_list(key);
         let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
         let count = count.unwrap_or(1);
         let mut popped = Vec::new();
@@ -196,10 +229,7 @@ impl ListOperations {
                             popped.push(value);
                             break;
                         }
-                        Err(_) => {
-                            // Gap detected, continue to next position
-                            continue;
-                        }
+                        Err(_) => continue,
                     }
                 }
 
@@ -215,6 +245,7 @@ impl ListOperations {
     }
 
     pub fn rpop(&self, key: &[u8], count: Option<usize>) -> Result<Vec<Bytes>> {
+        let _guard = lock_list(key);
         let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
         let count = count.unwrap_or(1);
         let mut popped = Vec::new();
@@ -276,7 +307,31 @@ impl ListOperations {
         }
     }
 
+    /// Check if a non-empty list exists under this key
+    pub fn exists(&self, key: &[u8]) -> bool {
+        self.llen(key).map(|count| count > 0).unwrap_or(false)
+    }
+
+    /// Delete the list's metadata and every element, returning whether it
+    /// had any
+    pub fn delete(&self, key: &[u8]) -> Result<bool> {
+        let _guard = lock_list(key);
+        let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
+        let Ok(meta_bytes) = self.store.get_bytes(meta_key.as_bytes()) else {
+            return Ok(false);
+        };
+
+        let (head, tail, count) = Self::parse_metadata(&meta_bytes);
+        for pos in head..tail {
+            let value_key = format!("L:{}:{}", String::from_utf8_lossy(key), pos);
+            self.store.delete(value_key.as_bytes()).ok();
+        }
+        self.store.delete(meta_key.as_bytes()).ok();
+        Ok(count > 0)
+    }
+
     pub fn lrange(&self, key: &[u8], start: i64, stop: i64) -> Result<Vec<Bytes>> {
+        let _guard = lock_list(key);
         let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
 
         let meta_bytes = match self.store.get_bytes(meta_key.as_bytes()) {
@@ -322,13 +377,13 @@ impl ListOperations {
             if let Ok(value) = self.store.get_bytes(value_key.as_bytes()) {
                 results.push(value);
             }
-            // Skip gaps silently
         }
 
         Ok(results)
     }
 
     pub fn lindex(&self, key: &[u8], index: i64) -> Result<Option<Bytes>> {
+        let _guard = lock_list(key);
         let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
 
         let meta_bytes = match self.store.get_bytes(meta_key.as_bytes()) {
@@ -359,9 +414,197 @@ impl ListOperations {
         let pos = head + actual_index;
         let value_key = format!("L:{}:{}", String::from_utf8_lossy(key), pos);
 
-        match self.store.get_bytes(value_key.as_bytes()) {
-            Ok(value) => Ok(Some(value)),
-            Err(_) => Ok(None), // Gap
+        Ok(self.store.get_bytes(value_key.as_bytes()).ok())
+    }
+    pub fn lset(&self, key: &[u8], index: i64, value: Bytes) -> Result<bool> {
+        let _guard = lock_list(key);
+        let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
+
+        let meta_bytes = match self.store.get_bytes(meta_key.as_bytes()) {
+            Ok(bytes) => bytes,
+            Err(_) => return Ok(false),
+        };
+
+        let (head, tail, count) = Self::parse_metadata(&meta_bytes);
+
+        if count == 0 {
+            return Ok(false);
+        }
+
+        let len = tail - head;
+        let actual_index = if index < 0 { len + index } else { index };
+        if actual_index < 0 || actual_index >= len {
+            return Ok(false);
+        }
+
+        let pos = head + actual_index;
+        let value_key = format!("L:{}:{}", String::from_utf8_lossy(key), pos);
+        self.store.insert(value_key.as_bytes(), &value)?;
+        Ok(true)
+    }
+
+    /// Remove elements equal to `value`
+    ///
+    /// A positive count removes from head to tail, a negative count from
+    /// tail to head, and zero removes every match.
+    pub fn lrem(&self, key: &[u8], count: i64, value: &[u8]) -> Result<i64> {
+        let _guard = lock_list(key);
+        let mut retries = 0;
+        loop {
+            let Some(snapshot) = self.load(key)? else {
+                return Ok(0);
+            };
+            let mut values = snapshot.values.clone();
+
+            let limit = if count == 0 {
+                usize::MAX
+            } else {
+                count.unsigned_abs() as usize
+            };
+
+            let mut matches: Vec<usize> = values
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.as_ref() == value)
+                .map(|(i, _)| i)
+                .collect();
+            if count < 0 {
+                matches.reverse();
+            }
+            matches.truncate(limit);
+
+            if matches.is_empty() {
+                return Ok(0);
+            }
+
+            let removed = matches.len() as i64;
+            let first_changed = *matches.iter().min().unwrap();
+            matches.sort_unstable();
+            for i in matches.into_iter().rev() {
+                values.remove(i);
+            }
+
+            if self.rewrite(key, &snapshot, &values, first_changed)? {
+                return Ok(removed);
+            }
+
+            retries += 1;
+            if retries >= MAX_RETRIES {
+                return Err(Error::System(
+                    "Operation failed due to contention".to_string(),
+                ));
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// Insert `value` before or after the first element equal to `pivot`
+    ///
+    /// Positions are contiguous integer slots, so the elements after the
+    /// insertion point are shifted one slot towards the tail. Returns the new
+    /// length, -1 if the pivot was not found, or 0 if the list is missing.
+    pub fn linsert(&self, key: &[u8], before: bool, pivot: &[u8], value: Bytes) -> Result<i64> {
+        let _guard = lock_list(key);
+        let mut retries = 0;
+        loop {
+            let Some(snapshot) = self.load(key)? else {
+                return Ok(0);
+            };
+
+            let Some(pivot_index) = snapshot.values.iter().position(|v| v.as_ref() == pivot) else {
+                return Ok(-1);
+            };
+
+            let insert_at = if before { pivot_index } else { pivot_index + 1 };
+            let mut values = snapshot.values.clone();
+            values.insert(insert_at, value.clone());
+
+            if self.rewrite(key, &snapshot, &values, insert_at)? {
+                return Ok(values.len() as i64);
+            }
+
+            retries += 1;
+            if retries >= MAX_RETRIES {
+                return Err(Error::System(
+                    "Operation failed due to contention".to_string(),
+                ));
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// Read the metadata and every element of a non-empty list
+    fn load(&self, key: &[u8]) -> Result<Option<ListSnapshot>> {
+        let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
+
+        let meta_bytes = match self.store.get_bytes(meta_key.as_bytes()) {
+            Ok(bytes) => bytes,
+            Err(_) => return Ok(None),
+        };
+
+        let (head, tail, count) = Self::parse_metadata(&meta_bytes);
+
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let mut values = Vec::with_capacity(count as usize);
+        for pos in head..tail {
+            let value_key = format!("L:{}:{}", String::from_utf8_lossy(key), pos);
+            if let Ok(value) = self.store.get_bytes(value_key.as_bytes()) {
+                values.push(value);
+            }
         }
+
+        Ok(Some(ListSnapshot {
+            meta: meta_bytes,
+            head,
+            tail,
+            values,
+        }))
+    }
+
+    /// Replace the snapshot's contents with `values`, packed from its head
+    ///
+    /// Elements before `from` are assumed unchanged and are not rewritten.
+    /// Returns false if the metadata changed since the snapshot was taken.
+    fn rewrite(
+        &self,
+        key: &[u8],
+        snapshot: &ListSnapshot,
+        values: &[Bytes],
+        from: usize,
+    ) -> Result<bool> {
+        let meta_key = format!("L:{}:meta", String::from_utf8_lossy(key));
+        let head = snapshot.head;
+        let new_tail = head + values.len() as i64;
+
+        if values.is_empty() {
+            let current = self.store.get_bytes(meta_key.as_bytes())?;
+            if current != snapshot.meta {
+                return Ok(false);
+            }
+            self.store.delete(meta_key.as_bytes())?;
+        } else {
+            let new_meta = Self::build_metadata(head, new_tail, values.len() as u64);
+            if !self
+                .store
+                .compare_and_swap(meta_key.as_bytes(), &snapshot.meta, &new_meta)?
+            {
+                return Ok(false);
+            }
+        }
+
+        for (i, value) in values.iter().enumerate().skip(from) {
+            let value_key = format!("L:{}:{}", String::from_utf8_lossy(key), head + i as i64);
+            self.store.insert(value_key.as_bytes(), value)?;
+        }
+
+        for pos in new_tail..snapshot.tail {
+            let value_key = format!("L:{}:{}", String::from_utf8_lossy(key), pos);
+            self.store.delete(value_key.as_bytes()).ok();
+        }
+
+        Ok(true)
     }
 }
@@ -1,13 +1,17 @@
 use crate::protocol::resp::RespValue;
 use bytes::Bytes;
+use once_cell::sync::Lazy;
 
 mod client;
+mod cursor;
 mod executor;
 mod hash;
 mod list;
 mod parser;
+mod set;
+mod zset;
 
-pub use executor::CommandExecutor;
+pub use executor::{CommandExecutor, WRONGTYPE_ERROR};
 
 #[derive(Debug, Clone)]
 pub enum Command {
@@ -18,9 +22,87 @@ pub enum Command {
         value: Bytes,
         ex: Option<u64>,
         px: Option<u64>,
+        // Unix time in milliseconds, from EXAT or PXAT
+        pxat: Option<u64>,
+        nx: bool,
+        xx: bool,
+        keepttl: bool,
+        get: bool,
+    },
+    // Expiry checked when run, so a bad TTL is an error reply
+    SetEx {
+        key: Vec<u8>,
+        seconds: i64,
+        value: Bytes,
+    },
+    PSetEx {
+        key: Vec<u8>,
+        milliseconds: i64,
+        value: Bytes,
+    },
+    SetNx {
+        key: Vec<u8>,
+        value: Bytes,
+    },
+    GetSet {
+        key: Vec<u8>,
+        value: Bytes,
+    },
+    GetDel(Vec<u8>),
+    GetEx {
+        key: Vec<u8>,
+        ex: Option<u64>,
+        px: Option<u64>,
+        persist: bool,
+    },
+    Append {
+        key: Vec<u8>,
+        value: Bytes,
+    },
+    StrLen(Vec<u8>),
+    SetRange {
+        key: Vec<u8>,
+        offset: usize,
+        value: Bytes,
+    },
+    GetRange {
+        key: Vec<u8>,
+        start: i64,
+        end: i64,
+    },
+    SetBit {
+        key: Vec<u8>,
+        offset: u64,
+        value: bool,
+    },
+    GetBit {
+        key: Vec<u8>,
+        offset: u64,
+    },
+    BitCount {
+        key: Vec<u8>,
+        // Inclusive start and end, in bits when `in_bits` is set and bytes
+        // if not
+        range: Option<(i64, i64)>,
+        in_bits: bool,
+    },
+    BitPos {
+        key: Vec<u8>,
+        bit: bool,
+        start: Option<i64>,
+        end: Option<i64>,
+        in_bits: bool,
+    },
+    BitOp {
+        op: BitOperation,
+        destination: Vec<u8>,
+        keys: Vec<Vec<u8>>,
     },
     Del(Vec<Vec<u8>>),
     Exists(Vec<Vec<u8>>),
+    Unlink(Vec<Vec<u8>>),
+    Touch(Vec<Vec<u8>>),
+    Type(Vec<u8>),
 
     // Atomic operations
     Incr(Vec<u8>),
@@ -38,30 +120,90 @@ pub enum Command {
     Expire {
         key: Vec<u8>,
         seconds: u64,
+        condition: Option<ExpireCondition>,
     },
     PExpire {
         key: Vec<u8>,
         milliseconds: u64,
+        condition: Option<ExpireCondition>,
+    },
+    ExpireAt {
+        key: Vec<u8>,
+        timestamp: i64,
     },
+    PExpireAt {
+        key: Vec<u8>,
+        timestamp_ms: i64,
+    },
+    ExpireTime(Vec<u8>),
+    PExpireTime(Vec<u8>),
     Ttl(Vec<u8>),
     PTtl(Vec<u8>),
     Persist(Vec<u8>),
+    Copy {
+        src: Vec<u8>,
+        dst: Vec<u8>,
+        db: Option<usize>,
+        replace: bool,
+    },
 
     // Bulk operations
     MGet(Vec<Vec<u8>>),
     MSet(Vec<(Vec<u8>, Bytes)>),
+    MSetNx(Vec<(Vec<u8>, Bytes)>),
 
     // Server commands
     Ping(Option<Bytes>),
     Echo(Bytes),
+    Lolwut,
     Info(Option<String>),
     Config {
         action: String,
         args: Vec<Bytes>,
     },
-    Command,
+    Command {
+        subcommand: Option<String>,
+        args: Vec<Bytes>,
+    },
+    Debug {
+        subcommand: String,
+        args: Vec<Bytes>,
+    },
+    Object {
+        subcommand: String,
+        args: Vec<Bytes>,
+    },
+    SlowLog {
+        subcommand: String,
+        args: Vec<Bytes>,
+    },
     Quit,
+    Reset,
+    Monitor,
+    Wait {
+        numreplicas: i64,
+        timeout_ms: i64,
+    },
+    /// Cluster routing commands, accepted so that cluster-aware clients
+    /// fall back to standalone
+    Asking,
+    ReadOnly,
+    ReadWrite,
+    Cluster {
+        subcommand: String,
+        args: Vec<Bytes>,
+    },
+    Select(usize),
     FlushDb,
+    FlushAll,
+    DbSize,
+    /// Write a keyspace snapshot before replying
+    Save,
+    /// Write a keyspace snapshot on a background thread
+    BgSave,
+    LastSave,
+    /// Compact the append-only log on a background thread
+    BgRewriteAof,
 
     // Key scanning
     Keys(String), // Pattern
@@ -69,6 +211,7 @@ pub enum Command {
         cursor: Vec<u8>,
         count: usize,
         pattern: Option<String>,
+        key_type: Option<String>,
     },
 
     // FeOx-specific
@@ -83,7 +226,16 @@ pub enum Command {
     },
 
     // Authentication
-    Auth(Vec<u8>),
+    Auth {
+        // None for the one-argument form, which logs in as the default user
+        username: Option<Vec<u8>>,
+        password: Vec<u8>,
+    },
+    Hello {
+        proto: Option<u8>,
+        auth: Option<(Bytes, Bytes)>,
+        setname: Option<Bytes>,
+    },
 
     // List commands
     LPush {
@@ -112,6 +264,32 @@ pub enum Command {
         key: Vec<u8>,
         index: i64,
     },
+    LSet {
+        key: Vec<u8>,
+        index: i64,
+        value: Bytes,
+    },
+    LRem {
+        key: Vec<u8>,
+        count: i64,
+        value: Bytes,
+    },
+    LInsert {
+        key: Vec<u8>,
+        before: bool,
+        pivot: Bytes,
+        value: Bytes,
+    },
+    /// Pop from the first non-empty list, waiting up to `timeout` for one;
+    /// a zero timeout waits forever
+    BLPop {
+        keys: Vec<Vec<u8>>,
+        timeout: std::time::Duration,
+    },
+    BRPop {
+        keys: Vec<Vec<u8>>,
+        timeout: std::time::Duration,
+    },
 
     Subscribe(Vec<Vec<u8>>),
     Unsubscribe(Option<Vec<Vec<u8>>>),
@@ -125,6 +303,12 @@ pub enum Command {
         subcommand: String,
         args: Vec<Vec<u8>>,
     },
+    SSubscribe(Vec<Vec<u8>>),
+    SUnsubscribe(Option<Vec<Vec<u8>>>),
+    SPublish {
+        channel: Vec<u8>,
+        message: Vec<u8>,
+    },
 
     // Client management
     Client {
@@ -139,6 +323,26 @@ pub enum Command {
     Watch(Vec<Vec<u8>>),
     Unwatch,
 
+    // Scripting commands; script keys are scoped by the commands they run
+    Eval {
+        script: Bytes,
+        keys: Vec<Vec<u8>>,
+        args: Vec<Bytes>,
+    },
+    EvalSha {
+        sha1: String,
+        keys: Vec<Vec<u8>>,
+        args: Vec<Bytes>,
+    },
+    Script {
+        subcommand: String,
+        args: Vec<Bytes>,
+    },
+    Acl {
+        subcommand: String,
+        args: Vec<Bytes>,
+    },
+
     // Hash commands
     HSet {
         key: Vec<u8>,
@@ -160,7 +364,22 @@ pub enum Command {
         key: Vec<u8>,
         field: Vec<u8>,
     },
+    HSetNx {
+        key: Vec<u8>,
+        field: Vec<u8>,
+        value: Bytes,
+    },
+    HStrLen {
+        key: Vec<u8>,
+        field: Vec<u8>,
+    },
     HGetAll(Vec<u8>),
+    HScan {
+        key: Vec<u8>,
+        cursor: Vec<u8>,
+        count: usize,
+        pattern: Option<String>,
+    },
     HLen(Vec<u8>),
     HKeys(Vec<u8>),
     HVals(Vec<u8>),
@@ -169,9 +388,600 @@ pub enum Command {
         field: Vec<u8>,
         delta: i64,
     },
+    HExpire {
+        key: Vec<u8>,
+        seconds: u64,
+        condition: Option<ExpireCondition>,
+        fields: Vec<Vec<u8>>,
+    },
+    HPExpire {
+        key: Vec<u8>,
+        milliseconds: u64,
+        condition: Option<ExpireCondition>,
+        fields: Vec<Vec<u8>>,
+    },
+    HTtl {
+        key: Vec<u8>,
+        fields: Vec<Vec<u8>>,
+    },
+    HPersist {
+        key: Vec<u8>,
+        fields: Vec<Vec<u8>>,
+    },
+
+    // Set commands
+    SAdd {
+        key: Vec<u8>,
+        members: Vec<Vec<u8>>,
+    },
+    SRem {
+        key: Vec<u8>,
+        members: Vec<Vec<u8>>,
+    },
+    SMembers(Vec<u8>),
+    SIsMember {
+        key: Vec<u8>,
+        member: Vec<u8>,
+    },
+    SMIsMember {
+        key: Vec<u8>,
+        members: Vec<Vec<u8>>,
+    },
+    SCard(Vec<u8>),
+    SPop {
+        key: Vec<u8>,
+        count: Option<usize>,
+    },
+    SUnion(Vec<Vec<u8>>),
+    SInter(Vec<Vec<u8>>),
+    SDiff(Vec<Vec<u8>>),
+    // A limit of 0 counts the whole intersection
+    SInterCard {
+        keys: Vec<Vec<u8>>,
+        limit: usize,
+    },
+    SUnionStore {
+        destination: Vec<u8>,
+        keys: Vec<Vec<u8>>,
+    },
+    SInterStore {
+        destination: Vec<u8>,
+        keys: Vec<Vec<u8>>,
+    },
+    SDiffStore {
+        destination: Vec<u8>,
+        keys: Vec<Vec<u8>>,
+    },
+
+    // Sorted set commands
+    ZAdd {
+        key: Vec<u8>,
+        options: ZAddOptions,
+        members: Vec<(f64, Vec<u8>)>,
+    },
+    ZScore {
+        key: Vec<u8>,
+        member: Vec<u8>,
+    },
+    ZIncrBy {
+        key: Vec<u8>,
+        delta: f64,
+        member: Vec<u8>,
+    },
+    ZCard(Vec<u8>),
+    ZRem {
+        key: Vec<u8>,
+        members: Vec<Vec<u8>>,
+    },
+    ZRange {
+        key: Vec<u8>,
+        start: i64,
+        stop: i64,
+        with_scores: bool,
+    },
+    ZRangeByScore {
+        key: Vec<u8>,
+        min: ScoreBound,
+        max: ScoreBound,
+        with_scores: bool,
+        limit: Option<(i64, i64)>,
+    },
+    ZRank {
+        key: Vec<u8>,
+        member: Vec<u8>,
+    },
+}
+
+/// One end of a ZRANGEBYSCORE interval
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreBound {
+    pub value: f64,
+    /// Set for `(` bounds that exclude the value itself
+    pub exclusive: bool,
+}
+
+/// Update conditions accepted by ZADD
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZAddOptions {
+    /// Only add new members
+    pub nx: bool,
+    /// Only update existing members
+    pub xx: bool,
+    /// Only update when the new score is greater
+    pub gt: bool,
+    /// Only update when the new score is less
+    pub lt: bool,
+    /// Count changed members instead of added ones
+    pub ch: bool,
+}
+
+/// Prefix that namespaces keys of a logical database in the shared store
+///
+/// Database 0 is stored unprefixed so existing data and the fast path are
+/// unaffected; every other database `n` lives under `db{n}:`.
+pub fn db_key_prefix(db: usize) -> Vec<u8> {
+    if db == 0 {
+        Vec::new()
+    } else {
+        format!("db{}:", db).into_bytes()
+    }
+}
+
+/// Database a logical key belongs to, given the configured database count
+pub fn db_of_key(key: &[u8], databases: usize) -> usize {
+    let Some(rest) = key.strip_prefix(b"db") else {
+        return 0;
+    };
+    let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 || rest.get(digits) != Some(&b':') || rest[0] == b'0' {
+        return 0;
+    }
+    match std::str::from_utf8(&rest[..digits])
+        .ok()
+        .and_then(|d| d.parse().ok())
+    {
+        Some(db) if db < databases => db,
+        _ => 0,
+    }
+}
+
+/// Record holding the deadline of a collection given a TTL
+///
+/// A collection spans many records, which cannot share one FeoxDB TTL, so
+/// its expiry is kept under `X:{key}` as Unix time in milliseconds. It is
+/// enforced when a command names the key, and by a periodic sweep.
+fn deadline_key(key: &[u8]) -> Vec<u8> {
+    [&b"X:"[..], key].concat()
+}
+
+/// Unix time in milliseconds a collection expires at, if it has a TTL
+pub fn collection_deadline(store: &feoxdb::FeoxStore, key: &[u8]) -> Option<u64> {
+    let value = store.get_bytes(&deadline_key(key)).ok()?;
+    Some(u64::from_le_bytes(value.as_ref().try_into().ok()?))
+}
+
+/// Delete every record whose key starts with `prefix`, in batches
+///
+/// Collections keep their elements under a shared prefix, so this drops all
+/// of one collection's element records.
+fn delete_prefixed(store: &feoxdb::FeoxStore, prefix: &[u8]) -> crate::error::Result<()> {
+    const BATCH: usize = 10000;
+
+    let mut start_key = prefix.to_vec();
+    let mut end_key = prefix.to_vec();
+    end_key.extend_from_slice(&[0xFF; 255]);
+    loop {
+        let batch = store.range_query(&start_key, &end_key, BATCH)?;
+        for (key, _) in &batch {
+            // A concurrent write may have removed it already
+            if key.starts_with(prefix) {
+                store.delete(key).ok();
+            }
+        }
+
+        match batch.last() {
+            Some((last, _)) if batch.len() == BATCH => {
+                start_key = last.clone();
+                start_key.push(0);
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Operation BITOP applies across its source strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    /// Takes a single source
+    Not,
+}
+
+/// Condition flag accepted by EXPIRE, PEXPIRE, HEXPIRE and HPEXPIRE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpireCondition {
+    /// Only set when the key has no expiry
+    Nx,
+    /// Only set when the key already has an expiry
+    Xx,
+    /// Only set when the new expiry is greater than the current one
+    Gt,
+    /// Only set when the new expiry is less than the current one
+    Lt,
+}
+
+/// Lowercase command names, in the order of `Command::index`
+pub const COMMAND_NAMES: [&str; 140] = [
+    "get",
+    "set",
+    "getset",
+    "getdel",
+    "getex",
+    "append",
+    "strlen",
+    "setrange",
+    "getrange",
+    "del",
+    "exists",
+    "unlink",
+    "touch",
+    "type",
+    "incr",
+    "incrby",
+    "decr",
+    "decrby",
+    "expire",
+    "pexpire",
+    "expireat",
+    "pexpireat",
+    "expiretime",
+    "pexpiretime",
+    "ttl",
+    "pttl",
+    "persist",
+    "mget",
+    "mset",
+    "ping",
+    "echo",
+    "info",
+    "config",
+    "command",
+    "debug",
+    "slowlog",
+    "quit",
+    "monitor",
+    "select",
+    "flushdb",
+    "flushall",
+    "dbsize",
+    "keys",
+    "scan",
+    "jsonpatch",
+    "cas",
+    "auth",
+    "hello",
+    "lpush",
+    "rpush",
+    "lpop",
+    "rpop",
+    "llen",
+    "lrange",
+    "lindex",
+    "lset",
+    "lrem",
+    "linsert",
+    "subscribe",
+    "unsubscribe",
+    "psubscribe",
+    "punsubscribe",
+    "publish",
+    "pubsub",
+    "client",
+    "multi",
+    "exec",
+    "discard",
+    "watch",
+    "unwatch",
+    "hset",
+    "hget",
+    "hmget",
+    "hdel",
+    "hexists",
+    "hsetnx",
+    "hstrlen",
+    "hgetall",
+    "hscan",
+    "hlen",
+    "hkeys",
+    "hvals",
+    "hincrby",
+    "sadd",
+    "srem",
+    "smembers",
+    "sismember",
+    "scard",
+    "spop",
+    "sunion",
+    "sinter",
+    "sdiff",
+    "sunionstore",
+    "sinterstore",
+    "sdiffstore",
+    "zadd",
+    "zscore",
+    "zincrby",
+    "zcard",
+    "zrem",
+    "zrange",
+    "zrangebyscore",
+    "zrank",
+    "wait",
+    "msetnx",
+    "copy",
+    "ssubscribe",
+    "sunsubscribe",
+    "spublish",
+    "blpop",
+    "brpop",
+    "save",
+    "bgsave",
+    "lastsave",
+    "bgrewriteaof",
+    "eval",
+    "evalsha",
+    "script",
+    "acl",
+    "reset",
+    "lolwut",
+    "setex",
+    "psetex",
+    "setnx",
+    "setbit",
+    "getbit",
+    "bitcount",
+    "bitpos",
+    "bitop",
+    "object",
+    "smismember",
+    "sintercard",
+    "hexpire",
+    "hpexpire",
+    "httl",
+    "hpersist",
+    "asking",
+    "readonly",
+    "readwrite",
+    "cluster",
+];
+
+/// Number of distinct commands, for tables indexed by `Command::index`
+pub const COMMAND_COUNT: usize = COMMAND_NAMES.len();
+
+/// Commands that can change the keyspace, making up the `@write` ACL
+/// category and what goes in the append-only log
+const WRITE_COMMANDS: [&str; 54] = [
+    "set",
+    "setex",
+    "psetex",
+    "setnx",
+    "getset",
+    "getdel",
+    "getex",
+    "append",
+    "setrange",
+    "setbit",
+    "bitop",
+    "del",
+    "unlink",
+    "incr",
+    "incrby",
+    "decr",
+    "decrby",
+    "expire",
+    "pexpire",
+    "expireat",
+    "pexpireat",
+    "persist",
+    "copy",
+    "mset",
+    "msetnx",
+    "flushdb",
+    "flushall",
+    "jsonpatch",
+    "cas",
+    "lpush",
+    "rpush",
+    "lpop",
+    "rpop",
+    "lset",
+    "lrem",
+    "linsert",
+    "blpop",
+    "brpop",
+    "hset",
+    "hdel",
+    "hsetnx",
+    "hincrby",
+    "hexpire",
+    "hpexpire",
+    "hpersist",
+    "sadd",
+    "srem",
+    "spop",
+    "sunionstore",
+    "sinterstore",
+    "sdiffstore",
+    "zadd",
+    "zincrby",
+    "zrem",
+];
+
+static IS_WRITE: Lazy<[bool; COMMAND_COUNT]> =
+    Lazy::new(|| COMMAND_NAMES.map(|name| WRITE_COMMANDS.contains(&name)));
+
+/// Check if the command at `index` in `COMMAND_NAMES` is a write command
+pub fn is_write_command(index: usize) -> bool {
+    IS_WRITE[index]
 }
 
 impl Command {
+    /// `index` of GET and SET, for callers that skip building a `Command`
+    pub const GET_INDEX: usize = 0;
+    pub const SET_INDEX: usize = 1;
+
+    /// Position of this command in `COMMAND_NAMES`
+    pub fn index(&self) -> usize {
+        match self {
+            Command::Get(..) => 0,
+            Command::Set { .. } => 1,
+            Command::GetSet { .. } => 2,
+            Command::GetDel(..) => 3,
+            Command::GetEx { .. } => 4,
+            Command::Append { .. } => 5,
+            Command::StrLen(..) => 6,
+            Command::SetRange { .. } => 7,
+            Command::GetRange { .. } => 8,
+            Command::Del(..) => 9,
+            Command::Exists(..) => 10,
+            Command::Unlink(..) => 11,
+            Command::Touch(..) => 12,
+            Command::Type(..) => 13,
+            Command::Incr(..) => 14,
+            Command::IncrBy { .. } => 15,
+            Command::Decr(..) => 16,
+            Command::DecrBy { .. } => 17,
+            Command::Expire { .. } => 18,
+            Command::PExpire { .. } => 19,
+            Command::ExpireAt { .. } => 20,
+            Command::PExpireAt { .. } => 21,
+            Command::ExpireTime(..) => 22,
+            Command::PExpireTime(..) => 23,
+            Command::Ttl(..) => 24,
+            Command::PTtl(..) => 25,
+            Command::Persist(..) => 26,
+            Command::MGet(..) => 27,
+            Command::MSet(..) => 28,
+            Command::Ping(..) => 29,
+            Command::Echo(..) => 30,
+            Command::Info(..) => 31,
+            Command::Config { .. } => 32,
+            Command::Command { .. } => 33,
+            Command::Debug { .. } => 34,
+            Command::SlowLog { .. } => 35,
+            Command::Quit => 36,
+            Command::Monitor => 37,
+            Command::Select(..) => 38,
+            Command::FlushDb => 39,
+            Command::FlushAll => 40,
+            Command::DbSize => 41,
+            Command::Keys(..) => 42,
+            Command::Scan { .. } => 43,
+            Command::JsonPatch { .. } => 44,
+            Command::Cas { .. } => 45,
+            Command::Auth { .. } => 46,
+            Command::Hello { .. } => 47,
+            Command::LPush { .. } => 48,
+            Command::RPush { .. } => 49,
+            Command::LPop { .. } => 50,
+            Command::RPop { .. } => 51,
+            Command::LLen(..) => 52,
+            Command::LRange { .. } => 53,
+            Command::LIndex { .. } => 54,
+            Command::LSet { .. } => 55,
+            Command::LRem { .. } => 56,
+            Command::LInsert { .. } => 57,
+            Command::Subscribe(..) => 58,
+            Command::Unsubscribe(..) => 59,
+            Command::PSubscribe(..) => 60,
+            Command::PUnsubscribe(..) => 61,
+            Command::Publish { .. } => 62,
+            Command::PubSub { .. } => 63,
+            Command::Client { .. } => 64,
+            Command::Multi => 65,
+            Command::Exec => 66,
+            Command::Discard => 67,
+            Command::Watch(..) => 68,
+            Command::Unwatch => 69,
+            Command::HSet { .. } => 70,
+            Command::HGet { .. } => 71,
+            Command::HMGet { .. } => 72,
+            Command::HDel { .. } => 73,
+            Command::HExists { .. } => 74,
+            Command::HSetNx { .. } => 75,
+            Command::HStrLen { .. } => 76,
+            Command::HGetAll(..) => 77,
+            Command::HScan { .. } => 78,
+            Command::HLen(..) => 79,
+            Command::HKeys(..) => 80,
+            Command::HVals(..) => 81,
+            Command::HIncrBy { .. } => 82,
+            Command::SAdd { .. } => 83,
+            Command::SRem { .. } => 84,
+            Command::SMembers(..) => 85,
+            Command::SIsMember { .. } => 86,
+            Command::SCard(..) => 87,
+            Command::SPop { .. } => 88,
+            Command::SUnion(..) => 89,
+            Command::SInter(..) => 90,
+            Command::SDiff(..) => 91,
+            Command::SUnionStore { .. } => 92,
+            Command::SInterStore { .. } => 93,
+            Command::SDiffStore { .. } => 94,
+            Command::ZAdd { .. } => 95,
+            Command::ZScore { .. } => 96,
+            Command::ZIncrBy { .. } => 97,
+            Command::ZCard(..) => 98,
+            Command::ZRem { .. } => 99,
+            Command::ZRange { .. } => 100,
+            Command::ZRangeByScore { .. } => 101,
+            Command::ZRank { .. } => 102,
+            Command::Wait { .. } => 103,
+            Command::MSetNx(..) => 104,
+            Command::Copy { .. } => 105,
+            Command::SSubscribe(..) => 106,
+            Command::SUnsubscribe(..) => 107,
+            Command::SPublish { .. } => 108,
+            Command::BLPop { .. } => 109,
+            Command::BRPop { .. } => 110,
+            Command::Save => 111,
+            Command::BgSave => 112,
+            Command::LastSave => 113,
+            Command::BgRewriteAof => 114,
+            Command::Eval { .. } => 115,
+            Command::EvalSha { .. } => 116,
+            Command::Script { .. } => 117,
+            Command::Acl { .. } => 118,
+            Command::Reset => 119,
+            Command::Lolwut => 120,
+            Command::SetEx { .. } => 121,
+            Command::PSetEx { .. } => 122,
+            Command::SetNx { .. } => 123,
+            Command::SetBit { .. } => 124,
+            Command::GetBit { .. } => 125,
+            Command::BitCount { .. } => 126,
+            Command::BitPos { .. } => 127,
+            Command::BitOp { .. } => 128,
+            Command::Object { .. } => 129,
+            Command::SMIsMember { .. } => 130,
+            Command::SInterCard { .. } => 131,
+            Command::HExpire { .. } => 132,
+            Command::HPExpire { .. } => 133,
+            Command::HTtl { .. } => 134,
+            Command::HPersist { .. } => 135,
+            Command::Asking => 136,
+            Command::ReadOnly => 137,
+            Command::ReadWrite => 138,
+            Command::Cluster { .. } => 139,
+        }
+    }
+
+    /// Lowercase command name, as used in INFO commandstats
+    pub fn name(&self) -> &'static str {
+        COMMAND_NAMES[self.index()]
+    }
+
     /// Parse command from RESP array
     #[inline(always)]
     pub fn from_resp(value: RespValue) -> Result<Self, String> {
@@ -188,9 +998,24 @@ impl Command {
                 | Command::PUnsubscribe(_)
                 | Command::Publish { .. }
                 | Command::PubSub { .. }
+                | Command::SSubscribe(_)
+                | Command::SUnsubscribe(_)
+                | Command::SPublish { .. }
         )
     }
 
+    /// Check if this command can be queued inside MULTI; the rest change
+    /// connection state that EXEC cannot run through the executor
+    pub fn is_allowed_in_transaction(&self) -> bool {
+        !(self.is_pubsub_command() || matches!(self, Command::Monitor | Command::Auth { .. }))
+    }
+
+    /// Check if this command can change the keyspace, and so goes in the
+    /// append-only log
+    pub fn is_write(&self) -> bool {
+        is_write_command(self.index())
+    }
+
     /// Check if this command is allowed in pub/sub mode
     pub fn is_allowed_in_pubsub_mode(&self) -> bool {
         matches!(
@@ -199,11 +1024,129 @@ impl Command {
                 | Command::Unsubscribe(_)
                 | Command::PSubscribe(_)
                 | Command::PUnsubscribe(_)
+                | Command::SSubscribe(_)
+                | Command::SUnsubscribe(_)
                 | Command::Ping(_)
                 | Command::Quit
+                | Command::Reset
         )
     }
 
+    /// Mutable references to every key this command operates on
+    ///
+    /// Channel names, patterns and hash/set members are not keys. KEYS and
+    /// SCAN take patterns and are scoped by the executor instead.
+    pub fn keys_mut(&mut self) -> Vec<&mut Vec<u8>> {
+        match self {
+            Command::Get(key)
+            | Command::GetDel(key)
+            | Command::StrLen(key)
+            | Command::Type(key)
+            | Command::Incr(key)
+            | Command::Decr(key)
+            | Command::ExpireTime(key)
+            | Command::PExpireTime(key)
+            | Command::Ttl(key)
+            | Command::PTtl(key)
+            | Command::Persist(key)
+            | Command::LLen(key)
+            | Command::HGetAll(key)
+            | Command::HLen(key)
+            | Command::HKeys(key)
+            | Command::HVals(key)
+            | Command::SMembers(key)
+            | Command::SCard(key)
+            | Command::ZCard(key)
+            | Command::Set { key, .. }
+            | Command::SetEx { key, .. }
+            | Command::PSetEx { key, .. }
+            | Command::SetNx { key, .. }
+            | Command::GetSet { key, .. }
+            | Command::GetEx { key, .. }
+            | Command::Append { key, .. }
+            | Command::SetRange { key, .. }
+            | Command::GetRange { key, .. }
+            | Command::SetBit { key, .. }
+            | Command::GetBit { key, .. }
+            | Command::BitCount { key, .. }
+            | Command::BitPos { key, .. }
+            | Command::IncrBy { key, .. }
+            | Command::DecrBy { key, .. }
+            | Command::Expire { key, .. }
+            | Command::PExpire { key, .. }
+            | Command::ExpireAt { key, .. }
+            | Command::PExpireAt { key, .. }
+            | Command::JsonPatch { key, .. }
+            | Command::Cas { key, .. }
+            | Command::LPush { key, .. }
+            | Command::RPush { key, .. }
+            | Command::LPop { key, .. }
+            | Command::RPop { key, .. }
+            | Command::LRange { key, .. }
+            | Command::LIndex { key, .. }
+            | Command::LSet { key, .. }
+            | Command::LRem { key, .. }
+            | Command::LInsert { key, .. }
+            | Command::HSet { key, .. }
+            | Command::HGet { key, .. }
+            | Command::HMGet { key, .. }
+            | Command::HDel { key, .. }
+            | Command::HExists { key, .. }
+            | Command::HSetNx { key, .. }
+            | Command::HExpire { key, .. }
+            | Command::HPExpire { key, .. }
+            | Command::HTtl { key, .. }
+            | Command::HPersist { key, .. }
+            | Command::HStrLen { key, .. }
+            | Command::HScan { key, .. }
+            | Command::HIncrBy { key, .. }
+            | Command::SAdd { key, .. }
+            | Command::SRem { key, .. }
+            | Command::SIsMember { key, .. }
+            | Command::SMIsMember { key, .. }
+            | Command::SPop { key, .. }
+            | Command::ZAdd { key, .. }
+            | Command::ZScore { key, .. }
+            | Command::ZIncrBy { key, .. }
+            | Command::ZRem { key, .. }
+            | Command::ZRange { key, .. }
+            | Command::ZRangeByScore { key, .. }
+            | Command::ZRank { key, .. } => vec![key],
+
+            Command::Del(keys)
+            | Command::Exists(keys)
+            | Command::Unlink(keys)
+            | Command::Touch(keys)
+            | Command::MGet(keys)
+            | Command::Watch(keys)
+            | Command::SUnion(keys)
+            | Command::SInter(keys)
+            | Command::SDiff(keys)
+            | Command::SInterCard { keys, .. }
+            | Command::BLPop { keys, .. }
+            | Command::BRPop { keys, .. } => keys.iter_mut().collect(),
+
+            Command::MSet(pairs) | Command::MSetNx(pairs) => {
+                pairs.iter_mut().map(|(key, _)| key).collect()
+            }
+
+            Command::Copy { src, dst, .. } => vec![src, dst],
+
+            Command::SUnionStore { destination, keys }
+            | Command::BitOp {
+                destination, keys, ..
+            }
+            | Command::SInterStore { destination, keys }
+            | Command::SDiffStore { destination, keys } => {
+                let mut all = vec![destination];
+                all.extend(keys.iter_mut());
+                all
+            }
+
+            _ => vec![],
+        }
+    }
+
     /// Convert to PubSubOp if this is a pub/sub command
     pub fn to_pubsub_op(self) -> Option<crate::network::PubSubOp> {
         match self {
@@ -216,12 +1159,25 @@ impl Command {
             Command::Publish { channel, message } => {
                 Some(crate::network::PubSubOp::Publish { channel, message })
             }
+            Command::SSubscribe(channels) => Some(crate::network::PubSubOp::SSubscribe(channels)),
+            Command::SUnsubscribe(channels) => {
+                Some(crate::network::PubSubOp::SUnsubscribe(channels))
+            }
+            Command::SPublish { channel, message } => {
+                Some(crate::network::PubSubOp::SPublish { channel, message })
+            }
             Command::PubSub { subcommand, args } => match subcommand.to_uppercase().as_str() {
                 "CHANNELS" => Some(crate::network::PubSubOp::PubSubChannels {
                     pattern: args.first().cloned(),
                 }),
                 "NUMSUB" => Some(crate::network::PubSubOp::PubSubNumSub { channels: args }),
                 "NUMPAT" => Some(crate::network::PubSubOp::PubSubNumPat),
+                "SHARDCHANNELS" => Some(crate::network::PubSubOp::PubSubShardChannels {
+                    pattern: args.first().cloned(),
+                }),
+                "SHARDNUMSUB" => {
+                    Some(crate::network::PubSubOp::PubSubShardNumSub { channels: args })
+                }
                 _ => None,
             },
             _ => None,
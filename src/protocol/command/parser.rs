@@ -1,4 +1,4 @@
-use super::Command;
+use super::{BitOperation, Command, ExpireCondition, ScoreBound, ZAddOptions};
 use crate::protocol::resp::RespValue;
 use bytes::Bytes;
 
@@ -32,9 +32,16 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     let key = extract_bytes(&args[0])?.to_vec();
                     let value = extract_bytes(&args[1])?;
 
-                    // Parse optional arguments (EX, PX, etc.)
+                    // Parse optional arguments (EX, PX, EXAT, PXAT, NX, XX,
+                    // KEEPTTL, GET)
                     let mut ex = None;
                     let mut px = None;
+                    let mut exat = None;
+                    let mut pxat = None;
+                    let mut nx = false;
+                    let mut xx = false;
+                    let mut keepttl = false;
+                    let mut get = false;
                     let mut i = 2;
 
                     while i < args.len() {
@@ -42,19 +49,320 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                         let opt_upper = opt.to_ascii_uppercase();
 
                         match &opt_upper[..] {
-                            b"EX" if i + 1 < args.len() => {
-                                ex = Some(extract_integer(&args[i + 1])? as u64);
+                            b"EX" | b"PX" | b"EXAT" | b"PXAT" if i + 1 < args.len() => {
+                                let time = match extract_integer(&args[i + 1])? {
+                                    time if time > 0 => Some(time as u64),
+                                    _ => {
+                                        return Err(
+                                            "invalid expire time in 'set' command".to_string()
+                                        )
+                                    }
+                                };
+                                match &opt_upper[..] {
+                                    b"EX" => ex = time,
+                                    b"PX" => px = time,
+                                    b"EXAT" => exat = time,
+                                    _ => pxat = time,
+                                }
                                 i += 2;
                             }
-                            b"PX" if i + 1 < args.len() => {
-                                px = Some(extract_integer(&args[i + 1])? as u64);
+                            b"NX" => {
+                                nx = true;
+                                i += 1;
+                            }
+                            b"XX" => {
+                                xx = true;
+                                i += 1;
+                            }
+                            b"KEEPTTL" => {
+                                keepttl = true;
+                                i += 1;
+                            }
+                            b"GET" => {
+                                get = true;
+                                i += 1;
+                            }
+                            _ => return Err("syntax error".to_string()),
+                        }
+                    }
+
+                    // Conditions and expiry options are mutually exclusive
+                    let expiry_opts = ex.is_some() as u8
+                        + px.is_some() as u8
+                        + exat.is_some() as u8
+                        + pxat.is_some() as u8
+                        + keepttl as u8;
+                    if (nx && xx) || expiry_opts > 1 {
+                        return Err("syntax error".to_string());
+                    }
+
+                    Ok(Command::Set {
+                        key,
+                        value,
+                        ex,
+                        px,
+                        pxat: exat.map(|secs: u64| secs.saturating_mul(1000)).or(pxat),
+                        nx,
+                        xx,
+                        keepttl,
+                        get,
+                    })
+                }
+
+                b"SETEX" | b"PSETEX" => {
+                    if args.len() != 3 {
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper)
+                        ));
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let ttl = extract_integer(&args[1])?;
+                    let value = extract_bytes(&args[2])?;
+                    Ok(if cmd_upper.as_slice() == b"SETEX" {
+                        Command::SetEx {
+                            key,
+                            seconds: ttl,
+                            value,
+                        }
+                    } else {
+                        Command::PSetEx {
+                            key,
+                            milliseconds: ttl,
+                            value,
+                        }
+                    })
+                }
+
+                b"SETNX" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'SETNX' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let value = extract_bytes(&args[1])?;
+                    Ok(Command::SetNx { key, value })
+                }
+
+                b"GETSET" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'GETSET' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let value = extract_bytes(&args[1])?;
+                    Ok(Command::GetSet { key, value })
+                }
+
+                b"GETDEL" => {
+                    if args.len() != 1 {
+                        return Err("wrong number of arguments for 'GETDEL' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::GetDel(key))
+                }
+
+                b"GETEX" => {
+                    // GETEX key [EX seconds | PX milliseconds | PERSIST]
+                    if args.is_empty() {
+                        return Err("wrong number of arguments for 'GETEX' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let mut ex = None;
+                    let mut px = None;
+                    let mut persist = false;
+                    let mut i = 1;
+
+                    while i < args.len() {
+                        let opt = extract_bytes(&args[i])?;
+                        let opt_upper = opt.to_ascii_uppercase();
+
+                        match &opt_upper[..] {
+                            b"EX" | b"PX" if i + 1 < args.len() => {
+                                let time = match extract_integer(&args[i + 1])? {
+                                    time if time > 0 => Some(time as u64),
+                                    _ => {
+                                        return Err(
+                                            "invalid expire time in 'getex' command".to_string()
+                                        )
+                                    }
+                                };
+                                match &opt_upper[..] {
+                                    b"EX" => ex = time,
+                                    _ => px = time,
+                                }
                                 i += 2;
                             }
-                            _ => i += 1,
+                            b"PERSIST" => {
+                                persist = true;
+                                i += 1;
+                            }
+                            _ => return Err("syntax error".to_string()),
+                        }
+                    }
+
+                    if ex.is_some() as u8 + px.is_some() as u8 + persist as u8 > 1 {
+                        return Err("syntax error".to_string());
+                    }
+
+                    Ok(Command::GetEx {
+                        key,
+                        ex,
+                        px,
+                        persist,
+                    })
+                }
+
+                b"APPEND" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'APPEND' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let value = extract_bytes(&args[1])?;
+                    Ok(Command::Append { key, value })
+                }
+
+                b"STRLEN" => {
+                    if args.len() != 1 {
+                        return Err("wrong number of arguments for 'STRLEN' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::StrLen(key))
+                }
+
+                b"SETRANGE" => {
+                    if args.len() != 3 {
+                        return Err("wrong number of arguments for 'SETRANGE' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let offset = extract_integer(&args[1])?;
+                    if offset < 0 {
+                        return Err("offset is out of range".to_string());
+                    }
+                    let value = extract_bytes(&args[2])?;
+                    Ok(Command::SetRange {
+                        key,
+                        offset: offset as usize,
+                        value,
+                    })
+                }
+
+                b"GETRANGE" => {
+                    if args.len() != 3 {
+                        return Err("wrong number of arguments for 'GETRANGE' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let start = extract_integer(&args[1])?;
+                    let end = extract_integer(&args[2])?;
+                    Ok(Command::GetRange { key, start, end })
+                }
+
+                b"SETBIT" => {
+                    if args.len() != 3 {
+                        return Err("wrong number of arguments for 'SETBIT' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let offset = extract_bit_offset(&args[1])?;
+                    let value = match &extract_bytes(&args[2])?[..] {
+                        b"0" => false,
+                        b"1" => true,
+                        _ => return Err("bit is not an integer or out of range".to_string()),
+                    };
+                    Ok(Command::SetBit { key, offset, value })
+                }
+
+                b"GETBIT" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'GETBIT' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let offset = extract_bit_offset(&args[1])?;
+                    Ok(Command::GetBit { key, offset })
+                }
+
+                b"BITCOUNT" => {
+                    // BITCOUNT key [start end [BYTE|BIT]]
+                    let (range, in_bits) = match args.len() {
+                        1 => (None, false),
+                        3 | 4 => {
+                            let start = extract_integer(&args[1])?;
+                            let end = extract_integer(&args[2])?;
+                            let in_bits = match args.get(3).map(extract_bytes).transpose()? {
+                                None => false,
+                                Some(unit) if unit.eq_ignore_ascii_case(b"BYTE") => false,
+                                Some(unit) if unit.eq_ignore_ascii_case(b"BIT") => true,
+                                Some(_) => return Err("syntax error".to_string()),
+                            };
+                            (Some((start, end)), in_bits)
+                        }
+                        2 => return Err("syntax error".to_string()),
+                        _ => {
+                            return Err(
+                                "wrong number of arguments for 'BITCOUNT' command".to_string()
+                            )
                         }
+                    };
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::BitCount {
+                        key,
+                        range,
+                        in_bits,
+                    })
+                }
+
+                b"BITPOS" => {
+                    // BITPOS key bit [start [end [BYTE|BIT]]]
+                    if args.len() < 2 || args.len() > 5 {
+                        return Err("wrong number of arguments for 'BITPOS' command".to_string());
                     }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let bit = match &extract_bytes(&args[1])?[..] {
+                        b"0" => false,
+                        b"1" => true,
+                        _ => return Err("The bit argument must be 1 or 0.".to_string()),
+                    };
+                    let start = args.get(2).map(extract_integer).transpose()?;
+                    let end = args.get(3).map(extract_integer).transpose()?;
+                    let in_bits = match args.get(4).map(extract_bytes).transpose()? {
+                        None => false,
+                        Some(unit) if unit.eq_ignore_ascii_case(b"BYTE") => false,
+                        Some(unit) if unit.eq_ignore_ascii_case(b"BIT") => true,
+                        Some(_) => return Err("syntax error".to_string()),
+                    };
+                    Ok(Command::BitPos {
+                        key,
+                        bit,
+                        start,
+                        end,
+                        in_bits,
+                    })
+                }
 
-                    Ok(Command::Set { key, value, ex, px })
+                b"BITOP" => {
+                    // BITOP AND|OR|XOR|NOT destkey key [key ...]
+                    if args.len() < 3 {
+                        return Err("wrong number of arguments for 'BITOP' command".to_string());
+                    }
+                    let op = match &extract_bytes(&args[0])?.to_ascii_uppercase()[..] {
+                        b"AND" => BitOperation::And,
+                        b"OR" => BitOperation::Or,
+                        b"XOR" => BitOperation::Xor,
+                        b"NOT" => BitOperation::Not,
+                        _ => return Err("syntax error".to_string()),
+                    };
+                    if op == BitOperation::Not && args.len() != 3 {
+                        return Err(
+                            "BITOP NOT must be called with a single source key.".to_string()
+                        );
+                    }
+                    let destination = extract_bytes(&args[1])?.to_vec();
+                    let keys = args[2..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::BitOp {
+                        op,
+                        destination,
+                        keys,
+                    })
                 }
 
                 b"DEL" => {
@@ -79,6 +387,36 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::Exists(keys))
                 }
 
+                b"UNLINK" => {
+                    if args.is_empty() {
+                        return Err("wrong number of arguments for 'UNLINK' command".to_string());
+                    }
+                    let keys = args
+                        .into_iter()
+                        .map(|arg| extract_bytes(&arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Unlink(keys))
+                }
+
+                b"TOUCH" => {
+                    if args.is_empty() {
+                        return Err("wrong number of arguments for 'TOUCH' command".to_string());
+                    }
+                    let keys = args
+                        .into_iter()
+                        .map(|arg| extract_bytes(&arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Touch(keys))
+                }
+
+                b"TYPE" => {
+                    if args.len() != 1 {
+                        return Err("wrong number of arguments for 'TYPE' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::Type(key))
+                }
+
                 b"INCR" => {
                     if args.len() != 1 {
                         return Err("wrong number of arguments for 'INCR' command".to_string());
@@ -114,21 +452,69 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                 }
 
                 b"EXPIRE" => {
-                    if args.len() != 2 {
+                    if args.len() != 2 && args.len() != 3 {
                         return Err("wrong number of arguments for 'EXPIRE' command".to_string());
                     }
                     let key = extract_bytes(&args[0])?.to_vec();
                     let seconds = extract_integer(&args[1])? as u64;
-                    Ok(Command::Expire { key, seconds })
+                    let condition = args.get(2).map(extract_expire_condition).transpose()?;
+                    Ok(Command::Expire {
+                        key,
+                        seconds,
+                        condition,
+                    })
                 }
 
                 b"PEXPIRE" => {
-                    if args.len() != 2 {
+                    if args.len() != 2 && args.len() != 3 {
                         return Err("wrong number of arguments for 'PEXPIRE' command".to_string());
                     }
                     let key = extract_bytes(&args[0])?.to_vec();
                     let milliseconds = extract_integer(&args[1])? as u64;
-                    Ok(Command::PExpire { key, milliseconds })
+                    let condition = args.get(2).map(extract_expire_condition).transpose()?;
+                    Ok(Command::PExpire {
+                        key,
+                        milliseconds,
+                        condition,
+                    })
+                }
+
+                b"EXPIREAT" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'EXPIREAT' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let timestamp = extract_integer(&args[1])?;
+                    Ok(Command::ExpireAt { key, timestamp })
+                }
+
+                b"PEXPIREAT" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'PEXPIREAT' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let timestamp_ms = extract_integer(&args[1])?;
+                    Ok(Command::PExpireAt { key, timestamp_ms })
+                }
+
+                b"EXPIRETIME" => {
+                    if args.len() != 1 {
+                        return Err(
+                            "wrong number of arguments for 'EXPIRETIME' command".to_string()
+                        );
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::ExpireTime(key))
+                }
+
+                b"PEXPIRETIME" => {
+                    if args.len() != 1 {
+                        return Err(
+                            "wrong number of arguments for 'PEXPIRETIME' command".to_string()
+                        );
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::PExpireTime(key))
                 }
 
                 b"TTL" => {
@@ -155,6 +541,39 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::Persist(key))
                 }
 
+                b"COPY" => {
+                    // COPY source destination [DB destination-db] [REPLACE]
+                    if args.len() < 2 {
+                        return Err("wrong number of arguments for 'COPY' command".to_string());
+                    }
+                    let src = extract_bytes(&args[0])?.to_vec();
+                    let dst = extract_bytes(&args[1])?.to_vec();
+                    let mut db = None;
+                    let mut replace = false;
+                    let mut i = 2;
+                    while i < args.len() {
+                        let option = extract_bytes(&args[i])?;
+                        if option.eq_ignore_ascii_case(b"REPLACE") {
+                            replace = true;
+                        } else if option.eq_ignore_ascii_case(b"DB") && i + 1 < args.len() {
+                            i += 1;
+                            match extract_integer(&args[i])? {
+                                index if index >= 0 => db = Some(index as usize),
+                                _ => return Err("DB index is out of range".to_string()),
+                            }
+                        } else {
+                            return Err("syntax error".to_string());
+                        }
+                        i += 1;
+                    }
+                    Ok(Command::Copy {
+                        src,
+                        dst,
+                        db,
+                        replace,
+                    })
+                }
+
                 b"MGET" => {
                     if args.is_empty() {
                         return Err("wrong number of arguments for 'MGET' command".to_string());
@@ -166,9 +585,12 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::MGet(keys))
                 }
 
-                b"MSET" => {
+                b"MSET" | b"MSETNX" => {
                     if args.is_empty() || args.len() % 2 != 0 {
-                        return Err("wrong number of arguments for 'MSET' command".to_string());
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper)
+                        ));
                     }
                     let mut pairs = Vec::with_capacity(args.len() / 2);
                     let mut i = 0;
@@ -178,7 +600,11 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                         pairs.push((key, value));
                         i += 2;
                     }
-                    Ok(Command::MSet(pairs))
+                    if cmd_upper.as_slice() == b"MSETNX" {
+                        Ok(Command::MSetNx(pairs))
+                    } else {
+                        Ok(Command::MSet(pairs))
+                    }
                 }
 
                 b"PING" => {
@@ -190,6 +616,10 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::Ping(msg))
                 }
 
+                // LOLWUT [VERSION version] draws nothing, so its options
+                // are ignored
+                b"LOLWUT" => Ok(Command::Lolwut),
+
                 b"ECHO" => {
                     if args.len() != 1 {
                         return Err("wrong number of arguments for 'ECHO' command".to_string());
@@ -223,9 +653,158 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     })
                 }
 
-                b"COMMAND" => Ok(Command::Command),
+                b"COMMAND" => {
+                    // COMMAND [COUNT | DOCS [name ...] | INFO [name ...]]
+                    let mut args = args.into_iter();
+                    let subcommand = match args.next() {
+                        Some(arg) => {
+                            Some(String::from_utf8_lossy(&extract_bytes(&arg)?).to_string())
+                        }
+                        None => None,
+                    };
+                    let args = args
+                        .map(|arg| extract_bytes(&arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Command { subcommand, args })
+                }
+                b"DEBUG" => {
+                    // DEBUG SLEEP seconds | JMAP | SET-ACTIVE-EXPIRE flag | OBJECT key
+                    let mut args = args.into_iter();
+                    let Some(subcommand) = args.next() else {
+                        return Err("wrong number of arguments for 'DEBUG' command".to_string());
+                    };
+                    let subcommand =
+                        String::from_utf8_lossy(&extract_bytes(&subcommand)?).to_string();
+                    let args = args
+                        .map(|arg| extract_bytes(&arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Debug { subcommand, args })
+                }
+                b"OBJECT" => {
+                    // OBJECT ENCODING | FREQ | IDLETIME | REFCOUNT key
+                    let mut args = args.into_iter();
+                    let Some(subcommand) = args.next() else {
+                        return Err("wrong number of arguments for 'OBJECT' command".to_string());
+                    };
+                    let subcommand =
+                        String::from_utf8_lossy(&extract_bytes(&subcommand)?).to_string();
+                    let args = args
+                        .map(|arg| extract_bytes(&arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Object { subcommand, args })
+                }
+                b"SLOWLOG" => {
+                    // SLOWLOG GET [count] | LEN | RESET
+                    let mut args = args.into_iter();
+                    let Some(subcommand) = args.next() else {
+                        return Err("wrong number of arguments for 'SLOWLOG' command".to_string());
+                    };
+                    let subcommand =
+                        String::from_utf8_lossy(&extract_bytes(&subcommand)?).to_string();
+                    let args = args
+                        .map(|arg| extract_bytes(&arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SlowLog { subcommand, args })
+                }
                 b"QUIT" => Ok(Command::Quit),
-                b"FLUSHDB" => Ok(Command::FlushDb),
+                b"RESET" => {
+                    if !args.is_empty() {
+                        return Err("wrong number of arguments for 'RESET' command".to_string());
+                    }
+                    Ok(Command::Reset)
+                }
+                b"MONITOR" => Ok(Command::Monitor),
+                b"WAIT" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'WAIT' command".to_string());
+                    }
+                    let numreplicas = extract_integer(&args[0])?;
+                    let timeout_ms = extract_integer(&args[1])?;
+                    if timeout_ms < 0 {
+                        return Err("timeout is negative".to_string());
+                    }
+                    Ok(Command::Wait {
+                        numreplicas,
+                        timeout_ms,
+                    })
+                }
+                b"ASKING" | b"READONLY" | b"READWRITE" => {
+                    if !args.is_empty() {
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper)
+                        ));
+                    }
+                    Ok(match cmd_upper.as_slice() {
+                        b"ASKING" => Command::Asking,
+                        b"READONLY" => Command::ReadOnly,
+                        _ => Command::ReadWrite,
+                    })
+                }
+                b"CLUSTER" => {
+                    // CLUSTER INFO | MYID; the rest report cluster support
+                    // as disabled
+                    let mut args = args.into_iter();
+                    let Some(subcommand) = args.next() else {
+                        return Err("wrong number of arguments for 'CLUSTER' command".to_string());
+                    };
+                    let subcommand =
+                        String::from_utf8_lossy(&extract_bytes(&subcommand)?).to_string();
+                    let args = args
+                        .map(|arg| extract_bytes(&arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Cluster { subcommand, args })
+                }
+
+                b"SELECT" => {
+                    if args.len() != 1 {
+                        return Err("wrong number of arguments for 'SELECT' command".to_string());
+                    }
+                    match extract_integer(&args[0])? {
+                        index if index >= 0 => Ok(Command::Select(index as usize)),
+                        _ => Err("DB index is out of range".to_string()),
+                    }
+                }
+                b"FLUSHDB" | b"FLUSHALL" => {
+                    // The ASYNC / SYNC modifier is accepted; flushing is always synchronous
+                    match args.as_slice() {
+                        [] => {}
+                        [mode] => {
+                            let mode = extract_bytes(mode)?.to_ascii_uppercase();
+                            if mode != b"ASYNC" && mode != b"SYNC" {
+                                return Err("syntax error".to_string());
+                            }
+                        }
+                        _ => return Err("syntax error".to_string()),
+                    }
+                    if cmd_upper == b"FLUSHALL" {
+                        Ok(Command::FlushAll)
+                    } else {
+                        Ok(Command::FlushDb)
+                    }
+                }
+
+                b"SAVE" | b"BGSAVE" | b"LASTSAVE" | b"BGREWRITEAOF" => {
+                    if !args.is_empty() {
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper)
+                        ));
+                    }
+                    Ok(match &cmd_upper[..] {
+                        b"SAVE" => Command::Save,
+                        b"BGSAVE" => Command::BgSave,
+                        b"BGREWRITEAOF" => Command::BgRewriteAof,
+                        _ => Command::LastSave,
+                    })
+                }
+
+                b"DBSIZE" => {
+                    if !args.is_empty() {
+                        return Err("wrong number of arguments for 'DBSIZE' command".to_string());
+                    }
+                    Ok(Command::DbSize)
+                }
 
                 b"KEYS" => {
                     if args.len() != 1 {
@@ -236,7 +815,7 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                 }
 
                 b"SCAN" => {
-                    // SCAN cursor [MATCH pattern] [COUNT count]
+                    // SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
                     if args.is_empty() {
                         return Err("wrong number of arguments for 'SCAN' command".to_string());
                     }
@@ -244,6 +823,7 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     let cursor = extract_bytes(&args[0])?.to_vec();
                     let mut count = 10; // Default count
                     let mut pattern = None;
+                    let mut key_type = None;
 
                     let mut i = 1;
                     while i < args.len() {
@@ -259,7 +839,19 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                                 i += 2;
                             }
                             b"COUNT" if i + 1 < args.len() => {
-                                count = extract_integer(&args[i + 1])? as usize;
+                                // A hint of at least one record per call
+                                let n = extract_integer(&args[i + 1])?;
+                                if n < 1 {
+                                    return Err("syntax error".to_string());
+                                }
+                                count = n as usize;
+                                i += 2;
+                            }
+                            b"TYPE" if i + 1 < args.len() => {
+                                key_type = Some(
+                                    String::from_utf8_lossy(&extract_bytes(&args[i + 1])?)
+                                        .to_lowercase(),
+                                );
                                 i += 2;
                             }
                             _ => {
@@ -272,6 +864,7 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                         cursor,
                         count,
                         pattern,
+                        key_type,
                     })
                 }
 
@@ -335,6 +928,25 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::LPop { key, count })
                 }
 
+                b"BLPOP" | b"BRPOP" => {
+                    if args.len() < 2 {
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper)
+                        ));
+                    }
+                    let timeout = extract_timeout(&args[args.len() - 1])?;
+                    let keys = args[..args.len() - 1]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if cmd_upper.as_slice() == b"BLPOP" {
+                        Ok(Command::BLPop { keys, timeout })
+                    } else {
+                        Ok(Command::BRPop { keys, timeout })
+                    }
+                }
+
                 b"RPOP" => {
                     if args.is_empty() || args.len() > 2 {
                         return Err("wrong number of arguments for 'RPOP' command".to_string());
@@ -375,16 +987,104 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::LIndex { key, index })
                 }
 
-                b"AUTH" => {
-                    if args.len() != 1 {
-                        return Err("wrong number of arguments for 'AUTH' command".to_string());
+                b"LSET" => {
+                    if args.len() != 3 {
+                        return Err("wrong number of arguments for 'LSET' command".to_string());
                     }
-                    let password = extract_bytes(&args[0])?.to_vec();
-                    Ok(Command::Auth(password))
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let index = extract_integer(&args[1])?;
+                    let value = extract_bytes(&args[2])?;
+                    Ok(Command::LSet { key, index, value })
                 }
 
-                b"SUBSCRIBE" => {
-                    if args.is_empty() {
+                b"LREM" => {
+                    if args.len() != 3 {
+                        return Err("wrong number of arguments for 'LREM' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let count = extract_integer(&args[1])?;
+                    let value = extract_bytes(&args[2])?;
+                    Ok(Command::LRem { key, count, value })
+                }
+
+                b"LINSERT" => {
+                    if args.len() != 4 {
+                        return Err("wrong number of arguments for 'LINSERT' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let before = match extract_bytes(&args[1])?.to_ascii_uppercase().as_slice() {
+                        b"BEFORE" => true,
+                        b"AFTER" => false,
+                        _ => return Err("syntax error".to_string()),
+                    };
+                    let pivot = extract_bytes(&args[2])?;
+                    let value = extract_bytes(&args[3])?;
+                    Ok(Command::LInsert {
+                        key,
+                        before,
+                        pivot,
+                        value,
+                    })
+                }
+
+                b"AUTH" => {
+                    // AUTH [username] password
+                    let (username, password) = match args.as_slice() {
+                        [password] => (None, password),
+                        [username, password] => (Some(extract_bytes(username)?.to_vec()), password),
+                        _ => return Err("wrong number of arguments for 'AUTH' command".to_string()),
+                    };
+                    let password = extract_bytes(password)?.to_vec();
+                    Ok(Command::Auth { username, password })
+                }
+
+                b"HELLO" => {
+                    // HELLO [protover [AUTH username password] [SETNAME clientname]]
+                    let proto = match args.first() {
+                        Some(arg) => match extract_integer(arg) {
+                            Ok(version) if (0..=255).contains(&version) => Some(version as u8),
+                            _ => {
+                                return Err("Protocol version is not an integer or out of range"
+                                    .to_string())
+                            }
+                        },
+                        None => None,
+                    };
+
+                    let mut auth = None;
+                    let mut setname = None;
+                    let mut i = 1;
+                    while i < args.len() {
+                        let opt = extract_bytes(&args[i])?;
+                        match opt.to_ascii_uppercase().as_slice() {
+                            b"AUTH" if i + 2 < args.len() => {
+                                let username = extract_bytes(&args[i + 1])?;
+                                let password = extract_bytes(&args[i + 2])?;
+                                auth = Some((username, password));
+                                i += 3;
+                            }
+                            b"SETNAME" if i + 1 < args.len() => {
+                                setname = Some(extract_bytes(&args[i + 1])?);
+                                i += 2;
+                            }
+                            _ => {
+                                return Err(format!(
+                                    "Syntax error in HELLO option '{}'",
+                                    String::from_utf8_lossy(&opt)
+                                ))
+                            }
+                        }
+                    }
+
+                    Ok(Command::Hello {
+                        proto,
+                        auth,
+                        setname,
+                    })
+                }
+
+                b"SUBSCRIBE" => {
+                    if args.is_empty() {
                         return Err("wrong number of arguments for 'SUBSCRIBE' command".to_string());
                     }
                     let channels = args
@@ -442,6 +1142,41 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::Publish { channel, message })
                 }
 
+                b"SSUBSCRIBE" => {
+                    if args.is_empty() {
+                        return Err(
+                            "wrong number of arguments for 'SSUBSCRIBE' command".to_string()
+                        );
+                    }
+                    let channels = args
+                        .into_iter()
+                        .map(|arg| extract_bytes(&arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SSubscribe(channels))
+                }
+
+                b"SUNSUBSCRIBE" => {
+                    let channels = if args.is_empty() {
+                        None
+                    } else {
+                        Some(
+                            args.into_iter()
+                                .map(|arg| extract_bytes(&arg).map(|b| b.to_vec()))
+                                .collect::<Result<Vec<_>, _>>()?,
+                        )
+                    };
+                    Ok(Command::SUnsubscribe(channels))
+                }
+
+                b"SPUBLISH" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'SPUBLISH' command".to_string());
+                    }
+                    let channel = extract_bytes(&args[0])?.to_vec();
+                    let message = extract_bytes(&args[1])?.to_vec();
+                    Ok(Command::SPublish { channel, message })
+                }
+
                 b"PUBSUB" => {
                     if args.is_empty() {
                         return Err("wrong number of arguments for 'PUBSUB' command".to_string());
@@ -474,6 +1209,77 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     })
                 }
 
+                b"EVAL" | b"EVALSHA" => {
+                    // EVAL script numkeys [key ...] [arg ...]
+                    if args.len() < 2 {
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper).to_lowercase()
+                        ));
+                    }
+                    let body = extract_bytes(&args[0])?;
+                    let numkeys = extract_integer(&args[1])?;
+                    if numkeys < 0 {
+                        return Err("Number of keys can't be negative".to_string());
+                    }
+                    let rest = &args[2..];
+                    let numkeys = numkeys as usize;
+                    if numkeys > rest.len() {
+                        return Err(
+                            "Number of keys can't be greater than number of args".to_string()
+                        );
+                    }
+                    let keys = rest[..numkeys]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let args = rest[numkeys..]
+                        .iter()
+                        .map(extract_bytes)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(if &cmd_upper[..] == b"EVAL" {
+                        Command::Eval {
+                            script: body,
+                            keys,
+                            args,
+                        }
+                    } else {
+                        Command::EvalSha {
+                            sha1: String::from_utf8_lossy(&body).to_string(),
+                            keys,
+                            args,
+                        }
+                    })
+                }
+
+                b"SCRIPT" => {
+                    // SCRIPT LOAD script | EXISTS sha1 [sha1 ...] | FLUSH [ASYNC|SYNC]
+                    let mut args = args.into_iter();
+                    let Some(subcommand) = args.next() else {
+                        return Err("wrong number of arguments for 'SCRIPT' command".to_string());
+                    };
+                    let subcommand =
+                        String::from_utf8_lossy(&extract_bytes(&subcommand)?).to_string();
+                    let args = args
+                        .map(|arg| extract_bytes(&arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Script { subcommand, args })
+                }
+
+                b"ACL" => {
+                    // ACL WHOAMI | LIST | USERS | CAT [category]
+                    let mut args = args.into_iter();
+                    let Some(subcommand) = args.next() else {
+                        return Err("wrong number of arguments for 'ACL' command".to_string());
+                    };
+                    let subcommand =
+                        String::from_utf8_lossy(&extract_bytes(&subcommand)?).to_string();
+                    let args = args
+                        .map(|arg| extract_bytes(&arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::Acl { subcommand, args })
+                }
+
                 b"HSET" => {
                     if args.len() < 3 || (args.len() - 1) % 2 != 0 {
                         return Err("wrong number of arguments for 'HSET' command".to_string());
@@ -512,7 +1318,6 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::HMGet { key, fields })
                 }
 
-
                 b"HDEL" => {
                     if args.len() < 2 {
                         return Err("wrong number of arguments for 'HDEL' command".to_string());
@@ -566,6 +1371,71 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::HVals(key))
                 }
 
+                b"HSETNX" => {
+                    if args.len() != 3 {
+                        return Err("wrong number of arguments for 'HSETNX' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let field = extract_bytes(&args[1])?.to_vec();
+                    let value = extract_bytes(&args[2])?;
+                    Ok(Command::HSetNx { key, field, value })
+                }
+
+                b"HSTRLEN" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'HSTRLEN' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let field = extract_bytes(&args[1])?.to_vec();
+                    Ok(Command::HStrLen { key, field })
+                }
+
+                b"HSCAN" => {
+                    // HSCAN key cursor [MATCH pattern] [COUNT count]
+                    if args.len() < 2 {
+                        return Err("wrong number of arguments for 'HSCAN' command".to_string());
+                    }
+
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let cursor = extract_bytes(&args[1])?.to_vec();
+                    let mut count = 10; // Default count
+                    let mut pattern = None;
+
+                    let mut i = 2;
+                    while i < args.len() {
+                        let opt = extract_bytes(&args[i])?;
+                        let opt_upper = opt.to_ascii_uppercase();
+
+                        match &opt_upper[..] {
+                            b"MATCH" if i + 1 < args.len() => {
+                                pattern = Some(
+                                    String::from_utf8_lossy(&extract_bytes(&args[i + 1])?)
+                                        .to_string(),
+                                );
+                                i += 2;
+                            }
+                            b"COUNT" if i + 1 < args.len() => {
+                                let n = extract_integer(&args[i + 1])?;
+                                if n < 1 {
+                                    return Err("syntax error".to_string());
+                                }
+                                count = n as usize;
+                                i += 2;
+                            }
+                            _ => {
+                                return Err("syntax error in HSCAN".to_string());
+                            }
+                        }
+                    }
+
+                    Ok(Command::HScan {
+                        key,
+                        cursor,
+                        count,
+                        pattern,
+                    })
+                }
+
                 b"HINCRBY" => {
                     if args.len() != 3 {
                         return Err("wrong number of arguments for 'HINCRBY' command".to_string());
@@ -576,6 +1446,416 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::HIncrBy { key, field, delta })
                 }
 
+                b"HEXPIRE" | b"HPEXPIRE" => {
+                    // HEXPIRE key seconds [NX | XX | GT | LT] FIELDS numfields field ...
+                    if args.len() < 5 {
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper)
+                        ));
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let ttl = extract_integer(&args[1])?;
+                    if ttl < 0 {
+                        return Err(format!(
+                            "invalid expire time in '{}' command",
+                            String::from_utf8_lossy(&cmd_upper).to_lowercase()
+                        ));
+                    }
+                    let (condition, rest) =
+                        if extract_bytes(&args[2])?.eq_ignore_ascii_case(b"FIELDS") {
+                            (None, &args[2..])
+                        } else {
+                            (Some(extract_expire_condition(&args[2])?), &args[3..])
+                        };
+                    let fields = extract_hash_fields(rest)?;
+                    if cmd_upper.as_slice() == b"HEXPIRE" {
+                        Ok(Command::HExpire {
+                            key,
+                            seconds: ttl as u64,
+                            condition,
+                            fields,
+                        })
+                    } else {
+                        Ok(Command::HPExpire {
+                            key,
+                            milliseconds: ttl as u64,
+                            condition,
+                            fields,
+                        })
+                    }
+                }
+
+                b"HTTL" | b"HPERSIST" => {
+                    // HTTL key FIELDS numfields field ...
+                    if args.len() < 4 {
+                        return Err(format!(
+                            "wrong number of arguments for '{}' command",
+                            String::from_utf8_lossy(&cmd_upper)
+                        ));
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let fields = extract_hash_fields(&args[1..])?;
+                    if cmd_upper.as_slice() == b"HTTL" {
+                        Ok(Command::HTtl { key, fields })
+                    } else {
+                        Ok(Command::HPersist { key, fields })
+                    }
+                }
+
+                b"SADD" => {
+                    if args.len() < 2 {
+                        return Err("wrong number of arguments for 'SADD' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let members = args[1..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SAdd { key, members })
+                }
+
+                b"SREM" => {
+                    if args.len() < 2 {
+                        return Err("wrong number of arguments for 'SREM' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let members = args[1..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SRem { key, members })
+                }
+
+                b"SMEMBERS" => {
+                    if args.len() != 1 {
+                        return Err("wrong number of arguments for 'SMEMBERS' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::SMembers(key))
+                }
+
+                b"SISMEMBER" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'SISMEMBER' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let member = extract_bytes(&args[1])?.to_vec();
+                    Ok(Command::SIsMember { key, member })
+                }
+
+                b"SMISMEMBER" => {
+                    if args.len() < 2 {
+                        return Err(
+                            "wrong number of arguments for 'SMISMEMBER' command".to_string()
+                        );
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let members = args[1..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SMIsMember { key, members })
+                }
+
+                b"SCARD" => {
+                    if args.len() != 1 {
+                        return Err("wrong number of arguments for 'SCARD' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::SCard(key))
+                }
+
+                b"SPOP" => {
+                    if args.is_empty() || args.len() > 2 {
+                        return Err("wrong number of arguments for 'SPOP' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let count = if args.len() == 2 {
+                        let n = extract_integer(&args[1])?;
+                        if n < 0 {
+                            return Err("value is out of range, must be positive".to_string());
+                        }
+                        Some(n as usize)
+                    } else {
+                        None
+                    };
+                    Ok(Command::SPop { key, count })
+                }
+
+                b"SUNION" => {
+                    if args.is_empty() {
+                        return Err("wrong number of arguments for 'SUNION' command".to_string());
+                    }
+                    let keys = args
+                        .into_iter()
+                        .map(|arg| extract_bytes(&arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SUnion(keys))
+                }
+
+                b"SINTER" => {
+                    if args.is_empty() {
+                        return Err("wrong number of arguments for 'SINTER' command".to_string());
+                    }
+                    let keys = args
+                        .into_iter()
+                        .map(|arg| extract_bytes(&arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SInter(keys))
+                }
+
+                b"SDIFF" => {
+                    if args.is_empty() {
+                        return Err("wrong number of arguments for 'SDIFF' command".to_string());
+                    }
+                    let keys = args
+                        .into_iter()
+                        .map(|arg| extract_bytes(&arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SDiff(keys))
+                }
+
+                b"SINTERCARD" => {
+                    // SINTERCARD numkeys key [key ...] [LIMIT limit]
+                    if args.len() < 2 {
+                        return Err(
+                            "wrong number of arguments for 'SINTERCARD' command".to_string()
+                        );
+                    }
+                    let numkeys = extract_integer(&args[0])?;
+                    if numkeys <= 0 {
+                        return Err("numkeys should be greater than 0".to_string());
+                    }
+                    let numkeys = numkeys as usize;
+                    if numkeys > args.len() - 1 {
+                        return Err(
+                            "Number of keys can't be greater than number of args".to_string()
+                        );
+                    }
+                    let keys = args[1..=numkeys]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let limit = match &args[numkeys + 1..] {
+                        [] => 0,
+                        [option, limit]
+                            if extract_bytes(option)?.eq_ignore_ascii_case(b"LIMIT") =>
+                        {
+                            let limit = extract_integer(limit)?;
+                            if limit < 0 {
+                                return Err("LIMIT can't be negative".to_string());
+                            }
+                            limit as usize
+                        }
+                        _ => return Err("syntax error".to_string()),
+                    };
+                    Ok(Command::SInterCard { keys, limit })
+                }
+
+                b"SUNIONSTORE" => {
+                    if args.len() < 2 {
+                        return Err(
+                            "wrong number of arguments for 'SUNIONSTORE' command".to_string()
+                        );
+                    }
+                    let destination = extract_bytes(&args[0])?.to_vec();
+                    let keys = args[1..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SUnionStore { destination, keys })
+                }
+
+                b"SINTERSTORE" => {
+                    if args.len() < 2 {
+                        return Err(
+                            "wrong number of arguments for 'SINTERSTORE' command".to_string()
+                        );
+                    }
+                    let destination = extract_bytes(&args[0])?.to_vec();
+                    let keys = args[1..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SInterStore { destination, keys })
+                }
+
+                b"SDIFFSTORE" => {
+                    if args.len() < 2 {
+                        return Err(
+                            "wrong number of arguments for 'SDIFFSTORE' command".to_string()
+                        );
+                    }
+                    let destination = extract_bytes(&args[0])?.to_vec();
+                    let keys = args[1..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::SDiffStore { destination, keys })
+                }
+
+                b"ZADD" => {
+                    // ZADD key [NX|XX] [GT|LT] [CH] score member [score member ...]
+                    if args.len() < 3 {
+                        return Err("wrong number of arguments for 'ZADD' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+
+                    let mut options = ZAddOptions::default();
+                    let mut i = 1;
+                    while i < args.len() {
+                        match extract_bytes(&args[i])?.to_ascii_uppercase().as_slice() {
+                            b"NX" => options.nx = true,
+                            b"XX" => options.xx = true,
+                            b"GT" => options.gt = true,
+                            b"LT" => options.lt = true,
+                            b"CH" => options.ch = true,
+                            _ => break,
+                        }
+                        i += 1;
+                    }
+
+                    if options.nx && options.xx {
+                        return Err(
+                            "XX and NX options at the same time are not compatible".to_string()
+                        );
+                    }
+                    if (options.gt && options.lt) || (options.nx && (options.gt || options.lt)) {
+                        return Err(
+                            "GT, LT, and/or NX options at the same time are not compatible"
+                                .to_string(),
+                        );
+                    }
+
+                    let pairs = &args[i..];
+                    if pairs.is_empty() || pairs.len() % 2 != 0 {
+                        return Err("syntax error".to_string());
+                    }
+                    let members = pairs
+                        .chunks(2)
+                        .map(|pair| {
+                            Ok((extract_score(&pair[0])?, extract_bytes(&pair[1])?.to_vec()))
+                        })
+                        .collect::<Result<Vec<_>, String>>()?;
+
+                    Ok(Command::ZAdd {
+                        key,
+                        options,
+                        members,
+                    })
+                }
+
+                b"ZSCORE" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'ZSCORE' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let member = extract_bytes(&args[1])?.to_vec();
+                    Ok(Command::ZScore { key, member })
+                }
+
+                b"ZINCRBY" => {
+                    if args.len() != 3 {
+                        return Err("wrong number of arguments for 'ZINCRBY' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let delta = extract_score(&args[1])?;
+                    let member = extract_bytes(&args[2])?.to_vec();
+                    Ok(Command::ZIncrBy { key, delta, member })
+                }
+
+                b"ZCARD" => {
+                    if args.len() != 1 {
+                        return Err("wrong number of arguments for 'ZCARD' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    Ok(Command::ZCard(key))
+                }
+
+                b"ZREM" => {
+                    if args.len() < 2 {
+                        return Err("wrong number of arguments for 'ZREM' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let members = args[1..]
+                        .iter()
+                        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Command::ZRem { key, members })
+                }
+
+                b"ZRANGE" => {
+                    if args.len() != 3 && args.len() != 4 {
+                        return Err("wrong number of arguments for 'ZRANGE' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let start = extract_integer(&args[1])?;
+                    let stop = extract_integer(&args[2])?;
+                    let with_scores = match args.get(3) {
+                        Some(arg) if extract_bytes(arg)?.eq_ignore_ascii_case(b"WITHSCORES") => {
+                            true
+                        }
+                        Some(_) => return Err("syntax error".to_string()),
+                        None => false,
+                    };
+                    Ok(Command::ZRange {
+                        key,
+                        start,
+                        stop,
+                        with_scores,
+                    })
+                }
+
+                b"ZRANGEBYSCORE" => {
+                    // ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
+                    if args.len() < 3 {
+                        return Err(
+                            "wrong number of arguments for 'ZRANGEBYSCORE' command".to_string()
+                        );
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let min = extract_score_bound(&args[1])?;
+                    let max = extract_score_bound(&args[2])?;
+
+                    let mut with_scores = false;
+                    let mut limit = None;
+                    let mut i = 3;
+                    while i < args.len() {
+                        let opt = extract_bytes(&args[i])?;
+                        match opt.to_ascii_uppercase().as_slice() {
+                            b"WITHSCORES" => {
+                                with_scores = true;
+                                i += 1;
+                            }
+                            b"LIMIT" if i + 2 < args.len() => {
+                                let offset = extract_integer(&args[i + 1])?;
+                                let count = extract_integer(&args[i + 2])?;
+                                limit = Some((offset, count));
+                                i += 3;
+                            }
+                            _ => return Err("syntax error".to_string()),
+                        }
+                    }
+
+                    Ok(Command::ZRangeByScore {
+                        key,
+                        min,
+                        max,
+                        with_scores,
+                        limit,
+                    })
+                }
+
+                b"ZRANK" => {
+                    if args.len() != 2 {
+                        return Err("wrong number of arguments for 'ZRANK' command".to_string());
+                    }
+                    let key = extract_bytes(&args[0])?.to_vec();
+                    let member = extract_bytes(&args[1])?.to_vec();
+                    Ok(Command::ZRank { key, member })
+                }
+
                 b"MULTI" => {
                     if !args.is_empty() {
                         return Err("wrong number of arguments for 'MULTI' command".to_string());
@@ -615,16 +1895,41 @@ pub fn parse_command(value: RespValue) -> Result<Command, String> {
                     Ok(Command::Unwatch)
                 }
 
-                _ => Err(format!(
-                    "unknown command '{}'",
-                    String::from_utf8_lossy(&cmd_name)
-                )),
+                _ => {
+                    // Echo the start of each argument, as Redis does
+                    let args: String = args
+                        .iter()
+                        .map(|arg| match arg {
+                            RespValue::BulkString(Some(s)) | RespValue::SimpleString(s) => {
+                                let s = String::from_utf8_lossy(s);
+                                format!("'{}' ", s.chars().take(128).collect::<String>())
+                            }
+                            _ => String::new(),
+                        })
+                        .collect();
+                    let name = String::from_utf8_lossy(&cmd_name);
+                    Err(format!(
+                        "unknown command '{}', with args beginning with: {}",
+                        name.chars().take(128).collect::<String>(),
+                        args
+                    ))
+                }
             }
         }
         _ => Err("Commands must be arrays".to_string()),
     }
 }
 
+/// Extract a SETBIT / GETBIT offset, which Redis caps at 2^32 - 1 so the
+/// value stays within 512 MB
+fn extract_bit_offset(value: &RespValue) -> Result<u64, String> {
+    extract_integer(value)
+        .ok()
+        .and_then(|offset| u32::try_from(offset).ok())
+        .map(u64::from)
+        .ok_or_else(|| "bit offset is not an integer or out of range".to_string())
+}
+
 /// Extract Bytes from RESP value
 #[inline]
 fn extract_bytes(value: &RespValue) -> Result<Bytes, String> {
@@ -647,3 +1952,84 @@ fn extract_integer(value: &RespValue) -> Result<i64, String> {
         _ => Err("Expected integer value".to_string()),
     }
 }
+
+/// Extract an EXPIRE condition flag (NX, XX, GT or LT)
+fn extract_expire_condition(value: &RespValue) -> Result<ExpireCondition, String> {
+    let flag = extract_bytes(value)?;
+    match flag.to_ascii_uppercase().as_slice() {
+        b"NX" => Ok(ExpireCondition::Nx),
+        b"XX" => Ok(ExpireCondition::Xx),
+        b"GT" => Ok(ExpireCondition::Gt),
+        b"LT" => Ok(ExpireCondition::Lt),
+        _ => Err(format!(
+            "Unsupported option {}",
+            String::from_utf8_lossy(&flag)
+        )),
+    }
+}
+
+/// Extract the `FIELDS numfields field ...` tail of the hash field TTL
+/// commands
+fn extract_hash_fields(args: &[RespValue]) -> Result<Vec<Vec<u8>>, String> {
+    let [fields, numfields, fields_args @ ..] = args else {
+        return Err(
+            "Mandatory argument FIELDS is missing or not at the right position".to_string(),
+        );
+    };
+    if !extract_bytes(fields)?.eq_ignore_ascii_case(b"FIELDS") {
+        return Err(
+            "Mandatory argument FIELDS is missing or not at the right position".to_string(),
+        );
+    }
+    let numfields = extract_integer(numfields)?;
+    if numfields <= 0 {
+        return Err("Parameter `numFields` should be greater than 0".to_string());
+    }
+    if numfields as usize != fields_args.len() {
+        return Err("The `numfields` parameter must match the number of arguments".to_string());
+    }
+    fields_args
+        .iter()
+        .map(|arg| extract_bytes(arg).map(|b| b.to_vec()))
+        .collect()
+}
+
+/// Extract a blocking timeout given in seconds, which may be fractional
+fn extract_timeout(value: &RespValue) -> Result<std::time::Duration, String> {
+    let bytes = extract_bytes(value)?;
+    let seconds = std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite())
+        .ok_or_else(|| "timeout is not a float or out of range".to_string())?;
+    if seconds < 0.0 {
+        return Err("timeout is negative".to_string());
+    }
+    std::time::Duration::try_from_secs_f64(seconds)
+        .map_err(|_| "timeout is not a float or out of range".to_string())
+}
+
+/// Extract a sorted set score, accepting `inf`, `+inf` and `-inf`
+fn extract_score(value: &RespValue) -> Result<f64, String> {
+    let bytes = extract_bytes(value)?;
+    std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|score| !score.is_nan())
+        .ok_or_else(|| "value is not a valid float".to_string())
+}
+
+/// Extract a ZRANGEBYSCORE bound such as `1.5`, `(1.5`, `-inf` or `+inf`
+fn extract_score_bound(value: &RespValue) -> Result<ScoreBound, String> {
+    let bytes = extract_bytes(value)?;
+    let (exclusive, number) = match bytes.strip_prefix(b"(") {
+        Some(rest) => (true, rest),
+        None => (false, &bytes[..]),
+    };
+    std::str::from_utf8(number)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|score| !score.is_nan())
+        .map(|value| ScoreBound { value, exclusive })
+        .ok_or_else(|| "min or max is not a float".to_string())
+}
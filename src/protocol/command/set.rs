@@ -0,0 +1,365 @@
+use super::hash::MetadataTracker;
+use crate::error::{Error, Result};
+use bytes::Bytes;
+use feoxdb::FeoxStore;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+/// Placeholder stored for each member, since FeOx values cannot be empty
+const MEMBER_VALUE: &[u8] = b"1";
+const SCAN_BATCH: usize = 10000;
+
+static SET_METADATA_TRACKER: Lazy<Arc<RwLock<MetadataTracker>>> =
+    Lazy::new(|| Arc::new(RwLock::new(MetadataTracker::new())));
+
+#[derive(Clone)]
+pub struct SetOperations {
+    store: Arc<FeoxStore>,
+}
+
+impl SetOperations {
+    pub fn new(store: Arc<FeoxStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn flush_metadata(&self) {
+        let mut tracker = SET_METADATA_TRACKER.write().unwrap();
+        let updates = tracker.take_updates();
+
+        for (meta_key, delta) in updates {
+            if delta != 0 {
+                self.store.atomic_increment(&meta_key, delta).ok();
+            }
+        }
+    }
+
+    fn maybe_flush_metadata(&self) {
+        let should_flush = SET_METADATA_TRACKER.read().unwrap().should_flush();
+        if should_flush {
+            self.flush_metadata();
+        }
+    }
+
+    fn track_count(&self, key: &[u8], delta: i64) {
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"S:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+
+        SET_METADATA_TRACKER
+            .write()
+            .unwrap()
+            .add_update(meta_key, delta);
+        self.maybe_flush_metadata();
+    }
+
+    fn member_prefix(key: &[u8]) -> Vec<u8> {
+        let mut prefix = Vec::with_capacity(key.len() + 5);
+        prefix.extend_from_slice(b"S:");
+        prefix.extend_from_slice(key);
+        prefix.extend_from_slice(b":m:");
+        prefix
+    }
+
+    fn member_key(key: &[u8], member: &[u8]) -> Vec<u8> {
+        let mut member_key = Self::member_prefix(key);
+        member_key.extend_from_slice(member);
+        member_key
+    }
+
+    pub fn sadd(&self, key: &[u8], members: Vec<Vec<u8>>) -> Result<i64> {
+        let mut added = 0i64;
+
+        for member in members {
+            let member_key = Self::member_key(key, &member);
+            if self.store.insert(&member_key, MEMBER_VALUE)? {
+                added += 1;
+            }
+        }
+
+        if added > 0 {
+            self.track_count(key, added);
+        }
+
+        Ok(added)
+    }
+
+    pub fn srem(&self, key: &[u8], members: Vec<Vec<u8>>) -> Result<i64> {
+        let mut removed = 0i64;
+
+        for member in members {
+            let member_key = Self::member_key(key, &member);
+            if self.store.delete(&member_key).is_ok() {
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            self.track_count(key, -removed);
+        }
+
+        Ok(removed)
+    }
+
+    pub fn sismember(&self, key: &[u8], member: &[u8]) -> Result<bool> {
+        Ok(self.store.contains_key(&Self::member_key(key, member)))
+    }
+
+    /// Whether each of `members` is in the set, in order
+    pub fn smismember(&self, key: &[u8], members: &[Vec<u8>]) -> Result<Vec<bool>> {
+        Ok(members
+            .iter()
+            .map(|member| self.store.contains_key(&Self::member_key(key, member)))
+            .collect())
+    }
+
+    pub fn smembers(&self, key: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let prefix = Self::member_prefix(key);
+        let prefix_len = prefix.len();
+
+        let mut start_key = prefix.clone();
+        let mut end_key = prefix.clone();
+        end_key.push(255);
+
+        let mut members = Vec::new();
+
+        loop {
+            let pairs = self
+                .store
+                .range_query(&start_key, &end_key, SCAN_BATCH)
+                .map_err(Error::Database)?;
+            let batch_len = pairs.len();
+
+            for (member_key, _) in pairs {
+                if member_key.starts_with(&prefix) {
+                    members.push(member_key[prefix_len..].to_vec());
+                }
+            }
+
+            if batch_len < SCAN_BATCH {
+                return Ok(members);
+            }
+
+            // Resume just past the last member seen
+            match members.last() {
+                Some(last) => {
+                    start_key = Self::member_key(key, last);
+                    start_key.push(0);
+                }
+                None => return Ok(members),
+            }
+        }
+    }
+
+    pub fn scard(&self, key: &[u8]) -> Result<i64> {
+        self.flush_metadata();
+
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"S:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+
+        match self.store.get_bytes(&meta_key) {
+            Ok(meta_bytes) if meta_bytes.len() >= 8 => {
+                Ok(i64::from_le_bytes(meta_bytes[0..8].try_into().unwrap()))
+            }
+            _ => Ok(0),
+        }
+    }
+
+    /// Remove and return up to `count` random members
+    pub fn spop(&self, key: &[u8], count: usize) -> Result<Vec<Bytes>> {
+        let mut members = self.smembers(key)?;
+        let mut rng = rand::rng();
+        let mut popped = Vec::new();
+
+        while popped.len() < count && !members.is_empty() {
+            let member = members.swap_remove(rng.random_range(0..members.len()));
+            // Another client may have removed it first
+            if self.store.delete(&Self::member_key(key, &member)).is_ok() {
+                popped.push(Bytes::from(member));
+            }
+        }
+
+        if !popped.is_empty() {
+            self.track_count(key, -(popped.len() as i64));
+        }
+
+        Ok(popped)
+    }
+
+    /// Members present in any of the sets
+    pub fn sunion(&self, keys: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+
+        for key in keys {
+            for member in self.smembers(key)? {
+                if seen.insert(member.clone()) {
+                    result.push(member);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Members present in every one of the sets
+    pub fn sinter(&self, keys: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(vec![]);
+        };
+
+        let mut result = self.smembers(first)?;
+        for key in rest {
+            // Nothing can survive an intersection with an empty set
+            if result.is_empty() {
+                break;
+            }
+            let other: HashSet<Vec<u8>> = self.smembers(key)?.into_iter().collect();
+            result.retain(|member| other.contains(member));
+        }
+
+        Ok(result)
+    }
+
+    /// Size of the intersection of the sets, counting no further than
+    /// `limit` unless it is 0
+    ///
+    /// The smallest set is walked in batches and each member looked up in
+    /// the others, so the intersection is never built.
+    pub fn sintercard(&self, keys: &[Vec<u8>], limit: usize) -> Result<usize> {
+        let mut sizes = Vec::with_capacity(keys.len());
+        for key in keys {
+            sizes.push((self.scard(key)?, key));
+        }
+        let Some(&(smallest, first)) = sizes.iter().min_by_key(|(size, _)| *size) else {
+            return Ok(0);
+        };
+        if smallest == 0 {
+            return Ok(0);
+        }
+
+        let prefix = Self::member_prefix(first);
+        let mut start_key = prefix.clone();
+        let mut end_key = prefix.clone();
+        end_key.push(255);
+
+        let mut count = 0;
+        loop {
+            let pairs = self
+                .store
+                .range_query(&start_key, &end_key, SCAN_BATCH)
+                .map_err(Error::Database)?;
+            let batch_len = pairs.len();
+
+            for (member_key, _) in &pairs {
+                let Some(member) = member_key.strip_prefix(prefix.as_slice()) else {
+                    continue;
+                };
+                let in_all = sizes
+                    .iter()
+                    .filter(|(_, key)| *key != first)
+                    .all(|(_, key)| self.store.contains_key(&Self::member_key(key, member)));
+                if in_all {
+                    count += 1;
+                    if count == limit {
+                        return Ok(count);
+                    }
+                }
+            }
+
+            // Resume just past the last member seen
+            match pairs.last() {
+                Some((last, _)) if batch_len == SCAN_BATCH => {
+                    start_key = last.clone();
+                    start_key.push(0);
+                }
+                _ => return Ok(count),
+            }
+        }
+    }
+
+    /// Members of the first set that are in none of the others
+    pub fn sdiff(&self, keys: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(vec![]);
+        };
+
+        let mut result = self.smembers(first)?;
+        for key in rest {
+            if result.is_empty() {
+                break;
+            }
+            let other: HashSet<Vec<u8>> = self.smembers(key)?.into_iter().collect();
+            result.retain(|member| !other.contains(member));
+        }
+
+        Ok(result)
+    }
+
+    /// Replace the contents of `key` with `members`, returning the new size
+    pub fn store(&self, key: &[u8], members: &[Vec<u8>]) -> Result<i64> {
+        // Settle pending deltas so they cannot land on the new counter
+        self.flush_metadata();
+
+        for member in self.smembers(key)? {
+            self.store.delete(&Self::member_key(key, &member)).ok();
+        }
+
+        for member in members {
+            self.store
+                .insert(&Self::member_key(key, member), MEMBER_VALUE)?;
+        }
+
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"S:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+
+        if members.is_empty() {
+            self.store.delete(&meta_key).ok();
+        } else {
+            let count = members.len() as i64;
+            self.store.insert(&meta_key, &count.to_le_bytes())?;
+        }
+
+        Ok(members.len() as i64)
+    }
+
+    /// Check if a set with at least one member exists under this key
+    ///
+    /// Probes the member range directly because the `:meta` counter is
+    /// flushed lazily.
+    pub fn exists(&self, key: &[u8]) -> bool {
+        let prefix = Self::member_prefix(key);
+        let mut end_key = prefix.clone();
+        end_key.push(255);
+
+        self.store
+            .range_query(&prefix, &end_key, 1)
+            .map(|pairs| !pairs.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Delete the set's counter and every member, returning whether it had
+    /// any
+    pub fn delete(&self, key: &[u8]) -> Result<bool> {
+        let existed = self.exists(key);
+
+        // Settle pending deltas so they cannot recreate the counter
+        self.flush_metadata();
+
+        super::delete_prefixed(&self.store, &Self::member_prefix(key))?;
+
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"S:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+        self.store.delete(&meta_key).ok();
+
+        Ok(existed)
+    }
+}
@@ -0,0 +1,454 @@
+use super::hash::MetadataTracker;
+use super::{ScoreBound, ZAddOptions};
+use crate::error::{Error, Result};
+use feoxdb::FeoxStore;
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+
+/// Placeholder stored in each score index entry, since FeOx values cannot be empty
+const INDEX_VALUE: &[u8] = b"1";
+const SCAN_BATCH: usize = 10000;
+
+static ZSET_METADATA_TRACKER: Lazy<Arc<RwLock<MetadataTracker>>> =
+    Lazy::new(|| Arc::new(RwLock::new(MetadataTracker::new())));
+
+/// Encode a score so that byte order matches numeric order
+///
+/// Positive scores get the sign bit flipped and negative scores have every
+/// bit inverted, then the result is written big-endian.
+fn encode_score(score: f64) -> [u8; 8] {
+    // Normalise -0.0 so it sorts together with 0.0
+    let score = if score == 0.0 { 0.0 } else { score };
+    let bits = score.to_bits();
+    let sortable = if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits ^ (1 << 63)
+    };
+    sortable.to_be_bytes()
+}
+
+/// Inverse of `encode_score`
+fn decode_score(bytes: &[u8]) -> f64 {
+    let sortable = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+    let bits = if sortable >> 63 == 1 {
+        sortable ^ (1 << 63)
+    } else {
+        !sortable
+    };
+    f64::from_bits(bits)
+}
+
+/// Format a score the way Redis replies with it
+///
+/// Like `%.17g` but with the shortest digits that read back as the same
+/// score: plain notation, or an exponent once that would be below -4 or
+/// above 16, as in `1e+17` and `1.5e-05`.
+pub fn format_score(score: f64) -> String {
+    if score.is_infinite() {
+        return if score > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+
+    let scientific = format!("{:e}", score);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    if score != 0.0 && !(-4..17).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    } else {
+        score.to_string()
+    }
+}
+
+#[derive(Clone)]
+pub struct ZSetOperations {
+    store: Arc<FeoxStore>,
+}
+
+impl ZSetOperations {
+    pub fn new(store: Arc<FeoxStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn flush_metadata(&self) {
+        let mut tracker = ZSET_METADATA_TRACKER.write().unwrap();
+        let updates = tracker.take_updates();
+
+        for (meta_key, delta) in updates {
+            if delta != 0 {
+                self.store.atomic_increment(&meta_key, delta).ok();
+            }
+        }
+    }
+
+    fn maybe_flush_metadata(&self) {
+        let should_flush = ZSET_METADATA_TRACKER.read().unwrap().should_flush();
+        if should_flush {
+            self.flush_metadata();
+        }
+    }
+
+    fn track_count(&self, key: &[u8], delta: i64) {
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"Z:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+
+        ZSET_METADATA_TRACKER
+            .write()
+            .unwrap()
+            .add_update(meta_key, delta);
+        self.maybe_flush_metadata();
+    }
+
+    fn member_key(key: &[u8], member: &[u8]) -> Vec<u8> {
+        let mut member_key = Vec::with_capacity(key.len() + member.len() + 5);
+        member_key.extend_from_slice(b"Z:");
+        member_key.extend_from_slice(key);
+        member_key.extend_from_slice(b":m:");
+        member_key.extend_from_slice(member);
+        member_key
+    }
+
+    fn score_prefix(key: &[u8]) -> Vec<u8> {
+        let mut prefix = Vec::with_capacity(key.len() + 5);
+        prefix.extend_from_slice(b"Z:");
+        prefix.extend_from_slice(key);
+        prefix.extend_from_slice(b":s:");
+        prefix
+    }
+
+    /// Upper bound for scans over the score index
+    ///
+    /// Encoded scores can start with 0xFF (e.g. +inf), so the bound pads past
+    /// the whole 8-byte score.
+    fn score_range_end(key: &[u8]) -> Vec<u8> {
+        let mut end_key = Self::score_prefix(key);
+        end_key.extend_from_slice(&[255; 9]);
+        end_key
+    }
+
+    fn score_key(key: &[u8], score: f64, member: &[u8]) -> Vec<u8> {
+        let mut score_key = Self::score_prefix(key);
+        score_key.extend_from_slice(&encode_score(score));
+        score_key.push(b':');
+        score_key.extend_from_slice(member);
+        score_key
+    }
+
+    /// Write a member's score to both indexes, replacing `old` if present
+    fn set_score(&self, key: &[u8], member: &[u8], score: f64, old: Option<f64>) -> Result<()> {
+        if let Some(old) = old {
+            self.store.delete(&Self::score_key(key, old, member)).ok();
+        }
+        self.store
+            .insert(&Self::score_key(key, score, member), INDEX_VALUE)?;
+        self.store
+            .insert(&Self::member_key(key, member), &score.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Add or update members, returning the number added (or changed with CH)
+    pub fn zadd(
+        &self,
+        key: &[u8],
+        members: Vec<(f64, Vec<u8>)>,
+        options: ZAddOptions,
+    ) -> Result<i64> {
+        let mut added = 0i64;
+        let mut changed = 0i64;
+
+        for (score, member) in members {
+            let old = self.zscore(key, &member)?;
+
+            let allowed = match old {
+                None => !options.xx,
+                Some(current) => {
+                    !options.nx
+                        && (!options.gt || score > current)
+                        && (!options.lt || score < current)
+                }
+            };
+            if !allowed || old == Some(score) {
+                continue;
+            }
+
+            self.set_score(key, &member, score, old)?;
+            if old.is_none() {
+                added += 1;
+            }
+            changed += 1;
+        }
+
+        if added > 0 {
+            self.track_count(key, added);
+        }
+
+        Ok(if options.ch { changed } else { added })
+    }
+
+    pub fn zscore(&self, key: &[u8], member: &[u8]) -> Result<Option<f64>> {
+        match self.store.get_bytes(&Self::member_key(key, member)) {
+            Ok(bytes) if bytes.len() == 8 => {
+                Ok(Some(f64::from_be_bytes(bytes[..8].try_into().unwrap())))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn zincrby(&self, key: &[u8], delta: f64, member: &[u8]) -> Result<f64> {
+        let old = self.zscore(key, member)?;
+        let score = old.unwrap_or(0.0) + delta;
+        if score.is_nan() {
+            return Err(Error::Protocol(
+                "resulting score is not a number (NaN)".to_string(),
+            ));
+        }
+
+        self.set_score(key, member, score, old)?;
+        if old.is_none() {
+            self.track_count(key, 1);
+        }
+
+        Ok(score)
+    }
+
+    pub fn zrem(&self, key: &[u8], members: Vec<Vec<u8>>) -> Result<i64> {
+        let mut removed = 0i64;
+
+        for member in members {
+            if let Some(score) = self.zscore(key, &member)? {
+                if self.store.delete(&Self::member_key(key, &member)).is_ok() {
+                    self.store
+                        .delete(&Self::score_key(key, score, &member))
+                        .ok();
+                    removed += 1;
+                }
+            }
+        }
+
+        if removed > 0 {
+            self.track_count(key, -removed);
+        }
+
+        Ok(removed)
+    }
+
+    pub fn zcard(&self, key: &[u8]) -> Result<i64> {
+        self.flush_metadata();
+
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"Z:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+
+        match self.store.get_bytes(&meta_key) {
+            Ok(meta_bytes) if meta_bytes.len() >= 8 => {
+                Ok(i64::from_le_bytes(meta_bytes[0..8].try_into().unwrap()))
+            }
+            _ => Ok(0),
+        }
+    }
+
+    /// Walk the score index from `start_key` in score order
+    ///
+    /// The callback receives each score and member and returns false to
+    /// stop the scan early.
+    fn scan_scores(
+        &self,
+        key: &[u8],
+        start_key: Vec<u8>,
+        mut f: impl FnMut(f64, &[u8]) -> bool,
+    ) -> Result<()> {
+        let prefix = Self::score_prefix(key);
+        let end_key = Self::score_range_end(key);
+        let mut start_key = start_key;
+
+        loop {
+            let pairs = self
+                .store
+                .range_query(&start_key, &end_key, SCAN_BATCH)
+                .map_err(Error::Database)?;
+
+            for (score_key, _) in &pairs {
+                // Layout after the prefix is `score(8) ':' member`
+                if !score_key.starts_with(&prefix) || score_key.len() < prefix.len() + 9 {
+                    continue;
+                }
+                let encoded = &score_key[prefix.len()..];
+                if !f(decode_score(&encoded[..8]), &encoded[9..]) {
+                    return Ok(());
+                }
+            }
+
+            match pairs.last() {
+                Some((last, _)) if pairs.len() == SCAN_BATCH => {
+                    // Resume just past the last entry seen
+                    start_key = last.clone();
+                    start_key.push(0);
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Members with ranks in `start..=stop`, negative ranks counting from the end
+    pub fn zrange(&self, key: &[u8], start: i64, stop: i64) -> Result<Vec<(Vec<u8>, f64)>> {
+        let len = self.zcard(key)?;
+        let start = if start < 0 {
+            (len + start).max(0)
+        } else {
+            start
+        };
+        let stop = if stop < 0 {
+            len + stop
+        } else {
+            stop.min(len - 1)
+        };
+
+        let mut results = Vec::new();
+        if start > stop || start >= len {
+            return Ok(results);
+        }
+
+        let mut rank = 0i64;
+        self.scan_scores(key, Self::score_prefix(key), |score, member| {
+            if rank >= start {
+                results.push((member.to_vec(), score));
+            }
+            rank += 1;
+            rank <= stop
+        })?;
+
+        Ok(results)
+    }
+
+    /// Members with scores between `min` and `max`, after skipping `offset`
+    ///
+    /// A negative `count` returns all remaining matches.
+    pub fn zrangebyscore(
+        &self,
+        key: &[u8],
+        min: ScoreBound,
+        max: ScoreBound,
+        offset: usize,
+        count: i64,
+    ) -> Result<Vec<(Vec<u8>, f64)>> {
+        let mut results = Vec::new();
+        if count == 0 {
+            return Ok(results);
+        }
+
+        let mut start_key = Self::score_prefix(key);
+        start_key.extend_from_slice(&encode_score(min.value));
+
+        let mut skipped = 0usize;
+        self.scan_scores(key, start_key, |score, member| {
+            if max.exclusive && score >= max.value || score > max.value {
+                return false;
+            }
+            if min.exclusive && score <= min.value {
+                return true;
+            }
+            if skipped < offset {
+                skipped += 1;
+                return true;
+            }
+            results.push((member.to_vec(), score));
+            count < 0 || (results.len() as i64) < count
+        })?;
+
+        Ok(results)
+    }
+
+    /// 0-based rank of a member in ascending score order
+    pub fn zrank(&self, key: &[u8], member: &[u8]) -> Result<Option<i64>> {
+        let Some(score) = self.zscore(key, member)? else {
+            return Ok(None);
+        };
+
+        // Everything before the member's own index entry ranks lower
+        let target = Self::score_key(key, score, member);
+        let mut rank = 0i64;
+        self.scan_scores(key, Self::score_prefix(key), |entry_score, entry_member| {
+            if Self::score_key(key, entry_score, entry_member) >= target {
+                return false;
+            }
+            rank += 1;
+            true
+        })?;
+
+        Ok(Some(rank))
+    }
+
+    /// Check if a sorted set with at least one member exists under this key
+    ///
+    /// Probes the score index directly because the `:meta` counter is
+    /// flushed lazily.
+    pub fn exists(&self, key: &[u8]) -> bool {
+        let prefix = Self::score_prefix(key);
+        let end_key = Self::score_range_end(key);
+
+        self.store
+            .range_query(&prefix, &end_key, 1)
+            .map(|pairs| !pairs.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Delete the sorted set's counter, members and score index, returning
+    /// whether it had any members
+    pub fn delete(&self, key: &[u8]) -> Result<bool> {
+        let existed = self.exists(key);
+
+        // Settle pending deltas so they cannot recreate the counter
+        self.flush_metadata();
+
+        super::delete_prefixed(&self.store, &Self::member_key(key, b""))?;
+        super::delete_prefixed(&self.store, &Self::score_prefix(key))?;
+
+        let mut meta_key = Vec::with_capacity(key.len() + 7);
+        meta_key.extend_from_slice(b"Z:");
+        meta_key.extend_from_slice(key);
+        meta_key.extend_from_slice(b":meta");
+        self.store.delete(&meta_key).ok();
+
+        Ok(existed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_scores_like_redis() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "-0"),
+            (1.0, "1"),
+            (-2.5, "-2.5"),
+            (0.1, "0.1"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (0.0001, "0.0001"),
+            (0.00001, "1e-05"),
+            (-1.5e-7, "-1.5e-07"),
+            (1e16, "10000000000000000"),
+            (1e17, "1e+17"),
+            (1.2345e20, "1.2345e+20"),
+            (f64::MAX, "1.7976931348623157e+308"),
+            (5e-324, "5e-324"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ];
+        for (score, expected) in cases {
+            assert_eq!(format_score(score), expected);
+        }
+    }
+
+    #[test]
+    fn formatted_scores_round_trip() {
+        for score in [0.1 + 0.2, 1e-5 / 3.0, 123456789.123, 9.999999999999999e16] {
+            assert_eq!(format_score(score).parse::<f64>().unwrap(), score);
+        }
+    }
+}
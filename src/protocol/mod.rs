@@ -1,4 +1,7 @@
 mod command;
 pub mod resp;
-pub use command::{Command, CommandExecutor};
+pub use command::{
+    collection_deadline, db_key_prefix, is_write_command, Command, CommandExecutor, COMMAND_COUNT,
+    COMMAND_NAMES, WRONGTYPE_ERROR,
+};
 pub use resp::{RespParser, RespValue};
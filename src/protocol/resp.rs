@@ -2,10 +2,26 @@ use bytes::{Bytes, BytesMut};
 use memchr::memchr2;
 use std::str;
 
+/// Longest bulk string accepted by default, matching Redis
+const DEFAULT_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Unparsed bytes buffered by default before giving up, matching Redis
+const DEFAULT_MAX_BUFFERED: usize = 1024 * 1024 * 1024;
+
+/// Most elements an array may declare by default, matching the limit Redis
+/// puts on unauthenticated clients
+const DEFAULT_MAX_MULTIBULK_LEN: usize = 1024 * 1024;
+
+/// Elements reserved up front for an array, however many it declares
+const MAX_PREALLOCATED_ELEMENTS: usize = 1024;
+
 /// RESP (REdis Serialization Protocol) parser
 pub struct RespParser {
     buffer: BytesMut,
     position: usize,
+    max_bulk_len: usize,
+    max_multibulk_len: usize,
+    max_buffered: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -15,13 +31,36 @@ pub enum RespValue {
     Integer(i64),
     BulkString(Option<Bytes>),
     Array(Option<Vec<RespValue>>),
+
+    // RESP3 types, downgraded to RESP2 equivalents for RESP2 clients
+    Map(Vec<(RespValue, RespValue)>),
+    Double(f64),
+    Boolean(bool),
+    Null,
+    VerbatimString(Bytes),
+    // Out-of-band data such as pub/sub messages, an array for RESP2 clients
+    Push(Vec<RespValue>),
 }
 
 impl RespParser {
     pub fn new() -> Self {
+        Self::with_limits(
+            DEFAULT_MAX_BULK_LEN,
+            DEFAULT_MAX_MULTIBULK_LEN,
+            DEFAULT_MAX_BUFFERED,
+        )
+    }
+
+    /// A parser refusing bulk strings longer than `max_bulk_len`, arrays of
+    /// more than `max_multibulk_len` elements, and partial values once more
+    /// than `max_buffered` bytes are waiting
+    pub fn with_limits(max_bulk_len: usize, max_multibulk_len: usize, max_buffered: usize) -> Self {
         Self {
             buffer: BytesMut::with_capacity(16 * 1024),
             position: 0,
+            max_bulk_len,
+            max_multibulk_len,
+            max_buffered,
         }
     }
 
@@ -30,28 +69,46 @@ impl RespParser {
         self.buffer.extend_from_slice(data);
     }
 
+    /// Bytes fed that do not yet form a whole value
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.position
+    }
+
     /// Parse next complete RESP value
     pub fn parse_next(&mut self) -> Result<Option<RespValue>, String> {
-        if self.position >= self.buffer.len() {
-            return Ok(None);
-        }
+        loop {
+            if self.position >= self.buffer.len() {
+                return Ok(None);
+            }
 
-        let remaining = &self.buffer[self.position..];
+            let remaining = &self.buffer[self.position..];
 
-        match self.parse_value(remaining) {
-            Ok(Some((value, consumed))) => {
-                self.position += consumed;
+            // Anything that doesn't start like RESP is an inline command line
+            let parsed = if matches!(remaining[0], b'+' | b'-' | b':' | b'$' | b'*') {
+                self.parse_value(remaining)?
+            } else {
+                parse_inline(remaining)?
+            };
 
-                // Compact buffer if needed
-                if self.position > self.buffer.len() / 2 {
-                    let _ = self.buffer.split_to(self.position);
-                    self.position = 0;
+            let Some((value, consumed)) = parsed else {
+                if self.buffered() > self.max_buffered {
+                    return Err("Protocol error: query buffer limit exceeded".to_string());
                 }
+                return Ok(None); // Need more data
+            };
+            self.position += consumed;
+
+            // Compact buffer if needed
+            if self.position > self.buffer.len() / 2 {
+                let _ = self.buffer.split_to(self.position);
+                self.position = 0;
+            }
 
-                Ok(Some(value))
+            // Blank inline lines are ignored, as Redis does
+            if matches!(&value, RespValue::Array(Some(args)) if args.is_empty()) {
+                continue;
             }
-            Ok(None) => Ok(None), // Need more data
-            Err(e) => Err(e),
+            return Ok(Some(value));
         }
     }
 
@@ -121,10 +178,13 @@ impl RespParser {
             .parse::<i64>()
             .map_err(|_| format!("Invalid bulk string length: {}", len_str))?;
 
-        if len < 0 {
+        if len == -1 {
             // Null bulk string
             return Ok(Some((RespValue::BulkString(None), len_end + 2)));
         }
+        if len < 0 || len as u64 > self.max_bulk_len as u64 {
+            return Err("Protocol error: invalid bulk length".to_string());
+        }
 
         let len = len as usize;
         let data_start = len_end + 2;
@@ -160,13 +220,16 @@ impl RespParser {
             .parse::<i64>()
             .map_err(|_| format!("Invalid array length: {}", len_str))?;
 
-        if len < 0 {
+        if len == -1 {
             // Null array
             return Ok(Some((RespValue::Array(None), len_end + 2)));
         }
+        if len < 0 || len as u64 > self.max_multibulk_len as u64 {
+            return Err("Protocol error: invalid multibulk length".to_string());
+        }
 
         let len = len as usize;
-        let mut elements = Vec::with_capacity(len);
+        let mut elements = Vec::with_capacity(len.min(MAX_PREALLOCATED_ELEMENTS));
         let mut pos = len_end + 2;
 
         // Parse array elements
@@ -184,14 +247,130 @@ impl RespParser {
     }
 }
 
+/// Longest inline command line accepted, matching Redis
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// Parse an inline command like `SET key "a value"\r\n` into an array of
+/// bulk strings, splitting on whitespace with quotes handled like redis-cli
+fn parse_inline(buf: &[u8]) -> Result<Option<(RespValue, usize)>, String> {
+    let Some(end) = memchr::memchr(b'\n', buf) else {
+        if buf.len() > MAX_INLINE_LEN {
+            return Err("Protocol error: too big inline request".to_string());
+        }
+        return Ok(None);
+    };
+
+    let line = buf[..end].strip_suffix(b"\r").unwrap_or(&buf[..end]);
+    let args = split_inline_args(line)?
+        .into_iter()
+        .map(|arg| RespValue::BulkString(Some(Bytes::from(arg))))
+        .collect();
+    Ok(Some((RespValue::Array(Some(args)), end + 1)))
+}
+
+/// Split an inline command line into arguments
+///
+/// Double quotes support `\n`, `\r`, `\t`, `\b`, `\a`, `\\`, `\"` and `\xHH`
+/// escapes; single quotes only `\'`. A closing quote must end the argument.
+fn split_inline_args(line: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    const UNBALANCED: &str = "Protocol error: unbalanced quotes in request";
+
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = Vec::new();
+        match line[i] {
+            b'"' => {
+                i += 1;
+                loop {
+                    match line.get(i) {
+                        None => return Err(UNBALANCED.to_string()),
+                        Some(b'"') => break,
+                        Some(b'\\') if i + 3 < line.len() && line[i + 1] == b'x' => {
+                            let hex = str::from_utf8(&line[i + 2..i + 4]).ok();
+                            match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                                Some(byte) => {
+                                    arg.push(byte);
+                                    i += 4;
+                                }
+                                None => {
+                                    arg.push(b'x');
+                                    i += 2;
+                                }
+                            }
+                        }
+                        Some(b'\\') if i + 1 < line.len() => {
+                            arg.push(match line[i + 1] {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                other => other,
+                            });
+                            i += 2;
+                        }
+                        Some(&b) => {
+                            arg.push(b);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                if line.get(i).is_some_and(|b| !b.is_ascii_whitespace()) {
+                    return Err(UNBALANCED.to_string());
+                }
+            }
+            b'\'' => {
+                i += 1;
+                loop {
+                    match line.get(i) {
+                        None => return Err(UNBALANCED.to_string()),
+                        Some(b'\'') => break,
+                        Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                            arg.push(b'\'');
+                            i += 2;
+                        }
+                        Some(&b) => {
+                            arg.push(b);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                if line.get(i).is_some_and(|b| !b.is_ascii_whitespace()) {
+                    return Err(UNBALANCED.to_string());
+                }
+            }
+            _ => {
+                while i < line.len() && !line[i].is_ascii_whitespace() {
+                    arg.push(line[i]);
+                    i += 1;
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
+
 /// Find CRLF in buffer
 #[inline]
 fn find_crlf(buf: &[u8]) -> Option<usize> {
+    if buf.len() < 2 {
+        return None;
+    }
     let mut pos = 0;
-    while pos < buf.len() - 1 {
+    while pos + 1 < buf.len() {
         if let Some(cr_pos) = memchr2(b'\r', b'\n', &buf[pos..]) {
             let cr_pos = pos + cr_pos;
-            if cr_pos < buf.len() - 1 && buf[cr_pos] == b'\r' && buf[cr_pos + 1] == b'\n' {
+            if cr_pos + 1 < buf.len() && buf[cr_pos] == b'\r' && buf[cr_pos + 1] == b'\n' {
                 return Some(cr_pos);
             }
             pos = cr_pos + 1;
@@ -211,6 +390,32 @@ pub fn format_resp_response(value: &RespValue) -> Vec<u8> {
 
 /// Write RESP value directly to buffer (zero-copy when possible)
 pub fn write_resp_value(buf: &mut Vec<u8>, value: &RespValue) {
+    write_value(buf, value, false);
+}
+
+/// Write RESP value using RESP3 encodings for maps, doubles, booleans and nulls
+pub fn write_resp3_value(buf: &mut Vec<u8>, value: &RespValue) {
+    write_value(buf, value, true);
+}
+
+/// Write RESP value for a connection speaking the given protocol version
+#[inline]
+pub fn write_resp_value_for(buf: &mut Vec<u8>, value: &RespValue, resp_version: u8) {
+    write_value(buf, value, resp_version >= 3);
+}
+
+/// Format a double the way Redis does (`inf`, `-inf`, `nan` or shortest form)
+fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else if d.is_infinite() {
+        if d > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        d.to_string()
+    }
+}
+
+fn write_value(buf: &mut Vec<u8>, value: &RespValue, resp3: bool) {
     match value {
         RespValue::SimpleString(s) => {
             buf.push(b'+');
@@ -236,7 +441,10 @@ pub fn write_resp_value(buf: &mut Vec<u8>, value: &RespValue) {
             buf.extend_from_slice(s);
             buf.extend_from_slice(b"\r\n");
         }
-        RespValue::BulkString(None) => {
+        RespValue::BulkString(None) | RespValue::Null if resp3 => {
+            buf.extend_from_slice(b"_\r\n");
+        }
+        RespValue::BulkString(None) | RespValue::Null => {
             buf.extend_from_slice(b"$-1\r\n");
         }
         RespValue::Array(Some(arr)) => {
@@ -245,12 +453,63 @@ pub fn write_resp_value(buf: &mut Vec<u8>, value: &RespValue) {
             buf.extend_from_slice(num_buf.format(arr.len()).as_bytes());
             buf.extend_from_slice(b"\r\n");
             for item in arr {
-                write_resp_value(buf, item);
+                write_value(buf, item, resp3);
             }
         }
+        RespValue::Array(None) if resp3 => {
+            buf.extend_from_slice(b"_\r\n");
+        }
         RespValue::Array(None) => {
             buf.extend_from_slice(b"*-1\r\n");
         }
+        RespValue::Map(pairs) => {
+            // RESP2 clients get a flat array of alternating keys and values
+            let len = if resp3 { pairs.len() } else { pairs.len() * 2 };
+            buf.push(if resp3 { b'%' } else { b'*' });
+            let mut num_buf = itoa::Buffer::new();
+            buf.extend_from_slice(num_buf.format(len).as_bytes());
+            buf.extend_from_slice(b"\r\n");
+            for (key, value) in pairs {
+                write_value(buf, key, resp3);
+                write_value(buf, value, resp3);
+            }
+        }
+        RespValue::Double(d) if resp3 => {
+            buf.push(b',');
+            buf.extend_from_slice(format_double(*d).as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        }
+        RespValue::Double(d) => {
+            let s = Bytes::from(format_double(*d));
+            write_value(buf, &RespValue::BulkString(Some(s)), false);
+        }
+        RespValue::Boolean(b) if resp3 => {
+            buf.extend_from_slice(if *b { b"#t\r\n" } else { b"#f\r\n" });
+        }
+        RespValue::Boolean(b) => {
+            write_value(buf, &RespValue::Integer(*b as i64), false);
+        }
+        RespValue::VerbatimString(s) if resp3 => {
+            // Payload is prefixed with its three-letter format
+            buf.push(b'=');
+            let mut num_buf = itoa::Buffer::new();
+            buf.extend_from_slice(num_buf.format(s.len() + 4).as_bytes());
+            buf.extend_from_slice(b"\r\ntxt:");
+            buf.extend_from_slice(s);
+            buf.extend_from_slice(b"\r\n");
+        }
+        RespValue::VerbatimString(s) => {
+            write_value(buf, &RespValue::BulkString(Some(s.clone())), false);
+        }
+        RespValue::Push(items) => {
+            buf.push(if resp3 { b'>' } else { b'*' });
+            let mut num_buf = itoa::Buffer::new();
+            buf.extend_from_slice(num_buf.format(items.len()).as_bytes());
+            buf.extend_from_slice(b"\r\n");
+            for item in items {
+                write_value(buf, item, resp3);
+            }
+        }
     }
 }
 
@@ -263,7 +522,7 @@ fn estimate_resp_size(value: &RespValue) -> usize {
         RespValue::Integer(_) => 24, // :number\r\n
         RespValue::BulkString(Some(s)) => s.len() + 20,
         RespValue::BulkString(None) => 5,
-        RespValue::Array(Some(arr)) => {
+        RespValue::Array(Some(arr)) | RespValue::Push(arr) => {
             let mut size = 10; // array header
             for item in arr {
                 size += estimate_resp_size(item);
@@ -271,6 +530,17 @@ fn estimate_resp_size(value: &RespValue) -> usize {
             size
         }
         RespValue::Array(None) => 5,
+        RespValue::Map(pairs) => {
+            let mut size = 10; // map header
+            for (key, value) in pairs {
+                size += estimate_resp_size(key) + estimate_resp_size(value);
+            }
+            size
+        }
+        RespValue::Double(_) => 32,
+        RespValue::Boolean(_) => 4,
+        RespValue::Null => 5,
+        RespValue::VerbatimString(s) => s.len() + 24,
     }
 }
 
@@ -279,3 +549,105 @@ impl Default for RespParser {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `chunks` one at a time, parsing after each as a connection would
+    fn parse_fragmented(chunks: &[&[u8]]) -> Vec<RespValue> {
+        let mut parser = RespParser::new();
+        let mut values = Vec::new();
+        for chunk in chunks {
+            parser.feed(chunk);
+            while let Some(value) = parser.parse_next().expect("valid RESP") {
+                values.push(value);
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn empty_bulk_string_split_after_length() {
+        let values = parse_fragmented(&[b"$0\r\n", b"\r\n"]);
+        assert!(matches!(
+            values.as_slice(),
+            [RespValue::BulkString(Some(empty))] if empty.is_empty()
+        ));
+
+        let values = parse_fragmented(&[b"*2\r\n$4\r\nECHO\r\n$0\r\n", b"\r\n"]);
+        let [RespValue::Array(Some(args))] = values.as_slice() else {
+            panic!("expected one array, got {:?}", values);
+        };
+        assert!(matches!(
+            args.as_slice(),
+            [RespValue::BulkString(Some(name)), RespValue::BulkString(Some(empty))]
+                if name.as_ref() == b"ECHO" && empty.is_empty()
+        ));
+    }
+
+    #[test]
+    fn empty_array_is_skipped_like_a_blank_line() {
+        let mut parser = RespParser::new();
+        parser.feed(b"*0\r\n");
+        assert!(parser.parse_next().unwrap().is_none());
+        assert_eq!(parser.buffered(), 0);
+
+        let values = parse_fragmented(&[b"*0", b"\r\n*1\r\n$4\r\nPING\r\n"]);
+        let [RespValue::Array(Some(args))] = values.as_slice() else {
+            panic!("expected one array, got {:?}", values);
+        };
+        assert!(matches!(
+            args.as_slice(),
+            [RespValue::BulkString(Some(name))] if name.as_ref() == b"PING"
+        ));
+    }
+
+    #[test]
+    fn find_crlf_needs_two_bytes() {
+        assert_eq!(find_crlf(b""), None);
+        assert_eq!(find_crlf(b"+"), None);
+        assert_eq!(find_crlf(b"\r"), None);
+        assert_eq!(find_crlf(b"+OK\r"), None);
+        assert_eq!(find_crlf(b"+OK\r\n"), Some(3));
+    }
+
+    #[test]
+    fn lone_type_byte_waits_for_the_rest() {
+        let mut parser = RespParser::new();
+        parser.feed(b"+");
+        assert!(parser.parse_next().unwrap().is_none());
+        parser.feed(b"OK\r");
+        assert!(parser.parse_next().unwrap().is_none());
+        parser.feed(b"\n");
+        assert!(matches!(
+            parser.parse_next().unwrap(),
+            Some(RespValue::SimpleString(status)) if status.as_ref() == b"OK"
+        ));
+    }
+
+    #[test]
+    fn multibulk_length_past_the_limit_is_rejected() {
+        let mut parser = RespParser::with_limits(512, 4, 1024);
+        parser.feed(b"*4\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\nd\r\n");
+        assert!(matches!(
+            parser.parse_next().unwrap(),
+            Some(RespValue::Array(Some(items))) if items.len() == 4
+        ));
+
+        // Refused from the header alone, before any element arrives
+        let mut parser = RespParser::with_limits(512, 4, 1024);
+        parser.feed(b"*5\r\n");
+        assert_eq!(
+            parser.parse_next().unwrap_err(),
+            "Protocol error: invalid multibulk length"
+        );
+
+        let mut parser = RespParser::with_limits(512, 4, 1024);
+        parser.feed(b"*-2\r\n");
+        assert_eq!(
+            parser.parse_next().unwrap_err(),
+            "Protocol error: invalid multibulk length"
+        );
+    }
+}
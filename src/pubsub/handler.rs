@@ -1,4 +1,5 @@
 use super::{BroadcastMsg, GlobalRegistry, PubSubMessage, ThreadLocalPubSub};
+use crate::glob::glob_match;
 use crate::network::{Connection, PubSubOp};
 use crate::protocol::resp::{write_resp_value, RespValue};
 use std::sync::Arc;
@@ -16,66 +17,71 @@ pub fn handle_pubsub_operation(
 
     match op {
         PubSubOp::Subscribe(channels) => {
-            let messages = pubsub_manager.subscribe(conn_id, channels);
-            for message in messages {
-                if let PubSubMessage::Subscribe { count, .. } = &message {
-                    connection.set_subscription_count(*count);
-                }
+            for message in pubsub_manager.subscribe(conn_id, channels) {
                 connection.queue_pubsub_message(message);
             }
         }
         PubSubOp::Unsubscribe(channels) => {
-            let messages = pubsub_manager.unsubscribe(conn_id, channels);
-            for message in messages {
-                if let PubSubMessage::Unsubscribe { count, .. } = &message {
-                    connection.set_subscription_count(*count);
-                }
+            for message in pubsub_manager.unsubscribe(conn_id, channels) {
                 connection.queue_pubsub_message(message);
             }
         }
         PubSubOp::PSubscribe(patterns) => {
-            let messages = pubsub_manager.psubscribe(conn_id, patterns);
-            for message in messages {
-                if let PubSubMessage::PSubscribe { count, .. } = &message {
-                    connection.set_subscription_count(*count);
-                }
+            for message in pubsub_manager.psubscribe(conn_id, patterns) {
                 connection.queue_pubsub_message(message);
             }
         }
         PubSubOp::PUnsubscribe(patterns) => {
-            let messages = pubsub_manager.punsubscribe(conn_id, patterns);
-            for message in messages {
-                if let PubSubMessage::PUnsubscribe { count, .. } = &message {
-                    connection.set_subscription_count(*count);
-                }
+            for message in pubsub_manager.punsubscribe(conn_id, patterns) {
                 connection.queue_pubsub_message(message);
             }
         }
+        PubSubOp::SSubscribe(channels) => {
+            for message in pubsub_manager.ssubscribe(conn_id, channels) {
+                connection.queue_pubsub_message(message);
+            }
+        }
+        PubSubOp::SUnsubscribe(channels) => {
+            for message in pubsub_manager.sunsubscribe(conn_id, channels) {
+                connection.queue_pubsub_message(message);
+            }
+        }
+        PubSubOp::UnsubscribeAll => pubsub_manager.connection_dropped(conn_id),
         PubSubOp::Publish { channel, message } => {
-            // First, publish locally and collect deliveries
-            local_deliveries =
-                pubsub_manager.publish_local(&channel, &bytes::Bytes::from(message.clone()));
+            // Every subscriber gets exactly one copy:
+            // - subscribers on this thread, by channel or by pattern and
+            //   including the publisher itself, come from `publish_local`
+            // - other threads are sent a Publish, which only reaches their
+            //   channel subscribers, and a PatternPublish, which only reaches
+            //   their pattern subscribers
+            // This thread is left out of both broadcasts, and `exclude_thread`
+            // makes it ignore them should one arrive anyway.
+            let payload = bytes::Bytes::from(message);
+            local_deliveries = pubsub_manager.publish_local(&channel, &payload);
 
-            // Get all threads interested in this channel
-            let channel_threads = pubsub_registry.get_channel_threads(&channel);
-            let pattern_threads = pubsub_registry.get_all_pattern_threads();
-
-            // Broadcast to channel subscribers (excluding our thread)
+            let channel_threads: Vec<_> = pubsub_registry
+                .get_channel_threads(&channel)
+                .into_iter()
+                .filter(|&thread| thread != thread_id)
+                .collect();
             let msg = BroadcastMsg::Publish {
                 channel: channel.clone(),
-                message: bytes::Bytes::from(message.clone()),
+                message: payload.clone(),
                 exclude_thread: Some(thread_id),
             };
             pubsub_registry.broadcast_to_threads(msg, &channel_threads);
 
-            // Broadcast to pattern subscribers (excluding our thread)
+            let pattern_threads: Vec<_> = pubsub_registry
+                .get_all_pattern_threads()
+                .into_iter()
+                .filter(|&thread| thread != thread_id)
+                .collect();
             let pattern_msg = BroadcastMsg::PatternPublish {
                 channel: channel.clone(),
-                message: bytes::Bytes::from(message),
+                message: payload,
                 exclude_thread: Some(thread_id),
             };
-            let pattern_thread_vec: Vec<_> = pattern_threads.into_iter().collect();
-            pubsub_registry.broadcast_to_threads(pattern_msg, &pattern_thread_vec);
+            pubsub_registry.broadcast_to_threads(pattern_msg, &pattern_threads);
 
             // Get the total count from global registry
             let total_count = pubsub_registry.get_channel_subscriber_count(&channel)
@@ -83,13 +89,35 @@ pub fn handle_pubsub_operation(
             let resp = RespValue::Integer(total_count as i64);
             write_resp_value(&mut connection.write_buffer, &resp);
         }
+        PubSubOp::SPublish { channel, message } => {
+            // Sharded channels have no patterns, so one broadcast reaches
+            // every other thread with subscribers
+            let payload = bytes::Bytes::from(message);
+            local_deliveries = pubsub_manager.shard_deliveries(&channel, &payload);
+
+            let shard_threads: Vec<_> = pubsub_registry
+                .get_shard_channel_threads(&channel)
+                .into_iter()
+                .filter(|&thread| thread != thread_id)
+                .collect();
+            let msg = BroadcastMsg::ShardPublish {
+                channel: channel.clone(),
+                message: payload,
+                exclude_thread: Some(thread_id),
+            };
+            pubsub_registry.broadcast_to_threads(msg, &shard_threads);
+
+            let total_count = pubsub_registry.get_shard_channel_subscriber_count(&channel);
+            let resp = RespValue::Integer(total_count as i64);
+            write_resp_value(&mut connection.write_buffer, &resp);
+        }
         PubSubOp::PubSubChannels { pattern } => {
             // Get all channels from the registry
             let all_channels = pubsub_registry.get_all_channels();
             let filtered = if let Some(pat) = pattern {
                 all_channels
                     .into_iter()
-                    .filter(|ch| GlobalRegistry::glob_match(&pat, ch))
+                    .filter(|ch| glob_match(&pat, ch))
                     .map(|ch| RespValue::BulkString(Some(ch.into())))
                     .collect()
             } else {
@@ -111,6 +139,30 @@ pub fn handle_pubsub_operation(
             let resp = RespValue::Array(Some(results));
             write_resp_value(&mut connection.write_buffer, &resp);
         }
+        PubSubOp::PubSubShardChannels { pattern } => {
+            let channels = pubsub_registry
+                .get_all_shard_channels()
+                .into_iter()
+                .filter(|ch| pattern.as_ref().is_none_or(|pat| glob_match(pat, ch)))
+                .map(|ch| RespValue::BulkString(Some(ch.into())))
+                .collect();
+            write_resp_value(
+                &mut connection.write_buffer,
+                &RespValue::Array(Some(channels)),
+            );
+        }
+        PubSubOp::PubSubShardNumSub { channels } => {
+            let mut results = Vec::new();
+            for channel in channels {
+                let count = pubsub_registry.get_shard_channel_subscriber_count(&channel);
+                results.push(RespValue::BulkString(Some(channel.into())));
+                results.push(RespValue::Integer(count as i64));
+            }
+            write_resp_value(
+                &mut connection.write_buffer,
+                &RespValue::Array(Some(results)),
+            );
+        }
         PubSubOp::PubSubNumPat => {
             let count = pubsub_registry.get_pattern_count();
             let resp = RespValue::Integer(count as i64);
@@ -118,5 +170,12 @@ pub fn handle_pubsub_operation(
         }
     }
 
+    // A connection stays in pub/sub mode while it has any kind of subscription
+    connection.set_subscription_counts(
+        pubsub_manager.get_connection_channel_count(conn_id),
+        pubsub_manager.get_connection_pattern_count(conn_id),
+        pubsub_manager.get_connection_shard_count(conn_id),
+    );
+
     local_deliveries
 }
@@ -13,6 +13,8 @@ pub struct ThreadLocalPubSub {
     registry: Arc<GlobalRegistry>,
     connection_channels: HashMap<ConnectionId, Vec<Vec<u8>>>,
     connection_patterns: HashMap<ConnectionId, Vec<Vec<u8>>>,
+    shard_subs: HashMap<Vec<u8>, Vec<ConnectionId>>,
+    connection_shard_channels: HashMap<ConnectionId, Vec<Vec<u8>>>,
 }
 
 impl ThreadLocalPubSub {
@@ -30,6 +32,8 @@ impl ThreadLocalPubSub {
             registry,
             connection_channels: HashMap::new(),
             connection_patterns: HashMap::new(),
+            shard_subs: HashMap::new(),
+            connection_shard_channels: HashMap::new(),
         }
     }
 
@@ -276,10 +280,110 @@ impl ThreadLocalPubSub {
         messages
     }
 
+    /// Subscribe to sharded channels
+    ///
+    /// Replies count only the connection's sharded channels, as in Redis.
+    pub fn ssubscribe(
+        &mut self,
+        conn_id: ConnectionId,
+        channels: Vec<Vec<u8>>,
+    ) -> Vec<PubSubMessage> {
+        let mut messages = Vec::new();
+
+        for channel in channels {
+            let is_new_channel = !self.shard_subs.contains_key(&channel);
+            let already_subscribed = self
+                .shard_subs
+                .get(&channel)
+                .map(|subs| subs.contains(&conn_id))
+                .unwrap_or(false);
+
+            if !already_subscribed {
+                self.shard_subs
+                    .entry(channel.clone())
+                    .or_default()
+                    .push(conn_id);
+                self.connection_shard_channels
+                    .entry(conn_id)
+                    .or_default()
+                    .push(channel.clone());
+                self.registry.increment_shard_channel_subscribers(&channel);
+            }
+
+            if is_new_channel {
+                self.registry
+                    .add_shard_channel_interest(channel.clone(), self.thread_id);
+            }
+
+            messages.push(PubSubMessage::SSubscribe {
+                channel,
+                count: self.get_connection_shard_count(conn_id),
+            });
+        }
+
+        messages
+    }
+
+    pub fn sunsubscribe(
+        &mut self,
+        conn_id: ConnectionId,
+        channels: Option<Vec<Vec<u8>>>,
+    ) -> Vec<PubSubMessage> {
+        let channels = match channels {
+            Some(channels) => channels,
+            None => self
+                .connection_shard_channels
+                .get(&conn_id)
+                .cloned()
+                .unwrap_or_default(),
+        };
+
+        let mut messages = Vec::new();
+        for channel in channels {
+            if let Some(subs) = self.shard_subs.get_mut(&channel) {
+                let was_subscribed = subs.contains(&conn_id);
+                subs.retain(|&id| id != conn_id);
+                if was_subscribed {
+                    self.registry.decrement_shard_channel_subscribers(&channel);
+                }
+                if subs.is_empty() {
+                    self.shard_subs.remove(&channel);
+                    self.registry
+                        .remove_shard_channel_interest(&channel, self.thread_id);
+                }
+            }
+
+            if let Some(conn_channels) = self.connection_shard_channels.get_mut(&conn_id) {
+                conn_channels.retain(|c| c != &channel);
+                if conn_channels.is_empty() {
+                    self.connection_shard_channels.remove(&conn_id);
+                }
+            }
+
+            messages.push(PubSubMessage::SUnsubscribe {
+                channel: Some(channel),
+                count: self.get_connection_shard_count(conn_id),
+            });
+        }
+
+        messages
+    }
+
     pub fn publish_local(
         &self,
         channel: &[u8],
         message: &Bytes,
+    ) -> Vec<(ConnectionId, PubSubMessage)> {
+        let mut deliveries = self.channel_deliveries(channel, message);
+        deliveries.extend(self.pattern_deliveries(channel, message));
+        deliveries
+    }
+
+    /// Messages for connections subscribed to exactly this channel
+    fn channel_deliveries(
+        &self,
+        channel: &[u8],
+        message: &Bytes,
     ) -> Vec<(ConnectionId, PubSubMessage)> {
         let mut deliveries = Vec::new();
 
@@ -295,21 +399,55 @@ impl ThreadLocalPubSub {
             }
         }
 
-        let pattern_matches = self.pattern_trie.find_matches(channel);
-        for (pattern, conn_id) in pattern_matches {
-            deliveries.push((
-                conn_id,
-                PubSubMessage::PatternMessage {
-                    pattern,
-                    channel: channel.to_vec(),
-                    payload: message.clone(),
-                },
-            ));
-        }
-
         deliveries
     }
 
+    /// Messages for connections subscribed to this sharded channel
+    pub fn shard_deliveries(
+        &self,
+        channel: &[u8],
+        message: &Bytes,
+    ) -> Vec<(ConnectionId, PubSubMessage)> {
+        self.shard_subs
+            .get(channel)
+            .map(|subs| {
+                subs.iter()
+                    .map(|&conn_id| {
+                        (
+                            conn_id,
+                            PubSubMessage::ShardMessage {
+                                channel: channel.to_vec(),
+                                payload: message.clone(),
+                            },
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Messages for connections with a pattern matching this channel
+    fn pattern_deliveries(
+        &self,
+        channel: &[u8],
+        message: &Bytes,
+    ) -> Vec<(ConnectionId, PubSubMessage)> {
+        self.pattern_trie
+            .find_matches(channel)
+            .into_iter()
+            .map(|(pattern, conn_id)| {
+                (
+                    conn_id,
+                    PubSubMessage::PatternMessage {
+                        pattern,
+                        channel: channel.to_vec(),
+                        payload: message.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+
     pub fn process_inbox(&mut self) -> Vec<(ConnectionId, PubSubMessage)> {
         let mut deliveries = Vec::new();
 
@@ -320,9 +458,9 @@ impl ThreadLocalPubSub {
                     message,
                     exclude_thread,
                 } => {
+                    // Pattern subscribers are reached by the matching PatternPublish
                     if Some(self.thread_id) != exclude_thread {
-                        let local_deliveries = self.publish_local(&channel, &message);
-                        deliveries.extend(local_deliveries);
+                        deliveries.extend(self.channel_deliveries(&channel, &message));
                     }
                 }
                 BroadcastMsg::PatternPublish {
@@ -331,17 +469,16 @@ impl ThreadLocalPubSub {
                     exclude_thread,
                 } => {
                     if Some(self.thread_id) != exclude_thread {
-                        let pattern_matches = self.pattern_trie.find_matches(&channel);
-                        for (pattern, conn_id) in pattern_matches {
-                            deliveries.push((
-                                conn_id,
-                                PubSubMessage::PatternMessage {
-                                    pattern,
-                                    channel: channel.clone(),
-                                    payload: message.clone(),
-                                },
-                            ));
-                        }
+                        deliveries.extend(self.pattern_deliveries(&channel, &message));
+                    }
+                }
+                BroadcastMsg::ShardPublish {
+                    channel,
+                    message,
+                    exclude_thread,
+                } => {
+                    if Some(self.thread_id) != exclude_thread {
+                        deliveries.extend(self.shard_deliveries(&channel, &message));
                     }
                 }
             }
@@ -353,25 +490,44 @@ impl ThreadLocalPubSub {
     pub fn connection_dropped(&mut self, conn_id: ConnectionId) {
         self.unsubscribe(conn_id, None);
         self.punsubscribe(conn_id, None);
+        self.sunsubscribe(conn_id, None);
     }
 
     pub fn get_connection_subscription_count(&self, conn_id: ConnectionId) -> usize {
-        let channel_count = self
-            .connection_channels
+        self.get_connection_channel_count(conn_id) + self.get_connection_pattern_count(conn_id)
+    }
+
+    pub fn get_connection_channel_count(&self, conn_id: ConnectionId) -> usize {
+        self.connection_channels
             .get(&conn_id)
             .map(|c| c.len())
-            .unwrap_or(0);
-        let pattern_count = self
-            .connection_patterns
+            .unwrap_or(0)
+    }
+
+    pub fn get_connection_pattern_count(&self, conn_id: ConnectionId) -> usize {
+        self.connection_patterns
             .get(&conn_id)
             .map(|p| p.len())
-            .unwrap_or(0);
-        channel_count + pattern_count
+            .unwrap_or(0)
+    }
+
+    pub fn get_connection_shard_count(&self, conn_id: ConnectionId) -> usize {
+        self.connection_shard_channels
+            .get(&conn_id)
+            .map(|c| c.len())
+            .unwrap_or(0)
+    }
+
+    /// Channels, patterns and sharded channels the connection is subscribed
+    /// to; it stays in pub/sub mode while this is non-zero
+    pub fn total_subscription_count(&self, conn_id: ConnectionId) -> usize {
+        self.get_connection_subscription_count(conn_id) + self.get_connection_shard_count(conn_id)
     }
 
     pub fn is_connection_subscribed(&self, conn_id: ConnectionId) -> bool {
         self.connection_channels.contains_key(&conn_id)
             || self.connection_patterns.contains_key(&conn_id)
+            || self.connection_shard_channels.contains_key(&conn_id)
     }
 
     pub fn get_all_channels(&self) -> Vec<Vec<u8>> {
@@ -13,6 +13,11 @@ pub enum BroadcastMsg {
         message: Bytes,
         exclude_thread: Option<ThreadId>,
     },
+    ShardPublish {
+        channel: Vec<u8>,
+        message: Bytes,
+        exclude_thread: Option<ThreadId>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +47,18 @@ pub enum PubSubMessage {
         pattern: Option<Vec<u8>>,
         count: usize,
     },
+    ShardMessage {
+        channel: Vec<u8>,
+        payload: Bytes,
+    },
+    SSubscribe {
+        channel: Vec<u8>,
+        count: usize,
+    },
+    SUnsubscribe {
+        channel: Option<Vec<u8>>,
+        count: usize,
+    },
 }
 
 impl PubSubMessage {
@@ -49,41 +66,56 @@ impl PubSubMessage {
         use crate::protocol::resp::RespValue;
 
         match self {
-            PubSubMessage::Message { channel, payload } => RespValue::Array(Some(vec![
+            PubSubMessage::Message { channel, payload } => RespValue::Push(vec![
                 RespValue::BulkString(Some(Bytes::from_static(b"message"))),
                 RespValue::BulkString(Some(Bytes::from(channel.clone()))),
                 RespValue::BulkString(Some(payload.clone())),
-            ])),
+            ]),
             PubSubMessage::PatternMessage {
                 pattern,
                 channel,
                 payload,
-            } => RespValue::Array(Some(vec![
+            } => RespValue::Push(vec![
                 RespValue::BulkString(Some(Bytes::from_static(b"pmessage"))),
                 RespValue::BulkString(Some(Bytes::from(pattern.clone()))),
                 RespValue::BulkString(Some(Bytes::from(channel.clone()))),
                 RespValue::BulkString(Some(payload.clone())),
-            ])),
-            PubSubMessage::Subscribe { channel, count } => RespValue::Array(Some(vec![
+            ]),
+            PubSubMessage::Subscribe { channel, count } => RespValue::Push(vec![
                 RespValue::BulkString(Some(Bytes::from_static(b"subscribe"))),
                 RespValue::BulkString(Some(Bytes::from(channel.clone()))),
                 RespValue::Integer(*count as i64),
-            ])),
-            PubSubMessage::Unsubscribe { channel, count } => RespValue::Array(Some(vec![
+            ]),
+            PubSubMessage::Unsubscribe { channel, count } => RespValue::Push(vec![
                 RespValue::BulkString(Some(Bytes::from_static(b"unsubscribe"))),
                 RespValue::BulkString(channel.as_ref().map(|c| Bytes::from(c.clone()))),
                 RespValue::Integer(*count as i64),
-            ])),
-            PubSubMessage::PSubscribe { pattern, count } => RespValue::Array(Some(vec![
+            ]),
+            PubSubMessage::PSubscribe { pattern, count } => RespValue::Push(vec![
                 RespValue::BulkString(Some(Bytes::from_static(b"psubscribe"))),
                 RespValue::BulkString(Some(Bytes::from(pattern.clone()))),
                 RespValue::Integer(*count as i64),
-            ])),
-            PubSubMessage::PUnsubscribe { pattern, count } => RespValue::Array(Some(vec![
+            ]),
+            PubSubMessage::PUnsubscribe { pattern, count } => RespValue::Push(vec![
                 RespValue::BulkString(Some(Bytes::from_static(b"punsubscribe"))),
                 RespValue::BulkString(pattern.as_ref().map(|p| Bytes::from(p.clone()))),
                 RespValue::Integer(*count as i64),
-            ])),
+            ]),
+            PubSubMessage::ShardMessage { channel, payload } => RespValue::Push(vec![
+                RespValue::BulkString(Some(Bytes::from_static(b"smessage"))),
+                RespValue::BulkString(Some(Bytes::from(channel.clone()))),
+                RespValue::BulkString(Some(payload.clone())),
+            ]),
+            PubSubMessage::SSubscribe { channel, count } => RespValue::Push(vec![
+                RespValue::BulkString(Some(Bytes::from_static(b"ssubscribe"))),
+                RespValue::BulkString(Some(Bytes::from(channel.clone()))),
+                RespValue::Integer(*count as i64),
+            ]),
+            PubSubMessage::SUnsubscribe { channel, count } => RespValue::Push(vec![
+                RespValue::BulkString(Some(Bytes::from_static(b"sunsubscribe"))),
+                RespValue::BulkString(channel.as_ref().map(|c| Bytes::from(c.clone()))),
+                RespValue::Integer(*count as i64),
+            ]),
         }
     }
 }
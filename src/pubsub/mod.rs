@@ -1,12 +1,17 @@
 mod handler;
 mod manager;
 mod message;
+mod notify;
 mod pattern;
 mod registry;
 
 pub use handler::handle_pubsub_operation;
 pub use manager::ThreadLocalPubSub;
 pub use message::{BroadcastMsg, PubSubMessage};
+pub use notify::{
+    notify_flags_to_string, parse_notify_flags, KeyspaceNotifier, NOTIFY_EXPIRED, NOTIFY_GENERIC,
+    NOTIFY_STRING,
+};
 pub use pattern::PatternTrie;
 pub use registry::GlobalRegistry;
 
@@ -0,0 +1,204 @@
+use super::GlobalRegistry;
+use crate::config::RuntimeConfig;
+use crate::protocol::db_key_prefix;
+use bytes::Bytes;
+use feoxdb::FeoxStore;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// `notify-keyspace-events` flags, one bit per character
+pub const NOTIFY_KEYSPACE: u32 = 1 << 0; // K
+pub const NOTIFY_KEYEVENT: u32 = 1 << 1; // E
+pub const NOTIFY_GENERIC: u32 = 1 << 2; // g
+pub const NOTIFY_STRING: u32 = 1 << 3; // $
+pub const NOTIFY_LIST: u32 = 1 << 4; // l
+pub const NOTIFY_SET: u32 = 1 << 5; // s
+pub const NOTIFY_HASH: u32 = 1 << 6; // h
+pub const NOTIFY_ZSET: u32 = 1 << 7; // z
+pub const NOTIFY_EXPIRED: u32 = 1 << 8; // x
+pub const NOTIFY_EVICTED: u32 = 1 << 9; // e
+pub const NOTIFY_ALL: u32 = NOTIFY_GENERIC
+    | NOTIFY_STRING
+    | NOTIFY_LIST
+    | NOTIFY_SET
+    | NOTIFY_HASH
+    | NOTIFY_ZSET
+    | NOTIFY_EXPIRED
+    | NOTIFY_EVICTED; // A
+
+const CLASS_FLAGS: [(char, u32); 8] = [
+    ('g', NOTIFY_GENERIC),
+    ('$', NOTIFY_STRING),
+    ('l', NOTIFY_LIST),
+    ('s', NOTIFY_SET),
+    ('h', NOTIFY_HASH),
+    ('z', NOTIFY_ZSET),
+    ('x', NOTIFY_EXPIRED),
+    ('e', NOTIFY_EVICTED),
+];
+
+/// Parse a `notify-keyspace-events` string such as `KEA` or `Ex`
+pub fn parse_notify_flags(flags: &str) -> Option<u32> {
+    let mut bits = 0;
+    for c in flags.chars() {
+        bits |= match c {
+            'K' => NOTIFY_KEYSPACE,
+            'E' => NOTIFY_KEYEVENT,
+            'A' => NOTIFY_ALL,
+            _ => CLASS_FLAGS.iter().find(|(flag, _)| *flag == c)?.1,
+        };
+    }
+    Some(bits)
+}
+
+/// Format flags back into the string CONFIG GET reports
+pub fn notify_flags_to_string(bits: u32) -> String {
+    let mut flags = String::new();
+    if bits & NOTIFY_ALL == NOTIFY_ALL {
+        flags.push('A');
+    } else {
+        for (c, bit) in CLASS_FLAGS {
+            if bits & bit != 0 {
+                flags.push(c);
+            }
+        }
+    }
+    if bits & NOTIFY_KEYSPACE != 0 {
+        flags.push('K');
+    }
+    if bits & NOTIFY_KEYEVENT != 0 {
+        flags.push('E');
+    }
+    flags
+}
+
+fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Keys with a TTL, watched so an `expired` event can be sent once they go
+#[derive(Default)]
+struct ExpiryQueue {
+    // (deadline in ms, db, stored key)
+    heap: BinaryHeap<Reverse<(u64, usize, Vec<u8>)>>,
+    // Latest deadline per key; heap entries that disagree are stale
+    deadlines: HashMap<(usize, Vec<u8>), u64>,
+}
+
+/// Publishes keyspace notifications through the pub/sub registry
+///
+/// FeOx expires keys internally without a callback, so keys given a TTL are
+/// tracked here and reported as `expired` once the deadline has passed and
+/// the key is gone.
+pub struct KeyspaceNotifier {
+    registry: Arc<GlobalRegistry>,
+    runtime: Arc<RuntimeConfig>,
+    expiring: Mutex<ExpiryQueue>,
+}
+
+impl KeyspaceNotifier {
+    pub fn new(registry: Arc<GlobalRegistry>, runtime: Arc<RuntimeConfig>) -> Self {
+        Self {
+            registry,
+            runtime,
+            expiring: Mutex::new(ExpiryQueue::default()),
+        }
+    }
+
+    /// Check whether any notifications are configured at all
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.runtime.notify_flags() & (NOTIFY_KEYSPACE | NOTIFY_KEYEVENT) != 0
+    }
+
+    /// Check whether events of this class are configured to be sent
+    #[inline]
+    pub fn is_enabled(&self, class: u32) -> bool {
+        let flags = self.runtime.notify_flags();
+        flags & (NOTIFY_KEYSPACE | NOTIFY_KEYEVENT) != 0 && flags & class != 0
+    }
+
+    /// Send `event` for a logical key in `db` to the keyspace and keyevent channels
+    pub fn notify(&self, class: u32, event: &str, key: &[u8], db: usize) {
+        let flags = self.runtime.notify_flags();
+        if flags & class == 0 {
+            return;
+        }
+
+        if flags & NOTIFY_KEYSPACE != 0 {
+            let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+            channel.extend_from_slice(key);
+            self.registry
+                .publish(channel, Bytes::copy_from_slice(event.as_bytes()));
+        }
+        if flags & NOTIFY_KEYEVENT != 0 {
+            let channel = format!("__keyevent@{}__:{}", db, event).into_bytes();
+            self.registry.publish(channel, Bytes::copy_from_slice(key));
+        }
+    }
+
+    /// Remember that a stored key expires after `ttl_secs`
+    pub fn track_expiry(&self, db: usize, key: &[u8], ttl_secs: u64) {
+        if !self.is_enabled(NOTIFY_EXPIRED) {
+            return;
+        }
+        let deadline = unix_time_millis() + ttl_secs * 1000;
+        let mut queue = self.expiring.lock().unwrap();
+        queue.deadlines.insert((db, key.to_vec()), deadline);
+        queue.heap.push(Reverse((deadline, db, key.to_vec())));
+    }
+
+    /// Stop tracking a key whose TTL was removed or that was deleted
+    pub fn forget_expiry(&self, db: usize, key: &[u8]) {
+        let mut queue = self.expiring.lock().unwrap();
+        if !queue.deadlines.is_empty() {
+            queue.deadlines.remove(&(db, key.to_vec()));
+        }
+    }
+
+    /// Send `expired` for tracked keys whose deadline has passed
+    ///
+    /// Called from every worker loop; only one thread does the work at a time.
+    pub fn publish_expired(&self, store: &FeoxStore) {
+        let Ok(mut queue) = self.expiring.try_lock() else {
+            return;
+        };
+
+        let now = unix_time_millis();
+        while let Some(Reverse((deadline, _, _))) = queue.heap.peek() {
+            if *deadline > now {
+                break;
+            }
+            let Reverse((deadline, db, key)) = queue.heap.pop().unwrap();
+
+            let entry = (db, key);
+            if queue.deadlines.get(&entry) != Some(&deadline) {
+                continue;
+            }
+            // TTLs are whole seconds, so the key may outlive the deadline a
+            // little; check again shortly unless the TTL was removed
+            if store.get_bytes(&entry.1).is_ok() {
+                match store.get_ttl(&entry.1) {
+                    Ok(Some(secs)) => {
+                        let next = now + (secs * 1000).max(100);
+                        queue.deadlines.insert(entry.clone(), next);
+                        queue.heap.push(Reverse((next, entry.0, entry.1)));
+                    }
+                    _ => {
+                        queue.deadlines.remove(&entry);
+                    }
+                }
+                continue;
+            }
+            queue.deadlines.remove(&entry);
+
+            let logical = &entry.1[db_key_prefix(db).len()..];
+            self.notify(NOTIFY_EXPIRED, "expired", logical, db);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use super::ConnectionId;
-use std::collections::HashMap;
+use crate::glob::{class_end, glob_match};
+use std::collections::{HashMap, HashSet};
 
 pub struct PatternTrie {
     root: TrieNode,
@@ -15,14 +16,35 @@ struct TrieNode {
     children: HashMap<u8, Box<TrieNode>>,
     wildcard_child: Option<Box<TrieNode>>,
     single_char_child: Option<Box<TrieNode>>,
-    char_class_child: Option<Box<CharClassNode>>,
+    // Keyed by the class as written, brackets included, so patterns with
+    // different classes at the same position don't share a node
+    char_class_children: Vec<(Vec<u8>, Box<TrieNode>)>,
     subscribers: Vec<(Vec<u8>, ConnectionId)>,
 }
 
-struct CharClassNode {
-    chars: Vec<u8>,
-    negated: bool,
-    next: Box<TrieNode>,
+/// One step of a pattern, as stored in the trie
+enum Token<'a> {
+    Literal(u8),
+    Wildcard,
+    SingleChar,
+    CharClass(&'a [u8]),
+}
+
+/// Split the next token off `pattern` at `i`, returning it and its length
+///
+/// Uses the same rules as `glob_match`, so the trie and the matcher agree
+/// on where classes and escapes end.
+fn next_token(pattern: &[u8], i: usize) -> (Token<'_>, usize) {
+    match pattern[i] {
+        b'*' => (Token::Wildcard, 1),
+        b'?' => (Token::SingleChar, 1),
+        b'[' => {
+            let end = class_end(pattern, i);
+            (Token::CharClass(&pattern[i..end]), end - i)
+        }
+        b'\\' if i + 1 < pattern.len() => (Token::Literal(pattern[i + 1]), 2),
+        c => (Token::Literal(c), 1),
+    }
 }
 
 impl PatternTrie {
@@ -37,178 +59,121 @@ impl PatternTrie {
         let mut i = 0;
 
         while i < pattern.len() {
-            match pattern[i] {
-                b'*' => {
-                    if node.wildcard_child.is_none() {
-                        node.wildcard_child = Some(Box::new(TrieNode::new()));
-                    }
-                    node = node.wildcard_child.as_mut().unwrap();
-                    i += 1;
-                }
-                b'?' => {
-                    if node.single_char_child.is_none() {
-                        node.single_char_child = Some(Box::new(TrieNode::new()));
-                    }
-                    node = node.single_char_child.as_mut().unwrap();
-                    i += 1;
-                }
-                b'[' => {
-                    let (char_class, next_i) = parse_char_class(&pattern[i..]);
-                    if let Some((chars, negated)) = char_class {
-                        if node.char_class_child.is_none() {
-                            node.char_class_child = Some(Box::new(CharClassNode {
-                                chars,
-                                negated,
-                                next: Box::new(TrieNode::new()),
-                            }));
+            let (token, len) = next_token(pattern, i);
+            node = match token {
+                Token::Wildcard => node
+                    .wildcard_child
+                    .get_or_insert_with(|| Box::new(TrieNode::new())),
+                Token::SingleChar => node
+                    .single_char_child
+                    .get_or_insert_with(|| Box::new(TrieNode::new())),
+                Token::CharClass(class) => {
+                    let index = match node
+                        .char_class_children
+                        .iter()
+                        .position(|(c, _)| c == class)
+                    {
+                        Some(index) => index,
+                        None => {
+                            node.char_class_children
+                                .push((class.to_vec(), Box::new(TrieNode::new())));
+                            node.char_class_children.len() - 1
                         }
-                        node = &mut node.char_class_child.as_mut().unwrap().next;
-                        i += next_i;
-                    } else {
-                        node = node
-                            .children
-                            .entry(pattern[i])
-                            .or_insert_with(|| Box::new(TrieNode::new()));
-                        i += 1;
-                    }
-                }
-                b'\\' if i + 1 < pattern.len() => {
-                    i += 1;
-                    node = node
-                        .children
-                        .entry(pattern[i])
-                        .or_insert_with(|| Box::new(TrieNode::new()));
-                    i += 1;
-                }
-                c => {
-                    node = node
-                        .children
-                        .entry(c)
-                        .or_insert_with(|| Box::new(TrieNode::new()));
-                    i += 1;
+                    };
+                    &mut node.char_class_children[index].1
                 }
-            }
+                Token::Literal(c) => node
+                    .children
+                    .entry(c)
+                    .or_insert_with(|| Box::new(TrieNode::new())),
+            };
+            i += len;
         }
 
         node.subscribers.push((pattern.to_vec(), conn_id));
     }
 
     pub fn remove(&mut self, pattern: &[u8], conn_id: ConnectionId) -> bool {
-        Self::remove_from_node(&mut self.root, pattern, 0, pattern, conn_id)
+        Self::remove_from_node(&mut self.root, pattern, 0, conn_id)
     }
 
     fn remove_from_node(
         node: &mut TrieNode,
         pattern: &[u8],
         pos: usize,
-        full_pattern: &[u8],
         conn_id: ConnectionId,
     ) -> bool {
         if pos == pattern.len() {
             let before_len = node.subscribers.len();
             node.subscribers
-                .retain(|(p, id)| !(p == full_pattern && *id == conn_id));
+                .retain(|(p, id)| !(p == pattern && *id == conn_id));
             return node.subscribers.len() < before_len;
         }
 
-        match pattern[pos] {
-            b'*' => {
-                if let Some(ref mut child) = node.wildcard_child {
-                    return Self::remove_from_node(child, pattern, pos + 1, full_pattern, conn_id);
-                }
-            }
-            b'?' => {
-                if let Some(ref mut child) = node.single_char_child {
-                    return Self::remove_from_node(child, pattern, pos + 1, full_pattern, conn_id);
-                }
-            }
-            b'[' => {
-                let (char_class, next_i) = parse_char_class(&pattern[pos..]);
-                if char_class.is_some() {
-                    if let Some(ref mut child) = node.char_class_child {
-                        return Self::remove_from_node(
-                            &mut child.next,
-                            pattern,
-                            pos + next_i,
-                            full_pattern,
-                            conn_id,
-                        );
-                    }
-                } else if let Some(ref mut child) = node.children.get_mut(&pattern[pos]) {
-                    return Self::remove_from_node(child, pattern, pos + 1, full_pattern, conn_id);
-                }
-            }
-            b'\\' if pos + 1 < pattern.len() => {
-                if let Some(ref mut child) = node.children.get_mut(&pattern[pos + 1]) {
-                    return Self::remove_from_node(child, pattern, pos + 2, full_pattern, conn_id);
-                }
-            }
-            c => {
-                if let Some(ref mut child) = node.children.get_mut(&c) {
-                    return Self::remove_from_node(child, pattern, pos + 1, full_pattern, conn_id);
-                }
-            }
+        let (token, len) = next_token(pattern, pos);
+        let child = match token {
+            Token::Wildcard => node.wildcard_child.as_mut(),
+            Token::SingleChar => node.single_char_child.as_mut(),
+            Token::CharClass(class) => node
+                .char_class_children
+                .iter_mut()
+                .find(|(c, _)| c == class)
+                .map(|(_, child)| child),
+            Token::Literal(c) => node.children.get_mut(&c),
+        };
+
+        match child {
+            Some(child) => Self::remove_from_node(child, pattern, pos + len, conn_id),
+            None => false,
         }
-
-        false
     }
 
+    /// Every (pattern, connection) subscription matching the channel, once
     pub fn find_matches(&self, channel: &[u8]) -> Vec<(Vec<u8>, ConnectionId)> {
-        let mut matches = Vec::new();
-        self.find_in_node(&self.root, channel, 0, &mut matches);
-        matches
+        let mut matches = HashSet::new();
+        Self::find_in_node(&self.root, channel, 0, &mut matches);
+        matches.into_iter().collect()
     }
 
+    /// Collect subscribers whose pattern matches `channel[pos..]` from `node`
+    ///
+    /// A pattern with several wildcards can reach the same node by more than
+    /// one split of the channel, so results are gathered into a set.
     fn find_in_node(
-        &self,
         node: &TrieNode,
         channel: &[u8],
         pos: usize,
-        matches: &mut Vec<(Vec<u8>, ConnectionId)>,
+        matches: &mut HashSet<(Vec<u8>, ConnectionId)>,
     ) {
-        if pos == channel.len() {
-            matches.extend(node.subscribers.clone());
-
-            if let Some(ref wildcard) = node.wildcard_child {
-                matches.extend(wildcard.subscribers.clone());
+        // `*` consumes any run of characters, including none
+        if let Some(ref wildcard) = node.wildcard_child {
+            for i in pos..=channel.len() {
+                Self::find_in_node(wildcard, channel, i, matches);
             }
+        }
+
+        if pos == channel.len() {
+            matches.extend(node.subscribers.iter().cloned());
             return;
         }
 
         let current_char = channel[pos];
 
         if let Some(child) = node.children.get(&current_char) {
-            self.find_in_node(child, channel, pos + 1, matches);
+            Self::find_in_node(child, channel, pos + 1, matches);
         }
 
         if let Some(ref single) = node.single_char_child {
-            self.find_in_node(single, channel, pos + 1, matches);
-        }
-
-        if let Some(ref char_class) = node.char_class_child {
-            if self.matches_char_class(current_char, &char_class.chars, char_class.negated) {
-                self.find_in_node(&char_class.next, channel, pos + 1, matches);
-            }
+            Self::find_in_node(single, channel, pos + 1, matches);
         }
 
-        if let Some(ref wildcard) = node.wildcard_child {
-            matches.extend(wildcard.subscribers.clone());
-
-            for i in pos..=channel.len() {
-                self.find_in_node(wildcard, channel, i, matches);
+        for (class, child) in &node.char_class_children {
+            if glob_match(class, &[current_char]) {
+                Self::find_in_node(child, channel, pos + 1, matches);
             }
         }
     }
 
-    fn matches_char_class(&self, c: u8, chars: &[u8], negated: bool) -> bool {
-        let matches = chars.contains(&c);
-        if negated {
-            !matches
-        } else {
-            matches
-        }
-    }
-
     pub fn clear(&mut self) {
         self.root = TrieNode::new();
     }
@@ -218,7 +183,7 @@ impl PatternTrie {
         Self::collect_patterns(&self.root, &mut patterns);
         patterns
             .into_iter()
-            .collect::<std::collections::HashSet<_>>()
+            .collect::<HashSet<_>>()
             .into_iter()
             .collect()
     }
@@ -240,52 +205,10 @@ impl PatternTrie {
             Self::collect_patterns(single, patterns);
         }
 
-        if let Some(ref char_class) = node.char_class_child {
-            Self::collect_patterns(&char_class.next, patterns);
-        }
-    }
-}
-
-fn parse_char_class(pattern: &[u8]) -> (Option<(Vec<u8>, bool)>, usize) {
-    if pattern.is_empty() || pattern[0] != b'[' {
-        return (None, 0);
-    }
-
-    let mut i = 1;
-    let negated = pattern.get(1) == Some(&b'^');
-    if negated {
-        i = 2;
-    }
-
-    let mut chars = Vec::new();
-    let mut escaped = false;
-
-    while i < pattern.len() {
-        if escaped {
-            chars.push(pattern[i]);
-            escaped = false;
-        } else if pattern[i] == b'\\' {
-            escaped = true;
-        } else if pattern[i] == b']' {
-            return (Some((chars, negated)), i + 1);
-        } else if pattern[i] == b'-'
-            && !chars.is_empty()
-            && i + 1 < pattern.len()
-            && pattern[i + 1] != b']'
-        {
-            let start = *chars.last().unwrap();
-            let end = pattern[i + 1];
-            for c in (start + 1)..=end {
-                chars.push(c);
-            }
-            i += 1;
-        } else {
-            chars.push(pattern[i]);
+        for (_, child) in &node.char_class_children {
+            Self::collect_patterns(child, patterns);
         }
-        i += 1;
     }
-
-    (None, 0)
 }
 
 impl TrieNode {
@@ -294,8 +217,67 @@ impl TrieNode {
             children: HashMap::new(),
             wildcard_child: None,
             single_char_child: None,
-            char_class_child: None,
+            char_class_children: Vec::new(),
             subscribers: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERNS: [&[u8]; 3] = [b"a*b*c", b"**", b"a*"];
+    const CHANNELS: [&[u8]; 10] = [
+        b"", b"a", b"abc", b"axbxc", b"abbc", b"abcabc", b"ab", b"ac", b"bac", b"aXbYcZ",
+    ];
+
+    fn trie() -> PatternTrie {
+        let mut trie = PatternTrie::new();
+        for (conn_id, pattern) in PATTERNS.iter().enumerate() {
+            trie.insert(pattern, conn_id as ConnectionId);
+        }
+        trie
+    }
+
+    #[test]
+    fn wildcards_match_as_glob_does() {
+        let trie = trie();
+        for channel in CHANNELS {
+            let mut found: Vec<_> = trie
+                .find_matches(channel)
+                .into_iter()
+                .map(|(pattern, _)| pattern)
+                .collect();
+            found.sort();
+            let mut expected: Vec<_> = PATTERNS
+                .iter()
+                .filter(|pattern| glob_match(pattern, channel))
+                .map(|pattern| pattern.to_vec())
+                .collect();
+            expected.sort();
+            assert_eq!(found, expected, "channel {:?}", channel);
+        }
+    }
+
+    #[test]
+    fn each_subscription_matches_once() {
+        let mut trie = trie();
+        // A second connection on a shared pattern is its own subscription
+        trie.insert(b"a*b*c", 9);
+        for channel in CHANNELS {
+            let matches = trie.find_matches(channel);
+            let unique: HashSet<_> = matches.iter().cloned().collect();
+            assert_eq!(matches.len(), unique.len(), "channel {:?}", channel);
+        }
+
+        let matches = trie.find_matches(b"axbxcxbxc");
+        let mut conns: Vec<_> = matches
+            .iter()
+            .filter(|(pattern, _)| pattern == b"a*b*c")
+            .map(|(_, conn_id)| *conn_id)
+            .collect();
+        conns.sort();
+        assert_eq!(conns, vec![0, 9]);
+    }
+}
@@ -1,4 +1,6 @@
 use super::{BroadcastMsg, PubSubStats, ThreadId};
+use crate::glob::glob_match;
+use bytes::Bytes;
 use crossbeam_channel::{bounded, Receiver, Sender};
 use dashmap::DashMap;
 use std::collections::HashSet;
@@ -10,6 +12,10 @@ pub struct GlobalRegistry {
     pattern_to_threads: DashMap<Vec<u8>, HashSet<ThreadId>>,
     channel_subscriber_counts: DashMap<Vec<u8>, usize>,
     pattern_subscriber_counts: DashMap<Vec<u8>, usize>,
+    // Sharded channels are a namespace of their own: SPUBLISH only reaches
+    // SSUBSCRIBE subscribers and PUBLISH never does
+    shard_channel_to_threads: DashMap<Vec<u8>, HashSet<ThreadId>>,
+    shard_channel_subscriber_counts: DashMap<Vec<u8>, usize>,
     thread_channels: Vec<Sender<BroadcastMsg>>,
     pub stats: Arc<PubSubStats>,
 }
@@ -30,6 +36,8 @@ impl GlobalRegistry {
             pattern_to_threads: DashMap::new(),
             channel_subscriber_counts: DashMap::new(),
             pattern_subscriber_counts: DashMap::new(),
+            shard_channel_to_threads: DashMap::new(),
+            shard_channel_subscriber_counts: DashMap::new(),
             thread_channels: senders,
             stats: Arc::new(PubSubStats::new()),
         });
@@ -49,10 +57,11 @@ impl GlobalRegistry {
             if entry.remove(&thread_id) {
                 self.stats.total_channels.fetch_sub(1, Ordering::Relaxed);
             }
-            if entry.is_empty() {
-                drop(entry);
-                self.channel_to_threads.remove(channel);
-            }
+            // Only drop the entry if no thread subscribed since the guard
+            // was released
+            drop(entry);
+            self.channel_to_threads
+                .remove_if(channel, |_, threads| threads.is_empty());
         }
     }
 
@@ -68,10 +77,25 @@ impl GlobalRegistry {
             if entry.remove(&thread_id) {
                 self.stats.total_patterns.fetch_sub(1, Ordering::Relaxed);
             }
-            if entry.is_empty() {
-                drop(entry);
-                self.pattern_to_threads.remove(pattern);
-            }
+            drop(entry);
+            self.pattern_to_threads
+                .remove_if(pattern, |_, threads| threads.is_empty());
+        }
+    }
+
+    pub fn add_shard_channel_interest(&self, channel: Vec<u8>, thread_id: ThreadId) {
+        self.shard_channel_to_threads
+            .entry(channel)
+            .or_default()
+            .insert(thread_id);
+    }
+
+    pub fn remove_shard_channel_interest(&self, channel: &[u8], thread_id: ThreadId) {
+        if let Some(mut entry) = self.shard_channel_to_threads.get_mut(channel) {
+            entry.remove(&thread_id);
+            drop(entry);
+            self.shard_channel_to_threads
+                .remove_if(channel, |_, threads| threads.is_empty());
         }
     }
 
@@ -82,6 +106,13 @@ impl GlobalRegistry {
             .unwrap_or_default()
     }
 
+    pub fn get_shard_channel_threads(&self, channel: &[u8]) -> Vec<ThreadId> {
+        self.shard_channel_to_threads
+            .get(channel)
+            .map(|entry| entry.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
     pub fn get_all_pattern_threads(&self) -> HashSet<ThreadId> {
         let mut threads = HashSet::new();
         for entry in self.pattern_to_threads.iter() {
@@ -100,6 +131,54 @@ impl GlobalRegistry {
         }
     }
 
+    /// Deliver to channel and pattern subscribers on every thread, the
+    /// caller's own included, returning how many there are
+    ///
+    /// For publishers without a connection of their own, like keyspace
+    /// events and scripts; connections publish through the handler, which
+    /// delivers on its own thread directly.
+    pub fn publish(&self, channel: Vec<u8>, message: Bytes) -> usize {
+        let receivers =
+            self.get_channel_subscriber_count(&channel) + self.get_total_pattern_matches(&channel);
+
+        let channel_threads = self.get_channel_threads(&channel);
+        if !channel_threads.is_empty() {
+            let msg = BroadcastMsg::Publish {
+                channel: channel.clone(),
+                message: message.clone(),
+                exclude_thread: None,
+            };
+            self.broadcast_to_threads(msg, &channel_threads);
+        }
+
+        let pattern_threads: Vec<_> = self.get_all_pattern_threads().into_iter().collect();
+        if !pattern_threads.is_empty() {
+            let msg = BroadcastMsg::PatternPublish {
+                channel,
+                message,
+                exclude_thread: None,
+            };
+            self.broadcast_to_threads(msg, &pattern_threads);
+        }
+        receivers
+    }
+
+    /// Deliver to sharded channel subscribers on every thread, returning how
+    /// many there are
+    pub fn shard_publish(&self, channel: Vec<u8>, message: Bytes) -> usize {
+        let receivers = self.get_shard_channel_subscriber_count(&channel);
+        let shard_threads = self.get_shard_channel_threads(&channel);
+        if !shard_threads.is_empty() {
+            let msg = BroadcastMsg::ShardPublish {
+                channel,
+                message,
+                exclude_thread: None,
+            };
+            self.broadcast_to_threads(msg, &shard_threads);
+        }
+        receivers
+    }
+
     pub fn broadcast_to_all_threads(&self, msg: BroadcastMsg, exclude: Option<ThreadId>) {
         self.stats.total_messages.fetch_add(1, Ordering::Relaxed);
 
@@ -117,6 +196,14 @@ impl GlobalRegistry {
             .collect()
     }
 
+    pub fn get_all_shard_channels(&self) -> Vec<Vec<u8>> {
+        self.shard_channel_to_threads
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Subscribers to `channel` across every worker thread
     pub fn get_channel_subscriber_count(&self, channel: &[u8]) -> usize {
         self.channel_subscriber_counts
             .get(channel)
@@ -136,10 +223,36 @@ impl GlobalRegistry {
             if *count > 0 {
                 *count -= 1;
             }
-            if *count == 0 {
-                drop(count);
-                self.channel_subscriber_counts.remove(channel);
+            // A subscriber on another thread may have incremented the count
+            // again since the guard was released
+            drop(count);
+            self.channel_subscriber_counts
+                .remove_if(channel, |_, count| *count == 0);
+        }
+    }
+
+    pub fn get_shard_channel_subscriber_count(&self, channel: &[u8]) -> usize {
+        self.shard_channel_subscriber_counts
+            .get(channel)
+            .map(|entry| *entry)
+            .unwrap_or(0)
+    }
+
+    pub fn increment_shard_channel_subscribers(&self, channel: &[u8]) {
+        *self
+            .shard_channel_subscriber_counts
+            .entry(channel.to_vec())
+            .or_insert(0) += 1;
+    }
+
+    pub fn decrement_shard_channel_subscribers(&self, channel: &[u8]) {
+        if let Some(mut count) = self.shard_channel_subscriber_counts.get_mut(channel) {
+            if *count > 0 {
+                *count -= 1;
             }
+            drop(count);
+            self.shard_channel_subscriber_counts
+                .remove_if(channel, |_, count| *count == 0);
         }
     }
 
@@ -155,10 +268,9 @@ impl GlobalRegistry {
             if *count > 0 {
                 *count -= 1;
             }
-            if *count == 0 {
-                drop(count);
-                self.pattern_subscriber_counts.remove(pattern);
-            }
+            drop(count);
+            self.pattern_subscriber_counts
+                .remove_if(pattern, |_, count| *count == 0);
         }
     }
 
@@ -175,41 +287,16 @@ impl GlobalRegistry {
         let mut total = 0;
         for entry in self.pattern_subscriber_counts.iter() {
             // Check if pattern matches channel using glob-style matching
-            if Self::glob_match(entry.key(), channel) {
+            if glob_match(entry.key(), channel) {
                 total += *entry.value();
             }
         }
         total
     }
 
-    pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
-        let mut p = 0;
-        let mut t = 0;
-        let mut star_idx = None;
-        let mut match_idx = 0;
-
-        while t < text.len() {
-            if p < pattern.len() && (pattern[p] == text[t] || pattern[p] == b'?') {
-                p += 1;
-                t += 1;
-            } else if p < pattern.len() && pattern[p] == b'*' {
-                star_idx = Some(p);
-                match_idx = t;
-                p += 1;
-            } else if let Some(idx) = star_idx {
-                p = idx + 1;
-                match_idx += 1;
-                t = match_idx;
-            } else {
-                return false;
-            }
-        }
-
-        while p < pattern.len() && pattern[p] == b'*' {
-            p += 1;
-        }
-
-        p == pattern.len()
+    /// Channels with at least one subscriber
+    pub fn get_channel_count(&self) -> usize {
+        self.channel_to_threads.len()
     }
 
     pub fn get_pattern_count(&self) -> usize {
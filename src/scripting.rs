@@ -0,0 +1,431 @@
+use crate::protocol::{Command, CommandExecutor, RespValue};
+use bytes::Bytes;
+use dashmap::DashMap;
+use mlua::{Function, IntoLua, Lua, LuaOptions, MultiValue, StdLib, Table, Value};
+use once_cell::sync::Lazy;
+use sha1::{Digest, Sha1};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
+
+/// Bodies of every script loaded or run, by lowercase SHA1, for EVALSHA
+static SCRIPTS: Lazy<DashMap<String, Bytes>> = Lazy::new(DashMap::new);
+
+thread_local! {
+    // Each worker thread runs scripts in its own interpreter
+    static INTERPRETER: Interpreter = Interpreter::new();
+}
+
+/// An error reply from `redis.call`, raised into the script
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct CallError(String);
+
+struct Interpreter {
+    lua: Lua,
+    // Compiled scripts by SHA1
+    functions: RefCell<HashMap<String, Function>>,
+}
+
+impl Interpreter {
+    fn new() -> Self {
+        let lua = Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+            LuaOptions::default(),
+        )
+        .expect("safe standard libraries always load");
+        if let Err(e) = Self::install_redis_lib(&lua) {
+            warn!("Could not set up the Lua redis library: {}", e);
+        }
+        Self {
+            lua,
+            functions: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Define the `redis` table, less `call` and `pcall`, which are bound to
+    /// the calling executor on each run, and drop the file loaders
+    fn install_redis_lib(lua: &Lua) -> mlua::Result<()> {
+        let globals = lua.globals();
+        globals.set("dofile", Value::Nil)?;
+        globals.set("loadfile", Value::Nil)?;
+
+        let redis = lua.create_table()?;
+        redis.set(
+            "status_reply",
+            lua.create_function(|lua, status: mlua::String| {
+                let reply = lua.create_table()?;
+                reply.set("ok", status)?;
+                Ok(reply)
+            })?,
+        )?;
+        redis.set(
+            "error_reply",
+            lua.create_function(|lua, error: mlua::String| {
+                let reply = lua.create_table()?;
+                reply.set("err", error)?;
+                Ok(reply)
+            })?,
+        )?;
+        redis.set(
+            "sha1hex",
+            lua.create_function(|_, data: mlua::String| Ok(sha1_hex(&data.as_bytes())))?,
+        )?;
+        redis.set(
+            "log",
+            lua.create_function(|_, (level, message): (i64, MultiValue)| {
+                let message = message
+                    .iter()
+                    .map(|part| part.to_string().unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match level {
+                    0 | 1 => debug!("Script: {}", message),
+                    2 => info!("Script: {}", message),
+                    _ => warn!("Script: {}", message),
+                }
+                Ok(())
+            })?,
+        )?;
+        for (level, name) in ["LOG_DEBUG", "LOG_VERBOSE", "LOG_NOTICE", "LOG_WARNING"]
+            .iter()
+            .enumerate()
+        {
+            redis.set(*name, level)?;
+        }
+        globals.set("redis", redis)
+    }
+
+    /// The compiled script for `sha`, compiling `body` the first time
+    fn function(&self, sha: &str, body: &[u8]) -> mlua::Result<Function> {
+        if let Some(function) = self.functions.borrow().get(sha) {
+            return Ok(function.clone());
+        }
+        let function = self
+            .lua
+            .load(body)
+            .set_name("=user_script")
+            .into_function()?;
+        self.functions
+            .borrow_mut()
+            .insert(sha.to_string(), function.clone());
+        Ok(function)
+    }
+
+    fn run(
+        &self,
+        executor: &CommandExecutor,
+        function: Function,
+        keys: Vec<Vec<u8>>,
+        args: Vec<Bytes>,
+    ) -> mlua::Result<RespValue> {
+        let lua = &self.lua;
+        let globals = lua.globals();
+        globals.set(
+            "KEYS",
+            lua.create_sequence_from(
+                keys.iter()
+                    .map(|k| lua.create_string(k))
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?,
+        )?;
+        globals.set(
+            "ARGV",
+            lua.create_sequence_from(
+                args.iter()
+                    .map(|a| lua.create_string(a))
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?,
+        )?;
+
+        lua.scope(|scope| {
+            let redis: Table = globals.get("redis")?;
+            redis.set(
+                "call",
+                scope.create_function(|lua, args| call(lua, executor, args, true))?,
+            )?;
+            redis.set(
+                "pcall",
+                scope.create_function(|lua, args| call(lua, executor, args, false))?,
+            )?;
+            let result: Value = function.call(())?;
+            Ok(to_resp(&result))
+        })
+    }
+}
+
+/// Run a script for EVAL, caching it for EVALSHA
+///
+/// A script runs to completion on its worker thread, so no other command on
+/// that worker runs in the middle of it; commands on other workers can, unless
+/// the append-only log is on, which holds every write to one at a time.
+/// A script that never returns stalls its worker.
+pub fn eval(
+    executor: &CommandExecutor,
+    script: Bytes,
+    keys: Vec<Vec<u8>>,
+    args: Vec<Bytes>,
+) -> RespValue {
+    let sha = sha1_hex(&script);
+    INTERPRETER.with(|interpreter| {
+        let function = match interpreter.function(&sha, &script) {
+            Ok(function) => function,
+            Err(e) => return compile_error(&e),
+        };
+        SCRIPTS.insert(sha.clone(), script);
+        interpreter
+            .run(executor, function, keys, args)
+            .unwrap_or_else(|e| run_error(&sha, &e))
+    })
+}
+
+/// Run a script cached by EVAL or SCRIPT LOAD
+pub fn evalsha(
+    executor: &CommandExecutor,
+    sha: &str,
+    keys: Vec<Vec<u8>>,
+    args: Vec<Bytes>,
+) -> RespValue {
+    let sha = sha.to_ascii_lowercase();
+    let Some(script) = SCRIPTS.get(&sha).map(|script| script.clone()) else {
+        return RespValue::Error("NOSCRIPT No matching script. Please use EVAL.".to_string());
+    };
+    INTERPRETER.with(|interpreter| {
+        let function = match interpreter.function(&sha, &script) {
+            Ok(function) => function,
+            Err(e) => return compile_error(&e),
+        };
+        interpreter
+            .run(executor, function, keys, args)
+            .unwrap_or_else(|e| run_error(&sha, &e))
+    })
+}
+
+/// SCRIPT LOAD | EXISTS | FLUSH
+pub fn script(subcommand: &str, args: Vec<Bytes>) -> RespValue {
+    match subcommand.to_uppercase().as_str() {
+        "LOAD" if args.len() == 1 => {
+            let script = args.into_iter().next().unwrap_or_default();
+            let sha = sha1_hex(&script);
+            let compiled = INTERPRETER.with(|interpreter| interpreter.function(&sha, &script));
+            if let Err(e) = compiled {
+                return compile_error(&e);
+            }
+            SCRIPTS.insert(sha.clone(), script);
+            RespValue::BulkString(Some(Bytes::from(sha)))
+        }
+        "EXISTS" if !args.is_empty() => RespValue::Array(Some(
+            args.iter()
+                .map(|sha| {
+                    let sha = String::from_utf8_lossy(sha).to_ascii_lowercase();
+                    RespValue::Integer(SCRIPTS.contains_key(&sha) as i64)
+                })
+                .collect(),
+        )),
+        "FLUSH" if args.len() <= 1 => {
+            SCRIPTS.clear();
+            INTERPRETER.with(|interpreter| interpreter.functions.borrow_mut().clear());
+            RespValue::SimpleString(Bytes::from_static(b"OK"))
+        }
+        "LOAD" | "EXISTS" | "FLUSH" => RespValue::Error(format!(
+            "ERR wrong number of arguments for 'script|{}' command",
+            subcommand.to_lowercase()
+        )),
+        _ => RespValue::Error(format!(
+            "ERR unknown subcommand '{}'. Try SCRIPT HELP.",
+            subcommand
+        )),
+    }
+}
+
+/// `redis.call` / `redis.pcall`: run a command through the executor, raising
+/// error replies into the script if `raise` is set
+fn call(
+    lua: &Lua,
+    executor: &CommandExecutor,
+    args: MultiValue,
+    raise: bool,
+) -> mlua::Result<Value> {
+    let mut argv = Vec::with_capacity(args.len());
+    for arg in args {
+        let arg = match arg {
+            Value::String(s) => Bytes::copy_from_slice(&s.as_bytes()),
+            Value::Integer(n) => Bytes::from(n.to_string()),
+            Value::Number(n) => Bytes::from(n.to_string()),
+            _ => {
+                return Err(mlua::Error::external(CallError(
+                    "ERR Lua redis lib command arguments must be strings or integers".to_string(),
+                )))
+            }
+        };
+        argv.push(arg);
+    }
+    if argv.is_empty() {
+        return Err(mlua::Error::external(CallError(
+            "ERR Please specify at least one argument for this redis lib call".to_string(),
+        )));
+    }
+
+    let request = RespValue::Array(Some(
+        argv.iter()
+            .map(|arg| RespValue::BulkString(Some(arg.clone())))
+            .collect(),
+    ));
+    let reply = match Command::from_resp(request) {
+        Ok(command) if !allowed_in_script(&command) => {
+            RespValue::Error("ERR This Redis command is not allowed from script".to_string())
+        }
+        Ok(command) if executor.check_permission(&command).is_some() => RespValue::Error(
+            "NOPERM The user executing the script can't run this command or subcommand".to_string(),
+        ),
+        Ok(command) => {
+            let writes = command.is_write();
+            let reply = executor.execute(command);
+            if writes && !matches!(reply, RespValue::Error(_)) {
+                executor.log_write(&argv, &reply);
+            }
+            reply
+        }
+        Err(e) => RespValue::Error(format!("ERR {}", e)),
+    };
+
+    match reply {
+        RespValue::Error(e) if raise => Err(mlua::Error::external(CallError(e))),
+        reply => to_lua(lua, &reply),
+    }
+}
+
+/// Commands that need the connection, or would nest or block a script
+fn allowed_in_script(command: &Command) -> bool {
+    !matches!(
+        command,
+        Command::Eval { .. }
+            | Command::EvalSha { .. }
+            | Command::Script { .. }
+            | Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Watch(_)
+            | Command::Unwatch
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+            | Command::PSubscribe(_)
+            | Command::PUnsubscribe(_)
+            | Command::SSubscribe(_)
+            | Command::SUnsubscribe(_)
+            | Command::Monitor
+            | Command::Select(_)
+            | Command::Auth { .. }
+            | Command::Acl { .. }
+            | Command::Hello { .. }
+            | Command::Quit
+            | Command::Reset
+    )
+}
+
+/// Convert a reply the way Redis hands it to Lua: nulls become false,
+/// status and error replies become `{ok = ...}` / `{err = ...}` tables, and
+/// RESP3 types take their RESP2 form
+fn to_lua(lua: &Lua, reply: &RespValue) -> mlua::Result<Value> {
+    Ok(match reply {
+        RespValue::Integer(n) => n.into_lua(lua)?,
+        RespValue::Boolean(b) => (*b as i64).into_lua(lua)?,
+        RespValue::BulkString(Some(s)) | RespValue::VerbatimString(s) => {
+            Value::String(lua.create_string(s)?)
+        }
+        RespValue::Double(d) => Value::String(lua.create_string(d.to_string())?),
+        RespValue::BulkString(None) | RespValue::Array(None) | RespValue::Null => {
+            Value::Boolean(false)
+        }
+        RespValue::SimpleString(s) => {
+            let table = lua.create_table()?;
+            table.set("ok", lua.create_string(s)?)?;
+            Value::Table(table)
+        }
+        RespValue::Error(e) => {
+            let table = lua.create_table()?;
+            table.set("err", e.as_str())?;
+            Value::Table(table)
+        }
+        RespValue::Array(Some(items)) | RespValue::Push(items) => Value::Table(
+            lua.create_sequence_from(
+                items
+                    .iter()
+                    .map(|item| to_lua(lua, item))
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?,
+        ),
+        RespValue::Map(pairs) => Value::Table(
+            lua.create_sequence_from(
+                pairs
+                    .iter()
+                    .flat_map(|(key, value)| [to_lua(lua, key), to_lua(lua, value)])
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?,
+        ),
+    })
+}
+
+/// Convert a script's return value to a reply: numbers are truncated to
+/// integers, true is 1, false and nil are null, and tables are arrays up to
+/// their first nil unless they carry an `ok` or `err` field
+fn to_resp(value: &Value) -> RespValue {
+    match value {
+        Value::Boolean(true) => RespValue::Integer(1),
+        Value::Integer(n) => RespValue::Integer(*n),
+        Value::Number(n) => RespValue::Integer(*n as i64),
+        Value::String(s) => RespValue::BulkString(Some(Bytes::copy_from_slice(&s.as_bytes()))),
+        Value::Table(table) => {
+            if let Ok(Value::String(err)) = table.raw_get::<Value>("err") {
+                return RespValue::Error(err.to_string_lossy());
+            }
+            if let Ok(Value::String(ok)) = table.raw_get::<Value>("ok") {
+                return RespValue::SimpleString(Bytes::copy_from_slice(&ok.as_bytes()));
+            }
+            RespValue::Array(Some(
+                table
+                    .clone()
+                    .sequence_values::<Value>()
+                    .map_while(Result::ok)
+                    .map(|item| to_resp(&item))
+                    .collect(),
+            ))
+        }
+        _ => RespValue::BulkString(None),
+    }
+}
+
+fn compile_error(e: &mlua::Error) -> RespValue {
+    RespValue::Error(format!(
+        "ERR Error compiling script (new function): {}",
+        first_line(e)
+    ))
+}
+
+/// An error reply from `redis.call` is passed on as is; any other failure
+/// names the script it came from
+fn run_error(sha: &str, e: &mlua::Error) -> RespValue {
+    if let Some(CallError(reply)) = e.chain().find_map(|e| e.downcast_ref::<CallError>()) {
+        return RespValue::Error(reply.clone());
+    }
+    RespValue::Error(format!(
+        "ERR Error running script (call to f_{}): {}",
+        sha,
+        first_line(e)
+    ))
+}
+
+/// Lua errors carry a traceback after the message
+fn first_line(e: &mlua::Error) -> String {
+    let message = match e {
+        mlua::Error::RuntimeError(message) | mlua::Error::SyntaxError { message, .. } => {
+            message.clone()
+        }
+        e => e.to_string(),
+    };
+    message.lines().next().unwrap_or_default().to_string()
+}
+
+fn sha1_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
@@ -1,14 +1,39 @@
+use crate::acl::Acl;
+use crate::affinity;
+use crate::aof::AppendLog;
+use crate::blocking::BlockingHub;
 use crate::client_registry::ClientRegistry;
-use crate::pubsub::{handle_pubsub_operation, GlobalRegistry, ThreadLocalPubSub};
-use crate::{config::Config, error::Result, network::Connection};
+use crate::config::{Config, RuntimeConfig};
+use crate::error::Result;
+use crate::monitor::MonitorHub;
+use crate::network::{load_server_config, ClientStream, Connection, TlsStream};
+use crate::pubsub::{
+    handle_pubsub_operation, BroadcastMsg, ConnectionId, GlobalRegistry, KeyspaceNotifier,
+    PubSubMessage, ThreadLocalPubSub,
+};
+use crate::slowlog::SlowLog;
+use crate::snapshot::Snapshots;
+use crate::stats::ServerStats;
+use crate::watch::WatchTable;
 use feoxdb::FeoxStore;
+use mio::{Interest, Poll, Registry, Token};
+use rand::Rng;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Write};
 use std::net::TcpListener;
-use std::os::fd::{AsRawFd, RawFd};
+use std::os::fd::AsRawFd;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use tracing::{debug, error, info};
 
+/// Bytes written to one connection before the worker moves on to others,
+/// so a large reply does not hold up the rest of the thread
+const WRITE_BUDGET: usize = 1024 * 1024;
+
+/// How long a worker keeps flushing buffered replies after shutdown starts
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// High-performance Redis-compatible server
 pub struct Server {
     config: Config,
@@ -16,14 +41,41 @@ pub struct Server {
     shutdown: AtomicBool,
     active_connections: AtomicUsize,
     pubsub_registry: Arc<GlobalRegistry>,
+    // Inboxes for the registry's per-thread senders, taken by `run`
+    pubsub_receivers: Mutex<Vec<crossbeam_channel::Receiver<BroadcastMsg>>>,
+    notifier: Arc<KeyspaceNotifier>,
+    slowlog: Arc<SlowLog>,
+    monitor_hub: Arc<MonitorHub>,
+    blocking_hub: Arc<BlockingHub>,
+    watch_table: Arc<WatchTable>,
+    snapshots: Arc<Snapshots>,
+    append_log: Option<Arc<AppendLog>>,
+    stats: Arc<ServerStats>,
     client_registry: Arc<ClientRegistry>,
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+    // Random id for this server instance, reported by INFO
+    run_id: Arc<str>,
+}
+
+/// Listening sockets a worker accepts from
+///
+/// Each worker owns its handles; where workers share a socket they hold
+/// duplicates, so every one can close its own on exit.
+struct WorkerListeners {
+    tcp: TcpListener,
+    tls: Option<TcpListener>,
 }
 
 impl Server {
     /// Create a new server with the given configuration
-    pub fn new(config: Config) -> Result<Self> {
+    pub fn new(mut config: Config) -> Result<Self> {
         config.validate()?;
 
+        // Fields may have been overridden after construction, so reseed the
+        // values CONFIG SET can change and the users
+        config.runtime = Arc::new(RuntimeConfig::from_config(&config));
+        config.acl_users = Arc::new(Acl::from_config(&config));
+
         // Create a single shared FeoxStore instance
         let store = if let Some(ref data_path) = config.data_path {
             let mut builder = FeoxStore::builder()
@@ -46,16 +98,63 @@ impl Server {
             )
         };
 
-        let (pubsub_registry, _receivers) = GlobalRegistry::new(config.threads);
+        let (pubsub_registry, pubsub_receivers) = GlobalRegistry::new(config.threads);
+        let notifier = Arc::new(KeyspaceNotifier::new(
+            Arc::clone(&pubsub_registry),
+            Arc::clone(&config.runtime),
+        ));
+        let slowlog = Arc::new(SlowLog::new(Arc::clone(&config.runtime)));
+        let monitor_hub = MonitorHub::new(config.threads);
+        let blocking_hub = BlockingHub::new(config.threads);
+
+        // Without a data file the append-only log, or else the last
+        // snapshot, is all that survives a restart
+        let snapshots = Snapshots::new(Arc::clone(&store), &config.dbfilename);
+        let append_log = if config.appendonly {
+            if let Some((keys, commands)) = AppendLog::replay(&store, &config)? {
+                info!(
+                    "Loaded {} keys and replayed {} commands from {}",
+                    keys, commands, config.appendfilename
+                );
+            }
+            Some(AppendLog::open(Arc::clone(&store), &config)?)
+        } else {
+            if config.data_path.is_none() {
+                if let Some(keys) = snapshots.load()? {
+                    info!("Loaded {} keys from {}", keys, config.dbfilename);
+                }
+            }
+            None
+        };
+        let stats = ServerStats::new(config.threads);
         let client_registry = Arc::new(ClientRegistry::new());
 
+        // Load certificates up front so a bad TLS setup fails at startup
+        let tls_config = match (&config.tls_cert_file, &config.tls_key_file) {
+            (Some(cert_file), Some(key_file)) if config.tls_port.is_some() => {
+                Some(load_server_config(cert_file, key_file)?)
+            }
+            _ => None,
+        };
+
         Ok(Self {
             config,
             store,
             shutdown: AtomicBool::new(false),
             active_connections: AtomicUsize::new(0),
             pubsub_registry,
+            pubsub_receivers: Mutex::new(pubsub_receivers),
+            notifier,
+            slowlog,
+            monitor_hub,
+            blocking_hub,
+            watch_table: WatchTable::new(),
+            snapshots,
+            append_log,
+            stats,
             client_registry,
+            tls_config,
+            run_id: generate_run_id().into(),
         })
     }
 
@@ -63,20 +162,40 @@ impl Server {
     ///
     /// This method blocks until the server is shut down.
     pub fn run(self: Arc<Self>) -> Result<()> {
-        // Create TCP listener
-        let listener =
-            TcpListener::bind(format!("{}:{}", self.config.bind_addr, self.config.port))?;
-
-        listener.set_nonblocking(true)?;
-        let listener_fd = listener.as_raw_fd();
+        // Create TCP listeners
+        let listeners = self.bind_listeners(self.config.port)?;
 
         info!(
             "Server listening on {}:{}",
             self.config.bind_addr, self.config.port
         );
 
-        // Create pub/sub receivers for each thread
-        let (_, mut pubsub_receivers) = GlobalRegistry::new(self.config.threads);
+        let tls_listeners = match self.config.tls_port {
+            Some(tls_port) if self.tls_config.is_some() => {
+                let listeners = self.bind_listeners(tls_port)?;
+                info!("TLS listening on {}:{}", self.config.bind_addr, tls_port);
+                listeners
+            }
+            _ => Vec::new(),
+        };
+
+        // Each worker drains the inbox the shared registry sends to
+        let mut pubsub_receivers = std::mem::take(&mut *self.pubsub_receivers.lock().unwrap());
+
+        let cores = if self.config.cpu_affinity {
+            affinity::worker_cores(self.config.numa_aware)
+        } else {
+            None
+        };
+        if let Some(cores) = &cores {
+            if cores.len() < self.config.threads {
+                tracing::warn!(
+                    "{} workers but only {} cores to pin them to; some will share a core",
+                    self.config.threads,
+                    cores.len()
+                );
+            }
+        }
 
         // Spawn worker threads
         let mut handles = Vec::new();
@@ -87,11 +206,23 @@ impl Server {
             let pubsub_registry = Arc::clone(&self.pubsub_registry);
             let pubsub_receiver = pubsub_receivers.remove(0);
             let client_registry = Arc::clone(&self.client_registry);
+            let worker_listeners = WorkerListeners {
+                tcp: listeners[thread_id % listeners.len()].try_clone()?,
+                tls: match tls_listeners.len() {
+                    0 => None,
+                    n => Some(tls_listeners[thread_id % n].try_clone()?),
+                },
+            };
+
+            let core = cores.as_ref().map(|cores| cores[thread_id % cores.len()]);
 
             let handle = thread::spawn(move || {
+                if let Some(core) = core {
+                    affinity::pin_worker(thread_id, core);
+                }
                 if let Err(e) = server.run_worker(
                     thread_id,
-                    listener_fd,
+                    worker_listeners,
                     store,
                     pubsub_registry,
                     pubsub_receiver,
@@ -111,6 +242,39 @@ impl Server {
         Ok(())
     }
 
+    /// Listen on `port`, with one socket per worker if `reuse_port` is set
+    /// or a single socket every worker accepts from otherwise
+    ///
+    /// Workers sharing a socket all wake for each new connection and race
+    /// to accept it; with SO_REUSEPORT the kernel spreads connections
+    /// evenly across the workers' own sockets instead. That needs Linux, so
+    /// elsewhere the shared socket is used regardless.
+    fn bind_listeners(&self, port: u16) -> Result<Vec<TcpListener>> {
+        let addr = format!("{}:{}", self.config.bind_addr, port);
+
+        #[cfg(target_os = "linux")]
+        let listeners = if self.config.reuse_port {
+            (0..self.config.threads)
+                .map(|_| bind_reuse_port(&addr))
+                .collect::<io::Result<Vec<_>>>()?
+        } else {
+            vec![TcpListener::bind(&addr)?]
+        };
+
+        #[cfg(not(target_os = "linux"))]
+        let listeners = {
+            if self.config.reuse_port {
+                tracing::warn!("reuse_port needs Linux; workers will share one listening socket");
+            }
+            vec![TcpListener::bind(&addr)?]
+        };
+
+        for listener in &listeners {
+            listener.set_nonblocking(true)?;
+        }
+        Ok(listeners)
+    }
+
     /// Signal the server to shut down gracefully
     pub fn shutdown(&self) {
         info!("Initiating server shutdown");
@@ -122,43 +286,70 @@ impl Server {
         self.active_connections.load(Ordering::Acquire)
     }
 
+    /// Get the registry of connected clients
+    pub fn client_registry(&self) -> &Arc<ClientRegistry> {
+        &self.client_registry
+    }
+
     fn run_worker(
         self: &Arc<Self>,
         thread_id: usize,
-        listener_fd: RawFd,
+        worker_listeners: WorkerListeners,
         store: Arc<FeoxStore>,
         pubsub_registry: Arc<GlobalRegistry>,
         pubsub_receiver: crossbeam_channel::Receiver<crate::pubsub::BroadcastMsg>,
         client_registry: Arc<ClientRegistry>,
     ) -> Result<()> {
-        use mio::net::{TcpListener as MioTcpListener, TcpStream as MioTcpStream};
-        use mio::{Events, Interest, Poll, Token};
-        use std::collections::HashMap;
-        use std::io::{ErrorKind, Read, Write};
-        use std::os::fd::FromRawFd;
+        use mio::net::TcpListener as MioTcpListener;
+        use mio::Events;
 
         // Create mio Poll instance
         let mut poll = Poll::new()?;
         let mut events = Events::with_capacity(1024);
 
-        // Convert raw fd to mio listener
-        let std_listener = unsafe { TcpListener::from_raw_fd(listener_fd) };
-        std_listener.set_nonblocking(true)?;
-        let mut listener = MioTcpListener::from_std(std_listener);
+        let mut listener = MioTcpListener::from_std(worker_listeners.tcp);
 
         // Register listener
         const SERVER: Token = Token(0);
         poll.registry()
             .register(&mut listener, SERVER, Interest::READABLE)?;
 
+        // TLS clients arrive on their own listener; connection tokens count
+        // up from 1 so the top token is free for it
+        const TLS_SERVER: Token = Token(usize::MAX);
+        let mut tls_listener = match worker_listeners.tls {
+            Some(std_listener) => {
+                let mut tls_listener = MioTcpListener::from_std(std_listener);
+                poll.registry()
+                    .register(&mut tls_listener, TLS_SERVER, Interest::READABLE)?;
+                Some(tls_listener)
+            }
+            None => None,
+        };
+
         // Connection tracking
-        let mut connections: HashMap<Token, (MioTcpStream, Connection)> = HashMap::new();
+        let mut connections: HashMap<Token, (ClientStream, Connection)> = HashMap::new();
+        // Token of each connection by id, for pub/sub deliveries
+        let mut conn_tokens: HashMap<ConnectionId, Token> = HashMap::new();
         let mut next_token = 1usize;
+        // Reused for every socket read; connections copy out what they parse
+        let mut read_buffer = vec![0u8; self.config.read_buffer_size];
+        // Connections whose replies drained below the pipeline limits, with
+        // commands or input still waiting
+        let mut resumable: Vec<Token> = Vec::new();
+        let mut last_idle_sweep = std::time::Instant::now();
+        // The first worker deletes expired collections nothing has touched
+        let collection_sweeper = (thread_id == 0)
+            .then(|| crate::protocol::CommandExecutor::new(Arc::clone(&store), &self.config));
+        let mut last_collection_sweep = std::time::Instant::now();
 
         // Initialize thread-local pub/sub
         let mut pubsub_manager =
             ThreadLocalPubSub::new(thread_id, pubsub_receiver, pubsub_registry.clone());
 
+        let monitor_inbox = self.monitor_hub.inbox(thread_id);
+        let blocking_inbox = self.blocking_hub.inbox(thread_id);
+
         info!("Worker {} started", thread_id);
 
         // Event loop
@@ -166,43 +357,244 @@ impl Server {
             // Process incoming pub/sub messages
             let pubsub_deliveries = pubsub_manager.process_inbox();
             for (conn_id, message) in pubsub_deliveries {
-                // Find connection by ID and queue message
-                for (_token, (stream, connection)) in connections.iter_mut() {
-                    if connection.connection_id == conn_id {
-                        connection.queue_pubsub_message(message);
-                        connection.process_pubsub_messages();
-
-                        // Write any pending data immediately
-                        while let Some(data) = connection.pending_writes() {
-                            let data_len = data.len();
-                            match stream.write(data) {
-                                Ok(n) => {
-                                    connection.consume_writes(n);
-                                    if n < data_len {
-                                        break;
-                                    }
-                                }
-                                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
-                                Err(_) => break,
-                            }
-                        }
+                let Some(&token) = conn_tokens.get(&conn_id) else {
+                    continue;
+                };
+                let Some((stream, connection)) = connections.get_mut(&token) else {
+                    continue;
+                };
+                connection.queue_pubsub_message(message);
+                connection.process_pubsub_messages();
+
+                // Write any pending data immediately
+                if flush_connection(poll.registry(), token, stream, connection).is_err() {
+                    self.close_connection(
+                        &mut poll,
+                        &mut connections,
+                        &mut conn_tokens,
+                        token,
+                        &mut pubsub_manager,
+                        &client_registry,
+                    );
+                }
+            }
+
+            // Stream commands from every worker to this thread's monitors
+            let monitor_lines: Vec<_> = monitor_inbox.try_iter().collect();
+            if !monitor_lines.is_empty() {
+                let mut failed = Vec::new();
+                for (token, (stream, connection)) in connections.iter_mut() {
+                    if !connection.is_monitor() {
+                        continue;
+                    }
+                    for line in &monitor_lines {
+                        connection.queue_monitor_line(line.clone());
+                    }
+                    if flush_connection(poll.registry(), *token, stream, connection).is_err() {
+                        failed.push(*token);
+                    }
+                }
+                for token in failed {
+                    self.close_connection(
+                        &mut poll,
+                        &mut connections,
+                        &mut conn_tokens,
+                        token,
+                        &mut pubsub_manager,
+                        &client_registry,
+                    );
+                }
+            }
+
+            // Serve clients blocked on lists that were pushed to, longest
+            // waiting first, and time out the rest; the poll timeout bounds
+            // how late a timeout fires
+            let mut resumed = std::mem::take(&mut resumable);
+            for key in blocking_inbox.try_iter() {
+                let mut waiting: Vec<_> = connections
+                    .iter()
+                    .filter_map(|(token, (_, conn))| conn.blocked_on(&key).map(|at| (at, *token)))
+                    .collect();
+                waiting.sort();
+                for (_, token) in waiting {
+                    let (_, conn) = connections.get_mut(&token).unwrap();
+                    if !conn.serve_blocked() {
                         break;
                     }
+                    resumed.push(token);
+                }
+            }
+            if self.blocking_hub.is_active() {
+                let now = std::time::Instant::now();
+                for (token, (_, conn)) in connections.iter_mut() {
+                    if conn.expire_blocked(now) {
+                        resumed.push(*token);
+                    }
+                }
+            }
+
+            // Run whatever the unblocked clients pipelined behind the pop,
+            // and what clients held back by the pipeline limits have sent
+            let mut deliveries_to_make = Vec::new();
+            let mut failed = Vec::new();
+            for token in resumed {
+                let Some((stream, connection)) = connections.get_mut(&token) else {
+                    continue;
+                };
+                let should_close = read_connection(
+                    stream,
+                    connection,
+                    &mut read_buffer,
+                    &mut pubsub_manager,
+                    &pubsub_registry,
+                    thread_id,
+                    &mut deliveries_to_make,
+                );
+                client_registry.update(connection);
+                if flush_connection(poll.registry(), token, stream, connection).is_err()
+                    || should_close
+                    || (connection.is_closed() && !connection.is_write_pending())
+                {
+                    failed.push(token);
+                } else if connection.can_resume() {
+                    resumable.push(token);
                 }
             }
+            for (conn_id, message) in deliveries_to_make {
+                let Some(&token) = conn_tokens.get(&conn_id) else {
+                    continue;
+                };
+                let Some((stream, connection)) = connections.get_mut(&token) else {
+                    continue;
+                };
+                connection.queue_pubsub_message(message);
+                connection.process_pubsub_messages();
+                if flush_connection(poll.registry(), token, stream, connection).is_err() {
+                    failed.push(token);
+                }
+            }
+            for token in failed {
+                self.close_connection(
+                    &mut poll,
+                    &mut connections,
+                    &mut conn_tokens,
+                    token,
+                    &mut pubsub_manager,
+                    &client_registry,
+                );
+            }
 
-            // Poll for events with 100ms timeout
-            poll.poll(&mut events, Some(std::time::Duration::from_millis(100)))?;
+            // Poll for events with 100ms timeout, or just check for them if
+            // held-back connections are ready to run again
+            let timeout = if resumable.is_empty() {
+                std::time::Duration::from_millis(100)
+            } else {
+                std::time::Duration::ZERO
+            };
+            poll.poll(&mut events, Some(timeout))?;
+
+            self.notifier.publish_expired(&store);
+
+            if let Some(sweeper) = &collection_sweeper {
+                if last_collection_sweep.elapsed().as_secs() >= 1 {
+                    last_collection_sweep = std::time::Instant::now();
+                    sweeper.expire_collections();
+                }
+            }
+
+            // Drop idle clients once a second; subscribers, monitors and
+            // blocked clients are exempt as in Redis
+            let idle_timeout = self.config.runtime.timeout();
+            if idle_timeout > 0 && last_idle_sweep.elapsed().as_secs() >= 1 {
+                last_idle_sweep = std::time::Instant::now();
+                let idle: Vec<Token> = connections
+                    .iter()
+                    .filter(|(_, (_, conn))| {
+                        !conn.is_in_pubsub_mode()
+                            && !conn.is_monitor()
+                            && !conn.is_blocked()
+                            && conn.idle_secs() >= idle_timeout
+                    })
+                    .map(|(token, _)| *token)
+                    .collect();
+                for token in idle {
+                    debug!("Closing idle connection {:?}", token);
+                    self.close_connection(
+                        &mut poll,
+                        &mut connections,
+                        &mut conn_tokens,
+                        token,
+                        &mut pubsub_manager,
+                        &client_registry,
+                    );
+                }
+            }
+
+            // Close the connections CLIENT KILL marked, here or on any worker
+            for conn_id in client_registry.take_kills(thread_id) {
+                if let Some(&token) = conn_tokens.get(&conn_id) {
+                    debug!("Closing killed connection {:?}", token);
+                    self.close_connection(
+                        &mut poll,
+                        &mut connections,
+                        &mut conn_tokens,
+                        token,
+                        &mut pubsub_manager,
+                        &client_registry,
+                    );
+                }
+            }
 
             for event in events.iter() {
                 match event.token() {
-                    SERVER => {
+                    SERVER | TLS_SERVER => {
+                        let tls = event.token() == TLS_SERVER;
+                        let accepting = if tls {
+                            tls_listener.as_mut()
+                        } else {
+                            Some(&mut listener)
+                        };
+                        let Some(accepting) = accepting else {
+                            continue;
+                        };
+
                         // Accept new connections
                         loop {
-                            match listener.accept() {
+                            match accepting.accept() {
                                 Ok((mut stream, addr)) => {
                                     debug!("New connection from {:?}", addr);
 
+                                    // Turn away clients over the limit instead of
+                                    // leaving them to time out
+                                    if self.active_connections.load(Ordering::Relaxed) as u64
+                                        >= self.config.runtime.maxclients()
+                                    {
+                                        debug!(
+                                            "Rejecting {:?}: max number of clients reached",
+                                            addr
+                                        );
+                                        // A TLS client could not read a plaintext reply
+                                        if !tls {
+                                            let _ = stream.write_all(
+                                                b"-ERR max number of clients reached\r\n",
+                                            );
+                                        }
+                                        continue;
+                                    }
+
+                                    let mut stream = if tls {
+                                        let config = self.tls_config.clone().unwrap();
+                                        match TlsStream::new(stream, config) {
+                                            Ok(stream) => ClientStream::Tls(Box::new(stream)),
+                                            Err(e) => {
+                                                error!("Error accepting TLS connection: {}", e);
+                                                continue;
+                                            }
+                                        }
+                                    } else {
+                                        ClientStream::Plain(stream)
+                                    };
+
                                     // Configure socket
                                     stream.set_nodelay(self.config.tcp_nodelay)?;
 
@@ -226,12 +618,30 @@ impl Server {
 
                                     // Set client registry for CLIENT command support
                                     connection.set_client_registry(Arc::clone(&client_registry));
+                                    connection.set_notifier(Arc::clone(&self.notifier));
+                                    connection.set_slowlog(Arc::clone(&self.slowlog));
+                                    connection
+                                        .set_monitor_hub(Arc::clone(&self.monitor_hub), thread_id);
+                                    connection.set_blocking_hub(
+                                        Arc::clone(&self.blocking_hub),
+                                        thread_id,
+                                    );
+                                    connection.set_watch_table(Arc::clone(&self.watch_table));
+                                    connection.set_snapshots(Arc::clone(&self.snapshots));
+                                    if let Some(append_log) = &self.append_log {
+                                        connection.set_append_log(Arc::clone(append_log));
+                                    }
+                                    connection.set_stats(Arc::clone(&self.stats), thread_id);
+                                    connection.set_run_id(Arc::clone(&self.run_id));
+                                    connection.set_pubsub_registry(Arc::clone(&pubsub_registry));
 
                                     // Register client in registry
                                     client_registry.register(&connection, thread_id);
 
+                                    conn_tokens.insert(connection.connection_id, token);
                                     connections.insert(token, (stream, connection));
                                     self.active_connections.fetch_add(1, Ordering::Relaxed);
+                                    self.stats.record_connection(thread_id);
                                 }
                                 Err(e) if e.kind() == ErrorKind::WouldBlock => break,
                                 Err(e) => {
@@ -251,82 +661,34 @@ impl Server {
                                 let mut should_close = false;
 
                                 if event.is_readable() {
-                                    // Use a simple buffer (optimize with pool later if needed)
-                                    let mut buffer = vec![0u8; 8192];
-
-                                    match stream.read(&mut buffer) {
-                                        Ok(0) => {
-                                            // Connection closed
-                                            should_close = true;
-                                        }
-                                        Ok(n) => {
-                                            // Process commands inline and get pub/sub operations
-                                            match connection.process_read(&buffer[..n]) {
-                                                Ok(pubsub_ops) => {
-                                                    // Process pub/sub operations
-                                                    for op in pubsub_ops {
-                                                        let deliveries = handle_pubsub_operation(
-                                                            &mut pubsub_manager,
-                                                            &pubsub_registry,
-                                                            connection.connection_id,
-                                                            op,
-                                                            connection,
-                                                            thread_id,
-                                                        );
-                                                        deliveries_to_make.extend(deliveries);
-                                                    }
-
-                                                    // Process any queued pub/sub messages
-                                                    connection.process_pubsub_messages();
-
-                                                    // Update client info in registry if needed
-                                                    client_registry.update(connection);
-
-                                                    // Write response immediately
-                                                    while let Some(response_data) =
-                                                        connection.pending_writes()
-                                                    {
-                                                        let response_len = response_data.len();
-                                                        match stream.write(response_data) {
-                                                            Ok(n) => {
-                                                                connection.consume_writes(n);
-                                                                if n < response_len {
-                                                                    // Partial write, would block
-                                                                    break;
-                                                                }
-                                                            }
-                                                            Err(e)
-                                                                if e.kind()
-                                                                    == ErrorKind::WouldBlock =>
-                                                            {
-                                                                break;
-                                                            }
-                                                            Err(e) => {
-                                                                error!("Error writing: {}", e);
-                                                                should_close = true;
-                                                                break;
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    error!("Error processing read: {}", e);
-                                                    should_close = true;
-                                                }
-                                            }
+                                    should_close = read_connection(
+                                        stream,
+                                        connection,
+                                        &mut read_buffer,
+                                        &mut pubsub_manager,
+                                        &pubsub_registry,
+                                        thread_id,
+                                        &mut deliveries_to_make,
+                                    );
+                                    client_registry.update(connection);
+                                }
 
-                                            if connection.is_closed() {
-                                                should_close = true;
-                                            }
-                                        }
-                                        Err(e) if e.kind() != ErrorKind::WouldBlock => {
-                                            if e.kind() != ErrorKind::ConnectionReset {
-                                                error!("Error reading: {}", e);
-                                            }
-                                            should_close = true;
-                                        }
-                                        Err(_) => {} // WouldBlock - ignore
-                                    }
+                                // Write responses immediately, or flush leftovers
+                                // from an earlier partial write once writable
+                                if let Err(e) =
+                                    flush_connection(poll.registry(), token, stream, connection)
+                                {
+                                    error!("Error writing: {}", e);
+                                    should_close = true;
+                                }
+                                // A client that sent QUIT goes once its replies are out
+                                should_close |=
+                                    connection.is_closed() && !connection.is_write_pending();
+
+                                // Once the client has read enough replies, what
+                                // it pipelined beyond the limits runs next round
+                                if !should_close && connection.can_resume() {
+                                    resumable.push(token);
                                 }
 
                                 should_close
@@ -335,35 +697,69 @@ impl Server {
                             };
 
                         if should_close {
-                            if let Some((mut stream, mut connection)) = connections.remove(&token) {
-                                let _ = poll.registry().deregister(&mut stream);
-
-                                // Clean up pub/sub subscriptions
-                                pubsub_manager.connection_dropped(connection.connection_id);
-
-                                // Unregister from client registry
-                                client_registry.unregister(connection.connection_id);
-
-                                connection.close();
-                                self.active_connections.fetch_sub(1, Ordering::Relaxed);
-                            }
+                            self.close_connection(
+                                &mut poll,
+                                &mut connections,
+                                &mut conn_tokens,
+                                token,
+                                &mut pubsub_manager,
+                                &client_registry,
+                            );
                         }
 
                         // Now deliver any pub/sub messages to local connections
+                        let mut failed = Vec::new();
                         for (delivery_conn_id, msg) in deliveries_to_make {
-                            for (_, (_, conn)) in connections.iter_mut() {
-                                if conn.connection_id == delivery_conn_id {
-                                    conn.queue_pubsub_message(msg);
-                                    conn.process_pubsub_messages();
-                                    break;
+                            let Some(&conn_token) = conn_tokens.get(&delivery_conn_id) else {
+                                continue;
+                            };
+                            if let Some((stream, conn)) = connections.get_mut(&conn_token) {
+                                conn.queue_pubsub_message(msg);
+                                conn.process_pubsub_messages();
+                                if flush_connection(poll.registry(), conn_token, stream, conn)
+                                    .is_err()
+                                {
+                                    failed.push(conn_token);
                                 }
                             }
                         }
+
+                        for token in failed {
+                            self.close_connection(
+                                &mut poll,
+                                &mut connections,
+                                &mut conn_tokens,
+                                token,
+                                &mut pubsub_manager,
+                                &client_registry,
+                            );
+                        }
                     }
                 }
             }
         }
 
+        // Stop accepting, then give clients a grace period to read the
+        // replies still buffered for them, such as QUIT's +OK
+        let _ = poll.registry().deregister(&mut listener);
+        if let Some(tls_listener) = tls_listener.as_mut() {
+            let _ = poll.registry().deregister(tls_listener);
+        }
+        let deadline = std::time::Instant::now() + SHUTDOWN_GRACE;
+        let mut draining: Vec<Token> = connections.keys().copied().collect();
+        loop {
+            draining.retain(|token| {
+                let (stream, connection) = connections.get_mut(token).unwrap();
+                flush_connection(poll.registry(), *token, stream, connection).is_ok()
+                    && connection.is_write_pending()
+            });
+            let now = std::time::Instant::now();
+            if draining.is_empty() || now >= deadline {
+                break;
+            }
+            poll.poll(&mut events, Some(deadline - now))?;
+        }
+
         // Cleanup
         for (_, (mut stream, mut connection)) in connections {
             let _ = poll.registry().deregister(&mut stream);
@@ -375,4 +771,208 @@ impl Server {
         info!("Worker {} shutting down", thread_id);
         Ok(())
     }
+
+    /// Deregister a connection and release its pub/sub and registry state
+    fn close_connection(
+        &self,
+        poll: &mut Poll,
+        connections: &mut HashMap<Token, (ClientStream, Connection)>,
+        conn_tokens: &mut HashMap<ConnectionId, Token>,
+        token: Token,
+        pubsub_manager: &mut ThreadLocalPubSub,
+        client_registry: &ClientRegistry,
+    ) {
+        if let Some((mut stream, mut connection)) = connections.remove(&token) {
+            let _ = poll.registry().deregister(&mut stream);
+            conn_tokens.remove(&connection.connection_id);
+
+            // Clean up pub/sub subscriptions
+            pubsub_manager.connection_dropped(connection.connection_id);
+
+            // Unregister from client registry
+            client_registry.unregister(connection.connection_id);
+
+            connection.close();
+            self.active_connections.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Read what a client has sent and run it, until the socket would block or
+/// the pipeline limits hold the connection back; returns whether to close it
+/// at once because it failed or the peer hung up
+///
+/// After QUIT nothing more is read; the caller closes the connection once
+/// its replies are written.
+///
+/// Commands held back earlier run before anything new is read. Nothing is
+/// read while replies are over the limits, so a client that does not read
+/// its replies stops being served rather than growing the write buffer;
+/// `resumable` brings the connection back once they drain.
+fn read_connection(
+    stream: &mut ClientStream,
+    connection: &mut Connection,
+    read_buffer: &mut [u8],
+    pubsub_manager: &mut ThreadLocalPubSub,
+    pubsub_registry: &Arc<GlobalRegistry>,
+    thread_id: usize,
+    deliveries: &mut Vec<(ConnectionId, PubSubMessage)>,
+) -> bool {
+    use std::io::Read;
+
+    loop {
+        if connection.is_closed() {
+            return false;
+        }
+
+        // A full buffer means the socket likely has more
+        let filled;
+        let result = if connection.can_resume() {
+            // Reads may have stopped with input left in the socket
+            filled = true;
+            connection.process_pending()
+        } else if connection.is_held_back() {
+            return false;
+        } else {
+            match stream.read(read_buffer) {
+                // Connection closed
+                Ok(0) => return true,
+                Ok(n) => {
+                    filled = n == read_buffer.len();
+                    // Process commands inline and get pub/sub operations
+                    connection.process_read(&read_buffer[..n])
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return false,
+                Err(e) => {
+                    if e.kind() != ErrorKind::ConnectionReset {
+                        error!("Error reading: {}", e);
+                    }
+                    return true;
+                }
+            }
+        };
+
+        match result {
+            Ok(pubsub_ops) => {
+                for op in pubsub_ops {
+                    deliveries.extend(handle_pubsub_operation(
+                        pubsub_manager,
+                        pubsub_registry,
+                        connection.connection_id,
+                        op,
+                        connection,
+                        thread_id,
+                    ));
+                }
+
+                // Process any queued pub/sub messages
+                connection.process_pubsub_messages();
+            }
+            Err(e) => {
+                error!("Error processing read: {}", e);
+                return true;
+            }
+        }
+
+        // Events are edge-triggered, so keep reading while input may be
+        // left; TLS can also have decrypted input left over that no
+        // readiness event will announce. A short plain read skips the
+        // extra read that would only return WouldBlock.
+        if !(filled || stream.has_buffered_input()) {
+            return false;
+        }
+    }
+}
+
+/// Bind a listening socket with SO_REUSEPORT set, so each worker can bind
+/// its own to the same address
+#[cfg(target_os = "linux")]
+fn bind_reuse_port(addr: &str) -> io::Result<TcpListener> {
+    use nix::sys::socket::{
+        bind, listen, setsockopt, socket, sockopt, AddressFamily, Backlog, SockFlag, SockType,
+        SockaddrStorage,
+    };
+    use std::net::ToSocketAddrs;
+
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no address to bind"))?;
+    let family = if addr.is_ipv4() {
+        AddressFamily::Inet
+    } else {
+        AddressFamily::Inet6
+    };
+
+    let socket = socket(family, SockType::Stream, SockFlag::SOCK_CLOEXEC, None)?;
+    // Like std's bind, allow rebinding while old connections linger
+    setsockopt(&socket, sockopt::ReuseAddr, &true)?;
+    setsockopt(&socket, sockopt::ReusePort, &true)?;
+    bind(socket.as_raw_fd(), &SockaddrStorage::from(addr))?;
+    listen(&socket, Backlog::MAXCONN)?;
+    Ok(TcpListener::from(socket))
+}
+
+/// 40 random hex characters, the format Redis uses for run ids
+fn generate_run_id() -> String {
+    let mut rng = rand::rng();
+    (0..40)
+        .map(|_| char::from_digit(rng.random_range(0..16), 16).unwrap())
+        .collect()
+}
+
+/// Write as much pending output as the socket accepts and keep the
+/// registered interest in sync with what is left in the write buffer
+///
+/// A partial write leaves the connection registered for writable events so
+/// the remainder is flushed as soon as the socket drains; once everything has
+/// been written the connection drops back to read-only interest. At most
+/// `WRITE_BUDGET` bytes go out per call; past that the connection is
+/// re-registered, which reports it writable again on the next poll if the
+/// socket still has room, after the other connections' events.
+fn flush_connection(
+    registry: &Registry,
+    token: Token,
+    stream: &mut ClientStream,
+    connection: &mut Connection,
+) -> io::Result<()> {
+    // Push out encrypted records left by an earlier partial TLS write
+    match stream.flush() {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut written = 0;
+    let mut yielded = false;
+    while let Some(data) = connection.pending_writes() {
+        if written >= WRITE_BUDGET {
+            yielded = true;
+            break;
+        }
+        let chunk = &data[..data.len().min(WRITE_BUDGET - written)];
+        match stream.write(chunk) {
+            Ok(0) => return Err(io::Error::from(ErrorKind::WriteZero)),
+            Ok(n) => {
+                written += n;
+                connection.consume_writes(n);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    let pending = connection.pending_writes().is_some() || stream.has_buffered_output();
+    if yielded || pending != connection.is_write_pending() {
+        let interest = if pending {
+            Interest::READABLE | Interest::WRITABLE
+        } else {
+            Interest::READABLE
+        };
+        registry.reregister(stream, token, interest)?;
+        connection.set_write_pending(pending);
+    }
+
+    Ok(())
 }
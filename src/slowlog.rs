@@ -0,0 +1,124 @@
+use crate::config::RuntimeConfig;
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Limits Redis applies to each logged command line
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    pub timestamp: u64,
+    pub duration_micros: u64,
+    pub args: Vec<Bytes>,
+    pub client_addr: Option<SocketAddr>,
+    pub client_name: Option<String>,
+}
+
+/// Recent commands that took longer than `slowlog-log-slower-than`
+///
+/// Shared by every worker; the threshold and length are read from the
+/// runtime config so CONFIG SET takes effect immediately.
+pub struct SlowLog {
+    entries: Mutex<VecDeque<SlowLogEntry>>,
+    next_id: AtomicU64,
+    runtime: Arc<RuntimeConfig>,
+}
+
+impl SlowLog {
+    pub fn new(runtime: Arc<RuntimeConfig>) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
+            runtime,
+        }
+    }
+
+    /// Check if commands should be timed at all
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.runtime.slowlog_log_slower_than() >= 0
+    }
+
+    /// Record the command if it ran for at least the configured threshold
+    pub fn record(
+        &self,
+        elapsed: Duration,
+        args: Vec<Bytes>,
+        client_addr: Option<SocketAddr>,
+        client_name: Option<&str>,
+    ) {
+        let threshold = self.runtime.slowlog_log_slower_than();
+        let micros = elapsed.as_micros() as u64;
+        if threshold < 0 || micros < threshold as u64 {
+            return;
+        }
+
+        let max_len = self.runtime.slowlog_max_len() as usize;
+        if max_len == 0 {
+            return;
+        }
+
+        let entry = SlowLogEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration_micros: micros,
+            args: truncate_args(args),
+            client_addr,
+            client_name: client_name.map(str::to_string),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_front(entry);
+        entries.truncate(max_len);
+    }
+
+    /// Up to `count` entries, newest first
+    pub fn get(&self, count: usize) -> Vec<SlowLogEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn reset(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Shorten long command lines the way Redis does before logging them
+fn truncate_args(mut args: Vec<Bytes>) -> Vec<Bytes> {
+    if args.len() > MAX_ARGS {
+        let more = args.len() - (MAX_ARGS - 1);
+        args.truncate(MAX_ARGS - 1);
+        args.push(Bytes::from(format!("... ({} more arguments)", more)));
+    }
+
+    args.into_iter()
+        .map(|arg| {
+            if arg.len() > MAX_ARG_LEN {
+                let mut short = arg[..MAX_ARG_LEN].to_vec();
+                short.extend_from_slice(
+                    format!("... ({} more bytes)", arg.len() - MAX_ARG_LEN).as_bytes(),
+                );
+                Bytes::from(short)
+            } else {
+                arg
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,280 @@
+use feoxdb::constants::MAX_KEY_SIZE;
+use feoxdb::FeoxStore;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+/// Opens every snapshot file, naming the format and its version
+pub(crate) const MAGIC: &[u8; 8] = b"FEOXSNP1";
+
+/// Length that marks the end of the records, where a key would start
+const END_OF_RECORDS: u32 = u32::MAX;
+
+/// Records fetched per range query while walking the keyspace
+const SAVE_BATCH: usize = 10000;
+
+fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Snapshots of the keyspace written by SAVE / BGSAVE, so a memory-only
+/// server can be restarted without losing its data
+///
+/// A snapshot is the magic header followed by one record per key: the key
+/// and the value, each prefixed with its length as a little-endian u32, and
+/// then the Unix time in seconds the key expires at as a little-endian u64,
+/// or 0 if it never does. A length of `u32::MAX` ends the records, so a
+/// snapshot can be embedded ahead of other data, as the append-only log's
+/// rewrite does. Keys with under a second left are not saved.
+///
+/// The store cannot be frozen while it is walked, so a key written during a
+/// save may or may not make it in. Snapshots are written under a temporary
+/// name and renamed into place, so a failed save leaves the last one intact.
+pub struct Snapshots {
+    store: Arc<FeoxStore>,
+    path: PathBuf,
+    // Unix time of the last successful save, or of startup before one
+    last_save: AtomicU64,
+    in_progress: AtomicBool,
+    last_save_ok: AtomicBool,
+    // Seconds the last save took, -1 before the first
+    last_save_secs: AtomicI64,
+}
+
+impl Snapshots {
+    pub fn new(store: Arc<FeoxStore>, path: impl Into<PathBuf>) -> Arc<Self> {
+        Arc::new(Self {
+            store,
+            path: path.into(),
+            last_save: AtomicU64::new(unix_time_secs()),
+            in_progress: AtomicBool::new(false),
+            last_save_ok: AtomicBool::new(true),
+            last_save_secs: AtomicI64::new(-1),
+        })
+    }
+
+    /// Unix time of the last successful save, as LASTSAVE reports it
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.in_progress.load(Ordering::Relaxed)
+    }
+
+    pub fn last_save_ok(&self) -> bool {
+        self.last_save_ok.load(Ordering::Relaxed)
+    }
+
+    pub fn last_save_secs(&self) -> i64 {
+        self.last_save_secs.load(Ordering::Relaxed)
+    }
+
+    /// Write a snapshot on the calling thread
+    pub fn save(&self) -> io::Result<usize> {
+        if !self.begin() {
+            return Err(io::Error::other("Background save already in progress"));
+        }
+        self.run()
+    }
+
+    /// Write a snapshot on a background thread, returning false if a save
+    /// is already running
+    pub fn bgsave(self: &Arc<Self>) -> bool {
+        if !self.begin() {
+            return false;
+        }
+        let snapshots = Arc::clone(self);
+        std::thread::spawn(move || {
+            if let Ok(keys) = snapshots.run() {
+                info!("Background save of {} keys done", keys);
+            }
+        });
+        true
+    }
+
+    /// Load the snapshot into the store, returning how many keys were
+    /// restored, or None if there is no snapshot yet
+    pub fn load(&self) -> io::Result<Option<usize>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        read_records(&self.store, &mut BufReader::new(file)).map(Some)
+    }
+
+    /// Claim the right to save, unless a save is already running
+    fn begin(&self) -> bool {
+        self.in_progress
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Write the snapshot and record the outcome for LASTSAVE and INFO
+    fn run(&self) -> io::Result<usize> {
+        let started = Instant::now();
+        let result = self.write();
+        match &result {
+            Ok(_) => self.last_save.store(unix_time_secs(), Ordering::Relaxed),
+            Err(e) => error!("Saving snapshot to {} failed: {}", self.path.display(), e),
+        }
+        self.last_save_ok.store(result.is_ok(), Ordering::Relaxed);
+        self.last_save_secs
+            .store(started.elapsed().as_secs() as i64, Ordering::Relaxed);
+        self.in_progress.store(false, Ordering::Release);
+        result
+    }
+
+    fn write(&self) -> io::Result<usize> {
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        let saved = write_records(&self.store, &mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&temp, &self.path)?;
+        Ok(saved)
+    }
+}
+
+/// Write the magic header and a record for every live key, returning how
+/// many were written
+pub fn write_records(store: &FeoxStore, writer: &mut impl Write) -> io::Result<usize> {
+    writer.write_all(MAGIC)?;
+
+    let now = unix_time_secs();
+    let end = vec![0xFF; MAX_KEY_SIZE];
+    let mut start = Vec::new();
+    let mut saved = 0;
+    loop {
+        let batch = store
+            .range_query(&start, &end, SAVE_BATCH)
+            .map_err(io::Error::other)?;
+
+        for (key, value) in &batch {
+            let expires_at = match store.get_ttl(key) {
+                Ok(Some(0)) => continue,
+                Ok(Some(ttl)) => now + ttl,
+                // Keys without a TTL, or a store running without TTLs
+                Ok(None) | Err(feoxdb::FeoxError::TtlNotEnabled) => 0,
+                // Deleted since the range query returned it
+                Err(_) => continue,
+            };
+            write_field(writer, key)?;
+            write_field(writer, value)?;
+            writer.write_all(&expires_at.to_le_bytes())?;
+            saved += 1;
+        }
+
+        match batch.last() {
+            Some((last, _)) if batch.len() == SAVE_BATCH => {
+                // Resume just past the last key seen
+                start = last.clone();
+                start.push(0);
+            }
+            _ => break,
+        }
+    }
+
+    writer.write_all(&END_OF_RECORDS.to_le_bytes())?;
+    Ok(saved)
+}
+
+/// Read a snapshot written by `write_records` into the store, returning how
+/// many keys were restored. The reader is left just past the last record.
+pub fn read_records(store: &FeoxStore, reader: &mut impl Read) -> io::Result<usize> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "not a FeOx snapshot",
+        ));
+    }
+
+    let now = unix_time_secs();
+    let mut restored = 0;
+    while let Some(key) = read_field(reader)? {
+        let value = read_field(reader)?.ok_or(ErrorKind::UnexpectedEof)?;
+        let mut expires_at = [0u8; 8];
+        reader.read_exact(&mut expires_at)?;
+        let expires_at = u64::from_le_bytes(expires_at);
+
+        let result = match expires_at {
+            0 => store.insert(&key, &value),
+            at if at > now => store.insert_with_ttl(&key, &value, at - now),
+            _ => continue,
+        };
+        result.map_err(io::Error::other)?;
+        restored += 1;
+    }
+    Ok(restored)
+}
+
+fn write_field(writer: &mut impl Write, field: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(field.len())
+        .ok()
+        .filter(|&len| len != END_OF_RECORDS)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "record too large to save"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(field)
+}
+
+/// Read a length-prefixed field, or None at the end-of-records marker
+fn read_field(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    if len == END_OF_RECORDS {
+        return Ok(None);
+    }
+    // The length is untrusted, so let the buffer grow with what is read
+    // rather than allocating it all up front
+    let mut field = Vec::new();
+    reader.take(len as u64).read_to_end(&mut field)?;
+    if field.len() != len as usize {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "snapshot field shorter than its length",
+        ));
+    }
+    Ok(Some(field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_longer_than_the_input_is_invalid() {
+        let mut input = (u32::MAX - 1).to_le_bytes().to_vec();
+        input.extend_from_slice(b"short");
+        let err = read_field(&mut input.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let mut buf = Vec::new();
+        write_field(&mut buf, b"key").unwrap();
+        write_field(&mut buf, b"").unwrap();
+        buf.extend_from_slice(&END_OF_RECORDS.to_le_bytes());
+
+        let mut reader = buf.as_slice();
+        assert_eq!(read_field(&mut reader).unwrap(), Some(b"key".to_vec()));
+        assert_eq!(read_field(&mut reader).unwrap(), Some(Vec::new()));
+        assert_eq!(read_field(&mut reader).unwrap(), None);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::protocol::COMMAND_COUNT;
+use feoxdb::stats::StatsSnapshot;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Latencies are bucketed by power of two nanoseconds, covering ~78 hours
+const LATENCY_BUCKETS: usize = 48;
+
+/// Call counts and latencies for one command
+#[derive(Debug, Clone, Default)]
+pub struct CommandStats {
+    pub calls: u64,
+    pub usec: u64,
+    pub failed_calls: u64,
+    latency: Vec<u64>,
+}
+
+impl CommandStats {
+    /// Approximate latency at percentile `p` (0-100), in microseconds
+    pub fn percentile_usec(&self, p: f64) -> f64 {
+        let total: u64 = self.latency.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let rank = ((p / 100.0) * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.latency.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                // Report the upper bound of the bucket
+                return (1u64 << bucket) as f64 / 1000.0;
+            }
+        }
+        (1u64 << (LATENCY_BUCKETS - 1)) as f64 / 1000.0
+    }
+}
+
+/// The store's operation and cache counters, counted from the last
+/// CONFIG RESETSTAT
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StoreCounters {
+    pub operations: u64,
+    pub gets: u64,
+    pub inserts: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl StoreCounters {
+    fn from_snapshot(snapshot: &StatsSnapshot) -> Self {
+        Self {
+            operations: snapshot.total_operations,
+            gets: snapshot.total_gets,
+            inserts: snapshot.total_inserts,
+            hits: snapshot.cache_hits,
+            misses: snapshot.cache_misses,
+        }
+    }
+
+    /// Percentage of lookups served from the cache
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 * 100.0 / lookups as f64,
+        }
+    }
+}
+
+/// Counters owned by one worker thread
+struct Shard {
+    calls: Vec<AtomicU64>,
+    nanos: Vec<AtomicU64>,
+    failed: Vec<AtomicU64>,
+    // COMMAND_COUNT rows of LATENCY_BUCKETS
+    latency: Vec<AtomicU64>,
+    reads: AtomicU64,
+    writes: AtomicU64,
+    connections: AtomicU64,
+}
+
+impl Shard {
+    fn new() -> Self {
+        let counters = |n: usize| (0..n).map(|_| AtomicU64::new(0)).collect();
+        Self {
+            calls: counters(COMMAND_COUNT),
+            nanos: counters(COMMAND_COUNT),
+            failed: counters(COMMAND_COUNT),
+            latency: counters(COMMAND_COUNT * LATENCY_BUCKETS),
+            reads: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            connections: AtomicU64::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        let counters = [&self.calls, &self.nanos, &self.failed, &self.latency];
+        for counter in counters.into_iter().flatten() {
+            counter.store(0, Ordering::Relaxed);
+        }
+        for counter in [&self.reads, &self.writes, &self.connections] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Server-wide statistics for INFO
+///
+/// Counters are indexed by `Command::index` and split into one shard per
+/// worker, so recording a command is a few uncontended atomic adds. Readers
+/// sum the shards.
+///
+/// The store's own counters cannot be cleared, so CONFIG RESETSTAT records
+/// them as a baseline that later readings are taken relative to.
+pub struct ServerStats {
+    shards: Vec<Shard>,
+    store_baseline: Mutex<StoreCounters>,
+}
+
+impl ServerStats {
+    pub fn new(num_threads: usize) -> Arc<Self> {
+        Arc::new(Self {
+            shards: (0..num_threads.max(1)).map(|_| Shard::new()).collect(),
+            store_baseline: Mutex::default(),
+        })
+    }
+
+    /// Zero every counter, taking the store's current counters as the new
+    /// baseline
+    pub fn reset(&self, snapshot: &StatsSnapshot) {
+        for shard in &self.shards {
+            shard.reset();
+        }
+        *self
+            .store_baseline
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = StoreCounters::from_snapshot(snapshot);
+    }
+
+    /// The store's counters since the last reset
+    pub fn store_counters(&self, snapshot: &StatsSnapshot) -> StoreCounters {
+        let baseline = *self
+            .store_baseline
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let current = StoreCounters::from_snapshot(snapshot);
+        StoreCounters {
+            operations: current.operations.saturating_sub(baseline.operations),
+            gets: current.gets.saturating_sub(baseline.gets),
+            inserts: current.inserts.saturating_sub(baseline.inserts),
+            hits: current.hits.saturating_sub(baseline.hits),
+            misses: current.misses.saturating_sub(baseline.misses),
+        }
+    }
+
+    /// Record one executed command
+    #[inline]
+    pub fn record_command(&self, shard: usize, command: usize, elapsed: Duration, failed: bool) {
+        let shard = &self.shards[shard];
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        let bucket = ((u64::BITS - nanos.leading_zeros()) as usize).min(LATENCY_BUCKETS - 1);
+
+        shard.calls[command].fetch_add(1, Ordering::Relaxed);
+        shard.nanos[command].fetch_add(nanos, Ordering::Relaxed);
+        shard.latency[command * LATENCY_BUCKETS + bucket].fetch_add(1, Ordering::Relaxed);
+        if failed {
+            shard.failed[command].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Record one read from a client socket
+    #[inline]
+    pub fn record_read(&self, shard: usize) {
+        self.shards[shard].reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record one write to a client socket
+    #[inline]
+    pub fn record_write(&self, shard: usize) {
+        self.shards[shard].writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record one accepted client connection
+    #[inline]
+    pub fn record_connection(&self, shard: usize) {
+        self.shards[shard]
+            .connections
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stats for the command at `index`, summed over every worker
+    pub fn command(&self, index: usize) -> CommandStats {
+        let mut stats = CommandStats {
+            latency: vec![0; LATENCY_BUCKETS],
+            ..Default::default()
+        };
+        let mut nanos = 0;
+        for shard in &self.shards {
+            stats.calls += shard.calls[index].load(Ordering::Relaxed);
+            stats.failed_calls += shard.failed[index].load(Ordering::Relaxed);
+            nanos += shard.nanos[index].load(Ordering::Relaxed);
+            let row = &shard.latency[index * LATENCY_BUCKETS..(index + 1) * LATENCY_BUCKETS];
+            for (total, count) in stats.latency.iter_mut().zip(row) {
+                *total += count.load(Ordering::Relaxed);
+            }
+        }
+        stats.usec = nanos / 1000;
+        stats
+    }
+
+    pub fn total_commands(&self) -> u64 {
+        self.shards
+            .iter()
+            .flat_map(|shard| &shard.calls)
+            .map(|calls| calls.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn total_reads(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.reads.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn total_writes(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.writes.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn total_connections(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.connections.load(Ordering::Relaxed))
+            .sum()
+    }
+}
@@ -0,0 +1,85 @@
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Modification counters for the keys clients WATCH
+///
+/// Only watched keys are tracked. WATCH registers the key, every write to it
+/// bumps its version, and EXEC aborts if the version moved since WATCH, even
+/// when the write left the same value behind. FLUSHDB and FLUSHALL touch
+/// every key at once, so they bump a shared epoch instead. Writes cost one
+/// atomic load while nothing is watched.
+#[derive(Debug, Default)]
+pub struct WatchTable {
+    watched: AtomicUsize,
+    // Version of each watched key, and how many connections watch it
+    keys: DashMap<Vec<u8>, (u64, usize)>,
+    epoch: AtomicU64,
+}
+
+/// The state of a watched key, as WATCH saw it and EXEC compares it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchVersion {
+    epoch: u64,
+    version: u64,
+}
+
+impl WatchTable {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Start tracking writes to `key`, returning its current version
+    pub fn watch(&self, key: &[u8]) -> WatchVersion {
+        let mut entry = self.keys.entry(key.to_vec()).or_insert((0, 0));
+        if entry.1 == 0 {
+            self.watched.fetch_add(1, Ordering::Relaxed);
+        }
+        entry.1 += 1;
+        WatchVersion {
+            epoch: self.epoch.load(Ordering::Relaxed),
+            version: entry.0,
+        }
+    }
+
+    /// Stop tracking `key` for one watcher
+    pub fn unwatch(&self, key: &[u8]) {
+        let removed = self.keys.remove_if_mut(key, |_, (_, watchers)| {
+            *watchers -= 1;
+            *watchers == 0
+        });
+        if removed.is_some() {
+            self.watched.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Current version of a watched key
+    pub fn version(&self, key: &[u8]) -> WatchVersion {
+        WatchVersion {
+            epoch: self.epoch.load(Ordering::Relaxed),
+            version: self.keys.get(key).map_or(0, |entry| entry.0),
+        }
+    }
+
+    /// Check if any key is watched
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.watched.load(Ordering::Relaxed) > 0
+    }
+
+    /// Record a write to `key`
+    #[inline]
+    pub fn touch(&self, key: &[u8]) {
+        if !self.is_active() {
+            return;
+        }
+        if let Some(mut entry) = self.keys.get_mut(key) {
+            entry.0 += 1;
+        }
+    }
+
+    /// Record a write to every key
+    pub fn touch_all(&self) {
+        self.epoch.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{integer, restart_server, start_server};
+use feox_server::Config;
+use std::thread;
+use std::time::Duration;
+
+fn append_only() -> Config {
+    Config {
+        threads: 2,
+        appendonly: true,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn replayed_expiries_keep_their_deadline() {
+    let server = start_server(append_only());
+    let mut client = server.client();
+    client.call(&[b"SET", b"one-second", b"v", b"EX", b"1"]);
+    client.call(&[b"SETEX", b"setex", b"1", b"v"]);
+    client.call(&[b"SET", b"long", b"v", b"EX", b"100"]);
+    client.call(&[b"SET", b"persistent", b"v"]);
+    thread::sleep(Duration::from_millis(1100));
+    drop(client);
+
+    let server = restart_server(append_only(), server.stop());
+    let mut client = server.client();
+    // Replay must not start a fresh second on either
+    assert_eq!(
+        integer(client.call(&[b"EXISTS", b"one-second", b"setex"])),
+        0
+    );
+    let ttl = integer(client.call(&[b"TTL", b"long"]));
+    assert!((98..=100).contains(&ttl), "TTL {}", ttl);
+    assert_eq!(integer(client.call(&[b"TTL", b"persistent"])), -1);
+}
+
+#[test]
+fn replayed_sub_second_expiries_are_not_rounded_up() {
+    let server = start_server(append_only());
+    let mut client = server.client();
+    client.call(&[b"SET", b"px", b"v", b"PX", b"200"]);
+    client.call(&[b"PSETEX", b"psetex", b"200", b"v"]);
+    client.call(&[b"SET", b"expire", b"v"]);
+    client.call(&[b"PEXPIRE", b"expire", b"200"]);
+    client.call(&[b"RPUSH", b"list", b"a"]);
+    client.call(&[b"PEXPIRE", b"list", b"200"]);
+    client.call(&[b"SET", b"later", b"v", b"PX", b"5000"]);
+    thread::sleep(Duration::from_millis(300));
+    drop(client);
+
+    let server = restart_server(append_only(), server.stop());
+    let mut client = server.client();
+    let exists = client.call(&[b"EXISTS", b"px", b"psetex", b"expire", b"list"]);
+    assert_eq!(integer(exists), 0);
+    let pttl = integer(client.call(&[b"PTTL", b"later"]));
+    assert!((1..=5000).contains(&pttl), "PTTL {}", pttl);
+}
+
+#[test]
+fn held_back_writes_log_no_expiry() {
+    let server = start_server(append_only());
+    let mut client = server.client();
+    client.call(&[b"SET", b"k", b"v"]);
+    // Neither of these sets an expiry, so replay must not add one
+    client.call(&[b"SET", b"k", b"other", b"NX", b"PX", b"100"]);
+    client.call(&[b"EXPIRE", b"k", b"1", b"XX"]);
+    thread::sleep(Duration::from_millis(300));
+    drop(client);
+
+    let server = restart_server(append_only(), server.stop());
+    let mut client = server.client();
+    assert_eq!(integer(client.call(&[b"TTL", b"k"])), -1);
+}
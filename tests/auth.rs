@@ -0,0 +1,94 @@
+mod common;
+
+use common::{start_server, TestServer};
+use feox_server::Config;
+
+fn server_with_password() -> TestServer {
+    start_server(Config {
+        threads: 2,
+        requirepass: Some("secret".to_string()),
+        ..Config::default()
+    })
+}
+
+#[test]
+fn auth_is_checked_before_transactions_and_queuing() {
+    let server = server_with_password();
+    let mut client = server.client();
+
+    for command in [
+        &[&b"MULTI"[..]][..],
+        &[b"WATCH", b"k"],
+        &[b"SET", b"k", b"v"],
+    ] {
+        client.send(command);
+        client.expect(b"-NOAUTH Authentication required.\r\n");
+    }
+    // Nothing was queued, so there is no transaction to run
+    client.send(&[b"EXEC"]);
+    client.expect(b"-NOAUTH Authentication required.\r\n");
+
+    client.send(&[b"AUTH", b"secret"]);
+    client.expect(b"+OK\r\n");
+    client.send(&[b"EXEC"]);
+    client.expect(b"-ERR EXEC without MULTI\r\n");
+    client.send(&[b"GET", b"k"]);
+    client.expect(b"$-1\r\n");
+}
+
+#[test]
+fn transaction_errors_have_one_dash() {
+    let server = common::server();
+    let mut client = server.client();
+
+    client.send(&[b"EXEC"]);
+    client.expect(b"-ERR EXEC without MULTI\r\n");
+    client.send(&[b"DISCARD"]);
+    client.expect(b"-ERR DISCARD without MULTI\r\n");
+
+    client.send(&[b"MULTI"]);
+    client.expect(b"+OK\r\n");
+    client.send(&[b"MULTI"]);
+    client.expect(b"-ERR MULTI calls can not be nested\r\n");
+    client.send(&[b"WATCH", b"k"]);
+    client.expect(b"-ERR WATCH inside MULTI is not allowed\r\n");
+    client.send(&[b"DISCARD"]);
+    client.expect(b"+OK\r\n");
+}
+
+#[test]
+fn auth_errors_have_one_dash() {
+    let server = server_with_password();
+    let mut client = server.client();
+    client.send(&[b"AUTH", b"wrong"]);
+    client.expect(b"-ERR invalid password\r\n");
+
+    let server = common::server();
+    let mut client = server.client();
+    client.send(&[b"AUTH", b"secret"]);
+    client.expect(b"-ERR Client sent AUTH, but no password is set\r\n");
+}
+
+#[test]
+fn auth_accepts_the_default_user_by_name() {
+    let server = server_with_password();
+    let mut client = server.client();
+    client.send(&[b"AUTH", b"default", b"secret"]);
+    client.expect(b"+OK\r\n");
+    client.send(&[b"SET", b"k", b"v"]);
+    client.expect(b"+OK\r\n");
+}
+
+#[test]
+fn auth_with_a_username_rejects_wrong_passwords() {
+    let server = server_with_password();
+    let mut client = server.client();
+    client.send(&[b"AUTH", b"default", b"wrong"]);
+    client.expect(b"-WRONGPASS invalid username-password pair or user is disabled.\r\n");
+    client.send(&[b"AUTH", b"nobody", b"secret"]);
+    client.expect(b"-WRONGPASS invalid username-password pair or user is disabled.\r\n");
+
+    // Still not logged in
+    client.send(&[b"GET", b"k"]);
+    client.expect(b"-NOAUTH Authentication required.\r\n");
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use common::{bulk, server};
+
+#[test]
+fn pipelined_setname_is_seen_by_the_rest_of_the_batch() {
+    let server = server();
+    let mut client = server.client();
+    client.pipeline(&[
+        &[b"CLIENT", b"SETNAME", b"x"],
+        &[b"CLIENT", b"GETNAME"],
+        &[b"CLIENT", b"LIST"],
+        &[b"CLIENT", b"INFO"],
+    ]);
+    client.expect(b"+OK\r\n");
+    client.expect(b"$1\r\nx\r\n");
+    let list = String::from_utf8(bulk(client.read())).unwrap();
+    assert!(list.contains(" name=x "), "{}", list);
+    let info = String::from_utf8(bulk(client.read())).unwrap();
+    assert!(
+        info.split_whitespace().any(|field| field == "name=x"),
+        "{}",
+        info
+    );
+
+    // Other connections see it too
+    let list = String::from_utf8(bulk(server.client().call(&[b"CLIENT", b"LIST"]))).unwrap();
+    assert!(list.contains(" name=x "), "{}", list);
+}
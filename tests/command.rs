@@ -0,0 +1,78 @@
+mod common;
+
+use common::{bulk, executor, integer, run};
+use feox_server::protocol::{RespValue, COMMAND_COUNT};
+
+/// The `[name, arity, flags, first_key, last_key, step]` of one COMMAND INFO entry
+fn info(reply: RespValue) -> (Vec<u8>, i64, Vec<Vec<u8>>, i64, i64, i64) {
+    let RespValue::Array(Some(mut entry)) = reply else {
+        panic!("expected a command entry, got {:?}", reply);
+    };
+    let step = integer(entry.pop().unwrap());
+    let last_key = integer(entry.pop().unwrap());
+    let first_key = integer(entry.pop().unwrap());
+    let flags = match entry.pop().unwrap() {
+        RespValue::Array(Some(flags)) => flags
+            .into_iter()
+            .map(|flag| match flag {
+                RespValue::SimpleString(flag) => flag.to_vec(),
+                other => panic!("expected a flag, got {:?}", other),
+            })
+            .collect(),
+        other => panic!("expected flags, got {:?}", other),
+    };
+    let arity = integer(entry.pop().unwrap());
+    (
+        bulk(entry.pop().unwrap()),
+        arity,
+        flags,
+        first_key,
+        last_key,
+        step,
+    )
+}
+
+#[test]
+fn command_count_covers_every_command() {
+    let executor = executor();
+    let count = integer(run(&executor, &[b"COMMAND", b"COUNT"]));
+    assert_eq!(count as usize, COMMAND_COUNT);
+
+    let RespValue::Array(Some(table)) = run(&executor, &[b"COMMAND"]) else {
+        panic!("expected the command table");
+    };
+    assert_eq!(table.len(), COMMAND_COUNT);
+}
+
+#[test]
+fn command_info_reports_arity_flags_and_keys() {
+    let executor = executor();
+    let RespValue::Array(Some(entries)) = run(
+        &executor,
+        &[
+            b"COMMAND",
+            b"INFO",
+            b"get",
+            b"MSET",
+            b"ping",
+            b"nosuchcommand",
+        ],
+    ) else {
+        panic!("expected command entries");
+    };
+    let mut entries = entries.into_iter();
+
+    let get = info(entries.next().unwrap());
+    assert_eq!(
+        get,
+        (b"get".to_vec(), 2, vec![b"readonly".to_vec()], 1, 1, 1)
+    );
+    let mset = info(entries.next().unwrap());
+    assert_eq!(
+        mset,
+        (b"mset".to_vec(), -3, vec![b"write".to_vec()], 1, -1, 2)
+    );
+    let ping = info(entries.next().unwrap());
+    assert_eq!(ping, (b"ping".to_vec(), -1, vec![], 0, 0, 0));
+    assert!(matches!(entries.next(), Some(RespValue::Array(None))));
+}
@@ -0,0 +1,342 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use feox_server::protocol::{Command, CommandExecutor, RespValue};
+use feox_server::{Config, Server};
+use feoxdb::FeoxStore;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// An executor over a fresh memory-only store
+pub fn executor() -> CommandExecutor {
+    executor_and_store().0
+}
+
+/// An executor and the store under it, for checking physical records
+pub fn executor_and_store() -> (CommandExecutor, Arc<FeoxStore>) {
+    let store = FeoxStore::builder()
+        .max_memory(256 * 1024 * 1024)
+        .enable_ttl(true)
+        .build()
+        .expect("store");
+    let store = Arc::new(store);
+    (
+        CommandExecutor::new(Arc::clone(&store), &Config::default()),
+        store,
+    )
+}
+
+/// Every record stored under `prefix`
+pub fn records_under(store: &FeoxStore, prefix: &[u8]) -> Vec<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    end.push(255);
+    store
+        .range_query(prefix, &end, 10_000)
+        .expect("range query")
+        .into_iter()
+        .map(|(key, _)| key)
+        .filter(|key| key.starts_with(prefix))
+        .collect()
+}
+
+fn request(args: &[&[u8]]) -> RespValue {
+    RespValue::Array(Some(
+        args.iter()
+            .map(|arg| RespValue::BulkString(Some(Bytes::copy_from_slice(arg))))
+            .collect(),
+    ))
+}
+
+/// Parse and run one command, as a client would send it
+pub fn run(executor: &CommandExecutor, args: &[&[u8]]) -> RespValue {
+    executor.execute(Command::from_resp(request(args)).expect("valid command"))
+}
+
+/// The error a command is refused with while parsing
+pub fn parse_error(args: &[&[u8]]) -> String {
+    match Command::from_resp(request(args)) {
+        Ok(command) => panic!("expected a parse error, got {:?}", command),
+        Err(e) => e,
+    }
+}
+
+pub fn integer(reply: RespValue) -> i64 {
+    match reply {
+        RespValue::Integer(n) => n,
+        other => panic!("expected an integer, got {:?}", other),
+    }
+}
+
+/// The bulk strings of an array reply
+pub fn strings(reply: RespValue) -> Vec<Vec<u8>> {
+    match reply {
+        RespValue::Array(Some(items)) => items.into_iter().map(bulk).collect(),
+        other => panic!("expected an array, got {:?}", other),
+    }
+}
+
+pub fn bulk(reply: RespValue) -> Vec<u8> {
+    match reply {
+        RespValue::BulkString(Some(value)) => value.to_vec(),
+        other => panic!("expected a bulk string, got {:?}", other),
+    }
+}
+
+/// Split a SCAN-family reply into its cursor and elements
+pub fn scan_page(reply: RespValue) -> (Vec<u8>, Vec<Vec<u8>>) {
+    match reply {
+        RespValue::Array(Some(mut parts)) if parts.len() == 2 => {
+            let elements = strings(parts.pop().unwrap());
+            (bulk(parts.pop().unwrap()), elements)
+        }
+        other => panic!("expected a scan page, got {:?}", other),
+    }
+}
+
+/// A server on its own port, shut down when dropped
+pub struct TestServer {
+    server: Arc<Server>,
+    pub port: u16,
+    // Holds the snapshot and append-only files, removed on drop
+    dir: PathBuf,
+}
+
+impl TestServer {
+    pub fn client(&self) -> Client {
+        Client::connect(self.port)
+    }
+
+    /// Connect until a connection lands on worker `thread`
+    pub fn client_on(&self, thread: usize) -> Client {
+        for _ in 0..1000 {
+            let mut client = self.client();
+            let id = integer(client.call(&[b"CLIENT", b"ID"]));
+            let owner = self
+                .server
+                .client_registry()
+                .get_client(id as usize)
+                .map(|info| info.thread_id);
+            if owner == Some(thread) {
+                return client;
+            }
+        }
+        panic!("no connection landed on worker {}", thread);
+    }
+
+    /// Shut the server down, keeping its directory for `restart_server`
+    pub fn stop(mut self) -> PathBuf {
+        std::mem::take(&mut self.dir)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.shutdown();
+        if !self.dir.as_os_str().is_empty() {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// Start a server with two worker threads
+pub fn server() -> TestServer {
+    start_server(Config {
+        threads: 2,
+        ..Config::default()
+    })
+}
+
+/// Start a server with `config`, on a free port and in a fresh directory
+pub fn start_server(mut config: Config) -> TestServer {
+    static SERVERS: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "feox-test-{}-{}",
+        std::process::id(),
+        SERVERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).expect("test directory");
+    start_server_in(config_in(&mut config, &dir), dir)
+}
+
+/// Point the files `config` writes into `dir`
+fn config_in(config: &mut Config, dir: &Path) -> Config {
+    config.dbfilename = dir.join("dump.rdb").to_string_lossy().into_owned();
+    config.appendfilename = dir.join("appendonly.aof").to_string_lossy().into_owned();
+    config.max_memory_per_shard = Some(64 * 1024 * 1024);
+    config.clone()
+}
+
+fn start_server_in(mut config: Config, dir: PathBuf) -> TestServer {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("free port")
+        .port();
+    config.bind_addr = "127.0.0.1".to_string();
+    config.port = port;
+
+    let server = Arc::new(Server::new(config).expect("server"));
+    let running = Arc::clone(&server);
+    thread::spawn(move || running.run());
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(Instant::now() < deadline, "server did not start");
+        thread::sleep(Duration::from_millis(10));
+    }
+    TestServer { server, port, dir }
+}
+
+/// Start a server in the directory of one that was dropped, reading what
+/// it left behind
+pub fn restart_server(config: Config, dir: PathBuf) -> TestServer {
+    let mut config = config;
+    start_server_in(config_in(&mut config, &dir), dir)
+}
+
+/// A blocking RESP2 client
+pub struct Client {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl Client {
+    pub fn connect(port: u16) -> Self {
+        let stream = TcpStream::connect(("127.0.0.1", port)).expect("connect");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("timeout");
+        Self {
+            stream,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Send one command without waiting for the reply
+    pub fn send(&mut self, args: &[&[u8]]) {
+        self.pipeline(&[args]);
+    }
+
+    /// Send several commands in a single write, so they arrive as one batch
+    pub fn pipeline(&mut self, commands: &[&[&[u8]]]) {
+        let mut frame = Vec::new();
+        for args in commands {
+            frame.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+            for arg in *args {
+                frame.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+                frame.extend_from_slice(arg);
+                frame.extend_from_slice(b"\r\n");
+            }
+        }
+        self.send_raw(&frame);
+    }
+
+    pub fn send_raw(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).expect("write");
+    }
+
+    /// Send one command and read its reply
+    pub fn call(&mut self, args: &[&[u8]]) -> RespValue {
+        self.send(args);
+        self.read()
+    }
+
+    /// Read exactly `expected` off the connection
+    pub fn expect(&mut self, expected: &[u8]) {
+        while self.buf.len() < expected.len() {
+            self.fill();
+        }
+        let got: Vec<u8> = self.buf.drain(..expected.len()).collect();
+        assert_eq!(
+            String::from_utf8_lossy(&got),
+            String::from_utf8_lossy(expected)
+        );
+    }
+
+    /// Read one reply
+    pub fn read(&mut self) -> RespValue {
+        loop {
+            if let Some((value, consumed)) = parse_reply(&self.buf) {
+                self.buf.drain(..consumed);
+                return value;
+            }
+            self.fill();
+        }
+    }
+
+    /// Check that nothing arrives within `wait`
+    pub fn expect_silence(&mut self, wait: Duration) {
+        self.stream.set_read_timeout(Some(wait)).expect("timeout");
+        let mut chunk = [0u8; 1024];
+        let read = self.stream.read(&mut chunk);
+        self.stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("timeout");
+        match read {
+            Ok(0) => panic!("connection closed"),
+            Ok(n) => panic!(
+                "unexpected reply {:?}",
+                String::from_utf8_lossy(&chunk[..n])
+            ),
+            Err(_) => assert!(self.buf.is_empty(), "unexpected reply {:?}", self.buf),
+        }
+    }
+
+    fn fill(&mut self) {
+        let mut chunk = [0u8; 16 * 1024];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => panic!("connection closed"),
+            Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+            Err(e) => panic!("read failed: {}", e),
+        }
+    }
+}
+
+/// Parse one RESP2 reply off the front of `buf`, returning it and its length
+fn parse_reply(buf: &[u8]) -> Option<(RespValue, usize)> {
+    let line_end = buf.windows(2).position(|w| w == b"\r\n")?;
+    let line = std::str::from_utf8(&buf[1..line_end]).expect("utf-8 header");
+    let after = line_end + 2;
+    match buf.first()? {
+        b'+' => Some((
+            RespValue::SimpleString(Bytes::copy_from_slice(line.as_bytes())),
+            after,
+        )),
+        b'-' => Some((RespValue::Error(line.to_string()), after)),
+        b':' => Some((RespValue::Integer(line.parse().expect("integer")), after)),
+        b'$' => match line.parse::<i64>().expect("bulk length") {
+            -1 => Some((RespValue::BulkString(None), after)),
+            len => {
+                let end = after + len as usize;
+                if buf.len() < end + 2 {
+                    return None;
+                }
+                Some((
+                    RespValue::BulkString(Some(Bytes::copy_from_slice(&buf[after..end]))),
+                    end + 2,
+                ))
+            }
+        },
+        b'*' => match line.parse::<i64>().expect("array length") {
+            -1 => Some((RespValue::Array(None), after)),
+            len => {
+                let mut items = Vec::with_capacity(len as usize);
+                let mut pos = after;
+                for _ in 0..len {
+                    let (item, consumed) = parse_reply(&buf[pos..])?;
+                    items.push(item);
+                    pos += consumed;
+                }
+                Some((RespValue::Array(Some(items)), pos))
+            }
+        },
+        other => panic!("unexpected reply type {:?}", *other as char),
+    }
+}
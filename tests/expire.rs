@@ -0,0 +1,59 @@
+mod common;
+
+use common::{executor, integer, parse_error, run};
+
+#[test]
+fn sub_second_ttls_round_up() {
+    let executor = executor();
+    run(&executor, &[b"SET", b"k", b"v"]);
+
+    run(&executor, &[b"PEXPIRE", b"k", b"1500"]);
+    assert_eq!(integer(run(&executor, &[b"TTL", b"k"])), 2);
+    let pttl = integer(run(&executor, &[b"PTTL", b"k"]));
+    assert!((1500..=2000).contains(&pttl), "PTTL {}", pttl);
+
+    run(&executor, &[b"PEXPIRE", b"k", b"500"]);
+    assert_eq!(integer(run(&executor, &[b"TTL", b"k"])), 1);
+    let pttl = integer(run(&executor, &[b"PTTL", b"k"]));
+    assert!((500..=1000).contains(&pttl), "PTTL {}", pttl);
+
+    run(&executor, &[b"SET", b"k", b"v", b"PX", b"1"]);
+    assert_eq!(integer(run(&executor, &[b"TTL", b"k"])), 1);
+    assert!(integer(run(&executor, &[b"PTTL", b"k"])) > 0);
+}
+
+#[test]
+fn ttl_and_pttl_agree() {
+    let executor = executor();
+    run(&executor, &[b"SET", b"k", b"v", b"EX", b"10"]);
+    assert_eq!(integer(run(&executor, &[b"TTL", b"k"])), 10);
+    let pttl = integer(run(&executor, &[b"PTTL", b"k"]));
+    assert_eq!((pttl + 999) / 1000, 10);
+
+    run(&executor, &[b"RPUSH", b"list", b"a"]);
+    run(&executor, &[b"PEXPIRE", b"list", b"1500"]);
+    assert_eq!(integer(run(&executor, &[b"TTL", b"list"])), 2);
+    let pttl = integer(run(&executor, &[b"PTTL", b"list"]));
+    assert!((1500..=2000).contains(&pttl), "PTTL {}", pttl);
+
+    run(&executor, &[b"SET", b"plain", b"v"]);
+    assert_eq!(integer(run(&executor, &[b"TTL", b"plain"])), -1);
+    assert_eq!(integer(run(&executor, &[b"PTTL", b"missing"])), -2);
+}
+
+#[test]
+fn getex_rejects_non_positive_times() {
+    for option in [&b"EX"[..], b"PX"] {
+        for time in [&b"0"[..], b"-1"] {
+            assert_eq!(
+                parse_error(&[b"GETEX", b"k", option, time]),
+                "invalid expire time in 'getex' command"
+            );
+        }
+    }
+
+    let executor = executor();
+    run(&executor, &[b"SET", b"k", b"v"]);
+    run(&executor, &[b"GETEX", b"k", b"PX", b"1500"]);
+    assert_eq!(integer(run(&executor, &[b"TTL", b"k"])), 2);
+}
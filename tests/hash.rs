@@ -0,0 +1,96 @@
+mod common;
+
+use common::{executor, executor_and_store, integer, records_under, run, scan_page, strings};
+use std::collections::HashSet;
+
+/// HSCAN the whole hash page by page, returning the field/value elements
+fn hscan_all(executor: &feox_server::protocol::CommandExecutor, args: &[&[u8]]) -> Vec<Vec<u8>> {
+    let mut elements = Vec::new();
+    let mut cursor = b"0".to_vec();
+    for _ in 0..10_000 {
+        let mut command: Vec<&[u8]> = vec![b"HSCAN", b"h", &cursor];
+        command.extend_from_slice(args);
+        let (next, page) = scan_page(run(executor, &command));
+        elements.extend(page);
+        if next == b"0" {
+            return elements;
+        }
+        assert!(
+            next.iter().all(u8::is_ascii_digit),
+            "cursor is not an integer"
+        );
+        cursor = next;
+    }
+    panic!("hscan did not finish");
+}
+
+#[test]
+fn hscan_visits_a_field_named_zero() {
+    let executor = executor();
+    for field in ["0", "00", "1", "a", "b", "c"] {
+        run(&executor, &[b"HSET", b"h", field.as_bytes(), b"v"]);
+    }
+
+    let fields: HashSet<Vec<u8>> = hscan_all(&executor, &[b"COUNT", b"1"])
+        .into_iter()
+        .step_by(2)
+        .collect();
+    let expected: HashSet<Vec<u8>> = ["0", "00", "1", "a", "b", "c"]
+        .iter()
+        .map(|field| field.as_bytes().to_vec())
+        .collect();
+    assert_eq!(fields, expected);
+}
+
+#[test]
+fn hscan_match_keeps_the_cursor_past_filtered_fields() {
+    let executor = executor();
+    for i in 0..100 {
+        let field = format!("{}:{}", if i % 10 == 0 { "keep" } else { "skip" }, i);
+        run(&executor, &[b"HSET", b"h", field.as_bytes(), b"v"]);
+    }
+
+    let fields: Vec<Vec<u8>> = hscan_all(&executor, &[b"MATCH", b"keep:*", b"COUNT", b"3"])
+        .into_iter()
+        .step_by(2)
+        .collect();
+    assert_eq!(fields.len(), 10);
+    assert!(fields.iter().all(|field| field.starts_with(b"keep:")));
+}
+
+#[test]
+fn hlen_agrees_with_hkeys_after_many_writes() {
+    let executor = executor();
+    for round in 0..20 {
+        for i in 0..100 {
+            let field = format!("f{}", (i * 7 + round) % 150);
+            run(&executor, &[b"HSET", b"h", field.as_bytes(), b"v"]);
+        }
+        for i in 0..60 {
+            let field = format!("f{}", (i * 11 + round * 3) % 150);
+            run(&executor, &[b"HDEL", b"h", field.as_bytes()]);
+        }
+        let field = format!("n{}", round);
+        run(&executor, &[b"HINCRBY", b"h", field.as_bytes(), b"1"]);
+
+        let len = integer(run(&executor, &[b"HLEN", b"h"]));
+        let keys = strings(run(&executor, &[b"HKEYS", b"h"]));
+        assert_eq!(len as usize, keys.len(), "round {}", round);
+    }
+}
+
+#[test]
+fn del_removes_every_hash_record() {
+    let (executor, store) = executor_and_store();
+    run(&executor, &[b"HSET", b"myhash", b"f1", b"v", b"f2", b"v"]);
+    run(&executor, &[b"HSET", b"other", b"f", b"v"]);
+    assert!(!records_under(&store, b"H:myhash:").is_empty());
+
+    assert_eq!(integer(run(&executor, &[b"DEL", b"myhash"])), 1);
+    assert_eq!(records_under(&store, b"H:myhash:"), Vec::<Vec<u8>>::new());
+    assert_eq!(integer(run(&executor, &[b"HLEN", b"other"])), 1);
+
+    run(&executor, &[b"HSET", b"myhash", b"f", b"v"]);
+    assert_eq!(integer(run(&executor, &[b"UNLINK", b"myhash"])), 1);
+    assert_eq!(records_under(&store, b"H:myhash:"), Vec::<Vec<u8>>::new());
+}
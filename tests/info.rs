@@ -0,0 +1,88 @@
+mod common;
+
+use common::{bulk, integer, server};
+
+/// The `db{n}:` lines of an INFO keyspace reply
+fn keyspace(info: Vec<u8>) -> Vec<String> {
+    String::from_utf8(info)
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("db"))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn keyspace_counts_keys_per_database() {
+    let server = server();
+    let mut client = server.client();
+    client.call(&[b"SET", b"string", b"v"]);
+    client.call(&[b"RPUSH", b"list", b"a", b"b", b"c"]);
+    client.call(&[b"HSET", b"hash", b"f1", b"v", b"f2", b"v"]);
+    client.call(&[b"SADD", b"set", b"a", b"b"]);
+    client.call(&[b"ZADD", b"zset", b"1", b"a", b"2", b"b"]);
+    client.call(&[b"EXPIRE", b"list", b"100"]);
+    client.call(&[b"SELECT", b"3"]);
+    client.call(&[b"SET", b"other", b"v"]);
+    client.call(&[b"HSET", b"other-hash", b"f", b"v"]);
+
+    assert_eq!(integer(client.call(&[b"DBSIZE"])), 2);
+    client.call(&[b"SELECT", b"0"]);
+    assert_eq!(integer(client.call(&[b"DBSIZE"])), 5);
+
+    let info = bulk(client.call(&[b"INFO", b"keyspace"]));
+    assert_eq!(
+        keyspace(info),
+        [
+            "db0:keys=5,expires=0,avg_ttl=0",
+            "db3:keys=2,expires=0,avg_ttl=0"
+        ]
+    );
+
+    client.call(&[b"FLUSHALL"]);
+    assert!(keyspace(bulk(client.call(&[b"INFO", b"keyspace"]))).is_empty());
+}
+
+/// The value of `name` in an INFO or CLIENT reply
+fn field(reply: &str, name: &str) -> String {
+    reply
+        .split_whitespace()
+        .find_map(|entry| {
+            entry
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix(['=', ':']))
+        })
+        .unwrap_or_else(|| panic!("no {} in {:?}", name, reply))
+        .to_string()
+}
+
+#[test]
+fn pipelined_commands_are_counted_once() {
+    let server = server();
+    let mut client = server.client();
+    let mut other = server.client();
+
+    for _ in 0..10 {
+        client.send(&[b"INCR", b"counter"]);
+    }
+    for i in 1..=10 {
+        assert_eq!(integer(client.read()), i);
+    }
+
+    // The registry has the whole batch as soon as its replies are out
+    let list = String::from_utf8(bulk(other.call(&[b"CLIENT", b"LIST"]))).unwrap();
+    let pipelined = list
+        .lines()
+        .find(|line| !line.contains("cmd=client|list"))
+        .expect("pipelining client listed");
+    assert_eq!(field(pipelined, "cmd"), "incr");
+    assert_eq!(field(pipelined, "tot-cmds"), "10");
+
+    // The batch and CLIENT LIST, with INFO not yet counted
+    let info = String::from_utf8(bulk(other.call(&[b"INFO", b"stats"]))).unwrap();
+    assert_eq!(field(&info, "total_commands_processed"), "11");
+
+    let own = String::from_utf8(bulk(client.call(&[b"CLIENT", b"INFO"]))).unwrap();
+    assert_eq!(field(&own, "cmd"), "client|info");
+    assert_eq!(field(&own, "tot-cmds"), "11");
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::{executor, executor_and_store, integer, parse_error, records_under, run, strings};
+use feox_server::protocol::RespValue;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::thread;
+
+#[test]
+fn concurrent_push_and_pop_keep_llen_and_lrange_in_step() {
+    let executor = executor();
+    for i in 0..200 {
+        let seed = format!("seed:{}", i);
+        run(&executor, &[b"RPUSH", b"list", seed.as_bytes()]);
+    }
+
+    let popped = AtomicI64::new(0);
+    thread::scope(|scope| {
+        for worker in 0..4 {
+            let executor = executor.clone();
+            scope.spawn(move || {
+                for i in 0..500 {
+                    let value = format!("{}:{}", worker, i);
+                    let push: &[u8] = if i % 2 == 0 { b"LPUSH" } else { b"RPUSH" };
+                    run(&executor, &[push, b"list", value.as_bytes()]);
+                }
+            });
+        }
+        for worker in 0..4 {
+            let executor = executor.clone();
+            let popped = &popped;
+            scope.spawn(move || {
+                for _ in 0..400 {
+                    let pop: &[u8] = if worker % 2 == 0 { b"LPOP" } else { b"RPOP" };
+                    if let RespValue::BulkString(Some(_)) = run(&executor, &[pop, b"list"]) {
+                        popped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    let len = integer(run(&executor, &[b"LLEN", b"list"]));
+    let elements = strings(run(&executor, &[b"LRANGE", b"list", b"0", b"-1"]));
+    assert_eq!(len as usize, elements.len());
+    assert_eq!(len, 200 + 4 * 500 - popped.load(Ordering::Relaxed));
+}
+
+#[test]
+fn del_removes_every_list_record() {
+    let (executor, store) = executor_and_store();
+    run(&executor, &[b"LPUSH", b"mylist", b"a", b"b", b"c"]);
+    run(&executor, &[b"EXPIRE", b"mylist", b"100"]);
+    run(&executor, &[b"RPUSH", b"other", b"a"]);
+    assert!(!records_under(&store, b"L:mylist:").is_empty());
+
+    assert_eq!(integer(run(&executor, &[b"DEL", b"mylist"])), 1);
+    assert_eq!(records_under(&store, b"L:mylist:"), Vec::<Vec<u8>>::new());
+    assert_eq!(records_under(&store, b"X:mylist"), Vec::<Vec<u8>>::new());
+    // Only the deleted list goes
+    assert_eq!(integer(run(&executor, &[b"LLEN", b"other"])), 1);
+
+    run(&executor, &[b"RPUSH", b"mylist", b"a", b"b"]);
+    assert_eq!(integer(run(&executor, &[b"UNLINK", b"mylist"])), 1);
+    assert_eq!(records_under(&store, b"L:mylist:"), Vec::<Vec<u8>>::new());
+}
+
+#[test]
+fn linsert_places_the_element_around_the_pivot() {
+    let executor = executor();
+    run(&executor, &[b"RPUSH", b"l", b"a", b"c"]);
+
+    assert_eq!(
+        integer(run(&executor, &[b"LINSERT", b"l", b"BEFORE", b"c", b"b"])),
+        3
+    );
+    assert_eq!(
+        integer(run(&executor, &[b"LINSERT", b"l", b"after", b"c", b"d"])),
+        4
+    );
+    assert_eq!(
+        integer(run(&executor, &[b"LINSERT", b"l", b"BEFORE", b"a", b"_"])),
+        5
+    );
+    assert_eq!(
+        strings(run(&executor, &[b"LRANGE", b"l", b"0", b"-1"])),
+        [&b"_"[..], b"a", b"b", b"c", b"d"]
+    );
+}
+
+#[test]
+fn linsert_reports_missing_pivots_and_keys() {
+    let executor = executor();
+    run(&executor, &[b"RPUSH", b"l", b"a"]);
+    assert_eq!(
+        integer(run(&executor, &[b"LINSERT", b"l", b"BEFORE", b"x", b"b"])),
+        -1
+    );
+    assert_eq!(integer(run(&executor, &[b"LLEN", b"l"])), 1);
+
+    assert_eq!(
+        integer(run(&executor, &[b"LINSERT", b"none", b"AFTER", b"a", b"b"])),
+        0
+    );
+    assert_eq!(integer(run(&executor, &[b"EXISTS", b"none"])), 0);
+}
+
+#[test]
+fn linsert_rejects_other_types() {
+    let executor = executor();
+    run(&executor, &[b"SET", b"s", b"a"]);
+    match run(&executor, &[b"LINSERT", b"s", b"BEFORE", b"a", b"b"]) {
+        RespValue::Error(e) => assert!(e.starts_with("WRONGTYPE"), "{}", e),
+        other => panic!("expected WRONGTYPE, got {:?}", other),
+    }
+    assert_eq!(
+        parse_error(&[b"LINSERT", b"l", b"BESIDE", b"a", b"b"]),
+        "syntax error"
+    );
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::{integer, server, Client};
+use std::time::Duration;
+
+fn subscribe(client: &mut Client, channel: &str) {
+    client.send(&[b"SUBSCRIBE", channel.as_bytes()]);
+    client.expect(
+        format!(
+            "*3\r\n$9\r\nsubscribe\r\n${}\r\n{}\r\n:1\r\n",
+            channel.len(),
+            channel
+        )
+        .as_bytes(),
+    );
+}
+
+fn psubscribe(client: &mut Client, pattern: &str) {
+    client.send(&[b"PSUBSCRIBE", pattern.as_bytes()]);
+    client.expect(
+        format!(
+            "*3\r\n$10\r\npsubscribe\r\n${}\r\n{}\r\n:1\r\n",
+            pattern.len(),
+            pattern
+        )
+        .as_bytes(),
+    );
+}
+
+#[test]
+fn numsub_counts_subscribers_on_every_thread() {
+    let server = server();
+    let mut first = server.client_on(0);
+    let mut second = server.client_on(1);
+    subscribe(&mut first, "ch");
+    subscribe(&mut second, "ch");
+
+    for thread in [0, 1] {
+        let mut query = server.client_on(thread);
+        query.send(&[b"PUBSUB", b"NUMSUB", b"ch", b"missing"]);
+        query.expect(b"*4\r\n$2\r\nch\r\n:2\r\n$7\r\nmissing\r\n:0\r\n");
+    }
+
+    second.send(&[b"UNSUBSCRIBE", b"ch"]);
+    second.expect(b"*3\r\n$11\r\nunsubscribe\r\n$2\r\nch\r\n:0\r\n");
+    let mut query = server.client();
+    query.send(&[b"PUBSUB", b"NUMSUB", b"ch"]);
+    query.expect(b"*2\r\n$2\r\nch\r\n:1\r\n");
+}
+
+#[test]
+fn publish_reaches_each_subscriber_once() {
+    let server = server();
+    let mut subscribers = Vec::new();
+    for thread in [0, 1] {
+        let mut channel = server.client_on(thread);
+        subscribe(&mut channel, "news.1");
+        let mut pattern = server.client_on(thread);
+        psubscribe(&mut pattern, "news.*");
+        subscribers.push((channel, pattern));
+    }
+
+    // Publish both from a thread with subscribers of each kind and from
+    // one the other subscribers are not on
+    for thread in [0, 1] {
+        let mut publisher = server.client_on(thread);
+        assert_eq!(
+            integer(publisher.call(&[b"PUBLISH", b"news.1", b"hello"])),
+            4
+        );
+        for (channel, pattern) in &mut subscribers {
+            channel.expect(b"*3\r\n$7\r\nmessage\r\n$6\r\nnews.1\r\n$5\r\nhello\r\n");
+            pattern
+                .expect(b"*4\r\n$8\r\npmessage\r\n$6\r\nnews.*\r\n$6\r\nnews.1\r\n$5\r\nhello\r\n");
+        }
+        for (channel, pattern) in &mut subscribers {
+            channel.expect_silence(Duration::from_millis(100));
+            pattern.expect_silence(Duration::from_millis(100));
+        }
+    }
+}
+
+#[test]
+fn ping_while_subscribed_replies_as_a_message() {
+    let server = server();
+    let mut client = server.client();
+    subscribe(&mut client, "ch");
+
+    client.send(&[b"PING"]);
+    client.expect(b"*2\r\n$4\r\npong\r\n$0\r\n\r\n");
+    client.send(&[b"PING", b"hello"]);
+    client.expect(b"*2\r\n$4\r\npong\r\n$5\r\nhello\r\n");
+
+    // Back to a plain reply once nothing is subscribed
+    client.send(&[b"UNSUBSCRIBE"]);
+    client.expect(b"*3\r\n$11\r\nunsubscribe\r\n$2\r\nch\r\n:0\r\n");
+    client.send(&[b"PING"]);
+    client.expect(b"+PONG\r\n");
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use common::{executor, run, scan_page, server, strings};
+use std::collections::HashSet;
+
+#[test]
+fn scan_in_pages_returns_every_key() {
+    let executor = executor();
+    let mut expected = HashSet::new();
+    for i in 0..10_000 {
+        let key = format!("key:{}", i);
+        run(&executor, &[b"SET", key.as_bytes(), b"v"]);
+        expected.insert(key.into_bytes());
+    }
+
+    let mut seen = HashSet::new();
+    let mut cursor = b"0".to_vec();
+    let mut pages = 0;
+    loop {
+        let (next, keys) = scan_page(run(&executor, &[b"SCAN", &cursor, b"COUNT", b"100"]));
+        seen.extend(keys);
+        pages += 1;
+        assert!(pages <= 10_000, "scan did not finish");
+        if next == b"0" {
+            break;
+        }
+        assert!(
+            next.iter().all(u8::is_ascii_digit),
+            "cursor is not an integer"
+        );
+        cursor = next;
+    }
+    assert!(pages > 1);
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn scan_cursor_can_be_reused() {
+    let executor = executor();
+    for i in 0..50 {
+        run(&executor, &[b"SET", format!("k{}", i).as_bytes(), b"v"]);
+    }
+
+    let (cursor, _) = scan_page(run(&executor, &[b"SCAN", b"0", b"COUNT", b"10"]));
+    let first = scan_page(run(&executor, &[b"SCAN", &cursor, b"COUNT", b"10"]));
+    let again = scan_page(run(&executor, &[b"SCAN", &cursor, b"COUNT", b"10"]));
+    assert_eq!(first, again);
+}
+
+#[test]
+fn scan_rejects_unknown_cursors() {
+    let executor = executor();
+    let reply = run(&executor, &[b"SCAN", b"not-a-cursor"]);
+    assert!(matches!(reply, feox_server::protocol::RespValue::Error(_)));
+}
+
+#[test]
+fn scan_cursors_for_long_keys_fit_in_u64() {
+    let executor = executor();
+    let mut expected = HashSet::new();
+    for i in 0..2_000 {
+        let key = format!("a-rather-long-key-prefix:{:040}", i);
+        run(&executor, &[b"SET", key.as_bytes(), b"v"]);
+        expected.insert(key.into_bytes());
+    }
+
+    let mut seen = HashSet::new();
+    let mut cursor = b"0".to_vec();
+    for _ in 0..10_000 {
+        let (next, keys) = scan_page(run(&executor, &[b"SCAN", &cursor, b"COUNT", b"7"]));
+        seen.extend(keys);
+        if next == b"0" {
+            break;
+        }
+        let parsed = std::str::from_utf8(&next)
+            .ok()
+            .and_then(|n| n.parse::<u64>().ok());
+        assert!(parsed.is_some(), "cursor {:?} is not a u64", next);
+        cursor = next;
+    }
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn scan_count_one_pages_through_every_key() {
+    let executor = executor();
+    let keys: Vec<Vec<u8>> = (0..5).map(|i| format!("key:{}", i).into_bytes()).collect();
+    for key in &keys {
+        run(&executor, &[b"SET", key, b"v"]);
+    }
+
+    let mut seen = Vec::new();
+    let mut cursor = b"0".to_vec();
+    loop {
+        let (next, page) = scan_page(run(&executor, &[b"SCAN", &cursor, b"COUNT", b"1"]));
+        assert!(page.len() <= 1, "COUNT 1 returned {} keys", page.len());
+        seen.extend(page);
+        if next == b"0" {
+            break;
+        }
+        assert!(seen.len() <= keys.len(), "scan did not finish");
+        cursor = next;
+    }
+    // Each page resumes at the key the last one stopped before, neither
+    // skipping nor repeating it
+    seen.sort();
+    assert_eq!(seen, keys);
+}
+
+#[test]
+fn scan_resumes_at_the_boundary_key() {
+    let executor = executor();
+    for key in [&b"a"[..], b"b", b"c"] {
+        run(&executor, &[b"SET", key, b"v"]);
+    }
+
+    let (cursor, first) = scan_page(run(&executor, &[b"SCAN", b"0", b"COUNT", b"1"]));
+    let (_, second) = scan_page(run(&executor, &[b"SCAN", &cursor, b"COUNT", b"1"]));
+    // The key the first page stopped before opens the second
+    assert_eq!(first, [b"a".to_vec()]);
+    assert_eq!(second, [b"b".to_vec()]);
+}
+
+#[test]
+fn scan_rejects_count_zero() {
+    let server = server();
+    let mut client = server.client();
+    client.send(&[b"SCAN", b"0", b"COUNT", b"0"]);
+    client.expect(b"-ERR syntax error\r\n");
+    client.send(&[b"SCAN", b"0", b"COUNT", b"-1"]);
+    client.expect(b"-ERR syntax error\r\n");
+}
+
+#[test]
+fn keys_matches_character_classes() {
+    let executor = executor();
+    for key in [
+        &b"user:1"[..],
+        b"user:42:name",
+        b"user:x",
+        b"user:",
+        b"users:1",
+    ] {
+        run(&executor, &[b"SET", key, b"v"]);
+    }
+
+    let mut keys = strings(run(&executor, &[b"KEYS", b"user:[0-9]*"]));
+    keys.sort();
+    assert_eq!(keys, [b"user:1".to_vec(), b"user:42:name".to_vec()]);
+}
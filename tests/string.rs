@@ -0,0 +1,63 @@
+mod common;
+
+use common::{bulk, executor, integer, parse_error, run};
+use feox_server::protocol::RespValue;
+
+#[test]
+fn setrange_rejects_offsets_past_the_bulk_limit() {
+    let executor = executor();
+    run(&executor, &[b"SET", b"k", b"hello"]);
+    for offset in [&b"4000000000"[..], b"536870912", b"9223372036854775807"] {
+        let reply = run(&executor, &[b"SETRANGE", b"k", offset, b"x"]);
+        assert!(
+            matches!(&reply, RespValue::Error(e)
+                if e == "ERR string exceeds maximum allowed size (proto-max-bulk-len)"),
+            "offset {:?} gave {:?}",
+            offset,
+            reply
+        );
+    }
+    assert_eq!(integer(run(&executor, &[b"STRLEN", b"k"])), 5);
+    assert_eq!(integer(run(&executor, &[b"SETRANGE", b"k", b"6", b"x"])), 7);
+}
+
+#[test]
+fn msetnx_sets_all_or_nothing() {
+    let executor = executor();
+    assert_eq!(
+        integer(run(&executor, &[b"MSETNX", b"a", b"1", b"b", b"2"])),
+        1
+    );
+    assert_eq!(bulk(run(&executor, &[b"GET", b"b"])), b"2");
+
+    // One existing key holds back every other
+    assert_eq!(
+        integer(run(&executor, &[b"MSETNX", b"c", b"3", b"a", b"new"])),
+        0
+    );
+    assert_eq!(bulk(run(&executor, &[b"GET", b"a"])), b"1");
+    assert_eq!(integer(run(&executor, &[b"EXISTS", b"c"])), 0);
+
+    // So does a collection under one of the keys
+    run(&executor, &[b"RPUSH", b"list", b"x"]);
+    assert_eq!(
+        integer(run(&executor, &[b"MSETNX", b"c", b"3", b"list", b"v"])),
+        0
+    );
+    assert_eq!(integer(run(&executor, &[b"EXISTS", b"c"])), 0);
+    assert_eq!(integer(run(&executor, &[b"LLEN", b"list"])), 1);
+}
+
+#[test]
+fn msetnx_needs_key_value_pairs() {
+    for args in [
+        &[&b"MSETNX"[..]][..],
+        &[b"MSETNX", b"a"],
+        &[b"MSETNX", b"a", b"1", b"b"],
+    ] {
+        assert_eq!(
+            parse_error(args),
+            "wrong number of arguments for 'MSETNX' command"
+        );
+    }
+}